@@ -0,0 +1,13 @@
+// Generates the gRPC service of the `grpc` feature; protox compiles the proto file so
+// building doesn't need protoc installed.
+fn main() {
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto");
+        let descriptors = protox::compile(["proto/reference_data.proto"], ["proto"]).expect("invalid proto file");
+        tonic_prost_build::configure()
+            .build_client(false)
+            .compile_fds(descriptors)
+            .expect("cannot generate the gRPC service");
+    }
+}
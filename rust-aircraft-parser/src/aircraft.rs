@@ -0,0 +1,44 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// An aircraft type as published in the reference data, keyed by its ICAO type designator.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[serde(rename_all = "camelCase")]
+pub struct Aircraft {
+    /// ICAO type designator, e.g. `B38M`.
+    #[serde(alias = "icao_code")]
+    pub icao_code: String,
+    /// IATA aircraft type code, e.g. `7M8`. None when the type has none, which the input
+    /// gives as an empty string, `null`, or by leaving the field out.
+    #[serde(alias = "iata_code", default, deserialize_with = "empty_as_none")]
+    pub iata_code: Option<String>,
+    /// Human readable name, e.g. `Boeing 737 MAX 8`.
+    pub description: String,
+    /// The name in other languages, by locale, e.g. `fr`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub descriptions: BTreeMap<String, String>,
+    /// Other designators the type is known by, such as superseded ICAO designators, which
+    /// lookups resolve to this aircraft.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+}
+
+impl Aircraft {
+    /// The aircraft with the description in `lang` as its `description`, falling back from
+    /// a regional locale such as `fr-CA` to its language, and to the description as it is
+    /// when there is no translation.
+    pub fn localized(mut self, lang: &str) -> Self {
+        let language = lang.split(['-', '_']).next().unwrap_or(lang);
+        if let Some(description) = self.descriptions.get(lang).or_else(|| self.descriptions.get(language)) {
+            self.description = description.clone();
+        }
+        self
+    }
+}
+
+// Reads an empty string as a missing code.
+fn empty_as_none<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    Ok(Option::<String>::deserialize(deserializer)?.filter(|code| !code.is_empty()))
+}
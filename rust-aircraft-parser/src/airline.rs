@@ -0,0 +1,81 @@
+use std::borrow::Cow;
+use mongodb::bson::doc;
+use mongodb::IndexModel;
+use serde::{Deserialize, Serialize};
+use crate::input::OpenFlightsRow;
+use crate::record::Record;
+use crate::validate::is_alphanumeric;
+use crate::Result;
+
+/// An airline as published in the reference data, keyed by its ICAO designator.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[serde(rename_all = "camelCase")]
+pub struct Airline {
+    /// ICAO airline designator, e.g. `BAW`.
+    pub icao_code: String,
+    /// IATA airline designator, e.g. `BA`. Empty when the airline has none.
+    #[serde(default)]
+    pub iata_code: String,
+    /// Name, e.g. `British Airways`.
+    pub name: String,
+    /// Radio callsign, e.g. `SPEEDBIRD`.
+    #[serde(default)]
+    pub callsign: String,
+    /// Country, e.g. `United Kingdom`.
+    #[serde(default)]
+    pub country: String,
+    /// Whether the airline is still operating.
+    #[serde(default = "active_by_default")]
+    pub active: bool,
+}
+
+fn active_by_default() -> bool {
+    true
+}
+
+impl Record for Airline {
+    const COLLECTION: &'static str = "airlines";
+    const KEY_FIELD: &'static str = "icaoCode";
+
+    fn key(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.icao_code)
+    }
+
+    /// * `icaoCode` must be 3 letters.
+    /// * `iataCode` must be exactly 2 letters or digits, or empty.
+    /// * `name` must not be blank.
+    fn validate(&self) -> Vec<String> {
+        let mut reasons = Vec::new();
+        if self.icao_code.chars().count() != 3 || !self.icao_code.chars().all(|character| character.is_ascii_alphabetic()) {
+            reasons.push(format!("icaoCode {:?} is not 3 letters", self.icao_code));
+        }
+        if !self.iata_code.is_empty() && (self.iata_code.chars().count() != 2 || !is_alphanumeric(&self.iata_code)) {
+            reasons.push(format!("iataCode {:?} is neither empty nor 2 alphanumeric characters", self.iata_code));
+        }
+        if self.name.trim().is_empty() {
+            reasons.push("name is empty".to_string());
+        }
+        reasons
+    }
+
+    /// `airlines.dat`: id, name, alias, IATA, ICAO, callsign, country, active (`Y` or `N`).
+    fn openflights() -> Option<fn(&OpenFlightsRow) -> Result<Self>> {
+        Some(|row| {
+            Ok(Airline {
+                icao_code: row.text(4),
+                iata_code: row.text(3),
+                name: row.text(1),
+                callsign: row.text(5),
+                country: row.text(6),
+                active: row.get(7) != Some("N"),
+            })
+        })
+    }
+
+    /// A non-unique index on `iataCode`, which several (mostly defunct) airlines can share.
+    fn indexes() -> Vec<IndexModel> {
+        vec![IndexModel::builder().keys(doc! { "iataCode": 1 }).build()]
+    }
+}
@@ -0,0 +1,167 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use mongodb::bson::{self, doc, Document};
+use mongodb::IndexModel;
+use serde::{Deserialize, Serialize};
+use crate::input::OpenFlightsRow;
+use crate::record::Record;
+use crate::validate::is_alphanumeric;
+use crate::Result;
+
+/// An airport as published in the reference data, keyed by its ICAO location indicator.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[serde(rename_all = "camelCase")]
+pub struct Airport {
+    /// ICAO location indicator, e.g. `EGLL`.
+    pub icao_code: String,
+    /// IATA airport code, e.g. `LHR`. Empty when the airport has none.
+    #[serde(default)]
+    pub iata_code: String,
+    /// Name, e.g. `London Heathrow Airport`.
+    pub name: String,
+    /// City served, e.g. `London`.
+    #[serde(default)]
+    pub city: String,
+    /// Country, e.g. `United Kingdom`.
+    #[serde(default)]
+    pub country: String,
+    /// Latitude in decimal degrees, north positive.
+    pub latitude: f64,
+    /// Longitude in decimal degrees, east positive.
+    pub longitude: f64,
+    /// Elevation in feet above mean sea level.
+    #[serde(default)]
+    pub elevation: Option<i32>,
+    /// IANA time zone, e.g. `Europe/London`.
+    #[serde(default)]
+    pub timezone: Option<String>,
+}
+
+// Mean radius of the Earth, in kilometres.
+const EARTH_RADIUS_KM: f64 = 6371.0088;
+
+/// An airport with its great-circle distance from the point it was looked up around.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NearbyAirport {
+    #[serde(flatten)]
+    pub airport: Airport,
+    pub distance_km: f64,
+}
+
+impl Airport {
+    /// Great-circle distance in kilometres from the point at `latitude`, `longitude`
+    /// (decimal degrees), by the haversine formula on a spherical Earth.
+    pub fn distance_km(&self, latitude: f64, longitude: f64) -> f64 {
+        let (from, to) = (self.latitude.to_radians(), latitude.to_radians());
+        let half_latitude = (to - from) / 2.0;
+        let half_longitude = (longitude - self.longitude).to_radians() / 2.0;
+        let a = half_latitude.sin().powi(2) + from.cos() * to.cos() * half_longitude.sin().powi(2);
+        2.0 * EARTH_RADIUS_KM * a.sqrt().min(1.0).asin()
+    }
+}
+
+/// The airports of `airports` within `radius_km` of the point at `latitude`, `longitude`,
+/// or all of them without a radius, nearest first.
+pub fn nearest(airports: impl IntoIterator<Item = Airport>, latitude: f64, longitude: f64, radius_km: Option<f64>) -> Vec<NearbyAirport> {
+    let mut nearby: Vec<NearbyAirport> = airports
+        .into_iter()
+        .map(|airport| NearbyAirport { distance_km: airport.distance_km(latitude, longitude), airport })
+        .filter(|nearby| radius_km.is_none_or(|radius| nearby.distance_km <= radius))
+        .collect();
+    nearby.sort_by(|left, right| left.distance_km.total_cmp(&right.distance_km).then_with(|| left.airport.icao_code.cmp(&right.airport.icao_code)));
+    nearby
+}
+
+/// Airports looked up by either their ICAO or their IATA code, as other records refer to
+/// them.
+#[derive(Debug, Default)]
+pub struct AirportIndex {
+    by_code: HashMap<String, Airport>,
+}
+
+impl AirportIndex {
+    pub fn new(airports: impl IntoIterator<Item = Airport>) -> Self {
+        let mut by_code = HashMap::new();
+        for airport in airports {
+            if !airport.iata_code.is_empty() {
+                by_code.insert(airport.iata_code.clone(), airport.clone());
+            }
+            by_code.insert(airport.icao_code.clone(), airport);
+        }
+        AirportIndex { by_code }
+    }
+
+    /// The airport whose ICAO or IATA code is `code`.
+    pub fn get(&self, code: &str) -> Option<&Airport> {
+        self.by_code.get(code)
+    }
+}
+
+impl Record for Airport {
+    const COLLECTION: &'static str = "airports";
+    const KEY_FIELD: &'static str = "icaoCode";
+
+    fn key(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.icao_code)
+    }
+
+    /// * `icaoCode` must be 4 letters or digits.
+    /// * `iataCode` must be exactly 3 letters or digits, or empty.
+    /// * `name` must not be blank.
+    /// * `latitude` and `longitude` must be within ±90 and ±180 degrees.
+    fn validate(&self) -> Vec<String> {
+        let mut reasons = Vec::new();
+        if self.icao_code.chars().count() != 4 || !is_alphanumeric(&self.icao_code) {
+            reasons.push(format!("icaoCode {:?} is not 4 alphanumeric characters", self.icao_code));
+        }
+        if !self.iata_code.is_empty() && (self.iata_code.chars().count() != 3 || !is_alphanumeric(&self.iata_code)) {
+            reasons.push(format!("iataCode {:?} is neither empty nor 3 alphanumeric characters", self.iata_code));
+        }
+        if self.name.trim().is_empty() {
+            reasons.push("name is empty".to_string());
+        }
+        if !(-90.0..=90.0).contains(&self.latitude) {
+            reasons.push(format!("latitude {} is outside -90..90", self.latitude));
+        }
+        if !(-180.0..=180.0).contains(&self.longitude) {
+            reasons.push(format!("longitude {} is outside -180..180", self.longitude));
+        }
+        reasons
+    }
+
+    /// `airports.dat`: id, name, city, country, IATA, ICAO, latitude, longitude, altitude,
+    /// UTC offset, DST rule, time zone, type, source.
+    fn openflights() -> Option<fn(&OpenFlightsRow) -> Result<Self>> {
+        Some(|row| {
+            Ok(Airport {
+                icao_code: row.text(5),
+                iata_code: row.text(4),
+                name: row.text(1),
+                city: row.text(2),
+                country: row.text(3),
+                latitude: row.require(6, "latitude")?,
+                longitude: row.require(7, "longitude")?,
+                elevation: row.parse(8, "altitude")?,
+                timezone: row.get(11).map(str::to_string),
+            })
+        })
+    }
+
+    /// The airport's fields plus a GeoJSON `location` point for the geospatial index.
+    fn to_document(&self) -> Result<Document> {
+        let mut document = bson::to_document(self)?;
+        document.insert("location", doc! { "type": "Point", "coordinates": [self.longitude, self.latitude] });
+        Ok(document)
+    }
+
+    /// A `2dsphere` index on `location`, for nearest-airport and radius queries, and one on `iataCode`.
+    fn indexes() -> Vec<IndexModel> {
+        vec![
+            IndexModel::builder().keys(doc! { "location": "2dsphere" }).build(),
+            IndexModel::builder().keys(doc! { "iataCode": 1 }).build(),
+        ]
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use clap::ValueEnum;
+use rust_aircraft_parser::input::Format;
+use rust_aircraft_parser::{Error, Result};
+use crate::cli;
+
+/// File naming the dataset of input files whose name doesn't, next to them.
+pub const MANIFEST: &str = "manifest.toml";
+
+/// The kind of reference data an input file holds.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Aircraft,
+    Airports,
+    Airlines,
+    Routes,
+    Countries,
+    Registrations,
+    Hexdb,
+}
+
+impl Kind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Aircraft => "aircraft",
+            Kind::Airports => "airports",
+            Kind::Airlines => "airlines",
+            Kind::Routes => "routes",
+            Kind::Countries => "countries",
+            Kind::Registrations => "registrations",
+            Kind::Hexdb => "hexdb",
+        }
+    }
+
+    // From a file stem such as `airports` or `airports-2024`; `planes` is the OpenFlights name.
+    fn from_stem(stem: &str) -> Option<Kind> {
+        let name = stem.split(['-', '_', '.']).next().unwrap_or_default().to_ascii_lowercase();
+        match name.as_str() {
+            "planes" => Some(Kind::Aircraft),
+            name => Kind::from_str(name, true).ok(),
+        }
+    }
+}
+
+/// One file of a multi-file load.
+#[derive(Clone, Debug)]
+pub struct Input {
+    pub path: PathBuf,
+    pub kind: Kind,
+    pub format: Format,
+}
+
+impl Input {
+    /// `report`, e.g. `rejects.json`, named after this file, e.g. `rejects-airports.json`,
+    /// so the files of one run don't overwrite each other's reports.
+    pub fn report(&self, report: &Path) -> PathBuf {
+        let name = self.path.file_name().unwrap_or_default().to_string_lossy();
+        let name = name.split('.').next().unwrap_or_default();
+        let stem = report.file_stem().unwrap_or_default().to_string_lossy();
+        match report.extension() {
+            Some(extension) => report.with_file_name(format!("{}-{}.{}", stem, name, extension.to_string_lossy())),
+            None => report.with_file_name(format!("{}-{}", stem, name)),
+        }
+    }
+
+    /// The `load` subcommand loading this file with the options of `args`, `None` for aircraft.
+    pub fn dataset(&self, args: &cli::LoadArgs) -> Option<cli::Dataset> {
+        let dataset = cli::DatasetArgs {
+            file: Some(self.path.clone()),
+            format: self.format,
+            upsert: args.upsert,
+            run: cli::RunArgs {
+                rejects: self.report(&args.run.rejects),
+                report: args.run.report.as_deref().map(|report| self.report(report)),
+                ..args.run.clone()
+            },
+            write: cli::WriteArgs { failures: self.report(&args.write.failures), ..args.write.clone() },
+            skip_indexes: args.skip_indexes,
+            orphans: self.report(Path::new("orphans.json")),
+        };
+        Some(match self.kind {
+            Kind::Aircraft => return None,
+            Kind::Airports => cli::Dataset::Airports(cli::AirportArgs { dataset, types: Vec::new(), countries: Vec::new() }),
+            Kind::Airlines => cli::Dataset::Airlines(dataset),
+            Kind::Routes => cli::Dataset::Routes(dataset),
+            Kind::Countries => cli::Dataset::Countries(dataset),
+            Kind::Registrations => cli::Dataset::Registrations(cli::RegistrationArgs { dataset, acftref: None }),
+            Kind::Hexdb => cli::Dataset::Hexdb(dataset),
+        })
+    }
+}
+
+/// Whether `paths` call for a multi-file load: more than one path, a directory or a glob pattern.
+pub fn is_batch(paths: &[&Path]) -> bool {
+    paths.len() > 1 || paths.iter().any(|path| path.is_dir() || is_pattern(path))
+}
+
+fn is_pattern(path: &Path) -> bool {
+    path.to_str().is_some_and(|path| path.contains(['*', '?', '[']))
+}
+
+/// The files `paths` stand for, in order: directories list their files, patterns the files
+/// they match and other paths themselves. Each file's dataset comes from the `manifest.toml`
+/// in its directory, or else from its name; its format from its extension, ignoring a `.gz`
+/// or `.zip` suffix. Files found through a directory or pattern that match neither are
+/// skipped and returned separately; a named file that matches neither is an error.
+pub fn discover(paths: &[&Path]) -> Result<(Vec<Input>, Vec<PathBuf>)> {
+    let mut inputs = Vec::new();
+    let mut skipped = Vec::new();
+    let mut manifests = HashMap::new();
+    for path in paths {
+        let (files, named) = if path.is_dir() {
+            (list(path)?, false)
+        } else if is_pattern(path) {
+            (expand(path)?, false)
+        } else {
+            (vec![path.to_path_buf()], true)
+        };
+        for file in files {
+            let directory = file.parent().unwrap_or(Path::new("")).to_path_buf();
+            if !manifests.contains_key(&directory) {
+                let manifest = read_manifest(&directory)?;
+                manifests.insert(directory.clone(), manifest);
+            }
+            match classify(&file, &manifests[&directory]) {
+                Some((kind, format)) => inputs.push(Input { path: file, kind, format }),
+                None if named => {
+                    return Err(Error::Config(format!(
+                        "cannot tell the dataset and format of {}; name it after the dataset or list it in {}",
+                        file.display(),
+                        MANIFEST
+                    )))
+                }
+                None => skipped.push(file),
+            }
+        }
+    }
+    Ok((inputs, skipped))
+}
+
+fn classify(file: &Path, manifest: &HashMap<String, Kind>) -> Option<(Kind, Format)> {
+    let name = file.file_name()?.to_str()?;
+    let plain = name.strip_suffix(".gz").or_else(|| name.strip_suffix(".zip")).unwrap_or(name);
+    let (stem, extension) = plain.rsplit_once('.')?;
+    let format = match extension {
+        "json" => Format::Json,
+        "json5" => Format::Json5,
+        "ndjson" | "jsonl" => Format::Ndjson,
+        "yaml" | "yml" => Format::Yaml,
+        "csv" => Format::Csv,
+        "dat" => Format::Openflights,
+        _ => return None,
+    };
+    let kind = manifest.get(name).copied().or_else(|| Kind::from_stem(stem))?;
+    Some((kind, format))
+}
+
+fn list(directory: &Path) -> Result<Vec<PathBuf>> {
+    let io_error = |source| Error::Io { path: directory.to_path_buf(), source };
+    let mut files = Vec::new();
+    for entry in fs::read_dir(directory).map_err(io_error)? {
+        let path = entry.map_err(io_error)?.path();
+        if path.is_file() && path.file_name() != Some(MANIFEST.as_ref()) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn expand(pattern: &Path) -> Result<Vec<PathBuf>> {
+    let pattern = pattern.to_string_lossy();
+    let paths = glob::glob(&pattern).map_err(|error| Error::Config(format!("invalid pattern {}: {}", pattern, error)))?;
+    let mut files = Vec::new();
+    for path in paths {
+        let path = path.map_err(|error| Error::Io { path: error.path().to_path_buf(), source: error.into() })?;
+        if path.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+// `manifest.toml` maps file names to dataset names, e.g. `"fleet.json" = "aircraft"`.
+fn read_manifest(directory: &Path) -> Result<HashMap<String, Kind>> {
+    let path = directory.join(MANIFEST);
+    if !path.is_file() {
+        return Ok(HashMap::new());
+    }
+    let text = fs::read_to_string(&path).map_err(|source| Error::Io { path: path.clone(), source })?;
+    let entries: HashMap<String, String> =
+        toml::from_str(&text).map_err(|error| Error::Config(format!("{}: {}", path.display(), error)))?;
+    entries
+        .into_iter()
+        .map(|(file, kind)| {
+            let kind = Kind::from_str(&kind, true)
+                .map_err(|_| Error::Config(format!("{}: unknown dataset {:?} for {}", path.display(), kind, file)))?;
+            Ok((file, kind))
+        })
+        .collect()
+}
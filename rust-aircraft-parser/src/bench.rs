@@ -0,0 +1,120 @@
+//! Measuring how fast aircraft are parsed, converted to BSON and inserted, to tune the
+//! batch size and concurrency of loads for a cluster.
+
+use std::io::Cursor;
+use std::time::{Duration, Instant};
+use crate::fields::FieldNames;
+use crate::ids::{IdStrategy, UuidEncoding};
+use crate::input::read_aircraft_json;
+use crate::load::{self, LoadOptions};
+use crate::storage::aircraft_document;
+use crate::{Aircraft, AircraftStore, Result, Storage};
+
+// Letters and digits the synthetic codes are spelled with.
+const ALPHABET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+/// How long one stage took over `records` aircraft; the batch size and concurrency are
+/// only set for inserts.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Measurement {
+    pub stage: &'static str,
+    pub batch_size: Option<usize>,
+    pub concurrency: Option<usize>,
+    pub records: u64,
+    pub elapsed: Duration,
+}
+
+impl Measurement {
+    pub fn per_second(&self) -> f64 {
+        self.records as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// `count` valid aircraft: ICAO codes counting up in base 36 from `0000`, distinct for up
+/// to 36⁴ aircraft, and IATA codes cycling through the three-character ones.
+pub fn synthetic(count: usize) -> Vec<Aircraft> {
+    (0..count)
+        .map(|index| Aircraft {
+            icao_code: code(index, 4),
+            iata_code: Some(code(index, 3)),
+            description: format!("Synthetic aircraft {}", index),
+            descriptions: Default::default(),
+            aliases: Vec::new(),
+        })
+        .collect()
+}
+
+// The last `length` base-36 digits of `index`.
+fn code(mut index: usize, length: usize) -> String {
+    let mut digits = vec![b'0'; length];
+    for digit in digits.iter_mut().rev() {
+        *digit = ALPHABET[index % ALPHABET.len()];
+        index /= ALPHABET.len();
+    }
+    String::from_utf8(digits).unwrap_or_default()
+}
+
+/// Parses `aircrafts` back from the JSON array input they make, which is written untimed.
+pub fn parse(aircrafts: &[Aircraft]) -> Result<Measurement> {
+    let json = serde_json::to_vec(aircrafts)?;
+    let started = Instant::now();
+    let mut records = 0;
+    for aircraft in read_aircraft_json(Cursor::new(json)) {
+        aircraft?;
+        records += 1;
+    }
+    Ok(Measurement { stage: "parse", batch_size: None, concurrency: None, records, elapsed: started.elapsed() })
+}
+
+/// Converts `aircrafts` to the documents a MongoDB load inserts, stored under `fields`.
+pub fn convert(aircrafts: &[Aircraft], fields: &FieldNames) -> Measurement {
+    let ids = IdStrategy::default();
+    let started = Instant::now();
+    let documents: Vec<_> = aircrafts.iter().map(|aircraft| aircraft_document(aircraft, &ids, UuidEncoding::default(), fields)).collect();
+    Measurement { stage: "bson", batch_size: None, concurrency: None, records: documents.len() as u64, elapsed: started.elapsed() }
+}
+
+/// Loads `aircrafts` into `scratch`, a collection next to the store's with the same indexes
+/// that is dropped afterwards, `batch_size` at a time with up to `concurrency` batches being
+/// written.
+pub async fn insert(store: &AircraftStore, scratch: &str, aircrafts: &[Aircraft], batch_size: usize, concurrency: usize) -> Result<Measurement> {
+    let target = AircraftStore::new(store.sibling(scratch)).with_field_names(store.field_names().clone());
+    target.ensure_indexes().await?;
+    let options = LoadOptions { batch_size, concurrency, ..LoadOptions::default() };
+    // Copied before the clock starts, as the load takes the records it reads.
+    let records: Vec<Result<Aircraft>> = aircrafts.iter().cloned().map(Ok).collect();
+    let started = Instant::now();
+    let summary = load::load(&target, records.into_iter(), &options).await;
+    let elapsed = started.elapsed();
+    target.collection().drop(None).await?;
+    Ok(Measurement { stage: "insert", batch_size: Some(batch_size), concurrency: Some(concurrency), records: summary?.written, elapsed })
+}
+
+/// `measurements` as aligned columns, one row each.
+pub fn table(measurements: &[Measurement]) -> String {
+    let mut rows = vec![["stage", "batch size", "concurrency", "records", "seconds", "records/s"].map(str::to_string)];
+    rows.extend(measurements.iter().map(|measurement| {
+        let or_dash = |value: Option<usize>| value.map_or_else(|| "-".to_string(), |value| value.to_string());
+        [
+            measurement.stage.to_string(),
+            or_dash(measurement.batch_size),
+            or_dash(measurement.concurrency),
+            measurement.records.to_string(),
+            format!("{:.3}", measurement.elapsed.as_secs_f64()),
+            format!("{:.0}", measurement.per_second()),
+        ]
+    }));
+    let mut widths = [0; 6];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let mut table = String::new();
+    for row in &rows {
+        let cells: Vec<String> = row.iter().zip(&widths).map(|(cell, width)| format!("{:<width$}", cell, width = width)).collect();
+        table.push_str(cells.join("  ").trim_end());
+        table.push('\n');
+    }
+    table
+}
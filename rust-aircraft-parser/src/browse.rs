@@ -0,0 +1,420 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::path::PathBuf;
+use mongodb::bson::doc;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Tabs, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use rust_aircraft_parser::storage::{RecordStore, Sink};
+use rust_aircraft_parser::{Aircraft, AircraftStore, Airline, Airport, Error, Record, Result, Storage};
+
+// Rows moved by PageUp and PageDown.
+const PAGE: isize = 20;
+
+const HELP: &str = "tab switch  / search  d mark for deletion  e edit  w write changes  q quit";
+
+// What the browser shows and edits of each kind of record: a row of the list, every field
+// labelled for the detail pane, and the field `e` edits, the description of an aircraft and
+// the name of anything else.
+trait Browsable: Record {
+    fn row(&self) -> String;
+    fn details(&self) -> Vec<(&'static str, String)>;
+    fn text(&self) -> &str;
+    fn set_text(&mut self, text: String);
+}
+
+impl Browsable for Aircraft {
+    fn row(&self) -> String {
+        format!("{:<4}  {:<3}  {}", self.icao_code, self.iata_code.as_deref().unwrap_or_default(), self.description)
+    }
+
+    fn details(&self) -> Vec<(&'static str, String)> {
+        let mut details = vec![
+            ("ICAO code", self.icao_code.clone()),
+            ("IATA code", self.iata_code.clone().unwrap_or_default()),
+            ("Description", self.description.clone()),
+        ];
+        details.extend(self.descriptions.iter().map(|(lang, description)| ("Translation", format!("{}: {}", lang, description))));
+        details
+    }
+
+    fn text(&self) -> &str {
+        &self.description
+    }
+
+    fn set_text(&mut self, text: String) {
+        self.description = text;
+    }
+}
+
+impl Browsable for Airline {
+    fn row(&self) -> String {
+        format!("{:<3}  {:<2}  {}", self.icao_code, self.iata_code, self.name)
+    }
+
+    fn details(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("ICAO code", self.icao_code.clone()),
+            ("IATA code", self.iata_code.clone()),
+            ("Name", self.name.clone()),
+            ("Callsign", self.callsign.clone()),
+            ("Country", self.country.clone()),
+            ("Active", self.active.to_string()),
+        ]
+    }
+
+    fn text(&self) -> &str {
+        &self.name
+    }
+
+    fn set_text(&mut self, text: String) {
+        self.name = text;
+    }
+}
+
+impl Browsable for Airport {
+    fn row(&self) -> String {
+        format!("{:<4}  {:<3}  {}", self.icao_code, self.iata_code, self.name)
+    }
+
+    fn details(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("ICAO code", self.icao_code.clone()),
+            ("IATA code", self.iata_code.clone()),
+            ("Name", self.name.clone()),
+            ("City", self.city.clone()),
+            ("Country", self.country.clone()),
+            ("Position", format!("{:.4}, {:.4}", self.latitude, self.longitude)),
+            ("Elevation", self.elevation.map(|feet| format!("{} ft", feet)).unwrap_or_default()),
+            ("Time zone", self.timezone.clone().unwrap_or_default()),
+        ]
+    }
+
+    fn text(&self) -> &str {
+        &self.name
+    }
+
+    fn set_text(&mut self, text: String) {
+        self.name = text;
+    }
+}
+
+// The records of one kind, the rows matching the search and the changes not written yet.
+struct Tab<T> {
+    title: &'static str,
+    records: Vec<T>,
+    // Indexes into `records` of the rows shown.
+    shown: Vec<usize>,
+    selected: usize,
+    deleted: BTreeSet<String>,
+    edited: BTreeMap<String, T>,
+}
+
+impl<T: Browsable> Tab<T> {
+    fn new(title: &'static str, mut records: Vec<T>) -> Self {
+        records.sort_by(|left, right| left.key().cmp(&right.key()));
+        let shown = (0..records.len()).collect();
+        Tab { title, records, shown, selected: 0, deleted: BTreeSet::new(), edited: BTreeMap::new() }
+    }
+
+    // The selected record as edited, if anything is shown.
+    fn current(&self) -> Option<&T> {
+        let record = &self.records[*self.shown.get(self.selected)?];
+        Some(self.edited.get(record.key().as_ref()).unwrap_or(record))
+    }
+
+    // Applies the changes once written to `records`, passing on how many were written and
+    // deleted.
+    fn applied(&mut self, written: u64, deleted: u64) -> (u64, u64) {
+        let edited = std::mem::take(&mut self.edited);
+        let removed = std::mem::take(&mut self.deleted);
+        for record in &mut self.records {
+            if let Some(edit) = edited.get(record.key().as_ref()) {
+                *record = edit.clone();
+            }
+        }
+        self.records.retain(|record| !removed.contains(record.key().as_ref()));
+        (written, deleted)
+    }
+
+    fn changes(&self) -> (Vec<T>, Vec<String>) {
+        let edited = self.edited.iter().filter(|(key, _)| !self.deleted.contains(*key)).map(|(_, record)| record.clone()).collect();
+        (edited, self.deleted.iter().cloned().collect())
+    }
+}
+
+// A tab whatever kind of record it holds.
+trait Pane {
+    fn title(&self) -> &'static str;
+    fn filter(&mut self, query: &str);
+    fn rows(&self) -> Vec<ListItem<'static>>;
+    fn selected(&self) -> Option<usize>;
+    fn move_by(&mut self, rows: isize);
+    fn details(&self) -> Vec<Line<'static>>;
+    fn toggle_delete(&mut self);
+    fn text(&self) -> Option<String>;
+    fn edit(&mut self, text: String);
+    fn pending(&self) -> usize;
+}
+
+impl<T: Browsable> Pane for Tab<T> {
+    fn title(&self) -> &'static str {
+        self.title
+    }
+
+    fn filter(&mut self, query: &str) {
+        let query = query.to_lowercase();
+        self.shown = (0..self.records.len()).filter(|&index| self.records[index].row().to_lowercase().contains(&query)).collect();
+        self.selected = self.selected.min(self.shown.len().saturating_sub(1));
+    }
+
+    fn rows(&self) -> Vec<ListItem<'static>> {
+        self.shown
+            .iter()
+            .map(|&index| {
+                let record = &self.records[index];
+                let key = record.key();
+                let (mark, record) = match (self.deleted.contains(key.as_ref()), self.edited.get(key.as_ref())) {
+                    (true, _) => ("D ", record),
+                    (false, Some(edit)) => ("* ", edit),
+                    (false, None) => ("  ", record),
+                };
+                let item = ListItem::new(format!("{}{}", mark, record.row()));
+                if mark == "D " { item.crossed_out() } else { item }
+            })
+            .collect()
+    }
+
+    fn selected(&self) -> Option<usize> {
+        (!self.shown.is_empty()).then_some(self.selected)
+    }
+
+    fn move_by(&mut self, rows: isize) {
+        let last = self.shown.len().saturating_sub(1) as isize;
+        self.selected = (self.selected as isize + rows).clamp(0, last) as usize;
+    }
+
+    fn details(&self) -> Vec<Line<'static>> {
+        let Some(record) = self.current() else { return Vec::new() };
+        let mut lines: Vec<Line> = record.details().into_iter().map(|(label, value)| Line::from(format!("{:<12} {}", label, value))).collect();
+        if self.deleted.contains(record.key().as_ref()) {
+            lines.push(Line::from(""));
+            lines.push(Line::from("marked for deletion").red());
+        }
+        lines
+    }
+
+    fn toggle_delete(&mut self) {
+        let Some(key) = self.current().map(|record| record.key().into_owned()) else { return };
+        if !self.deleted.remove(&key) {
+            self.deleted.insert(key);
+        }
+    }
+
+    fn text(&self) -> Option<String> {
+        self.current().map(|record| record.text().to_string())
+    }
+
+    fn edit(&mut self, text: String) {
+        let Some(mut record) = self.current().cloned() else { return };
+        record.set_text(text);
+        self.edited.insert(record.key().into_owned(), record);
+    }
+
+    fn pending(&self) -> usize {
+        self.deleted.len() + self.edited.keys().filter(|key| !self.deleted.contains(*key)).count()
+    }
+}
+
+enum Mode {
+    Normal,
+    Search(String),
+    Edit(String),
+}
+
+struct App {
+    aircraft: Tab<Aircraft>,
+    airlines: Tab<Airline>,
+    airports: Tab<Airport>,
+    current: usize,
+    mode: Mode,
+    status: String,
+    // Set by a q with changes pending, so a second q discards them.
+    quitting: bool,
+}
+
+impl App {
+    fn pane(&mut self) -> &mut dyn Pane {
+        match self.current {
+            0 => &mut self.aircraft,
+            1 => &mut self.airlines,
+            _ => &mut self.airports,
+        }
+    }
+
+    fn panes(&self) -> [&dyn Pane; 3] {
+        [&self.aircraft, &self.airlines, &self.airports]
+    }
+
+    fn pending(&self) -> usize {
+        self.panes().iter().map(|pane| pane.pending()).sum()
+    }
+}
+
+/// Lists the aircraft, airlines and airports of `store` until `q`, with incremental search,
+/// a detail pane, and deletions and edits of descriptions or names marked in the list, which
+/// `w` writes through the same upserts and deletes as the other commands.
+pub async fn browse(store: &AircraftStore) -> Result<()> {
+    let airlines = store.records::<Airline>(Airline::COLLECTION);
+    let airports = store.records::<Airport>(Airport::COLLECTION);
+    let mut app = App {
+        aircraft: Tab::new("Aircraft", store.find_all().await?),
+        airlines: Tab::new("Airlines", airlines.find(doc! {}).await?),
+        airports: Tab::new("Airports", airports.find(doc! {}).await?),
+        current: 0,
+        mode: Mode::Normal,
+        status: HELP.to_string(),
+        quitting: false,
+    };
+    let mut terminal = ratatui::init();
+    let result = run(&mut terminal, &mut app, store, &airlines, &airports).await;
+    ratatui::restore();
+    result
+}
+
+async fn run(
+    terminal: &mut DefaultTerminal,
+    app: &mut App,
+    store: &AircraftStore,
+    airlines: &RecordStore<Airline>,
+    airports: &RecordStore<Airport>,
+) -> Result<()> {
+    loop {
+        terminal.draw(|frame| draw(frame, app)).map_err(terminal_error)?;
+        let Event::Key(key) = event::read().map_err(terminal_error)? else { continue };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        if let Mode::Normal = app.mode {
+            if key.code == KeyCode::Char('w') {
+                app.status = match write(app, store, airlines, airports).await {
+                    Ok((written, deleted)) => format!("wrote {} records, deleted {}", written, deleted),
+                    Err(error) => format!("error: {}", error),
+                };
+                continue;
+            }
+        }
+        if !handle(app, key) {
+            return Ok(());
+        }
+    }
+}
+
+// Applies `key`, returning false when the browser should close.
+fn handle(app: &mut App, key: KeyEvent) -> bool {
+    let quitting = std::mem::take(&mut app.quitting);
+    match &mut app.mode {
+        Mode::Search(query) => {
+            match key.code {
+                KeyCode::Char(character) => query.push(character),
+                KeyCode::Backspace => {
+                    query.pop();
+                }
+                KeyCode::Esc => query.clear(),
+                _ => {}
+            }
+            let query = query.clone();
+            app.pane().filter(&query);
+            if matches!(key.code, KeyCode::Enter | KeyCode::Esc) {
+                app.mode = Mode::Normal;
+            }
+        }
+        Mode::Edit(text) => match key.code {
+            KeyCode::Char(character) => text.push(character),
+            KeyCode::Backspace => {
+                text.pop();
+            }
+            KeyCode::Enter => {
+                let text = std::mem::take(text);
+                app.pane().edit(text);
+                app.mode = Mode::Normal;
+            }
+            KeyCode::Esc => app.mode = Mode::Normal,
+            _ => {}
+        },
+        Mode::Normal => match key.code {
+            KeyCode::Char('q') | KeyCode::Esc if app.pending() > 0 && !quitting => {
+                app.quitting = true;
+                app.status = format!("{} changes not written: w to write them, q again to discard them", app.pending());
+            }
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Tab => app.current = (app.current + 1) % 3,
+            KeyCode::BackTab => app.current = (app.current + 2) % 3,
+            KeyCode::Down | KeyCode::Char('j') => app.pane().move_by(1),
+            KeyCode::Up | KeyCode::Char('k') => app.pane().move_by(-1),
+            KeyCode::PageDown => app.pane().move_by(PAGE),
+            KeyCode::PageUp => app.pane().move_by(-PAGE),
+            KeyCode::Home => app.pane().move_by(isize::MIN / 2),
+            KeyCode::End => app.pane().move_by(isize::MAX / 2),
+            KeyCode::Char('/') => app.mode = Mode::Search(String::new()),
+            KeyCode::Char('d') => app.pane().toggle_delete(),
+            KeyCode::Char('e') => {
+                if let Some(text) = app.pane().text() {
+                    app.mode = Mode::Edit(text);
+                }
+            }
+            _ => {}
+        },
+    }
+    true
+}
+
+// Upserts the edited records and deletes the marked ones, tab by tab.
+async fn write(
+    app: &mut App,
+    store: &AircraftStore,
+    airlines: &RecordStore<Airline>,
+    airports: &RecordStore<Airport>,
+) -> Result<(u64, u64)> {
+    let (edited, deleted) = app.aircraft.changes();
+    let aircraft = app.aircraft.applied(Storage::upsert(store, &edited).await?, store.delete_by_icao(&deleted).await?);
+    let (edited, deleted) = app.airlines.changes();
+    let airline = app.airlines.applied(Sink::upsert(airlines, &edited).await?, airlines.delete_by_keys(&deleted).await?);
+    let (edited, deleted) = app.airports.changes();
+    let airport = app.airports.applied(Sink::upsert(airports, &edited).await?, airports.delete_by_keys(&deleted).await?);
+    for pane in [&mut app.aircraft as &mut dyn Pane, &mut app.airlines, &mut app.airports] {
+        pane.filter("");
+    }
+    Ok((aircraft.0 + airline.0 + airport.0, aircraft.1 + airline.1 + airport.1))
+}
+
+fn draw(frame: &mut Frame, app: &App) {
+    let [tabs, body, status] = Layout::vertical([Constraint::Length(1), Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+    let [list, details] = Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(body);
+    let panes = app.panes();
+    let titles: Vec<String> = panes.iter().map(|pane| format!("{} ({})", pane.title(), pane.pending())).collect();
+    frame.render_widget(Tabs::new(titles).select(app.current).highlight_style(Style::new().bold().reversed()), tabs);
+
+    let pane = panes[app.current];
+    let rows = List::new(pane.rows())
+        .block(Block::bordered().title(pane.title()))
+        .highlight_style(Style::new().reversed())
+        .highlight_symbol("> ");
+    frame.render_stateful_widget(rows, list, &mut ListState::default().with_selected(pane.selected()));
+    let text = Paragraph::new(pane.details()).block(Block::bordered().title("Details")).wrap(Wrap { trim: false });
+    frame.render_widget(text, details);
+
+    let line = match &app.mode {
+        Mode::Normal => app.status.clone(),
+        Mode::Search(query) => format!("/{}", query),
+        Mode::Edit(text) => format!("edit: {}", text),
+    };
+    frame.render_widget(Paragraph::new(line), status);
+}
+
+fn terminal_error(source: io::Error) -> Error {
+    Error::Write { path: PathBuf::from("terminal"), source }
+}
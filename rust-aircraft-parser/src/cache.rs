@@ -0,0 +1,106 @@
+//! Caching the aircraft lookups of `serve` in memory, so repeated requests for a code stay
+//! off the database. Entries are dropped as a MongoDB change stream reports the writes that
+//! make them stale, and expire after a time to live in case one is missed.
+
+use std::time::Duration;
+use futures::StreamExt;
+use moka::future::Cache;
+use tracing::{info, warn};
+use crate::tail::{self, Change};
+use crate::{Aircraft, AircraftStore};
+
+/// Lookups cached unless told otherwise.
+pub const DEFAULT_CAPACITY: u64 = 10_000;
+
+/// How long a lookup is cached at most unless told otherwise.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(300);
+
+// Wait before reopening a change stream that failed.
+const REOPEN_DELAY: Duration = Duration::from_secs(5);
+
+/// The aircraft looked up by ICAO code or alias, by the upper-case code, least recently
+/// used first out. Codes no aircraft is known by are cached too, as `None`.
+#[derive(Clone, Debug)]
+pub struct LookupCache {
+    entries: Cache<String, Option<Aircraft>>,
+}
+
+impl LookupCache {
+    /// Holds at most `capacity` lookups, each for at most `ttl`.
+    pub fn new(capacity: u64, ttl: Duration) -> Self {
+        let entries = Cache::builder().max_capacity(capacity).time_to_live(ttl).support_invalidation_closures().build();
+        LookupCache { entries }
+    }
+
+    fn key(code: &str) -> String {
+        code.trim().to_ascii_uppercase()
+    }
+
+    /// The cached lookup of `code`, if there is one.
+    pub async fn get(&self, code: &str) -> Option<Option<Aircraft>> {
+        self.entries.get(&LookupCache::key(code)).await
+    }
+
+    pub async fn insert(&self, code: &str, aircraft: Option<Aircraft>) {
+        self.entries.insert(LookupCache::key(code), aircraft).await;
+    }
+
+    /// Drops the lookups `aircraft` being written makes stale: those of its ICAO code and
+    /// aliases, which may have been misses, and those that found it under codes it may no
+    /// longer have.
+    pub async fn invalidate(&self, aircraft: &Aircraft) {
+        for code in std::iter::once(&aircraft.icao_code).chain(&aircraft.aliases) {
+            self.entries.invalidate(&LookupCache::key(code)).await;
+        }
+        let icao_code = aircraft.icao_code.clone();
+        let stale = move |_: &String, cached: &Option<Aircraft>| cached.as_ref().is_some_and(|cached| cached.icao_code == icao_code);
+        // Only fails without support_invalidation_closures, which `new` turns on.
+        let _ = self.entries.invalidate_entries_if(stale);
+    }
+
+    pub fn invalidate_all(&self) {
+        self.entries.invalidate_all();
+    }
+
+    async fn apply(&self, change: &Change) {
+        match &change.aircraft {
+            Some(aircraft) => self.invalidate(aircraft).await,
+            // Deletes, drops and renames don't say which aircraft went.
+            None => self.invalidate_all(),
+        }
+    }
+}
+
+/// Follows the writes to `store`'s collection, dropping the lookups they make stale from
+/// `cache`, for as long as the server runs. When the change stream fails the whole cache is
+/// dropped and the stream reopened; when it cannot be opened at all, as on a standalone
+/// server, the entries only expire.
+pub async fn invalidate_on_change(store: AircraftStore, cache: LookupCache) {
+    let mut reopened = false;
+    loop {
+        let changes = match tail::changes(&store).await {
+            Ok(changes) => changes,
+            Err(error) => {
+                warn!(%error, "cannot follow changes; cached lookups only expire");
+                return;
+            }
+        };
+        // Writes made while the stream was closed went unseen.
+        if reopened {
+            cache.invalidate_all();
+        }
+        futures::pin_mut!(changes);
+        while let Some(change) = changes.next().await {
+            match change {
+                Ok(change) => cache.apply(&change).await,
+                Err(error) => {
+                    warn!(%error, "change stream failed");
+                    break;
+                }
+            }
+        }
+        info!(delay_secs = REOPEN_DELAY.as_secs(), "reopening the change stream");
+        tokio::time::sleep(REOPEN_DELAY).await;
+        reopened = true;
+    }
+}
@@ -0,0 +1,77 @@
+//! Saving how far a load got, so `load --resume` can continue an interrupted run.
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use crate::load::Progress;
+use crate::{Error, Result};
+
+/// How far the load `load_id` of an input with `checksum` got: its first `offset` entries
+/// are written.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Checkpoint {
+    pub load_id: String,
+    pub checksum: String,
+    pub offset: u64,
+}
+
+impl Checkpoint {
+    /// Where the checkpoint of a load of `input` is kept: `<input>.checkpoint` next to it.
+    pub fn path(input: &Path) -> PathBuf {
+        let mut path = input.as_os_str().to_owned();
+        path.push(".checkpoint");
+        PathBuf::from(path)
+    }
+
+    /// The checkpoint saved at `path`, if any.
+    pub fn read(path: &Path) -> Result<Option<Checkpoint>> {
+        match fs::read(path) {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+            Err(source) => Err(Error::Io { path: path.to_path_buf(), source }),
+        }
+    }
+
+    /// Removes the checkpoint at `path` once its load finished.
+    pub fn remove(path: &Path) -> Result<()> {
+        match fs::remove_file(path) {
+            Err(error) if error.kind() != ErrorKind::NotFound => Err(Error::Write { path: path.to_path_buf(), source: error }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Saves a [`Checkpoint`] to `path` whenever more of the input is written, `base` entries
+/// past the start of the input for a resumed load, and passes every batch on to `inner`.
+pub struct Checkpointer<'a> {
+    pub path: PathBuf,
+    pub load_id: String,
+    pub checksum: String,
+    pub base: u64,
+    pub inner: &'a dyn Progress,
+}
+
+impl Progress for Checkpointer<'_> {
+    fn read(&self, batch: u64, parsed: u64, rejected: u64) {
+        self.inner.read(batch, parsed, rejected);
+    }
+
+    fn written(&self, batch: u64, written: u64, failed: u64) {
+        self.inner.written(batch, written, failed);
+    }
+
+    fn committed(&self, consumed: u64) {
+        self.inner.committed(consumed);
+        let checkpoint = Checkpoint { load_id: self.load_id.clone(), checksum: self.checksum.clone(), offset: self.base + consumed };
+        // A checkpoint not saved only means a resume repeats more of the input.
+        let saved = serde_json::to_vec(&checkpoint).map_err(Error::from).and_then(|json| {
+            fs::write(&self.path, json).map_err(|source| Error::Write { path: self.path.clone(), source })
+        });
+        if let Err(error) = saved {
+            warn!(path = %self.path.display(), %error, "could not save checkpoint");
+        }
+    }
+}
@@ -0,0 +1,294 @@
+//! Deriving manufacturer, model, variant and body type from aircraft descriptions.
+
+use std::time::Instant;
+use mongodb::bson::{doc, Document};
+use serde::Serialize;
+use tracing::info;
+use crate::{AircraftStore, Result, Storage};
+
+/// The broad class of an aircraft type.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyType {
+    /// Twin-aisle jets, e.g. the Boeing 777.
+    Widebody,
+    /// Single-aisle mainline jets, e.g. the Airbus A320.
+    Narrowbody,
+    /// Regional jets, e.g. the Embraer 175.
+    Regional,
+    /// Turboprops, e.g. the ATR 72.
+    Turboprop,
+    Helicopter,
+}
+
+impl BodyType {
+    /// The name stored in `bodyType`, e.g. `widebody`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BodyType::Widebody => "widebody",
+            BodyType::Narrowbody => "narrowbody",
+            BodyType::Regional => "regional",
+            BodyType::Turboprop => "turboprop",
+            BodyType::Helicopter => "helicopter",
+        }
+    }
+}
+
+// Manufacturers as descriptions spell them, the longer of two sharing a start first.
+const MANUFACTURERS: &[&str] = &[
+    "Airbus Helicopters",
+    "Airbus",
+    "Boeing",
+    "Embraer",
+    "Bombardier",
+    "Canadair",
+    "De Havilland Canada",
+    "De Havilland",
+    "ATR",
+    "McDonnell Douglas",
+    "Douglas",
+    "Lockheed Martin",
+    "Lockheed",
+    "British Aerospace",
+    "BAe",
+    "Avro",
+    "Fokker",
+    "Saab",
+    "Dornier",
+    "Fairchild Dornier",
+    "Antonov",
+    "Ilyushin",
+    "Tupolev",
+    "Yakovlev",
+    "Sukhoi",
+    "Irkut",
+    "COMAC",
+    "Mitsubishi",
+    "Xian",
+    "Beechcraft",
+    "Beech",
+    "Cessna",
+    "Pilatus",
+    "Gulfstream",
+    "Dassault",
+    "Bell",
+    "Sikorsky",
+    "Eurocopter",
+    "AgustaWestland",
+    "Robinson",
+];
+
+// Body types by a lowercase part of the description, the first rule matching winning.
+const BODY_TYPES: &[(&str, BodyType)] = &[
+    ("helicopter", BodyType::Helicopter),
+    ("airbus helicopters", BodyType::Helicopter),
+    ("eurocopter", BodyType::Helicopter),
+    ("sikorsky", BodyType::Helicopter),
+    ("agusta", BodyType::Helicopter),
+    ("robinson", BodyType::Helicopter),
+    ("bell ", BodyType::Helicopter),
+    ("mil mi-", BodyType::Helicopter),
+    ("kamov", BodyType::Helicopter),
+    ("turboprop", BodyType::Turboprop),
+    ("atr ", BodyType::Turboprop),
+    ("atr-", BodyType::Turboprop),
+    ("dash 8", BodyType::Turboprop),
+    ("dhc-8", BodyType::Turboprop),
+    ("dhc-6", BodyType::Turboprop),
+    ("twin otter", BodyType::Turboprop),
+    ("q400", BodyType::Turboprop),
+    ("saab 340", BodyType::Turboprop),
+    ("saab 2000", BodyType::Turboprop),
+    ("fokker 50", BodyType::Turboprop),
+    ("jetstream", BodyType::Turboprop),
+    ("emb 120", BodyType::Turboprop),
+    ("brasilia", BodyType::Turboprop),
+    ("king air", BodyType::Turboprop),
+    ("beech 1900", BodyType::Turboprop),
+    ("dornier 228", BodyType::Turboprop),
+    ("caravan", BodyType::Turboprop),
+    ("pc-12", BodyType::Turboprop),
+    ("an-24", BodyType::Turboprop),
+    ("an-26", BodyType::Turboprop),
+    ("ma60", BodyType::Turboprop),
+    ("boeing 747", BodyType::Widebody),
+    ("boeing 767", BodyType::Widebody),
+    ("boeing 777", BodyType::Widebody),
+    ("boeing 787", BodyType::Widebody),
+    ("airbus a300", BodyType::Widebody),
+    ("airbus a310", BodyType::Widebody),
+    ("airbus a330", BodyType::Widebody),
+    ("airbus a340", BodyType::Widebody),
+    ("airbus a350", BodyType::Widebody),
+    ("airbus a380", BodyType::Widebody),
+    ("dc-10", BodyType::Widebody),
+    ("md-11", BodyType::Widebody),
+    ("l-1011", BodyType::Widebody),
+    ("il-86", BodyType::Widebody),
+    ("il-96", BodyType::Widebody),
+    ("crj", BodyType::Regional),
+    ("regional jet", BodyType::Regional),
+    ("erj", BodyType::Regional),
+    ("embraer 1", BodyType::Regional),
+    ("embraer e1", BodyType::Regional),
+    ("fokker 70", BodyType::Regional),
+    ("avro rj", BodyType::Regional),
+    ("bae 146", BodyType::Regional),
+    ("superjet", BodyType::Regional),
+    ("arj21", BodyType::Regional),
+    ("328jet", BodyType::Regional),
+    ("spacejet", BodyType::Regional),
+    ("boeing 707", BodyType::Narrowbody),
+    ("boeing 717", BodyType::Narrowbody),
+    ("boeing 727", BodyType::Narrowbody),
+    ("boeing 737", BodyType::Narrowbody),
+    ("boeing 757", BodyType::Narrowbody),
+    ("airbus a318", BodyType::Narrowbody),
+    ("airbus a319", BodyType::Narrowbody),
+    ("airbus a32", BodyType::Narrowbody),
+    ("airbus a220", BodyType::Narrowbody),
+    ("dc-9", BodyType::Narrowbody),
+    ("md-8", BodyType::Narrowbody),
+    ("md-90", BodyType::Narrowbody),
+    ("fokker 100", BodyType::Narrowbody),
+    ("c919", BodyType::Narrowbody),
+];
+
+/// What a description says about its type. Parts the description doesn't give are empty.
+#[derive(Serialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Classification {
+    /// Manufacturer, e.g. `Boeing`.
+    pub manufacturer: String,
+    /// Model, e.g. `777`.
+    pub model: String,
+    /// Variant of the model, e.g. `300ER`.
+    pub variant: String,
+    pub body_type: Option<BodyType>,
+}
+
+impl Classification {
+    /// The parts that are present, under their stored field names. Empty parts are left
+    /// out so they never blank a field already stored.
+    pub fn fields(&self) -> Document {
+        let mut fields = Document::new();
+        for (name, value) in [("manufacturer", &self.manufacturer), ("model", &self.model), ("variant", &self.variant)] {
+            if !value.is_empty() {
+                fields.insert(name, value);
+            }
+        }
+        if let Some(body_type) = self.body_type {
+            fields.insert("bodyType", body_type.as_str());
+        }
+        fields
+    }
+}
+
+/// Splits `description` into manufacturer, model and variant, e.g. `Boeing 777-300ER` into
+/// `Boeing`, `777` and `300ER`, and looks its body type up in the built-in rules. The
+/// manufacturer is a known one the description starts with, or else its first word; the
+/// model is the word after it up to a hyphen, and the variant the rest.
+pub fn classify(description: &str) -> Classification {
+    let description = description.split_whitespace().collect::<Vec<_>>().join(" ");
+    let lowercase = description.to_lowercase();
+    let manufacturer = MANUFACTURERS
+        .iter()
+        .find(|name| {
+            description.get(..name.len()).is_some_and(|start| start.eq_ignore_ascii_case(name))
+                && matches!(description.as_bytes().get(name.len()), None | Some(b' ' | b'-'))
+        })
+        .map(|name| &description[..name.len()])
+        .unwrap_or_else(|| description.split(' ').next().unwrap_or_default());
+    let rest = description[manufacturer.len()..].trim_start_matches([' ', '-']);
+    let (model, variant) = match rest.split_once(' ') {
+        Some((word, more)) => match word.split_once('-') {
+            Some((model, variant)) => (model.to_string(), format!("{} {}", variant, more)),
+            None => (word.to_string(), more.to_string()),
+        },
+        None => match rest.split_once('-') {
+            Some((model, variant)) => (model.to_string(), variant.to_string()),
+            None => (rest.to_string(), String::new()),
+        },
+    };
+    let body_type = BODY_TYPES.iter().find(|(part, _)| lowercase.contains(part)).map(|(_, body_type)| *body_type);
+    Classification { manufacturer: manufacturer.to_string(), model, variant: variant.trim().to_string(), body_type }
+}
+
+/// Outcome of [`classify_stored`].
+#[derive(Debug, Default)]
+pub struct ClassifySummary {
+    /// Stored aircraft read.
+    pub parsed: u64,
+    /// Stored aircraft that received derived fields.
+    pub classified: u64,
+    /// ICAO codes of the aircraft no body type rule matched.
+    pub unclassified: Vec<String>,
+}
+
+/// Adds the fields [`classify`] derives from the description of every stored aircraft to its
+/// document, next to the fields the loader wrote. `manufacturer` and `model` are the fields
+/// DOC 8643 enrichment sets too; whichever runs last wins.
+pub async fn classify_stored(store: &AircraftStore) -> Result<ClassifySummary> {
+    let started = Instant::now();
+    let mut summary = ClassifySummary::default();
+    for aircraft in store.find_all().await? {
+        summary.parsed += 1;
+        let classification = classify(&aircraft.description);
+        if classification.body_type.is_none() {
+            summary.unclassified.push(aircraft.icao_code.clone());
+        }
+        let fields = classification.fields();
+        if fields.is_empty() {
+            continue;
+        }
+        if store.merge(doc! { &store.field_names().icao_code: &aircraft.icao_code }, fields).await? {
+            summary.classified += 1;
+        }
+    }
+    info!(
+        parsed = summary.parsed,
+        classified = summary.classified,
+        unclassified = summary.unclassified.len(),
+        elapsed_ms = started.elapsed().as_millis() as u64,
+        "classify finished"
+    );
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_a_description_into_its_parts() {
+        let classification = classify("Boeing 777-300ER");
+        assert_eq!(
+            classification,
+            Classification { manufacturer: "Boeing".to_string(), model: "777".to_string(), variant: "300ER".to_string(), body_type: Some(BodyType::Widebody) }
+        );
+    }
+
+    #[test]
+    fn prefers_the_longest_known_manufacturer() {
+        let classification = classify("De Havilland Canada DHC-8-400 Dash 8");
+        assert_eq!(classification.manufacturer, "De Havilland Canada");
+        assert_eq!(classification.model, "DHC");
+        assert_eq!(classification.variant, "8-400 Dash 8");
+        assert_eq!(classification.body_type, Some(BodyType::Turboprop));
+    }
+
+    #[test]
+    fn falls_back_to_the_first_word() {
+        let classification = classify("  Pipistrel   Velis ");
+        assert_eq!(classification.manufacturer, "Pipistrel");
+        assert_eq!(classification.model, "Velis");
+        assert_eq!(classification.variant, "");
+        assert_eq!(classification.body_type, None);
+    }
+
+    #[test]
+    fn leaves_empty_parts_out_of_the_fields() {
+        assert_eq!(classify("Pipistrel Velis").fields(), doc! { "manufacturer": "Pipistrel", "model": "Velis" });
+        assert_eq!(classify("Robinson R44").fields().get_str("bodyType"), Ok("helicopter"));
+    }
+}
@@ -0,0 +1,1824 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsString;
+use std::net::IpAddr;
+#[cfg(feature = "server")]
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use encoding_rs::Encoding;
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgGroup, ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use mongodb::options::{Acknowledgment, AuthMechanism, ReadPreference, ReadPreferenceOptions};
+use uuid::Uuid;
+use rust_aircraft_parser::cache;
+#[cfg(feature = "server")]
+use rust_aircraft_parser::cache::LookupCache;
+#[cfg(feature = "server")]
+use rust_aircraft_parser::daemon::DaemonOptions;
+use rust_aircraft_parser::dedup::DedupStrategy;
+use rust_aircraft_parser::export::{self, Direction, ExportFormat, ExportOptions};
+use rust_aircraft_parser::fields::{FieldNames, MissingCode};
+use rust_aircraft_parser::filter::Filter;
+use rust_aircraft_parser::hooks::Hook;
+use rust_aircraft_parser::ids::{IdStrategy, UuidEncoding, DEFAULT_NAMESPACE};
+use rust_aircraft_parser::input::{self, CsvColumns, Format, InputOptions, OurAirportsFilter, XmlMapping};
+use rust_aircraft_parser::load::{LoadOptions, DEFAULT_BATCH_SIZE, DEFAULT_BUFFER};
+use rust_aircraft_parser::merge::Source;
+#[cfg(feature = "s3")]
+use rust_aircraft_parser::s3::S3Options;
+use rust_aircraft_parser::quality::{ReportFormat, MIN_DESCRIPTION};
+use rust_aircraft_parser::schedule::Schedule;
+use rust_aircraft_parser::schema::ValidationLevel;
+#[cfg(feature = "server")]
+use rust_aircraft_parser::server::AccessOptions;
+use rust_aircraft_parser::storage::{ClientSettings, Inactive};
+use rust_aircraft_parser::sync::{MergePolicy, SyncOptions};
+use rust_aircraft_parser::webhook::{Webhook, WebhookFormat};
+use rust_aircraft_parser::{wikidata, Error, Result};
+use crate::config::Config;
+
+#[derive(Parser, Debug)]
+#[command(name = "rust-aircraft-parser", version, about = "Loads aircraft reference data into MongoDB")]
+pub struct Cli {
+    /// Write a man page for the tool and each subcommand into this directory, then exit
+    #[arg(long, value_name = "DIR", exclusive = true)]
+    pub generate_man: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub global: GlobalArgs,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+impl Cli {
+    /// Parses the command line, then fills the options left unset from --config or a
+    /// `parser.toml` in the working directory, and its --profile.
+    pub fn load() -> Result<Cli> {
+        // --generate-man stands in for the subcommand.
+        let matches = Cli::command().subcommand_required(false).get_matches();
+        if let Some(dir) = matches.get_one::<PathBuf>("generate_man") {
+            generate_man(dir)?;
+            std::process::exit(0);
+        }
+        let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|error| error.exit());
+        cli.global.typed = typed_globals(&matches);
+        let config = match &cli.global.config {
+            Some(path) => Some(Config::read(path)?),
+            None => Config::discover()?,
+        };
+        // Without a file, a typed --profile is still refused.
+        config.unwrap_or_default().apply(&mut cli, &matches)?;
+        if let Some(tenant) = cli.global.tenant.clone() {
+            cli.global = cli.global.for_tenant(&tenant)?;
+        }
+        Ok(cli)
+    }
+}
+
+// The global options typed on the command line, each with its values as typed. Global
+// options are handed down to every subcommand, so the deepest one has them all, wherever
+// they were typed.
+fn typed_globals(matches: &ArgMatches) -> Vec<OsString> {
+    let mut leaf = matches;
+    while let Some((_, subcommand)) = leaf.subcommand() {
+        leaf = subcommand;
+    }
+    let mut typed = Vec::new();
+    for arg in GlobalArgs::augment_args(clap::Command::new("globals")).get_arguments() {
+        let id = arg.get_id().as_str();
+        let (Some(long), Some(ValueSource::CommandLine)) = (arg.get_long(), leaf.value_source(id)) else { continue };
+        let flag = OsString::from(format!("--{}", long));
+        match arg.get_action() {
+            ArgAction::Count => typed.extend(std::iter::repeat_n(flag, leaf.get_count(id).into())),
+            action if action.takes_values() => {
+                for value in leaf.get_raw(id).into_iter().flatten() {
+                    let mut option = flag.clone();
+                    option.push("=");
+                    option.push(value);
+                    typed.push(option);
+                }
+            }
+            _ => typed.push(flag),
+        }
+    }
+    typed
+}
+
+// Writes `rust-aircraft-parser.1`, and a page per subcommand such as
+// `rust-aircraft-parser-load.1`, into `dir`.
+fn generate_man(dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(dir).map_err(|source| Error::Write { path: dir.to_path_buf(), source })?;
+    clap_mangen::generate_to(Cli::command(), dir).map_err(|source| Error::Write { path: dir.to_path_buf(), source })
+}
+
+/// Prints the completion script of `shell` for the whole command line.
+pub fn write_completions(shell: Shell) {
+    let mut command = Cli::command();
+    let name = command.get_name().to_string();
+    clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct GlobalArgs {
+    /// Config file providing defaults for these options, parser.toml in the working directory when omitted
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Profile of the config file, e.g. prod, whose [profile.NAME] table overrides the rest of the file
+    #[arg(long, global = true, env = "APP_ENV")]
+    pub profile: Option<String>,
+
+    /// Fetch the MongoDB connection string from this secret instead of MONGODB_URL: an arn:aws:secretsmanager: ARN (secretsmanager feature) or vault://PATH, with #FIELD to pick a field
+    #[arg(long, global = true, env = "MONGO_URI_SECRET")]
+    pub mongo_uri_secret: Option<String>,
+
+    /// Path of the aircraft file to read
+    #[arg(short, long, global = true, default_value = "aircraft.json")]
+    pub input: PathBuf,
+
+    /// Storage backend to read from and write to
+    #[arg(long, global = true, value_enum, default_value_t = Backend::Mongo)]
+    pub backend: Backend,
+
+    /// SQLite database file, created if missing
+    #[cfg(feature = "sqlite")]
+    #[arg(long, global = true, default_value = "aircraft.db")]
+    pub db: PathBuf,
+
+    /// Kafka topic written aircraft are published to, keyed by ICAO code; with another backend they are published in addition to being written there
+    #[cfg(feature = "kafka")]
+    #[arg(long, global = true, env = "KAFKA_TOPIC")]
+    pub topic: Option<String>,
+
+    /// Seconds after which the keys the redis backend writes expire; they never do when omitted
+    #[cfg(feature = "redis")]
+    #[arg(long, global = true, value_name = "SECONDS")]
+    pub redis_ttl: Option<u64>,
+
+    /// Name of the MongoDB database
+    #[arg(long, global = true, env = "MONGODB_DATABASE", default_value = "flights-admin")]
+    pub database: String,
+
+    /// Name of the MongoDB collection (or SQL table)
+    #[arg(long, global = true, env = "MONGODB_COLLECTION", default_value = "aircraft")]
+    pub collection: String,
+
+    /// How identifiers of newly written records are generated
+    #[arg(long, global = true, value_enum, default_value_t = IdStrategyArg::Random)]
+    pub id_strategy: IdStrategyArg,
+
+    /// Namespace for --id-strategy uuid5
+    #[arg(long, global = true, default_value_t = DEFAULT_NAMESPACE)]
+    pub id_namespace: Uuid,
+
+    /// Customer the run writes for: its database gets the -TENANT suffix and its uuid5 ids their own namespace, unless [tenants.TENANT] of the config says otherwise, and load history records it
+    #[arg(long, global = true, env = "TENANT", value_parser = parse_tenant, conflicts_with = "all_tenants")]
+    pub tenant: Option<String>,
+
+    /// Run the command once for each tenant of [tenants] in the config, carrying on past those that fail
+    #[arg(long, global = true)]
+    pub all_tenants: bool,
+
+    /// Store UUID _ids in MongoDB as strings, as earlier versions did, instead of binaries
+    #[arg(long, global = true)]
+    pub string_ids: bool,
+
+    /// Naming of the aircraft fields in MongoDB documents
+    #[arg(long, global = true, value_enum, default_value_t = FieldCase::Camel)]
+    pub field_case: FieldCase,
+
+    /// Store an aircraft field of MongoDB documents under another name, e.g. icaoCode=icao,description=name
+    #[arg(long, global = true, value_delimiter = ',', value_parser = parse_rename)]
+    pub rename_field: Vec<(String, String)>,
+
+    /// How MongoDB documents store the IATA code of an aircraft without one
+    #[arg(long, global = true, value_enum, default_value_t = MissingCode::Empty)]
+    pub missing_iata: MissingCode,
+
+    /// Acknowledgement MongoDB writes wait for: majority, a number of nodes or a tag set; from MONGODB_URL when omitted
+    #[arg(long, global = true, env = "MONGODB_WRITE_CONCERN", value_parser = parse_write_concern)]
+    pub write_concern: Option<Acknowledgment>,
+
+    /// MongoDB servers reads are sent to; from MONGODB_URL when omitted
+    #[arg(long, global = true, value_enum)]
+    pub read_preference: Option<ReadPreferenceArg>,
+
+    /// Seconds a MongoDB operation waits for a suitable server
+    #[arg(long, global = true, value_name = "SECONDS")]
+    pub server_selection_timeout: Option<u64>,
+
+    /// Seconds opening a MongoDB connection may take
+    #[arg(long, global = true, value_name = "SECONDS")]
+    pub connect_timeout: Option<u64>,
+
+    /// Application name reported to MongoDB, shown in its logs
+    #[arg(long, global = true)]
+    pub app_name: Option<String>,
+
+    /// Connect to MongoDB over TLS; implied by the other --tls options
+    #[arg(long, global = true)]
+    pub tls: bool,
+
+    /// PEM bundle of the certificate authorities the MongoDB server certificate is checked against
+    #[arg(long, global = true, value_name = "PATH")]
+    pub tls_ca_file: Option<PathBuf>,
+
+    /// PEM file holding the client certificate and its private key, for mutual TLS with MongoDB
+    #[arg(long, global = true, value_name = "PATH")]
+    pub tls_certificate_key_file: Option<PathBuf>,
+
+    /// Accept any MongoDB server certificate; only for testing
+    #[arg(long, global = true)]
+    pub tls_allow_invalid_certificates: bool,
+
+    /// How to authenticate to MongoDB; x509 uses the subject of --tls-certificate-key-file as the user
+    #[arg(long, global = true, value_enum)]
+    pub auth_mechanism: Option<AuthMechanismArg>,
+
+    /// Database the MongoDB user is defined in; admin by default, $external for x509
+    #[arg(long, global = true)]
+    pub auth_source: Option<String>,
+
+    /// MongoDB user, over the one of MONGODB_URL
+    #[arg(long, global = true, env = "MONGODB_USERNAME")]
+    pub username: Option<String>,
+
+    /// Password of --username, only taken from the environment
+    #[arg(long, global = true, env = "MONGODB_PASSWORD", hide = true, hide_env_values = true)]
+    pub password: Option<String>,
+
+    /// Keep every version of the aircraft written in aircraft_history, valid from when it was written until it changed, for query --as-of (MongoDB only)
+    #[arg(long, global = true)]
+    pub history: bool,
+
+    /// Record every insert, update and delete of an aircraft in audit_log, with its fields before and after, for the history command (MongoDB only)
+    #[arg(long, global = true)]
+    pub audit: bool,
+
+    /// Who audit_log records as making the writes; the USER of the environment when omitted
+    #[arg(long, global = true, env = "AUDIT_ACTOR")]
+    pub actor: Option<String>,
+
+    /// Curated corrections applied to every aircraft loads and syncs read, and recorded on the MongoDB documents they correct; overrides.json in the working directory when omitted
+    #[arg(long, global = true, env = "OVERRIDES_FILE")]
+    pub overrides: Option<PathBuf>,
+
+    /// Times a transient MongoDB failure is retried before giving up
+    #[arg(long, global = true, default_value_t = 3)]
+    pub max_retries: u32,
+
+    /// Hours the documents of staging collections are kept before a TTL index removes them, should the load writing them be abandoned
+    #[arg(long, value_name = "HOURS", global = true, default_value_t = 24, value_parser = clap::value_parser!(u64).range(1..))]
+    pub staging_ttl: u64,
+
+    /// Log more detail; repeat for per-record tracing
+    #[arg(short, long, global = true, action = ArgAction::Count, conflicts_with = "quiet")]
+    pub verbose: u8,
+
+    /// Log less; repeat to only show errors
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    pub quiet: u8,
+
+    /// Format of the log lines written to stderr
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// Don't draw a progress bar on stderr during loads; it is only drawn on a terminal anyway
+    #[arg(long, global = true)]
+    pub no_progress: bool,
+
+    /// POST a JSON summary of each load, sync or enrich run, finished or failed, to this URL
+    #[arg(long, global = true, env = "NOTIFY_URL")]
+    pub notify_url: Option<String>,
+
+    /// Body POSTed to --notify-url
+    #[arg(long, global = true, value_enum, default_value_t = WebhookFormat::Json)]
+    pub notify_format: WebhookFormat,
+
+    // Staging collections a load --atomic writes to, by the live collection they replace.
+    #[arg(skip)]
+    pub staged: BTreeMap<String, String>,
+
+    // The --output of the load, sync or watch running.
+    #[arg(skip)]
+    pub run_output: RunOutput,
+
+    // The connection string of the config file or profile, used over MONGODB_URL.
+    #[arg(skip)]
+    pub mongodb_url: Option<String>,
+
+    // Set by require_yes in the config file or profile: destructive commands need --yes even
+    // on a terminal.
+    #[arg(skip)]
+    pub require_yes: bool,
+
+    // Set from the --inactive of query and export: which aircraft lookups see.
+    #[arg(skip)]
+    pub inactive: Inactive,
+
+    // The global options as typed, set by Cli::load, so the loads the daemon runs are
+    // configured like it.
+    #[arg(skip)]
+    pub typed: Vec<OsString>,
+
+    // The [tenants] of the config file or profile, by name.
+    #[arg(skip)]
+    pub tenants: BTreeMap<String, Tenant>,
+}
+
+/// Where a tenant's data goes, as [tenants.NAME] of the config gives it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Tenant {
+    /// The --database with the -NAME suffix when not given.
+    pub database: Option<String>,
+    /// The --collection when not given.
+    pub collection: Option<String>,
+    /// Derived from --id-namespace and the name when not given.
+    pub id_namespace: Option<Uuid>,
+}
+
+impl GlobalArgs {
+    /// These options for a run writing for `tenant`: its database, collection and uuid5
+    /// namespace in place of the shared ones. Tenants of the config must be listed there.
+    pub fn for_tenant(&self, tenant: &str) -> Result<GlobalArgs> {
+        let configured = match self.tenants.get(tenant) {
+            Some(configured) => configured.clone(),
+            None if self.tenants.is_empty() => Tenant::default(),
+            None => return Err(Error::Config(format!("no tenant {:?} in config", tenant))),
+        };
+        Ok(GlobalArgs {
+            database: configured.database.unwrap_or_else(|| format!("{}-{}", self.database, tenant)),
+            collection: configured.collection.unwrap_or_else(|| self.collection.clone()),
+            id_namespace: configured.id_namespace.unwrap_or_else(|| Uuid::new_v5(&self.id_namespace, tenant.as_bytes())),
+            tenant: Some(tenant.to_string()),
+            ..self.clone()
+        })
+    }
+
+    /// Whether loads draw a progress bar: not with JSON logs, which are meant for machines.
+    pub fn progress(&self) -> bool {
+        !self.no_progress && matches!(self.log_format, LogFormat::Text)
+    }
+
+    /// Where run summaries are POSTed, if anywhere.
+    pub fn webhook(&self) -> Option<Webhook> {
+        self.notify_url.as_deref().map(|url| Webhook::new(url, self.notify_format))
+    }
+
+    pub fn id_strategy(&self) -> IdStrategy {
+        match self.id_strategy {
+            IdStrategyArg::Random => IdStrategy::Random,
+            IdStrategyArg::Uuid5 => IdStrategy::Uuid5 { namespace: self.id_namespace },
+            IdStrategyArg::Natural => IdStrategy::Natural,
+            IdStrategyArg::Composite => IdStrategy::Composite,
+        }
+    }
+
+    pub fn uuid_encoding(&self) -> UuidEncoding {
+        match self.string_ids {
+            true => UuidEncoding::String,
+            false => UuidEncoding::Binary,
+        }
+    }
+
+    /// Who audited writes are recorded as made by, with --audit.
+    pub fn actor(&self) -> Option<String> {
+        let actor = self.actor.clone().or_else(|| env::var("USER").ok()).unwrap_or_else(|| "unknown".to_string());
+        self.audit.then_some(actor)
+    }
+
+    /// The MongoDB client options given, to be set over those of MONGODB_URL.
+    pub fn client_settings(&self) -> ClientSettings {
+        ClientSettings {
+            write_concern: self.write_concern.clone(),
+            read_preference: self.read_preference.map(ReadPreferenceArg::read_preference),
+            server_selection_timeout: self.server_selection_timeout.map(Duration::from_secs),
+            connect_timeout: self.connect_timeout.map(Duration::from_secs),
+            app_name: self.app_name.clone(),
+            tls: self.tls,
+            tls_ca_file: self.tls_ca_file.clone(),
+            tls_certificate_key_file: self.tls_certificate_key_file.clone(),
+            tls_allow_invalid_certificates: self.tls_allow_invalid_certificates,
+            auth_mechanism: self.auth_mechanism.map(AuthMechanismArg::mechanism),
+            auth_source: self.auth_source.clone(),
+            username: self.username.clone(),
+            password: self.password.clone(),
+        }
+    }
+
+    /// Whether a MongoDB connection string is given, by --mongo-uri-secret, the config file
+    /// or MONGODB_URL.
+    #[cfg(feature = "embedded")]
+    pub fn has_mongodb(&self) -> bool {
+        self.mongo_uri_secret.is_some() || self.mongodb_url.is_some() || env::var_os("MONGODB_URL").is_some()
+    }
+
+    /// The stored field names chosen with --field-case and --rename-field.
+    pub fn field_names(&self) -> Result<FieldNames> {
+        let fields = match self.field_case {
+            FieldCase::Camel => FieldNames::default(),
+            FieldCase::Snake => FieldNames::snake_case(),
+        };
+        let fields = fields.rename(self.rename_field.iter().map(|(field, name)| (field.as_str(), name.as_str())))?;
+        Ok(fields.with_missing_iata(self.missing_iata))
+    }
+
+    /// The maximum log level selected by -v/-q, starting from INFO.
+    pub fn log_level(&self) -> tracing::Level {
+        match i16::from(self.verbose) - i16::from(self.quiet) {
+            i16::MIN..=-2 => tracing::Level::ERROR,
+            -1 => tracing::Level::WARN,
+            0 => tracing::Level::INFO,
+            1 => tracing::Level::DEBUG,
+            _ => tracing::Level::TRACE,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IdStrategyArg {
+    /// Random UUIDv4, different on every load
+    Random,
+    /// UUIDv5 of --id-namespace and the ICAO code, identical on every load
+    Uuid5,
+    /// The ICAO code itself, which saves the separate ICAO code index
+    Natural,
+    /// The ICAO and IATA codes joined by a colon, e.g. B738:738
+    Composite,
+}
+
+/// One of the --fields of query and export.
+pub fn parse_column(value: &str) -> std::result::Result<String, String> {
+    let value = value.trim();
+    match export::COLUMNS.contains(&value) {
+        true => Ok(value.to_string()),
+        false => Err(format!("expected one of {}", export::COLUMNS.join(", "))),
+    }
+}
+
+/// A --write-concern: `majority`, a number of nodes or the name of a custom tag set.
+pub fn parse_write_concern(value: &str) -> std::result::Result<Acknowledgment, String> {
+    Ok(match value {
+        "majority" => Acknowledgment::Majority,
+        "" => return Err("the write concern cannot be empty".to_string()),
+        _ => match value.parse::<u32>() {
+            Ok(0) => return Err("unacknowledged writes (0) are not supported".to_string()),
+            Ok(nodes) => Acknowledgment::Nodes(nodes),
+            Err(_) => Acknowledgment::Custom(value.to_string()),
+        },
+    })
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadPreferenceArg {
+    /// The primary only
+    Primary,
+    /// The primary, or a secondary while there is none
+    PrimaryPreferred,
+    /// Secondaries only
+    Secondary,
+    /// A secondary, or the primary while there is none
+    SecondaryPreferred,
+    /// The member with the lowest latency
+    Nearest,
+}
+
+impl ReadPreferenceArg {
+    fn read_preference(self) -> ReadPreference {
+        let options = ReadPreferenceOptions::default();
+        match self {
+            ReadPreferenceArg::Primary => ReadPreference::Primary,
+            ReadPreferenceArg::PrimaryPreferred => ReadPreference::PrimaryPreferred { options },
+            ReadPreferenceArg::Secondary => ReadPreference::Secondary { options },
+            ReadPreferenceArg::SecondaryPreferred => ReadPreference::SecondaryPreferred { options },
+            ReadPreferenceArg::Nearest => ReadPreference::Nearest { options },
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuthMechanismArg {
+    /// SCRAM-SHA-1 with --username and MONGODB_PASSWORD
+    ScramSha1,
+    /// SCRAM-SHA-256 with --username and MONGODB_PASSWORD
+    ScramSha256,
+    /// The client certificate of --tls-certificate-key-file
+    X509,
+}
+
+impl AuthMechanismArg {
+    fn mechanism(self) -> AuthMechanism {
+        match self {
+            AuthMechanismArg::ScramSha1 => AuthMechanism::ScramSha1,
+            AuthMechanismArg::ScramSha256 => AuthMechanism::ScramSha256,
+            AuthMechanismArg::X509 => AuthMechanism::MongoDbX509,
+        }
+    }
+}
+
+// A cron expression of --schedule.
+fn parse_schedule(value: &str) -> std::result::Result<Schedule, String> {
+    value.parse().map_err(|error: rust_aircraft_parser::Error| error.to_string())
+}
+
+// An RFC 3339 time or a date of --as-of.
+fn parse_instant(value: &str) -> std::result::Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| format!("{:?} is neither an RFC 3339 time nor a YYYY-MM-DD date", value))?;
+    Ok(date.and_time(NaiveTime::MIN).and_utc())
+}
+
+/// A point on the Earth given as `LAT,LON`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Point {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+fn parse_point(value: &str) -> std::result::Result<Point, String> {
+    let invalid = || format!("{:?} is not LAT,LON in decimal degrees", value);
+    let (latitude, longitude) = value.split_once(',').ok_or_else(invalid)?;
+    let latitude: f64 = latitude.trim().parse().map_err(|_| invalid())?;
+    let longitude: f64 = longitude.trim().parse().map_err(|_| invalid())?;
+    if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+        return Err(format!("{:?} is outside -90..90, -180..180", value));
+    }
+    Ok(Point { latitude, longitude })
+}
+
+// A FIELD=NAME pair of --rename-field.
+fn parse_encoding(value: &str) -> std::result::Result<&'static Encoding, String> {
+    input::encoding_for(value).ok_or_else(|| format!("unknown encoding {:?}", value))
+}
+
+fn parse_hook(value: &str) -> std::result::Result<Hook, String> {
+    Ok(Hook::parse(value))
+}
+
+// A NAME=PATH source of --source, read in the load's --format.
+fn parse_source(value: &str) -> std::result::Result<Source, String> {
+    let (name, path) = value.split_once('=').ok_or_else(|| format!("{:?} is not of the form NAME=PATH", value))?;
+    Ok(Source { name: name.trim().to_string(), path: PathBuf::from(path.trim()), format: None })
+}
+
+// Tenant names end up in database names, so only take letters, digits, - and _.
+fn parse_tenant(value: &str) -> std::result::Result<String, String> {
+    match !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        true => Ok(value.to_string()),
+        false => Err(format!("{:?} is not a tenant name of letters, digits, - and _", value)),
+    }
+}
+
+fn parse_rename(value: &str) -> std::result::Result<(String, String), String> {
+    let (field, name) = value.split_once('=').ok_or_else(|| format!("{:?} is not of the form FIELD=NAME", value))?;
+    Ok((field.trim().to_string(), name.trim().to_string()))
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldCase {
+    /// icaoCode, iataCode, description
+    Camel,
+    /// icao_code, iata_code, description
+    Snake,
+}
+
+/// How a run tells how it went on stdout.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RunOutput {
+    /// Lines for people
+    #[default]
+    Text,
+    /// One JSON object per run, as POSTed to --notify-url, for schedulers to parse
+    Json,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human readable lines
+    Text,
+    /// One JSON object per line, for log shippers
+    Json,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    /// MongoDB, connected via MONGODB_URL
+    Mongo,
+    /// PostgreSQL, connected via POSTGRES_URL
+    #[cfg(feature = "postgres")]
+    Postgres,
+    /// SQLite file chosen with --db
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+    /// An Elasticsearch or OpenSearch index named as --collection, on the cluster of ELASTICSEARCH_URL
+    #[cfg(feature = "elasticsearch")]
+    Elasticsearch,
+    /// A DynamoDB table named as --collection, with the AWS configuration of the environment
+    #[cfg(feature = "dynamodb")]
+    Dynamodb,
+    /// Redis hashes, connected via REDIS_URL, for lookups from a cache
+    #[cfg(feature = "redis")]
+    Redis,
+    /// Publish to the Kafka --topic only, on the brokers of KAFKA_BROKERS
+    #[cfg(feature = "kafka")]
+    Kafka,
+    /// The baseline dataset compiled into the binary, held in memory so writes are gone when the command ends; lookups use it when no MongoDB connection string is configured
+    #[cfg(feature = "embedded")]
+    Embedded,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Load the input file into the collection
+    Load(Box<LoadArgs>),
+    /// Dump the collection as JSON or CSV
+    Export(ExportArgs),
+    /// Write only the differences between the input file and the collection
+    Sync(SyncArgs),
+    /// Sync the input file, then sync it again whenever it changes, until Ctrl-C
+    Watch(WatchArgs),
+    /// Print every write to the collection as a JSON line as it happens, until Ctrl-C (MongoDB replica sets only)
+    Tail(TailArgs),
+    /// Look up aircraft by ICAO or IATA code
+    Query(QueryArgs),
+    /// Find aircraft whose description or codes match some text, best match first
+    Search(SearchArgs),
+    /// Resolve a file of ICAO and IATA codes at once, listing the aircraft each matches, the codes none does and those several do
+    Resolve(ResolveArgs),
+    /// Add one record, validated, refusing a code already stored
+    #[command(subcommand)]
+    Add(AddRecord),
+    /// Change fields of one stored record, validated
+    #[command(subcommand)]
+    Edit(EditRecord),
+    /// Delete one stored record
+    #[command(subcommand)]
+    Delete(DeleteRecord),
+    /// Delete every document in the collection, or those written by one load
+    Purge(PurgeArgs),
+    /// Undo a load by deleting the documents it wrote
+    Rollback(RollbackArgs),
+    /// Replace the collection with a snapshot taken by load --snapshot (MongoDB only)
+    Restore(RestoreArgs),
+    /// Show the writes to one aircraft recorded with --audit, oldest first (MongoDB only)
+    History(HistoryArgs),
+    /// Add details from another source to the stored aircraft (MongoDB only)
+    Enrich(EnrichArgs),
+    /// Serve the aircraft, airports and airlines over a read-only HTTP API, and gRPC with --grpc-port (MongoDB only)
+    Serve(ServeArgs),
+    /// Keep running and start loads on request: POST /loads with the dataset and source URL queues one, GET /loads/{id} tells how it went
+    Daemon(DaemonArgs),
+    /// Connect, list what the credentials can see and check the collection can be written to (MongoDB only)
+    Check,
+    /// Manage the validators that keep other tools from changing the shape of stored documents (MongoDB only)
+    #[command(subcommand)]
+    Schema(SchemaCommand),
+    /// Apply the migrations of the stored documents not applied yet, in order (MongoDB only)
+    Migrate(MigrateArgs),
+    /// Report missing, duplicated and suspicious codes and descriptions, with a score
+    Quality(QualityArgs),
+    /// Print the aircraft added, removed and changed between two input files, without connecting to the database
+    Diff(DiffArgs),
+    /// Load, sync and export a sample corpus end to end in a scratch collection, checking counts and indexes (MongoDB only)
+    SelfTest(SelfTestArgs),
+    /// Time parsing, BSON conversion and inserts of synthetic aircraft at several batch sizes and concurrencies
+    Bench(BenchArgs),
+    /// Browse the aircraft, airlines and airports, marking records for deletion and editing descriptions (MongoDB only)
+    Browse,
+    /// Summarize the collection: aircraft per manufacturer, missing IATA codes, the last load, growth and sizes (MongoDB only)
+    Stats(StatsArgs),
+    /// Drop staging collections abandoned by interrupted loads and delete expired staged documents now (MongoDB only)
+    Gc(GcArgs),
+    /// Print a shell completion script, e.g. `completions bash > /etc/bash_completion.d/rust-aircraft-parser`
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+impl Command {
+    /// The subcommand as typed, e.g. `load`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Load(_) => "load",
+            Command::Export(_) => "export",
+            Command::Sync(_) => "sync",
+            Command::Watch(_) => "watch",
+            Command::Tail(_) => "tail",
+            Command::Query(_) => "query",
+            Command::Search(_) => "search",
+            Command::Resolve(_) => "resolve",
+            Command::Add(_) => "add",
+            Command::Edit(_) => "edit",
+            Command::Delete(_) => "delete",
+            Command::Purge(_) => "purge",
+            Command::Rollback(_) => "rollback",
+            Command::Restore(_) => "restore",
+            Command::History(_) => "history",
+            Command::Enrich(_) => "enrich",
+            Command::Serve(_) => "serve",
+            Command::Daemon(_) => "daemon",
+            Command::Check => "check",
+            Command::Schema(_) => "schema",
+            Command::Migrate(_) => "migrate",
+            Command::Quality(_) => "quality",
+            Command::Diff(_) => "diff",
+            Command::SelfTest(_) => "self-test",
+            Command::Bench(_) => "bench",
+            Command::Browse => "browse",
+            Command::Stats(_) => "stats",
+            Command::Gc(_) => "gc",
+            Command::Completions { .. } => "completions",
+        }
+    }
+
+    /// How runs of the subcommand print their summaries.
+    pub fn run_output(&self) -> RunOutput {
+        match self {
+            Command::Load(args) => args.output,
+            Command::Sync(args) => args.output,
+            Command::Watch(args) => args.sync.output,
+            _ => RunOutput::Text,
+        }
+    }
+
+    /// Whether the subcommand only looks aircraft up, and so can read the embedded dataset.
+    #[cfg(feature = "embedded")]
+    pub fn is_lookup(&self) -> bool {
+        matches!(self, Command::Query(_) | Command::Search(_) | Command::Resolve(_) | Command::Serve(_))
+    }
+
+    /// Whether the subcommand writes reference data, and so reports to --notify-url.
+    pub fn is_run(&self) -> bool {
+        matches!(self, Command::Load(_) | Command::Sync(_) | Command::Watch(_) | Command::Enrich(_))
+    }
+}
+
+/// Options of the commands that write to a collection, which they lock while they do, so
+/// no other run writes to it meanwhile.
+#[derive(Args, Clone, Copy, Debug, Default)]
+pub struct LockArgs {
+    /// Break the lock another run holds on the collection, should it have died without releasing it (MongoDB only)
+    #[arg(long, global = true)]
+    pub force: bool,
+}
+
+/// Options of the commands that delete or overwrite stored records.
+#[derive(Args, Clone, Copy, Debug, Default)]
+pub struct ConfirmArgs {
+    /// Don't ask for confirmation
+    #[arg(short, long)]
+    pub yes: bool,
+}
+
+/// What a run does with the entries of its input that fail to parse.
+#[derive(Args, Clone, Copy, Debug, Default)]
+pub struct ParseArgs {
+    /// Skip and log entries that fail to parse instead of aborting
+    #[arg(long)]
+    pub lenient: bool,
+}
+
+/// Options of every run writing the records of a file: loads of aircraft and of the other
+/// datasets, and syncs.
+#[derive(Args, Clone, Debug)]
+pub struct RunArgs {
+    /// Do nothing if the input's checksum matches the last recorded run
+    #[arg(long)]
+    pub skip_unchanged: bool,
+
+    /// Number of records written per batch
+    #[arg(long, default_value_t = DEFAULT_BATCH_SIZE)]
+    pub batch_size: usize,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+
+    /// File the records failing validation are written to
+    #[arg(long, default_value = "rejects.json")]
+    pub rejects: PathBuf,
+
+    /// Also write a report of the run for change tickets to this file: Markdown if it ends in .md, HTML otherwise
+    #[arg(long)]
+    pub report: Option<PathBuf>,
+}
+
+/// How a load writes its batches.
+#[derive(Args, Clone, Debug)]
+pub struct WriteArgs {
+    /// Number of batches written at once
+    #[arg(long, default_value_t = 1)]
+    pub concurrency: usize,
+
+    /// Batches parsed and checked ahead of the writes, which bounds the memory a load holds whatever the size of its input
+    #[arg(long, default_value_t = DEFAULT_BUFFER)]
+    pub buffer: usize,
+
+    /// Write at most this many records a second on average, so loads leave room for other traffic on the cluster
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_docs_per_sec: Option<u32>,
+
+    /// Insert the records of a batch independently, so a duplicate key only fails its own record (MongoDB only)
+    #[arg(long, conflicts_with = "upsert")]
+    pub unordered: bool,
+
+    /// File the records an --unordered load could not write are written to, ready to be loaded again
+    #[arg(long, default_value = "failures.json")]
+    pub failures: PathBuf,
+}
+
+impl WriteArgs {
+    /// The options of a load writing this way, in the batches of `run`.
+    pub fn load_options(&self, run: &RunArgs) -> LoadOptions {
+        LoadOptions {
+            batch_size: run.batch_size,
+            lenient: run.parse.lenient,
+            concurrency: self.concurrency,
+            buffer: self.buffer,
+            unordered: self.unordered,
+            max_docs_per_sec: self.max_docs_per_sec,
+            ..LoadOptions::default()
+        }
+    }
+}
+
+/// Where to read aircraft from and how to parse them.
+#[derive(Args, Clone, Debug)]
+pub struct SourceArgs {
+    /// File to read, - for stdin, overrides --input; s3://bucket/key objects are read from S3 with the s3 feature
+    pub file: Option<PathBuf>,
+
+    /// Layout of the input file
+    #[arg(long, value_enum, default_value_t = Format::Json)]
+    pub format: Format,
+
+    /// CSV column holding the ICAO code
+    #[arg(long, default_value = "icaoCode")]
+    pub csv_icao_column: String,
+
+    /// CSV column holding the IATA code
+    #[arg(long, default_value = "iataCode")]
+    pub csv_iata_column: String,
+
+    /// CSV column holding the description
+    #[arg(long, default_value = "description")]
+    pub csv_description_column: String,
+
+    /// XML element holding each aircraft
+    #[arg(long, default_value = "aircraft")]
+    pub xml_record: String,
+
+    /// XML child element, or @attribute of the record element, holding the ICAO code
+    #[arg(long, default_value = "icaoCode")]
+    pub xml_icao_field: String,
+
+    /// XML child element or @attribute holding the IATA code
+    #[arg(long, default_value = "iataCode")]
+    pub xml_iata_field: String,
+
+    /// XML child element or @attribute holding the description
+    #[arg(long, default_value = "description")]
+    pub xml_description_field: String,
+
+    /// Encoding of input without a byte order mark, e.g. utf-16le or windows-1252; UTF-8 when omitted
+    #[arg(long, value_parser = parse_encoding)]
+    pub encoding: Option<&'static Encoding>,
+
+    /// Read the aircraft as they are, without trimming and uppercasing codes, collapsing
+    /// whitespace in descriptions, or normalizing Unicode to NFC
+    #[arg(long)]
+    pub no_normalize: bool,
+
+    /// Pass every aircraft read through this transform before it is written: a shell command fed one JSON aircraft per line, answering each with one line, or a .wasm module (wasm feature); repeat to chain them
+    #[arg(long = "hook", value_name = "COMMAND|MODULE.wasm", value_parser = parse_hook)]
+    pub hooks: Vec<Hook>,
+
+    /// AWS region of s3:// inputs, from the AWS configuration when omitted
+    #[cfg(feature = "s3")]
+    #[arg(long)]
+    pub s3_region: Option<String>,
+
+    /// AWS profile used for s3:// inputs
+    #[cfg(feature = "s3")]
+    #[arg(long)]
+    pub s3_profile: Option<String>,
+}
+
+impl SourceArgs {
+    /// The positional file if given, `input` otherwise.
+    pub fn path<'a>(&'a self, input: &'a Path) -> &'a Path {
+        self.file.as_deref().unwrap_or(input)
+    }
+
+    pub fn input_options(&self) -> InputOptions {
+        InputOptions {
+            format: self.format,
+            csv_columns: CsvColumns {
+                icao_code: self.csv_icao_column.clone(),
+                iata_code: self.csv_iata_column.clone(),
+                description: self.csv_description_column.clone(),
+            },
+            xml_mapping: XmlMapping {
+                record: self.xml_record.clone(),
+                icao_code: self.xml_icao_field.clone(),
+                iata_code: self.xml_iata_field.clone(),
+                description: self.xml_description_field.clone(),
+            },
+            normalize: !self.no_normalize,
+            encoding: self.encoding,
+        }
+    }
+
+    #[cfg(feature = "s3")]
+    pub fn s3_options(&self) -> S3Options {
+        S3Options { region: self.s3_region.clone(), profile: self.s3_profile.clone() }
+    }
+}
+
+/// Fetching the input file over HTTP(S).
+#[derive(Args, Debug)]
+pub struct RemoteArgs {
+    /// Fetch the input file over HTTP(S) instead, skipping the run when it is not modified since the last fetch
+    #[arg(long, conflicts_with = "file")]
+    pub url: Option<String>,
+
+    /// Directory fetched --url files and their ETag and Last-Modified are kept in
+    #[arg(long, default_value = ".cache/rust-aircraft-parser")]
+    pub cache_dir: PathBuf,
+
+    /// Download the --url file even if the cached copy is current
+    #[arg(long)]
+    pub no_cache: bool,
+}
+
+#[derive(Args, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct LoadArgs {
+    /// Reference data other than aircraft to load instead
+    #[command(subcommand)]
+    pub dataset: Option<Dataset>,
+
+    #[command(flatten)]
+    pub source: SourceArgs,
+
+    /// Further files to load in the same run; a directory or glob pattern as FILE also loads many files, each as the dataset its name or manifest.toml names
+    #[arg(value_name = "MORE")]
+    pub files: Vec<PathBuf>,
+
+    #[command(flatten)]
+    pub remote: RemoteArgs,
+
+    /// Load the baseline dataset compiled into the binary instead of a file, to bootstrap a fresh environment (needs the embedded feature)
+    #[arg(long, conflicts_with_all = ["file", "files", "url"])]
+    pub from_embedded: bool,
+
+    /// Merge these sources per ICAO code instead of reading one file, highest priority first: each field is taken from the first source giving it; also read from the [[sources]] of the config
+    #[arg(long = "source", value_name = "NAME=PATH", value_parser = parse_source, conflicts_with_all = ["file", "files", "url", "from_embedded"])]
+    pub sources: Vec<Source>,
+
+    /// File each aircraft merged from --source is written to with the source of each of its fields
+    #[arg(long, default_value = "origins.json")]
+    pub origins: PathBuf,
+
+    /// How the summary of the run, or of each file loaded, is printed
+    #[arg(long, global = true, value_enum, default_value_t = RunOutput::Text)]
+    pub output: RunOutput,
+
+    /// Upsert keyed on icaoCode instead of inserting new documents
+    #[arg(long)]
+    pub upsert: bool,
+
+    #[command(flatten)]
+    pub run: RunArgs,
+
+    #[command(flatten)]
+    pub write: WriteArgs,
+
+    /// Load into a staging collection and atomically rename it over the live one (MongoDB only)
+    #[arg(long, conflicts_with = "upsert")]
+    pub swap: bool,
+
+    /// Continue the interrupted load of the same input from its <input>.checkpoint, under its load id, instead of starting over
+    #[arg(long, conflicts_with_all = ["swap", "atomic", "dry_run"])]
+    pub resume: bool,
+
+    /// With several files, load each into a staging collection and rename them over the live ones only once every file loaded, so a failure leaves all collections as they were (MongoDB only)
+    #[arg(long, conflicts_with_all = ["upsert", "swap", "skip_existing", "snapshot"])]
+    pub atomic: bool,
+
+    /// Don't create the icaoCode and iataCode indexes before loading
+    #[arg(long)]
+    pub skip_indexes: bool,
+
+    /// Only insert aircraft whose ICAO code is not stored yet, counting the others as skipped
+    #[arg(long, conflicts_with_all = ["upsert", "swap"])]
+    pub skip_existing: bool,
+
+    /// Copy the collection into aircraft_snapshots under the load id first, for restore --snapshot (MongoDB only)
+    #[arg(long)]
+    pub snapshot: bool,
+
+    #[command(flatten)]
+    pub lock: LockArgs,
+
+    /// Write one entry per ICAO code listed more than once in the input, picked this way; the whole input is read first
+    #[arg(long, value_enum)]
+    pub dedup: Option<DedupStrategy>,
+
+    /// File the ICAO codes listed with different fields are written to, with --dedup
+    #[arg(long, default_value = "conflicts.json")]
+    pub conflicts: PathBuf,
+
+    /// Parse and check the input, print what would be inserted, and never connect to the database
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Number of generated documents printed by --dry-run
+    #[arg(long, default_value_t = 3, requires = "dry_run")]
+    pub sample: usize,
+}
+
+impl LoadArgs {
+    /// The positional file and any further ones.
+    pub fn paths(&self) -> Vec<&Path> {
+        self.source.file.iter().chain(&self.files).map(PathBuf::as_path).collect()
+    }
+
+    pub fn load_options(&self) -> LoadOptions {
+        LoadOptions { upsert: self.upsert, ..self.write.load_options(&self.run) }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Dataset {
+    /// Load airports into the airports collection (MongoDB only)
+    Airports(AirportArgs),
+    /// Load airlines into the airlines collection (MongoDB only)
+    Airlines(DatasetArgs),
+    /// Load routes into the routes collection, after checking their airlines and airports are loaded (MongoDB only)
+    Routes(DatasetArgs),
+    /// Load ISO countries into the countries collection (MongoDB only)
+    Countries(DatasetArgs),
+    /// Load aircraft registrations into the registrations collection, linking them to the loaded aircraft types (MongoDB only)
+    Registrations(RegistrationArgs),
+    /// Load Mode S hex addresses into the hexdb collection, from readsb NDJSON or --format mictronics (MongoDB only)
+    Hexdb(DatasetArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct DatasetArgs {
+    /// File to load, - for stdin, <dataset>.json (or the extension of --format) when omitted
+    pub file: Option<PathBuf>,
+
+    /// Layout of the input file; CSV headers must match the field names, OpenFlights columns their documented order
+    #[arg(long, value_enum, default_value_t = Format::Json)]
+    pub format: Format,
+
+    /// Upsert keyed on the dataset's code instead of inserting new documents
+    #[arg(long)]
+    pub upsert: bool,
+
+    #[command(flatten)]
+    pub run: RunArgs,
+
+    #[command(flatten)]
+    pub write: WriteArgs,
+
+    /// Don't create the dataset's indexes before loading
+    #[arg(long)]
+    pub skip_indexes: bool,
+
+    /// File the records referring to codes that are not loaded are written to; they are not loaded
+    #[arg(long, default_value = "orphans.json")]
+    pub orphans: PathBuf,
+}
+
+impl DatasetArgs {
+    /// The positional file if given, `<name>.<extension of the format>` otherwise.
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.file.clone().unwrap_or_else(|| PathBuf::from(format!("{}.{}", name, self.format.extension())))
+    }
+
+    pub fn load_options(&self) -> LoadOptions {
+        LoadOptions { upsert: self.upsert, ..self.write.load_options(&self.run) }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct AirportArgs {
+    #[command(flatten)]
+    pub dataset: DatasetArgs,
+
+    /// With --format ourairports, only load airports of these types, e.g. large_airport,medium_airport
+    #[arg(long, value_delimiter = ',')]
+    pub types: Vec<String>,
+
+    /// With --format ourairports, only load airports in these ISO countries, e.g. US,CA
+    #[arg(long, value_delimiter = ',')]
+    pub countries: Vec<String>,
+}
+
+impl AirportArgs {
+    pub fn filter(&self) -> OurAirportsFilter {
+        OurAirportsFilter { types: self.types.clone(), countries: self.countries.clone() }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct RegistrationArgs {
+    #[command(flatten)]
+    pub dataset: DatasetArgs,
+
+    /// With --format faa, the ACFTREF.txt file, ACFTREF.txt next to the MASTER file when omitted
+    #[arg(long)]
+    pub acftref: Option<PathBuf>,
+}
+
+impl RegistrationArgs {
+    /// The --acftref file if given, `ACFTREF.txt` in the directory of `master` otherwise.
+    pub fn acftref(&self, master: &Path) -> PathBuf {
+        self.acftref.clone().unwrap_or_else(|| master.with_file_name("ACFTREF.txt"))
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct EnrichArgs {
+    #[command(subcommand)]
+    pub enrichment: Enrichment,
+
+    #[command(flatten)]
+    pub lock: LockArgs,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Enrichment {
+    /// Add manufacturer, model, category, engines and wake category from ICAO DOC 8643 data, keyed by type designator
+    Doc8643(EnrichFileArgs),
+    /// Add range, cruise speed, MTOW and typical seats, keyed by ICAO code, flagging types only one side has
+    Performance(EnrichFileArgs),
+    /// Add descriptions in other languages from a file of icaoCode, lang and description entries
+    Translations(EnrichFileArgs),
+    /// Set the other designators each type is known by, which lookups resolve to it, from a file of icaoCode and aliases entries; CSV files list the aliases of a type in one column, separated by spaces
+    Aliases(EnrichFileArgs),
+    /// Derive manufacturer, model, variant and body type from the description of every stored aircraft
+    Classify,
+    /// Add first flight, manufacturer entity and Wikipedia URL from Wikidata, queried per type designator
+    Wikidata(WikidataArgs),
+    /// Set the IANA time zone of every stored airport from its coordinates (needs the timezones feature)
+    Timezones,
+    /// Tag every stored registration with the ISO code of its country, from its nationality mark and the loaded countries
+    Nationality,
+}
+
+/// The file an enrichment reads its entries from.
+#[derive(Args, Debug)]
+pub struct EnrichFileArgs {
+    /// File to read, <enrichment>.json (or the extension of --format) when omitted, e.g. performance.json
+    pub file: Option<PathBuf>,
+
+    /// Layout of the input file; CSV headers must match the field names
+    #[arg(long, value_enum, default_value_t = Format::Json)]
+    pub format: Format,
+
+    #[command(flatten)]
+    pub parse: ParseArgs,
+}
+
+impl EnrichFileArgs {
+    /// The positional file if given, `<enrichment>.<extension of the format>` otherwise.
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.file.clone().unwrap_or_else(|| PathBuf::from(format!("{}.{}", name, self.format.extension())))
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct WikidataArgs {
+    /// SPARQL endpoint to query
+    #[arg(long, default_value = wikidata::ENDPOINT)]
+    pub endpoint: String,
+
+    /// Directory the answers are kept in, so later runs only query designators not seen yet
+    #[arg(long, default_value = ".cache/rust-aircraft-parser/wikidata")]
+    pub cache_dir: PathBuf,
+
+    /// Milliseconds to wait between two queries
+    #[arg(long, value_name = "MILLISECONDS", default_value_t = 1000)]
+    pub delay: u64,
+}
+
+#[derive(Args, Debug)]
+pub struct SyncArgs {
+    #[command(flatten)]
+    pub source: SourceArgs,
+
+    #[command(flatten)]
+    pub remote: RemoteArgs,
+
+    /// How the summary of each run is printed
+    #[arg(long, value_enum, default_value_t = RunOutput::Text)]
+    pub output: RunOutput,
+
+    #[command(flatten)]
+    pub lock: LockArgs,
+
+    /// Keep running and sync whenever this cron expression matches local time, e.g. "0 3 * * *" for 03:00 nightly
+    #[arg(long, value_parser = parse_schedule)]
+    pub schedule: Option<Schedule>,
+
+    /// Delete aircraft that are stored but missing from the input
+    #[arg(long)]
+    pub prune: bool,
+
+    /// Mark aircraft that are stored but missing from the input inactive, with active: false and retiredAt, instead of deleting them (MongoDB only)
+    #[arg(long, conflicts_with = "prune")]
+    pub retire: bool,
+
+    #[command(flatten)]
+    pub run: RunArgs,
+
+    /// Append each add, update, delete, retirement and reactivation of the run, with the aircraft before and after, to this NDJSON file
+    #[arg(long, value_name = "PATH")]
+    pub change_feed: Option<PathBuf>,
+
+    /// POST the changes of each run that made any to this URL, as an NDJSON body of the events --change-feed writes
+    #[arg(long, value_name = "URL", env = "CHANGE_FEED_URL")]
+    pub change_feed_url: Option<String>,
+
+    /// How each field of a stored aircraft is updated, from the [merge] table of the config file
+    #[arg(skip)]
+    pub merge: MergePolicy,
+}
+
+impl SyncArgs {
+    pub fn sync_options(&self) -> SyncOptions {
+        SyncOptions { batch_size: self.run.batch_size, prune: self.prune, retire: self.retire, lenient: self.run.parse.lenient, merge: self.merge }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct WatchArgs {
+    #[command(flatten)]
+    pub sync: SyncArgs,
+
+    /// Milliseconds the file must stay unchanged before it is synced
+    #[arg(long, default_value_t = 500)]
+    pub debounce: u64,
+}
+
+impl WatchArgs {
+    pub fn debounce(&self) -> Duration {
+        Duration::from_millis(self.debounce)
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct TailArgs {
+    /// Also POST each change as JSON to this URL, e.g. for a cache to invalidate its copy
+    #[arg(long)]
+    pub forward_url: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct ExportArgs {
+    /// Export a mapping between codes instead of the aircraft
+    #[command(subcommand)]
+    pub mapping: Option<ExportMapping>,
+
+    /// File to write to, stdout when omitted
+    #[arg(short, long, global = true)]
+    pub out: Option<PathBuf>,
+
+    /// Layout of the exported file; a mapping is written as json or csv
+    #[arg(long, value_enum, default_value_t = ExportFormat::Json, global = true)]
+    pub format: ExportFormat,
+
+    /// Include each record's stored _id
+    #[arg(long)]
+    pub keep_id: bool,
+
+    #[command(flatten)]
+    pub input: FromInputArgs,
+
+    /// Write the description in this language, e.g. fr, where there is a translation; bson-archive keeps every one
+    #[arg(long)]
+    pub lang: Option<String>,
+
+    /// Whether aircraft retired by sync --retire are exported (MongoDB only)
+    #[arg(long, value_enum, default_value_t = Inactive::Include)]
+    pub inactive: Inactive,
+
+    /// Export only the aircraft matching these field=value terms, e.g. manufacturer=Boeing,icaoCode=B7* where * matches any ending, or this MongoDB query as JSON, e.g. '{"iataCode": "738"}' (MongoDB only)
+    #[arg(long, conflicts_with = "from_input")]
+    pub filter: Option<Filter>,
+
+    /// Export only these fields, in this order, separated by commas, e.g. icaoCode,iataCode
+    #[arg(long, value_delimiter = ',', value_parser = parse_column)]
+    pub fields: Vec<String>,
+}
+
+/// Reading the aircraft from --input instead of the database.
+#[derive(Args, Clone, Copy, Debug)]
+pub struct FromInputArgs {
+    /// Use the aircraft parsed from --input instead of the stored ones, without connecting to the database
+    #[arg(long, global = true)]
+    pub from_input: bool,
+
+    /// Layout of --input read with --from-input
+    #[arg(long, value_enum, default_value_t = Format::Json, requires = "from_input", global = true)]
+    pub input_format: Format,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ExportMapping {
+    /// Write which ICAO codes share each IATA code, or the IATA code of each ICAO code
+    Mapping {
+        /// Which codes the mapping is keyed by
+        #[arg(long, value_enum, default_value_t = Direction::IataToIcao)]
+        direction: Direction,
+    },
+}
+
+impl ExportArgs {
+    pub fn export_options(&self) -> ExportOptions {
+        ExportOptions { format: self.format, keep_id: self.keep_id, fields: self.fields.clone() }
+    }
+}
+
+/// What `add` adds. Single-record writes are recorded in load_history, and in audit_log with the mongo backend.
+#[derive(Subcommand, Debug)]
+pub enum AddRecord {
+    /// Add an aircraft type
+    Aircraft(AddAircraftArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct AddAircraftArgs {
+    /// ICAO type designator, e.g. B39M
+    #[arg(long)]
+    pub icao: String,
+
+    /// IATA code, e.g. 7M9; left out when the type has none
+    #[arg(long)]
+    pub iata: Option<String>,
+
+    /// Description, e.g. "Boeing 737 MAX 9"
+    #[arg(long)]
+    pub description: String,
+}
+
+/// What `edit` changes.
+#[derive(Subcommand, Debug)]
+pub enum EditRecord {
+    /// Change an aircraft type
+    Aircraft(EditAircraftArgs),
+}
+
+#[derive(Args, Debug)]
+#[command(group(ArgGroup::new("change").required(true).multiple(true)))]
+pub struct EditAircraftArgs {
+    /// ICAO type designator of the aircraft to change, e.g. B39M
+    #[arg(long)]
+    pub icao: String,
+
+    /// New IATA code
+    #[arg(long, group = "change", conflicts_with = "no_iata")]
+    pub iata: Option<String>,
+
+    /// Remove the IATA code
+    #[arg(long, group = "change")]
+    pub no_iata: bool,
+
+    /// New description
+    #[arg(long, group = "change")]
+    pub description: Option<String>,
+}
+
+/// What `delete` deletes.
+#[derive(Subcommand, Debug)]
+pub enum DeleteRecord {
+    /// Delete an aircraft type
+    Aircraft(DeleteAircraftArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct DeleteAircraftArgs {
+    /// ICAO type designator of the aircraft to delete, e.g. B39M
+    #[arg(long)]
+    pub icao: String,
+
+    #[command(flatten)]
+    pub confirm: ConfirmArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct PurgeArgs {
+    /// Only delete the documents written by this load
+    #[arg(long)]
+    pub load_id: Option<String>,
+
+    #[command(flatten)]
+    pub confirm: ConfirmArgs,
+
+    #[command(flatten)]
+    pub lock: LockArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct RollbackArgs {
+    /// Load to undo, as printed when it finished
+    #[arg(long)]
+    pub load_id: String,
+
+    #[command(flatten)]
+    pub confirm: ConfirmArgs,
+
+    #[command(flatten)]
+    pub lock: LockArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct RestoreArgs {
+    /// Snapshot to restore: the id of the load it was taken before
+    #[arg(long)]
+    pub snapshot: String,
+
+    #[command(flatten)]
+    pub confirm: ConfirmArgs,
+
+    #[command(flatten)]
+    pub lock: LockArgs,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SchemaCommand {
+    /// Install a $jsonSchema validator matching the models on the aircraft, airports and airlines collections
+    Apply(SchemaArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct SchemaArgs {
+    /// Whether updates to documents that already break the schema are rejected too (strict) or allowed (moderate)
+    #[arg(long, value_enum, default_value_t = ValidationLevel::Strict)]
+    pub validation_level: ValidationLevel,
+}
+
+#[derive(Args, Debug)]
+pub struct StatsArgs {
+    /// Print the summary as JSON instead of a table
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Args, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct MigrateArgs {
+    #[command(subcommand)]
+    pub command: Option<Migration>,
+
+    /// Stop after this version instead of applying every pending migration
+    #[arg(long)]
+    pub to: Option<u32>,
+
+    /// List the migrations and whether each has been applied, without applying any
+    #[arg(long, conflicts_with = "to")]
+    pub list: bool,
+
+    #[command(flatten)]
+    pub lock: LockArgs,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Migration {
+    /// Convert the UUID _ids of the aircraft and every other reference collection to binaries, or back to strings with --string-ids
+    Ids(MigrateIdsArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct MigrateIdsArgs {
+    /// Count the _ids that would be converted without converting any
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct QualityArgs {
+    /// File to write the report to, stdout when omitted
+    #[arg(short, long)]
+    pub out: Option<PathBuf>,
+
+    /// Layout of the report
+    #[arg(long, value_enum, default_value_t = ReportFormat::Json)]
+    pub format: ReportFormat,
+
+    /// Flag descriptions shorter than this many characters
+    #[arg(long, default_value_t = MIN_DESCRIPTION)]
+    pub min_description: usize,
+
+    #[command(flatten)]
+    pub input: FromInputArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct DiffArgs {
+    /// The earlier file, e.g. the one last loaded
+    pub old: PathBuf,
+
+    /// The later file, e.g. a new upstream release
+    pub new: PathBuf,
+
+    /// Layout of both files
+    #[arg(long, value_enum, default_value_t = Format::Json)]
+    pub format: Format,
+
+    /// Print the differences as JSON, with the old and new value of every changed field
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct SelfTestArgs {
+    /// Start a disposable MongoDB in a container instead of using MONGODB_URL (needs the testcontainers feature and Docker)
+    #[arg(long)]
+    pub container: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct GcArgs {
+    /// List what would be dropped and count the expired documents without deleting anything
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct BenchArgs {
+    /// Number of synthetic aircraft, at most 1679616 so their ICAO codes stay distinct
+    #[arg(long, default_value_t = 100_000, value_parser = clap::value_parser!(u64).range(1..=1_679_616))]
+    pub records: u64,
+
+    /// Batch sizes to time inserts with
+    #[arg(long, value_delimiter = ',', default_values_t = [500, 1000, 5000])]
+    pub batch_size: Vec<usize>,
+
+    /// Numbers of batches written at once to time inserts with
+    #[arg(long, value_delimiter = ',', default_values_t = [1, 4])]
+    pub concurrency: Vec<usize>,
+
+    /// Only time parsing and BSON conversion, without connecting to the database
+    #[arg(long)]
+    pub no_insert: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct HistoryArgs {
+    /// ICAO type designator of the aircraft, e.g. B744
+    #[arg(long)]
+    pub icao: String,
+}
+
+#[derive(Args, Debug)]
+#[command(args_conflicts_with_subcommands = true, group(ArgGroup::new("code").required(true)))]
+pub struct QueryArgs {
+    #[command(subcommand)]
+    pub lookup: Option<Lookup>,
+
+    /// ICAO type designator, e.g. B38M
+    #[arg(long, group = "code")]
+    pub icao: Option<String>,
+
+    /// IATA code, e.g. 7M8
+    #[arg(long, group = "code")]
+    pub iata: Option<String>,
+
+    /// Field=value terms the aircraft have to match, e.g. manufacturer=Boeing,icaoCode=B7* where * matches any ending, or a MongoDB query as JSON (MongoDB only)
+    #[arg(long, group = "code", conflicts_with = "as_of")]
+    pub filter: Option<Filter>,
+
+    #[command(flatten)]
+    pub print: PrintArgs,
+
+    /// Show the aircraft as aircraft_history recorded them at this RFC 3339 time, or at the start of this date in UTC, e.g. 2023-06-01 (MongoDB only)
+    #[arg(long, value_parser = parse_instant)]
+    pub as_of: Option<DateTime<Utc>>,
+
+    /// Show the description in this language, e.g. fr, where there is a translation
+    #[arg(long)]
+    pub lang: Option<String>,
+
+    /// Whether aircraft retired by sync --retire are shown (MongoDB only)
+    #[arg(long, value_enum, default_value_t = Inactive::Include)]
+    pub inactive: Inactive,
+}
+
+/// How the aircraft a lookup finds are printed.
+#[derive(Args, Clone, Debug)]
+pub struct PrintArgs {
+    /// Print only these fields, in this order, separated by commas, e.g. icaoCode,iataCode
+    #[arg(long, value_delimiter = ',', value_parser = parse_column)]
+    pub fields: Vec<String>,
+
+    /// How the matching aircraft are printed
+    #[arg(long, value_enum, default_value_t = ExportFormat::Table)]
+    pub output: ExportFormat,
+}
+
+impl PrintArgs {
+    pub fn export_options(&self) -> ExportOptions {
+        ExportOptions { format: self.output, keep_id: false, fields: self.fields.clone() }
+    }
+}
+
+/// Where a server listens.
+#[derive(Args, Clone, Copy, Debug)]
+pub struct ListenArgs {
+    /// Address to listen on; 0.0.0.0 to accept connections from other hosts
+    #[arg(long, default_value = "127.0.0.1")]
+    pub bind: IpAddr,
+
+    /// Port to listen on: 8080 for serve and 8081 for daemon when omitted
+    #[arg(long)]
+    pub port: Option<u16>,
+}
+
+impl ListenArgs {
+    // The address to listen on, on `port` unless --port says otherwise.
+    #[cfg(feature = "server")]
+    fn address(&self, port: u16) -> SocketAddr {
+        SocketAddr::new(self.bind, self.port.unwrap_or(port))
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+    #[command(flatten)]
+    pub listen: ListenArgs,
+
+    /// Require this API key, or one of the others given, on every request; separate several with commas
+    #[arg(long, env = "API_KEYS", value_delimiter = ',', hide_env_values = true)]
+    pub api_key: Vec<String>,
+
+    /// Require an API key from the api_keys collection (or --api-key) on every request
+    #[arg(long)]
+    pub api_keys_collection: bool,
+
+    /// Requests each API key may make per minute
+    #[arg(long)]
+    pub rate_limit: Option<u32>,
+
+    /// Also serve the ReferenceData gRPC service on this port (needs the grpc feature); it checks no API keys
+    #[arg(long, conflicts_with_all = ["api_key", "api_keys_collection"])]
+    pub grpc_port: Option<u16>,
+
+    /// Lookups by ICAO code kept in memory, dropped as a change stream reports writes to them; 0 reads every one from the database
+    #[arg(long, default_value_t = cache::DEFAULT_CAPACITY)]
+    pub cache_size: u64,
+
+    /// Seconds a cached lookup is kept at most, in case a write goes unreported, e.g. on a standalone server
+    #[arg(long, default_value_t = cache::DEFAULT_TTL.as_secs())]
+    pub cache_ttl: u64,
+}
+
+#[derive(Args, Debug)]
+pub struct DaemonArgs {
+    #[command(flatten)]
+    pub listen: ListenArgs,
+
+    /// Require this API key, or one of the others given, to request and list loads; separate several with commas
+    #[arg(long, env = "DAEMON_API_KEYS", value_delimiter = ',', hide_env_values = true)]
+    pub api_key: Vec<String>,
+
+    /// Directory the requested source files are fetched into
+    #[arg(long, default_value = ".cache/rust-aircraft-parser")]
+    pub cache_dir: PathBuf,
+
+    /// Loads waiting to run at most; further requests are refused with 503 until one starts
+    #[arg(long, default_value_t = 100, value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_queued: u32,
+
+    /// Finished loads listed at most, the oldest being forgotten first
+    #[arg(long, default_value_t = 1000)]
+    pub max_jobs: usize,
+}
+
+impl DaemonArgs {
+    #[cfg(feature = "server")]
+    pub fn address(&self) -> SocketAddr {
+        self.listen.address(8081)
+    }
+
+    #[cfg(feature = "server")]
+    pub fn daemon_options(&self) -> DaemonOptions {
+        DaemonOptions {
+            api_keys: self.api_key.iter().filter(|key| !key.is_empty()).cloned().collect(),
+            max_queued: self.max_queued as usize,
+            max_jobs: self.max_jobs,
+        }
+    }
+}
+
+impl ServeArgs {
+    #[cfg(feature = "server")]
+    pub fn address(&self) -> SocketAddr {
+        self.listen.address(8080)
+    }
+
+    #[cfg(feature = "server")]
+    pub fn access_options(&self) -> AccessOptions {
+        AccessOptions {
+            api_keys: self.api_key.iter().filter(|key| !key.is_empty()).cloned().collect(),
+            key_collection: self.api_keys_collection,
+            rate_limit: self.rate_limit,
+        }
+    }
+
+    /// The cache of lookups, unless --cache-size is 0.
+    #[cfg(feature = "server")]
+    pub fn lookup_cache(&self) -> Option<LookupCache> {
+        (self.cache_size > 0).then(|| LookupCache::new(self.cache_size, Duration::from_secs(self.cache_ttl)))
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct SearchArgs {
+    /// Words to look for, e.g. "737 max"; case, punctuation and small typos don't matter
+    pub query: String,
+
+    /// Maximum number of aircraft printed
+    #[arg(long, default_value_t = 20)]
+    pub limit: usize,
+
+    #[command(flatten)]
+    pub print: PrintArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct ResolveArgs {
+    /// File of codes separated by lines, spaces or commas, - for stdin; at most 10000 of them
+    #[arg(long)]
+    pub file: PathBuf,
+
+    /// Print the resolution as JSON instead of a line per code
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Lookup {
+    /// Look up a 24-bit Mode S address in the hexdb collection, with its aircraft type (MongoDB only)
+    Hex {
+        /// Address as 6 hex digits, e.g. A1B2C3
+        hex: String,
+    },
+    /// Tell the country of a registration from its nationality mark, with the stored registration if any (MongoDB only)
+    Registration {
+        /// Registration mark, e.g. G-EUPT or N12345
+        registration: String,
+    },
+    /// List the airports within a radius of a point, nearest first (MongoDB only)
+    Airports {
+        /// Latitude and longitude in decimal degrees, e.g. 40.64,-73.78
+        #[arg(long, value_name = "LAT,LON", value_parser = parse_point, allow_hyphen_values = true)]
+        near: Point,
+
+        /// Radius around --near, in kilometres
+        #[arg(long, default_value_t = 50.0)]
+        radius_km: f64,
+
+        /// Maximum number of airports printed
+        #[arg(long)]
+        limit: Option<i64>,
+
+        /// Print the airports and their distances as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Resolve the IATA codes of a schedule's equipment string to ICAO type designators, listing unknown codes
+    Equipment {
+        /// IATA codes separated by spaces, commas or slashes, e.g. "320 321 32N"
+        equipment: String,
+
+        /// Print the codes and their aircraft as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_global_options_are_kept_as_typed_wherever_they_were_typed() {
+        let args = ["parser", "--database", "typed", "-vv", "daemon", "--collection", "types", "--port", "9000", "--rename-field", "icaoCode=icao,description=name", "--no-progress"];
+        let matches = Cli::command().try_get_matches_from(args).unwrap();
+        let mut typed: Vec<String> = typed_globals(&matches).into_iter().map(|arg| arg.into_string().unwrap()).collect();
+        typed.sort();
+        let expected = ["--collection=types", "--database=typed", "--no-progress", "--rename-field=description=name", "--rename-field=icaoCode=icao", "--verbose", "--verbose"];
+        assert_eq!(typed, expected);
+    }
+}
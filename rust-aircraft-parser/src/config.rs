@@ -0,0 +1,350 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, ValueEnum};
+use serde::Deserialize;
+use rust_aircraft_parser::fields::MissingCode;
+use rust_aircraft_parser::hooks::Hook;
+use rust_aircraft_parser::input::{self, Format};
+use rust_aircraft_parser::merge::Source;
+use rust_aircraft_parser::sync::MergePolicy;
+use rust_aircraft_parser::webhook::WebhookFormat;
+use rust_aircraft_parser::{Error, Result};
+use crate::cli::{parse_write_concern, AuthMechanismArg, Backend, Cli, Command, FieldCase, ReadPreferenceArg, SourceArgs, Tenant};
+
+/// Files looked for in the working directory when --config is not given, in order.
+const DISCOVERED: [&str; 3] = ["parser.toml", "parser.yaml", "parser.yml"];
+
+/// Settings read from `parser.toml` (or `.yaml`). Every key is optional and only fills in
+/// options that were given neither on the command line nor through the environment.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub input: Option<PathBuf>,
+    pub format: Option<String>,
+    /// Encoding of input without a byte order mark, as for --encoding.
+    pub encoding: Option<String>,
+    pub backend: Option<String>,
+    #[cfg(feature = "sqlite")]
+    pub db: Option<PathBuf>,
+    pub database: Option<String>,
+    pub collection: Option<String>,
+    pub write_concern: Option<String>,
+    pub read_preference: Option<String>,
+    /// In seconds, as the flags.
+    pub server_selection_timeout: Option<u64>,
+    pub connect_timeout: Option<u64>,
+    pub app_name: Option<String>,
+    pub tls: Option<bool>,
+    pub tls_ca_file: Option<PathBuf>,
+    pub tls_certificate_key_file: Option<PathBuf>,
+    /// As for --auth-mechanism, e.g. `x509`.
+    pub auth_mechanism: Option<String>,
+    pub auth_source: Option<String>,
+    pub username: Option<String>,
+    pub batch_size: Option<usize>,
+    /// Keys `serve` requires, as for --api-key.
+    pub api_keys: Option<Vec<String>>,
+    pub rate_limit: Option<u32>,
+    #[serde(default)]
+    pub fields: Fields,
+    pub field_case: Option<String>,
+    /// Stored names of the aircraft fields, by camelCase name, as for --rename-field.
+    pub rename_fields: Option<BTreeMap<String, String>>,
+    /// How a missing IATA code is stored, as for --missing-iata.
+    pub missing_iata: Option<String>,
+    /// Where run summaries are POSTed, as for --notify-url.
+    pub notify_url: Option<String>,
+    pub notify_format: Option<String>,
+    /// How sync updates each field of the stored aircraft, e.g. `description = "keep-existing"`.
+    pub merge: Option<MergePolicy>,
+    /// Curated corrections applied after every load and sync, as for --overrides.
+    pub overrides: Option<PathBuf>,
+    /// Whether writes are recorded in audit_log, as with --audit.
+    pub audit: Option<bool>,
+    pub actor: Option<String>,
+    /// Transforms every aircraft read is passed through, in order, as for --hook.
+    pub hooks: Option<Vec<String>>,
+    /// Sources `load` merges when told nothing else to read, highest priority first, as
+    /// for --source.
+    pub sources: Option<Vec<SourceConfig>>,
+    /// Where each tenant's data goes, for --tenant and --all-tenants, e.g.
+    /// `[tenants.acme]` with `database = "acme-aircraft"`.
+    pub tenants: Option<BTreeMap<String, TenantConfig>>,
+    /// Connection string of the mongo backend, used over MONGODB_URL.
+    pub mongodb_url: Option<String>,
+    /// Secret the connection string is fetched from, as for --mongo-uri-secret.
+    pub mongo_uri_secret: Option<String>,
+    /// Whether purge, rollback, restore and delete need --yes even on a terminal.
+    pub require_yes: Option<bool>,
+    /// Named sets of these settings, e.g. `[profile.prod]`; the one --profile or APP_ENV
+    /// selects overrides the rest of the file.
+    #[serde(default)]
+    pub profile: BTreeMap<String, Config>,
+}
+
+/// Input columns holding each aircraft field, as for the --csv-*-column flags.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Fields {
+    pub icao_code: Option<String>,
+    pub iata_code: Option<String>,
+    pub description: Option<String>,
+}
+
+/// A `[[sources]]` entry, e.g. `{ name = "doc8643", path = "doc8643.csv", format = "csv" }`.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SourceConfig {
+    pub name: String,
+    pub path: PathBuf,
+    /// As for --format; the load's own format when left out.
+    pub format: Option<String>,
+}
+
+impl SourceConfig {
+    fn to_source(&self) -> Result<Source> {
+        let format = self
+            .format
+            .as_deref()
+            .map(|format| Format::from_str(format, true).map_err(|_| Error::Config(format!("unknown format {:?} of source {:?} in config", format, self.name))))
+            .transpose()?;
+        Ok(Source { name: self.name.clone(), path: self.path.clone(), format })
+    }
+}
+
+/// A `[tenants.NAME]` table. Keys left out take the defaults of --tenant.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct TenantConfig {
+    pub database: Option<String>,
+    pub collection: Option<String>,
+    /// Namespace of the tenant's uuid5 ids.
+    pub id_namespace: Option<String>,
+}
+
+impl TenantConfig {
+    fn to_tenant(&self, name: &str) -> Result<Tenant> {
+        let id_namespace = self
+            .id_namespace
+            .as_deref()
+            .map(|namespace| namespace.parse().map_err(|_| Error::Config(format!("invalid id namespace {:?} of tenant {:?} in config", namespace, name))))
+            .transpose()?;
+        Ok(Tenant { database: self.database.clone(), collection: self.collection.clone(), id_namespace })
+    }
+}
+
+impl Config {
+    /// Reads `path`, as YAML for a `.yaml` or `.yml` extension and as TOML otherwise.
+    pub fn read(path: &Path) -> Result<Config> {
+        let text = fs::read_to_string(path).map_err(|source| Error::Io { path: path.to_path_buf(), source })?;
+        let yaml = matches!(path.extension().and_then(|extension| extension.to_str()), Some("yaml" | "yml"));
+        let parsed = if yaml {
+            serde_yaml::from_str(&text).map_err(|error| error.to_string())
+        } else {
+            toml::from_str(&text).map_err(|error| error.to_string())
+        };
+        parsed.map_err(|error| Error::Config(format!("{}: {}", path.display(), error)))
+    }
+
+    /// Reads the first of [`DISCOVERED`] present in the working directory, if any.
+    pub fn discover() -> Result<Option<Config>> {
+        DISCOVERED
+            .iter()
+            .map(Path::new)
+            .find(|path| path.is_file())
+            .map(Config::read)
+            .transpose()
+    }
+
+    /// Fills the options of `cli` that `matches` shows were left at their defaults, from the
+    /// profile selected by --profile or APP_ENV over the rest of the file.
+    pub fn apply(&self, cli: &mut Cli, matches: &ArgMatches) -> Result<()> {
+        self.apply_settings(cli, matches)?;
+        let Some(name) = cli.global.profile.clone() else { return Ok(()) };
+        match self.profile.get(&name) {
+            Some(profile) if !profile.profile.is_empty() => Err(Error::Config(format!("profile {:?} holds profiles of its own", name))),
+            Some(profile) => profile.apply_settings(cli, matches),
+            // APP_ENV may name an environment the file has nothing particular for.
+            None if !typed(matches, "profile") => Ok(()),
+            None => Err(Error::Config(format!("no profile {:?} in config", name))),
+        }
+    }
+
+    fn apply_settings(&self, cli: &mut Cli, matches: &ArgMatches) -> Result<()> {
+        let subcommand = matches.subcommand().map(|(_, matches)| matches);
+        // Global options can be given before or after the subcommand.
+        let unset_global = |id: &str| !explicit(matches, id) && !subcommand.is_some_and(|matches| explicit(matches, id));
+        let unset = |id: &str| !subcommand.is_some_and(|matches| explicit(matches, id));
+
+        let global = &mut cli.global;
+        set(&mut global.input, &self.input, unset_global("input"));
+        if let (Some(backend), true) = (&self.backend, unset_global("backend")) {
+            global.backend = Backend::from_str(backend, true)
+                .map_err(|_| Error::Config(format!("unknown backend {:?} in config", backend)))?;
+        }
+        #[cfg(feature = "sqlite")]
+        set(&mut global.db, &self.db, unset_global("db"));
+        set(&mut global.database, &self.database, unset_global("database"));
+        set(&mut global.collection, &self.collection, unset_global("collection"));
+        if let (Some(write_concern), true) = (&self.write_concern, unset_global("write_concern")) {
+            let write_concern = parse_write_concern(write_concern).map_err(|error| Error::Config(format!("{} in config", error)))?;
+            global.write_concern = Some(write_concern);
+        }
+        if let (Some(read_preference), true) = (&self.read_preference, unset_global("read_preference")) {
+            let read_preference = ReadPreferenceArg::from_str(read_preference, true)
+                .map_err(|_| Error::Config(format!("unknown read preference {:?} in config", read_preference)))?;
+            global.read_preference = Some(read_preference);
+        }
+        fill(&mut global.server_selection_timeout, &self.server_selection_timeout, unset_global("server_selection_timeout"));
+        fill(&mut global.connect_timeout, &self.connect_timeout, unset_global("connect_timeout"));
+        fill(&mut global.app_name, &self.app_name, unset_global("app_name"));
+        set(&mut global.tls, &self.tls, unset_global("tls"));
+        fill(&mut global.tls_ca_file, &self.tls_ca_file, unset_global("tls_ca_file"));
+        fill(&mut global.tls_certificate_key_file, &self.tls_certificate_key_file, unset_global("tls_certificate_key_file"));
+        if let (Some(mechanism), true) = (&self.auth_mechanism, unset_global("auth_mechanism")) {
+            let mechanism = AuthMechanismArg::from_str(mechanism, true)
+                .map_err(|_| Error::Config(format!("unknown auth mechanism {:?} in config", mechanism)))?;
+            global.auth_mechanism = Some(mechanism);
+        }
+        fill(&mut global.auth_source, &self.auth_source, unset_global("auth_source"));
+        fill(&mut global.username, &self.username, unset_global("username"));
+        if let (Some(field_case), true) = (&self.field_case, unset_global("field_case")) {
+            global.field_case = FieldCase::from_str(field_case, true)
+                .map_err(|_| Error::Config(format!("unknown field case {:?} in config", field_case)))?;
+        }
+        if let (Some(missing_iata), true) = (&self.missing_iata, unset_global("missing_iata")) {
+            global.missing_iata = MissingCode::from_str(missing_iata, true)
+                .map_err(|_| Error::Config(format!("unknown missing iata {:?} in config", missing_iata)))?;
+        }
+        fill(&mut global.notify_url, &self.notify_url, unset_global("notify_url"));
+        if let (Some(notify_format), true) = (&self.notify_format, unset_global("notify_format")) {
+            global.notify_format = WebhookFormat::from_str(notify_format, true)
+                .map_err(|_| Error::Config(format!("unknown notify format {:?} in config", notify_format)))?;
+        }
+        fill(&mut global.overrides, &self.overrides, unset_global("overrides"));
+        set(&mut global.audit, &self.audit, unset_global("audit"));
+        fill(&mut global.actor, &self.actor, unset_global("actor"));
+        fill(&mut global.mongodb_url, &self.mongodb_url, true);
+        fill(&mut global.mongo_uri_secret, &self.mongo_uri_secret, unset_global("mongo_uri_secret"));
+        set(&mut global.require_yes, &self.require_yes, true);
+        if let Some(tenants) = &self.tenants {
+            global.tenants = tenants.iter().map(|(name, tenant)| Ok((name.clone(), tenant.to_tenant(name)?))).collect::<Result<_>>()?;
+        }
+        if let (Some(renames), true) = (&self.rename_fields, unset_global("rename_field")) {
+            global.rename_field = renames.clone().into_iter().collect();
+        }
+
+        if let Command::Serve(args) = &mut cli.command {
+            set(&mut args.api_key, &self.api_keys, unset("api_key"));
+            fill(&mut args.rate_limit, &self.rate_limit, unset("rate_limit"));
+            return Ok(());
+        }
+        let (source, batch_size) = match &mut cli.command {
+            Command::Load(args) => {
+                // Only loads told nothing else to read merge the sources of the config.
+                let reads_other = !args.paths().is_empty() || args.remote.url.is_some() || args.from_embedded || args.dataset.is_some();
+                if let (Some(sources), true) = (&self.sources, unset("sources") && !reads_other) {
+                    args.sources = sources.iter().map(SourceConfig::to_source).collect::<Result<_>>()?;
+                }
+                (&mut args.source, &mut args.run.batch_size)
+            }
+            Command::Sync(args) => {
+                set(&mut args.merge, &self.merge, true);
+                (&mut args.source, &mut args.run.batch_size)
+            }
+            Command::Watch(args) => {
+                set(&mut args.sync.merge, &self.merge, true);
+                (&mut args.sync.source, &mut args.sync.run.batch_size)
+            }
+            _ => return Ok(()),
+        };
+        set(batch_size, &self.batch_size, unset("batch_size"));
+        self.apply_source(source, unset)
+    }
+
+    fn apply_source(&self, source: &mut SourceArgs, unset: impl Fn(&str) -> bool) -> Result<()> {
+        if let (Some(format), true) = (&self.format, unset("format")) {
+            source.format = Format::from_str(format, true)
+                .map_err(|_| Error::Config(format!("unknown format {:?} in config", format)))?;
+        }
+        if let (Some(encoding), true) = (&self.encoding, unset("encoding")) {
+            let encoding = input::encoding_for(encoding).ok_or_else(|| Error::Config(format!("unknown encoding {:?} in config", encoding)))?;
+            source.encoding = Some(encoding);
+        }
+        set(&mut source.csv_icao_column, &self.fields.icao_code, unset("csv_icao_column"));
+        set(&mut source.csv_iata_column, &self.fields.iata_code, unset("csv_iata_column"));
+        set(&mut source.csv_description_column, &self.fields.description, unset("csv_description_column"));
+        if let (Some(hooks), true) = (&self.hooks, unset("hooks")) {
+            source.hooks = hooks.iter().map(|hook| Hook::parse(hook)).collect();
+        }
+        Ok(())
+    }
+}
+
+fn explicit(matches: &ArgMatches, id: &str) -> bool {
+    matches!(matches.value_source(id), Some(ValueSource::CommandLine | ValueSource::EnvVariable))
+}
+
+// Whether the global option `id` was typed, before or after the subcommand, rather than
+// taken from the environment.
+fn typed(matches: &ArgMatches, id: &str) -> bool {
+    let on_command_line = |matches: &ArgMatches| matches.value_source(id) == Some(ValueSource::CommandLine);
+    on_command_line(matches) || matches.subcommand().is_some_and(|(_, matches)| on_command_line(matches))
+}
+
+fn set<T: Clone>(target: &mut T, value: &Option<T>, unset: bool) {
+    if let (Some(value), true) = (value, unset) {
+        *target = value.clone();
+    }
+}
+
+// `set` for options that have no default.
+fn fill<T: Clone>(target: &mut Option<T>, value: &Option<T>, unset: bool) {
+    if let (Some(value), true) = (value, unset) {
+        *target = Some(value.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{CommandFactory, FromArgMatches};
+    use super::*;
+
+    // `args` parsed and filled from the TOML `config`.
+    fn applied(config: &str, args: &[&str]) -> Result<Cli> {
+        let config: Config = toml::from_str(config).unwrap();
+        let matches = Cli::command().try_get_matches_from(args).unwrap();
+        let mut cli = Cli::from_arg_matches(&matches).unwrap();
+        config.apply(&mut cli, &matches)?;
+        Ok(cli)
+    }
+
+    #[test]
+    fn fills_only_the_options_left_unset() {
+        let config = "database = \"configured\"\ncollection = \"types\"\nbatch_size = 50\nformat = \"csv\"\n";
+        let cli = applied(config, &["parser", "load", "--database", "typed", "aircraft.csv"]).unwrap();
+        assert_eq!(cli.global.database, "typed");
+        assert_eq!(cli.global.collection, "types");
+        let Command::Load(args) = cli.command else { panic!("not a load") };
+        assert_eq!(args.run.batch_size, 50);
+        assert_eq!(args.source.format, Format::Csv);
+    }
+
+    #[test]
+    fn a_profile_overrides_the_rest_of_the_file() {
+        let config = "database = \"staging\"\ncollection = \"types\"\n[profile.prod]\ndatabase = \"prod\"\n";
+        let cli = applied(config, &["parser", "--profile", "prod", "stats"]).unwrap();
+        assert_eq!((cli.global.database.as_str(), cli.global.collection.as_str()), ("prod", "types"));
+        let missing = applied(config, &["parser", "--profile", "dev", "stats"]).err();
+        assert!(matches!(missing, Some(Error::Config(message)) if message == "no profile \"dev\" in config"));
+    }
+
+    #[test]
+    fn refuses_unknown_keys_and_values() {
+        assert!(toml::from_str::<Config>("databse = \"typo\"").is_err());
+        let unknown = applied("backend = \"cassandra\"", &["parser", "stats"]).err();
+        assert!(matches!(unknown, Some(Error::Config(message)) if message == "unknown backend \"cassandra\" in config"));
+    }
+}
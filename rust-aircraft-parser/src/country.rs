@@ -0,0 +1,63 @@
+use std::borrow::Cow;
+use mongodb::bson::doc;
+use mongodb::IndexModel;
+use serde::{Deserialize, Serialize};
+use crate::record::Record;
+use crate::validate::is_alphanumeric;
+
+/// A country as listed in ISO 3166-1, keyed by its alpha-2 code.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Country {
+    /// ISO 3166-1 alpha-2 code, e.g. `GB`.
+    pub iso_code: String,
+    /// ISO 3166-1 alpha-3 code, e.g. `GBR`. Empty when not given.
+    #[serde(default)]
+    pub iso3_code: String,
+    /// Short name, e.g. `United Kingdom`.
+    pub name: String,
+    /// ICAO nationality marks of aircraft registered in the country, e.g. `["G"]`.
+    #[serde(default)]
+    pub icao_prefixes: Vec<String>,
+}
+
+impl Record for Country {
+    const COLLECTION: &'static str = "countries";
+    const KEY_FIELD: &'static str = "isoCode";
+
+    fn key(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.iso_code)
+    }
+
+    /// * `isoCode` must be 2 upper case letters.
+    /// * `iso3Code` must be exactly 3 upper case letters, or empty.
+    /// * `name` must not be blank.
+    /// * `icaoPrefixes` must be 1 or 2 letters or digits each.
+    fn validate(&self) -> Vec<String> {
+        let mut reasons = Vec::new();
+        if self.iso_code.len() != 2 || !is_upper_case(&self.iso_code) {
+            reasons.push(format!("isoCode {:?} is not 2 upper case letters", self.iso_code));
+        }
+        if !self.iso3_code.is_empty() && (self.iso3_code.len() != 3 || !is_upper_case(&self.iso3_code)) {
+            reasons.push(format!("iso3Code {:?} is neither empty nor 3 upper case letters", self.iso3_code));
+        }
+        if self.name.trim().is_empty() {
+            reasons.push("name is empty".to_string());
+        }
+        for prefix in &self.icao_prefixes {
+            if !(1..=2).contains(&prefix.chars().count()) || !is_alphanumeric(prefix) {
+                reasons.push(format!("icaoPrefixes entry {:?} is not 1 or 2 alphanumeric characters", prefix));
+            }
+        }
+        reasons
+    }
+
+    /// An index on `iso3Code`.
+    fn indexes() -> Vec<IndexModel> {
+        vec![IndexModel::builder().keys(doc! { "iso3Code": 1 }).build()]
+    }
+}
+
+fn is_upper_case(code: &str) -> bool {
+    code.chars().all(|character| character.is_ascii_uppercase())
+}
@@ -0,0 +1,360 @@
+//! A long-running process starting loads on request over HTTP, for platforms that kick off
+//! ingests by API call rather than over SSH.
+//!
+//! | endpoint | response |
+//! |----------|----------|
+//! | `POST /loads` | 202 with the queued [`Job`] of a [`LoadRequest`] |
+//! | `GET /loads/{id}` | the [`Job`] with that id |
+//! | `GET /loads` | every job, most recently requested first |
+//!
+//! Loads run one at a time in the order requested: the source is fetched into the cache
+//! directory and loaded by this program's own `load` command, run as a child process with
+//! the [`Launcher`]'s options so each load has the process of its own. With API keys
+//! configured, requests must carry one as for [`server`](crate::server). The
+//! [`DaemonOptions`] bound the loads waiting to run, refused with 503 beyond it, and the
+//! finished jobs kept, the oldest being forgotten first.
+
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::ffi::OsString;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use axum::extract::{Path, Request, State};
+use axum::http::StatusCode;
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use tokio::process::Command;
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tracing::{info, warn};
+use uuid::Uuid;
+use crate::input::Format;
+use crate::provenance::rfc3339;
+use crate::remote;
+use crate::server::{api_key, lists_key, serve_router, ApiError, ApiResult};
+use crate::Result;
+
+/// What a load reads, as `load` and its dataset subcommands take it.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Dataset {
+    #[default]
+    Aircraft,
+    Airports,
+    Airlines,
+    Routes,
+    Countries,
+    Registrations,
+    Hexdb,
+}
+
+impl Dataset {
+    // The subcommand of `load` for the dataset; aircraft are loaded by `load` itself.
+    fn subcommand(self) -> Option<&'static str> {
+        match self {
+            Dataset::Aircraft => None,
+            Dataset::Airports => Some("airports"),
+            Dataset::Airlines => Some("airlines"),
+            Dataset::Routes => Some("routes"),
+            Dataset::Countries => Some("countries"),
+            Dataset::Registrations => Some("registrations"),
+            Dataset::Hexdb => Some("hexdb"),
+        }
+    }
+}
+
+/// The body of `POST /loads`.
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LoadRequest {
+    /// Aircraft when left out.
+    #[serde(default)]
+    pub dataset: Dataset,
+    /// Where the source file is fetched from over HTTP(S).
+    pub url: String,
+    /// Layout of the file as for --format, e.g. `csv`; that of the dataset's `load` when
+    /// left out.
+    pub format: Option<String>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// A load requested over the API.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Job {
+    pub id: String,
+    pub dataset: Dataset,
+    pub url: String,
+    pub format: Option<String>,
+    pub status: JobStatus,
+    pub requested_at: String,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+    /// What the load printed: its JSON summary for aircraft, its output otherwise.
+    pub summary: Option<serde_json::Value>,
+    /// Why the load failed.
+    pub error: Option<String>,
+}
+
+/// Who may request loads and how many jobs the daemon holds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DaemonOptions {
+    /// Keys requests must carry one of; any caller is served when empty.
+    pub api_keys: HashSet<String>,
+    /// Loads waiting to run at most, at least 1; further requests are refused until one
+    /// starts.
+    pub max_queued: usize,
+    /// Finished jobs kept at most, the oldest being forgotten first.
+    pub max_jobs: usize,
+}
+
+/// How loads are run: this program with the global options the daemon was started with,
+/// as they were typed, before the `load` subcommand.
+#[derive(Clone, Debug)]
+pub struct Launcher {
+    pub program: PathBuf,
+    pub args: Vec<OsString>,
+    /// Where fetched source files are kept.
+    pub cache_dir: PathBuf,
+}
+
+impl Launcher {
+    // Fetches the source of `request` and loads it, returning what the load printed.
+    async fn run(&self, request: &LoadRequest) -> std::result::Result<serde_json::Value, String> {
+        // Asked for explicitly, the file is loaded even when it has not changed.
+        let fetched = remote::fetch(&request.url, &self.cache_dir, false).await.map_err(|error| error.to_string())?;
+        let mut command = Command::new(&self.program);
+        command.args(&self.args).arg("load");
+        command.args(request.dataset.subcommand());
+        command.arg(&fetched.path).args(["--output", "json"]);
+        if let Some(format) = &request.format {
+            command.args(["--format", format]);
+        }
+        let output = command.output().await.map_err(|error| format!("cannot run {}: {}", self.program.display(), error))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(stderr.lines().last().map_or_else(|| output.status.to_string(), str::to_string));
+        }
+        fetched.keep().map_err(|error| error.to_string())?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let summary = stdout.lines().rev().find_map(|line| serde_json::from_str(line).ok());
+        Ok(summary.unwrap_or_else(|| serde_json::Value::String(stdout.trim().to_string())))
+    }
+}
+
+// The jobs requested, by id, with the ids of the finished ones in the order they finished.
+#[derive(Default)]
+struct Jobs {
+    by_id: BTreeMap<String, Job>,
+    finished: VecDeque<String>,
+}
+
+// The jobs requested and the queue of those still to run.
+#[derive(Clone)]
+struct Daemon {
+    jobs: Arc<Mutex<Jobs>>,
+    queue: Sender<(String, LoadRequest)>,
+    max_jobs: usize,
+}
+
+impl Daemon {
+    fn new(queue: Sender<(String, LoadRequest)>, max_jobs: usize) -> Daemon {
+        Daemon { jobs: Arc::new(Mutex::new(Jobs::default())), queue, max_jobs }
+    }
+
+    fn jobs(&self) -> std::sync::MutexGuard<'_, Jobs> {
+        self.jobs.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn update(&self, id: &str, change: impl FnOnce(&mut Job)) {
+        if let Some(job) = self.jobs().by_id.get_mut(id) {
+            change(job);
+        }
+    }
+
+    // Records the outcome of the job `id`, forgetting the jobs that finished first beyond
+    // the `max_jobs` kept.
+    fn finish(&self, id: &str, outcome: std::result::Result<serde_json::Value, String>) {
+        let mut jobs = self.jobs();
+        let Some(job) = jobs.by_id.get_mut(id) else { return };
+        job.finished_at = Some(rfc3339(SystemTime::now()));
+        match outcome {
+            Ok(summary) => {
+                job.status = JobStatus::Succeeded;
+                job.summary = Some(summary);
+            }
+            Err(error) => {
+                job.status = JobStatus::Failed;
+                job.error = Some(error);
+            }
+        }
+        jobs.finished.push_back(id.to_string());
+        while jobs.finished.len() > self.max_jobs {
+            let Some(oldest) = jobs.finished.pop_front() else { break };
+            jobs.by_id.remove(&oldest);
+        }
+    }
+}
+
+// Runs the queued loads one after the other for as long as the daemon runs.
+async fn work(daemon: Daemon, launcher: Launcher, mut queue: Receiver<(String, LoadRequest)>) {
+    while let Some((id, request)) = queue.recv().await {
+        info!(%id, url = %request.url, "starting load");
+        daemon.update(&id, |job| {
+            job.status = JobStatus::Running;
+            job.started_at = Some(rfc3339(SystemTime::now()));
+        });
+        let outcome = launcher.run(&request).await;
+        if let Err(error) = &outcome {
+            warn!(%id, %error, "load failed");
+        }
+        daemon.finish(&id, outcome);
+    }
+}
+
+/// The daemon's routes, queueing loads for `launcher` within the bounds of `options`, for
+/// callers with one of its API keys or any caller when there are none.
+pub fn router(launcher: Launcher, options: DaemonOptions) -> Router {
+    let (queue, requests) = mpsc::channel(options.max_queued);
+    let daemon = Daemon::new(queue, options.max_jobs);
+    tokio::spawn(work(daemon.clone(), launcher, requests));
+    Router::new()
+        .route("/loads", get(list_loads).post(request_load))
+        .route("/loads/{id}", get(get_load))
+        .layer(middleware::from_fn_with_state(Arc::new(options.api_keys), authorize))
+        .with_state(daemon)
+}
+
+/// Serves the [`router`] on `address` until the process receives Ctrl-C.
+pub async fn serve(launcher: Launcher, options: DaemonOptions, address: SocketAddr) -> Result<()> {
+    serve_router(router(launcher, options), address).await
+}
+
+async fn authorize(State(api_keys): State<Arc<HashSet<String>>>, request: Request, next: Next) -> Response {
+    if !api_keys.is_empty() {
+        match api_key(request.headers()) {
+            None => return ApiError::Unauthorized("missing API key").into_response(),
+            Some(key) if !lists_key(&api_keys, key) => return ApiError::Unauthorized("unknown API key").into_response(),
+            Some(_) => {}
+        }
+    }
+    next.run(request).await
+}
+
+async fn request_load(State(daemon): State<Daemon>, Json(request): Json<LoadRequest>) -> std::result::Result<(StatusCode, Json<Job>), ApiError> {
+    if !request.url.starts_with("http://") && !request.url.starts_with("https://") {
+        return Err(ApiError::BadRequest(format!("{:?} is not an HTTP(S) URL", request.url)));
+    }
+    if let Some(format) = &request.format {
+        Format::from_str(format, true).map_err(|_| ApiError::BadRequest(format!("unknown format {:?}", format)))?;
+    }
+    let job = Job {
+        id: Uuid::new_v4().to_string(),
+        dataset: request.dataset,
+        url: request.url.clone(),
+        format: request.format.clone(),
+        status: JobStatus::Queued,
+        requested_at: rfc3339(SystemTime::now()),
+        started_at: None,
+        finished_at: None,
+        summary: None,
+        error: None,
+    };
+    // Held while queueing, so the worker never starts a job that is not listed yet.
+    let mut jobs = daemon.jobs();
+    // The worker only stops with the runtime, so the queue can only be full.
+    if daemon.queue.try_send((job.id.clone(), request)).is_err() {
+        return Err(ApiError::Unavailable("too many loads are queued, try again later".to_string()));
+    }
+    jobs.by_id.insert(job.id.clone(), job.clone());
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+async fn get_load(State(daemon): State<Daemon>, Path(id): Path<String>) -> ApiResult<Job> {
+    match daemon.jobs().by_id.get(&id) {
+        Some(job) => Ok(Json(job.clone())),
+        None => Err(ApiError::NotFound(format!("no load {}", id))),
+    }
+}
+
+async fn list_loads(State(daemon): State<Daemon>) -> Json<Vec<Job>> {
+    let mut jobs: Vec<Job> = daemon.jobs().by_id.values().cloned().collect();
+    jobs.sort_by(|left, right| right.requested_at.cmp(&left.requested_at));
+    Json(jobs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A daemon without a worker, so what is queued stays queued.
+    fn idle(max_queued: usize, max_jobs: usize) -> (Daemon, Receiver<(String, LoadRequest)>) {
+        let (queue, requests) = mpsc::channel(max_queued);
+        (Daemon::new(queue, max_jobs), requests)
+    }
+
+    fn request(url: &str) -> Json<LoadRequest> {
+        Json(LoadRequest { dataset: Dataset::Aircraft, url: url.to_string(), format: None })
+    }
+
+    async fn requested(daemon: &Daemon, url: &str) -> std::result::Result<Job, ApiError> {
+        request_load(State(daemon.clone()), request(url)).await.map(|(_, Json(job))| job)
+    }
+
+    #[tokio::test]
+    async fn requests_beyond_the_queue_are_refused() {
+        let (daemon, mut requests) = idle(2, 10);
+        let first = requested(&daemon, "https://example.com/a.json").await.unwrap();
+        requested(&daemon, "https://example.com/b.json").await.unwrap();
+        let refused = requested(&daemon, "https://example.com/c.json").await;
+        assert!(matches!(refused, Err(ApiError::Unavailable(_))));
+        assert_eq!(daemon.jobs().by_id.len(), 2);
+
+        // Once a load starts, there is room for one more.
+        assert_eq!(requests.recv().await.unwrap().0, first.id);
+        requested(&daemon, "https://example.com/c.json").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn only_urls_and_known_formats_are_queued() {
+        let (daemon, _requests) = idle(2, 10);
+        assert!(matches!(requested(&daemon, "/etc/passwd").await, Err(ApiError::BadRequest(_))));
+        let unknown = LoadRequest { format: Some("docx".to_string()), ..request("https://example.com/a").0 };
+        assert!(matches!(request_load(State(daemon.clone()), Json(unknown)).await, Err(ApiError::BadRequest(_))));
+        assert!(daemon.jobs().by_id.is_empty());
+    }
+
+    #[tokio::test]
+    async fn the_oldest_finished_jobs_are_forgotten() {
+        let (daemon, _requests) = idle(10, 2);
+        let mut ids = Vec::new();
+        for name in ["a", "b", "c", "d"] {
+            ids.push(requested(&daemon, &format!("https://example.com/{}.json", name)).await.unwrap().id);
+        }
+        daemon.finish(&ids[1], Ok(serde_json::Value::Null));
+        daemon.finish(&ids[0], Err("failed".to_string()));
+        daemon.finish(&ids[2], Ok(serde_json::Value::Null));
+
+        // The job that finished first went, the one still queued stays.
+        let Json(jobs) = list_loads(State(daemon.clone())).await;
+        let mut kept: Vec<&str> = jobs.iter().map(|job| job.id.as_str()).collect();
+        kept.sort();
+        let mut expected = vec![ids[0].as_str(), ids[2].as_str(), ids[3].as_str()];
+        expected.sort();
+        assert_eq!(kept, expected);
+        assert!(matches!(get_load(State(daemon.clone()), Path(ids[1].clone())).await, Err(ApiError::NotFound(_))));
+        let Json(failed) = get_load(State(daemon), Path(ids[0].clone())).await.unwrap();
+        assert_eq!((failed.status, failed.error.as_deref()), (JobStatus::Failed, Some("failed")));
+    }
+}
@@ -0,0 +1,171 @@
+//! Resolving ICAO codes listed more than once in an input before it is written.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use clap::ValueEnum;
+use serde::Serialize;
+use tracing::{debug, warn};
+use crate::{Aircraft, Error, Result};
+
+/// Which entry of an ICAO code listed more than once is written.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DedupStrategy {
+    /// The first entry
+    First,
+    /// The last entry
+    Last,
+    /// None: a code listed with different fields fails the run
+    Error,
+    /// The entry with the longest description, its IATA code filled in from another entry if empty
+    MergeLongestDescription,
+}
+
+/// An ICAO code listed more than once with different fields.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Conflict {
+    pub icao_code: String,
+    /// Every entry of the code, in input order.
+    pub entries: Vec<Aircraft>,
+    /// The entry written; none with [`DedupStrategy::Error`].
+    pub kept: Option<Aircraft>,
+}
+
+/// The input with every ICAO code listed once, and the codes whose entries differed.
+#[derive(Debug, Default)]
+pub struct Deduped {
+    /// Each code at the place of its first entry, parse errors where they were read.
+    pub aircrafts: Vec<Result<Aircraft>>,
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Reads all of `aircrafts` and keeps one entry per ICAO code as `strategy` picks it.
+/// Entries identical to another of their code are dropped without being reported. With
+/// [`DedupStrategy::Error`] the conflicts are reported and no entry of their codes kept,
+/// the caller failing the run.
+pub fn dedup(aircrafts: impl Iterator<Item = Result<Aircraft>>, strategy: DedupStrategy) -> Deduped {
+    // Entries of each code, and the place in the output of its first one.
+    let mut entries: HashMap<String, (usize, Vec<Aircraft>)> = HashMap::new();
+    let mut slots: Vec<Option<Result<Aircraft>>> = Vec::new();
+    for aircraft in aircrafts {
+        match aircraft {
+            Ok(aircraft) => {
+                let slot = slots.len();
+                let (_, listed) = entries.entry(aircraft.icao_code.clone()).or_insert_with(|| (slot, Vec::new()));
+                if listed.is_empty() {
+                    slots.push(None);
+                }
+                listed.push(aircraft);
+            }
+            Err(error) => slots.push(Some(Err(error))),
+        }
+    }
+    let mut conflicts = Vec::new();
+    for (icao_code, (slot, listed)) in entries {
+        if listed.len() > 1 {
+            debug!(%icao_code, entries = listed.len(), "duplicate icao code");
+        }
+        let distinct = listed.iter().enumerate().filter(|(index, aircraft)| !listed[..*index].contains(*aircraft)).count();
+        let kept = match (distinct, strategy) {
+            (1, _) | (_, DedupStrategy::First) => Some(listed[0].clone()),
+            (_, DedupStrategy::Last) => listed.last().cloned(),
+            (_, DedupStrategy::Error) => None,
+            (_, DedupStrategy::MergeLongestDescription) => Some(merge_longest_description(&listed)),
+        };
+        if distinct > 1 {
+            warn!(%icao_code, entries = listed.len(), "conflicting entries");
+            conflicts.push(Conflict { icao_code, entries: listed, kept: kept.clone() });
+        }
+        slots[slot] = kept.map(Ok);
+    }
+    conflicts.sort_by(|left, right| left.icao_code.cmp(&right.icao_code));
+    Deduped { aircrafts: slots.into_iter().flatten().collect(), conflicts }
+}
+
+/// Writes `conflicts` to `path` as a pretty-printed JSON array.
+pub fn write_conflicts(path: &Path, conflicts: &[Conflict]) -> Result<()> {
+    let json = serde_json::to_string_pretty(conflicts)?;
+    fs::write(path, json).map_err(|source| Error::Write { path: path.to_path_buf(), source })
+}
+
+// The first entry with the longest description, taking the first IATA code of the others
+// when it has none.
+fn merge_longest_description(entries: &[Aircraft]) -> Aircraft {
+    let longest = entries[1..].iter().fold(&entries[0], |longest, aircraft| {
+        if aircraft.description.chars().count() > longest.description.chars().count() {
+            aircraft
+        } else {
+            longest
+        }
+    });
+    let mut merged = longest.clone();
+    if merged.iata_code.is_none() {
+        merged.iata_code = entries.iter().find_map(|aircraft| aircraft.iata_code.clone());
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use super::*;
+
+    fn aircraft(icao_code: &str, iata_code: Option<&str>, description: &str) -> Aircraft {
+        Aircraft {
+            icao_code: icao_code.to_string(),
+            iata_code: iata_code.map(str::to_string),
+            description: description.to_string(),
+            descriptions: BTreeMap::new(),
+            aliases: Vec::new(),
+        }
+    }
+
+    // Two differing entries of B738 around an A320 listed twice the same way.
+    fn input() -> Vec<Result<Aircraft>> {
+        vec![
+            Ok(aircraft("B738", Some("738"), "Boeing 737")),
+            Ok(aircraft("A320", Some("320"), "Airbus A320")),
+            Err(Error::InvalidInput("entry 3".to_string())),
+            Ok(aircraft("B738", None, "Boeing 737-800")),
+            Ok(aircraft("A320", Some("320"), "Airbus A320")),
+        ]
+    }
+
+    fn kept(deduped: &Deduped) -> Vec<&Aircraft> {
+        deduped.aircrafts.iter().filter_map(|aircraft| aircraft.as_ref().ok()).collect()
+    }
+
+    #[test]
+    fn keeps_each_code_at_its_first_place() {
+        let deduped = dedup(input().into_iter(), DedupStrategy::First);
+        let codes: Vec<&str> = kept(&deduped).iter().map(|aircraft| aircraft.icao_code.as_str()).collect();
+        assert_eq!(codes, ["B738", "A320"]);
+        assert_eq!(deduped.aircrafts.len(), 3);
+        assert!(deduped.aircrafts[2].is_err());
+    }
+
+    #[test]
+    fn reports_only_codes_whose_entries_differ() {
+        let deduped = dedup(input().into_iter(), DedupStrategy::Last);
+        assert_eq!(deduped.conflicts.len(), 1);
+        let conflict = &deduped.conflicts[0];
+        assert_eq!(conflict.icao_code, "B738");
+        assert_eq!(conflict.entries.len(), 2);
+        assert_eq!(conflict.kept.as_ref().map(|aircraft| aircraft.description.as_str()), Some("Boeing 737-800"));
+    }
+
+    #[test]
+    fn error_keeps_no_entry_of_a_conflicting_code() {
+        let deduped = dedup(input().into_iter(), DedupStrategy::Error);
+        let codes: Vec<&str> = kept(&deduped).iter().map(|aircraft| aircraft.icao_code.as_str()).collect();
+        assert_eq!(codes, ["A320"]);
+        assert_eq!(deduped.conflicts[0].kept, None);
+    }
+
+    #[test]
+    fn merge_longest_description_fills_in_the_iata_code() {
+        let deduped = dedup(input().into_iter(), DedupStrategy::MergeLongestDescription);
+        assert_eq!(*kept(&deduped)[0], aircraft("B738", Some("738"), "Boeing 737-800"));
+    }
+}
@@ -0,0 +1,165 @@
+//! Comparing two input files by ICAO code, so upstream changes can be reviewed before any
+//! load happens.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use crate::{Aircraft, Result};
+
+/// One field that differs between the old and new entry of an aircraft, by its JSON name.
+/// A field missing on one side is `null` there.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+/// An aircraft both files list, with the fields that differ.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Changed {
+    pub icao_code: String,
+    pub fields: Vec<FieldChange>,
+}
+
+/// What [`diff`] found, sorted by ICAO code.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct InputDiff {
+    /// Aircraft only the new file lists.
+    pub added: Vec<Aircraft>,
+    /// Aircraft only the old file lists.
+    pub removed: Vec<Aircraft>,
+    pub changed: Vec<Changed>,
+    /// Aircraft listed identically in both files.
+    pub unchanged: u64,
+}
+
+impl InputDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// The differences as lines for people: `+` for added, `-` for removed and `~` for changed
+    /// aircraft, the latter followed by their changed fields, then the totals.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for (sign, aircraft) in self.added.iter().map(|aircraft| ('+', aircraft)).chain(self.removed.iter().map(|aircraft| ('-', aircraft))) {
+            let iata_code = aircraft.iata_code.as_deref().unwrap_or("-");
+            let _ = writeln!(text, "{} {:<5} {:<4} {}", sign, aircraft.icao_code, iata_code, aircraft.description);
+        }
+        for changed in &self.changed {
+            let _ = writeln!(text, "~ {}", changed.icao_code);
+            for change in &changed.fields {
+                let _ = writeln!(text, "    {}: {} -> {}", change.field, change.old, change.new);
+            }
+        }
+        let _ = writeln!(
+            text,
+            "{} added, {} removed, {} changed, {} unchanged",
+            self.added.len(),
+            self.removed.len(),
+            self.changed.len(),
+            self.unchanged
+        );
+        text
+    }
+}
+
+/// Compares the aircraft of an `old` and a `new` input by ICAO code, field by field. When an
+/// input lists an ICAO code more than once the last entry wins, as it does for a sync. The
+/// first entry of either input that fails to parse stops the comparison.
+pub fn diff(old: impl Iterator<Item = Result<Aircraft>>, new: impl Iterator<Item = Result<Aircraft>>) -> Result<InputDiff> {
+    let mut old = by_icao(old)?;
+    let mut summary = InputDiff::default();
+    for (icao_code, aircraft) in by_icao(new)? {
+        let Some(previous) = old.remove(&icao_code) else {
+            summary.added.push(aircraft);
+            continue;
+        };
+        let fields = changes(&previous, &aircraft)?;
+        match fields.is_empty() {
+            true => summary.unchanged += 1,
+            false => summary.changed.push(Changed { icao_code, fields }),
+        }
+    }
+    summary.removed = old.into_values().collect();
+    Ok(summary)
+}
+
+fn by_icao(aircrafts: impl Iterator<Item = Result<Aircraft>>) -> Result<BTreeMap<String, Aircraft>> {
+    let mut by_icao = BTreeMap::new();
+    for aircraft in aircrafts {
+        let aircraft = aircraft?;
+        by_icao.insert(aircraft.icao_code.clone(), aircraft);
+    }
+    Ok(by_icao)
+}
+
+// The fields of `old` and `new` that differ: those of the new entry by name, then those only
+// the old one has.
+fn changes(old: &Aircraft, new: &Aircraft) -> Result<Vec<FieldChange>> {
+    let (mut old, new) = (fields(old)?, fields(new)?);
+    let mut changes = Vec::new();
+    for (field, value) in new {
+        let previous = old.remove(&field).unwrap_or(Value::Null);
+        if previous != value {
+            changes.push(FieldChange { field, old: previous, new: value });
+        }
+    }
+    changes.extend(old.into_iter().map(|(field, value)| FieldChange { field, old: value, new: Value::Null }));
+    Ok(changes)
+}
+
+fn fields(aircraft: &Aircraft) -> Result<Map<String, Value>> {
+    match serde_json::to_value(aircraft)? {
+        Value::Object(fields) => Ok(fields),
+        _ => Ok(Map::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::aircraft;
+    use crate::Error;
+
+    fn ok(aircraft: Vec<Aircraft>) -> impl Iterator<Item = Result<Aircraft>> {
+        aircraft.into_iter().map(Ok)
+    }
+
+    #[test]
+    fn sorts_aircraft_into_added_removed_changed_and_unchanged() {
+        let old = vec![aircraft("A320").build(), aircraft("B738").iata("738").build(), aircraft("MD11").build()];
+        let new = vec![
+            aircraft("B738").iata("73H").translation("fr", "Boeing 737").build(),
+            aircraft("A320").build(),
+            aircraft("E175").build(),
+        ];
+        let diff = diff(ok(old), ok(new)).unwrap();
+        assert_eq!(diff.added, vec![aircraft("E175").build()]);
+        assert_eq!(diff.removed, vec![aircraft("MD11").build()]);
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(diff.changed.len(), 1);
+        let fields: Vec<(&str, &Value, &Value)> = diff.changed[0].fields.iter().map(|change| (change.field.as_str(), &change.old, &change.new)).collect();
+        assert_eq!(
+            fields,
+            [("iataCode", &Value::from("738"), &Value::from("73H")), ("descriptions", &Value::Null, &serde_json::json!({ "fr": "Boeing 737" }))]
+        );
+        assert!(diff.text().ends_with("1 added, 1 removed, 1 changed, 1 unchanged\n"));
+    }
+
+    #[test]
+    fn the_last_entry_of_a_code_wins() {
+        let new = vec![aircraft("B738").build(), aircraft("B738").iata("738").build()];
+        let diff = diff(ok(vec![aircraft("B738").iata("738").build()]), ok(new)).unwrap();
+        assert!(diff.is_empty());
+    }
+
+    #[test]
+    fn stops_at_a_parse_error() {
+        let new = vec![Err(Error::InvalidInput("entry 1".to_string()))];
+        assert!(diff(ok(Vec::new()), new.into_iter()).is_err());
+    }
+}
@@ -0,0 +1,37 @@
+//! A baseline aircraft dataset compiled into the binary with the `embedded` feature, so
+//! lookups work without a database and a fresh environment can be loaded without an input
+//! file.
+
+use sha2::{Digest, Sha256};
+use crate::input::{self, AircraftStream, Format, InputOptions};
+use crate::provenance::Provenance;
+use crate::storage::InMemoryStorage;
+use crate::{Aircraft, Result};
+
+/// The dataset as a JSON array in the input layout.
+pub const DATASET: &[u8] = include_bytes!("../data/aircraft.json");
+
+/// What loads of the dataset record as their source file.
+pub const SOURCE: &str = "embedded";
+
+/// The aircraft of the dataset, read with `options` but as JSON whatever they say.
+pub fn stream(options: &InputOptions) -> Result<AircraftStream> {
+    input::stream_aircraft_from(DATASET, &InputOptions { format: Format::Json, ..options.clone() })
+}
+
+/// The aircraft of the dataset, normalized, in the order given.
+pub fn aircraft() -> Result<Vec<Aircraft>> {
+    stream(&InputOptions { normalize: true, ..InputOptions::default() })?.collect()
+}
+
+/// A store holding the dataset, for reading it as any backend is read. What is written to
+/// it is gone when it is dropped.
+pub fn storage() -> Result<InMemoryStorage> {
+    Ok(InMemoryStorage::with_aircraft(aircraft()?))
+}
+
+/// The provenance of a load of the dataset, whose checksum changes only with the dataset.
+pub fn provenance() -> Provenance {
+    let checksum = format!("{:x}", Sha256::digest(DATASET));
+    Provenance { source_file: Some(SOURCE.to_string()), checksum: Some(checksum), ..Provenance::new() }
+}
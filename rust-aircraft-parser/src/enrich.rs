@@ -0,0 +1,324 @@
+//! Merging additional type data into the aircraft already loaded.
+
+use std::collections::HashSet;
+use std::time::Instant;
+use mongodb::bson::{doc, Document};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+use tracing::{info, warn};
+use crate::fields::{ALIASES, DESCRIPTIONS};
+use crate::validate::is_alphanumeric;
+use crate::{AircraftStore, Result, Storage};
+
+/// Data about aircraft types, keyed by type designator, that [`enrich`] adds to the stored
+/// aircraft.
+pub trait TypeData: DeserializeOwned {
+    /// ICAO type designator the entry is about.
+    fn designator(&self) -> &str;
+
+    /// The values that are present, under their stored field names. Empty values are left
+    /// out so they never blank a field already stored.
+    fn fields(&self) -> Document;
+
+    /// Why the entry is invalid; an empty list means it is valid.
+    fn validate(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// Details of a type designator as published in ICAO DOC 8643. Besides this crate's
+/// camelCase names, the field names of ICAO's own JSON download are accepted.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TypeDetails {
+    /// ICAO type designator, e.g. `B38M`.
+    #[serde(alias = "Designator")]
+    pub designator: String,
+    /// Manufacturer, e.g. `BOEING`.
+    #[serde(default, alias = "ManufacturerCode")]
+    pub manufacturer: String,
+    /// Model, e.g. `737 MAX 8`.
+    #[serde(default, alias = "ModelFullName")]
+    pub model: String,
+    /// Aircraft category, e.g. `LandPlane` or `Helicopter`.
+    #[serde(default, alias = "AircraftDescription")]
+    pub category: String,
+    /// Number of engines.
+    #[serde(default, alias = "EngineCount", deserialize_with = "engine_count")]
+    pub engine_count: Option<u8>,
+    /// Engine type, e.g. `Jet`, `Turboprop/Turboshaft` or `Piston`.
+    #[serde(default, alias = "EngineType")]
+    pub engine_type: String,
+    /// Wake turbulence category: `L`, `M`, `H` or `J`.
+    #[serde(default, alias = "WTC")]
+    pub wake_category: String,
+}
+
+impl TypeData for TypeDetails {
+    fn designator(&self) -> &str {
+        &self.designator
+    }
+
+    fn fields(&self) -> Document {
+        let mut fields = Document::new();
+        for (name, value) in [
+            ("manufacturer", &self.manufacturer),
+            ("model", &self.model),
+            ("category", &self.category),
+            ("engineType", &self.engine_type),
+            ("wakeCategory", &self.wake_category),
+        ] {
+            if !value.trim().is_empty() {
+                fields.insert(name, value.trim());
+            }
+        }
+        if let Some(count) = self.engine_count {
+            fields.insert("engineCount", i32::from(count));
+        }
+        fields
+    }
+}
+
+// ICAO publishes the engine count as a string, and `C` for some rotorcraft.
+fn engine_count<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Option<u8>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Count {
+        Number(u8),
+        Text(String),
+    }
+    Ok(match Option::<Count>::deserialize(deserializer)? {
+        Some(Count::Number(count)) => Some(count),
+        Some(Count::Text(text)) => text.trim().parse().ok(),
+        None => None,
+    })
+}
+
+/// Performance figures of a type designator, in the units their names give.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Performance {
+    /// ICAO type designator, e.g. `B38M`.
+    #[serde(alias = "icao_code", alias = "designator")]
+    pub icao_code: String,
+    /// Maximum range in kilometres.
+    #[serde(default, alias = "range_km")]
+    pub range_km: Option<f64>,
+    /// Typical cruise speed in knots.
+    #[serde(default, alias = "cruise_speed_kts")]
+    pub cruise_speed_kts: Option<f64>,
+    /// Maximum take-off weight in kilograms.
+    #[serde(default, alias = "mtow_kg")]
+    pub mtow_kg: Option<f64>,
+    /// Seats in a typical configuration.
+    #[serde(default, alias = "typical_seats", alias = "seats")]
+    pub typical_seats: Option<u32>,
+}
+
+// Bounds each figure is checked against. Values outside them are most likely given in
+// another unit: miles or nautical miles, km/h, pounds.
+const RANGE_KM: (f64, f64) = (1.0, 20_000.0);
+const CRUISE_SPEED_KTS: (f64, f64) = (30.0, 700.0);
+const MTOW_KG: (f64, f64) = (100.0, 700_000.0);
+const MAX_SEATS: u32 = 900;
+
+impl TypeData for Performance {
+    fn designator(&self) -> &str {
+        &self.icao_code
+    }
+
+    fn fields(&self) -> Document {
+        let mut fields = Document::new();
+        for (name, value) in [("rangeKm", self.range_km), ("cruiseSpeedKts", self.cruise_speed_kts), ("mtowKg", self.mtow_kg)] {
+            if let Some(value) = value {
+                fields.insert(name, value);
+            }
+        }
+        if let Some(seats) = self.typical_seats {
+            fields.insert("typicalSeats", i64::from(seats));
+        }
+        fields
+    }
+
+    /// Each figure must lie within what aircraft plausibly reach in its unit.
+    fn validate(&self) -> Vec<String> {
+        let mut reasons = Vec::new();
+        for (name, value, (min, max), unit) in [
+            ("rangeKm", self.range_km, RANGE_KM, "km"),
+            ("cruiseSpeedKts", self.cruise_speed_kts, CRUISE_SPEED_KTS, "kts"),
+            ("mtowKg", self.mtow_kg, MTOW_KG, "kg"),
+        ] {
+            if let Some(value) = value.filter(|value| !(min..=max).contains(value)) {
+                reasons.push(format!("{} {} is outside {}-{} {}", name, value, min, max, unit));
+            }
+        }
+        if let Some(seats) = self.typical_seats.filter(|seats| *seats > MAX_SEATS) {
+            reasons.push(format!("typicalSeats {} is more than {}", seats, MAX_SEATS));
+        }
+        reasons
+    }
+}
+
+/// The description of a type designator in one language.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Translation {
+    /// ICAO type designator, e.g. `B38M`.
+    #[serde(alias = "icao_code", alias = "designator")]
+    pub icao_code: String,
+    /// Locale of the description, e.g. `fr` or `pt-BR`.
+    #[serde(alias = "locale")]
+    pub lang: String,
+    pub description: String,
+}
+
+impl TypeData for Translation {
+    fn designator(&self) -> &str {
+        &self.icao_code
+    }
+
+    fn fields(&self) -> Document {
+        doc! { format!("{}.{}", DESCRIPTIONS, self.lang.trim()): self.description.trim() }
+    }
+
+    /// The locale must be letters, digits, `-` and `_`, and the description not blank.
+    fn validate(&self) -> Vec<String> {
+        let mut reasons = Vec::new();
+        let lang = self.lang.trim();
+        if lang.is_empty() || !lang.chars().all(|character| character.is_ascii_alphanumeric() || character == '-' || character == '_') {
+            reasons.push(format!("lang {:?} is not a locale", self.lang));
+        }
+        if self.description.trim().is_empty() {
+            reasons.push("description is empty".to_string());
+        }
+        reasons
+    }
+}
+
+/// The other designators a type designator is known by. Besides a list, the aliases may be
+/// given as one string separated by spaces or commas, as CSV files do.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Alias {
+    /// ICAO type designator of the canonical aircraft, e.g. `B38M`.
+    #[serde(alias = "icao_code", alias = "designator")]
+    pub icao_code: String,
+    /// Designators that resolve to it, e.g. a superseded designator.
+    #[serde(alias = "alias", deserialize_with = "alias_list")]
+    pub aliases: Vec<String>,
+}
+
+impl TypeData for Alias {
+    fn designator(&self) -> &str {
+        &self.icao_code
+    }
+
+    fn fields(&self) -> Document {
+        let aliases: Vec<String> = self.aliases.iter().map(|alias| alias.trim().to_ascii_uppercase()).filter(|alias| !alias.is_empty()).collect();
+        match aliases.is_empty() {
+            true => Document::new(),
+            false => doc! { ALIASES: aliases },
+        }
+    }
+
+    /// Each alias must be 2 to 4 letters or digits, and not the designator itself.
+    fn validate(&self) -> Vec<String> {
+        let mut reasons = Vec::new();
+        for alias in self.aliases.iter().map(|alias| alias.trim()) {
+            if !(2..=4).contains(&alias.chars().count()) || !is_alphanumeric(alias) {
+                reasons.push(format!("alias {:?} is not 2-4 alphanumeric characters", alias));
+            } else if alias.eq_ignore_ascii_case(self.icao_code.trim()) {
+                reasons.push(format!("alias {:?} is the icaoCode itself", alias));
+            }
+        }
+        reasons
+    }
+}
+
+// Aliases as a list, or as one string separated by spaces or commas.
+fn alias_list<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Aliases {
+        List(Vec<String>),
+        Text(String),
+    }
+    Ok(match Aliases::deserialize(deserializer)? {
+        Aliases::List(aliases) => aliases,
+        Aliases::Text(text) => text.split(|c: char| c.is_whitespace() || c == ',').filter(|alias| !alias.is_empty()).map(str::to_string).collect(),
+    })
+}
+
+/// Outcome of [`enrich`].
+#[derive(Debug, Default)]
+pub struct EnrichSummary {
+    /// Entries read from the input.
+    pub parsed: u64,
+    /// Stored aircraft that received details.
+    pub enriched: u64,
+    /// Designators with no stored aircraft.
+    pub unknown: Vec<String>,
+    /// ICAO codes of stored aircraft the input has no entry for, in order.
+    pub missing: Vec<String>,
+    /// Messages for entries that failed validation and were left out.
+    pub rejected: Vec<String>,
+    /// Messages for entries that failed to parse and were skipped in lenient mode.
+    pub skipped: Vec<String>,
+}
+
+/// Adds the fields of each entry in `entries` to the stored aircraft with the same ICAO code,
+/// leaving the fields the loader wrote, such as `description`, untouched. When the input
+/// lists a designator more than once, the later entry's fields win. Invalid entries are
+/// reported and left out. With `lenient`, entries that fail to parse are skipped and
+/// reported instead of aborting.
+pub async fn enrich<T, I>(store: &AircraftStore, entries: I, lenient: bool) -> Result<EnrichSummary>
+where
+    T: TypeData,
+    I: IntoIterator<Item = Result<T>>,
+{
+    let started = Instant::now();
+    let mut summary = EnrichSummary::default();
+    let mut listed = HashSet::new();
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(error) if lenient => {
+                warn!(%error, "skipped unparseable entry");
+                summary.skipped.push(error.to_string());
+                continue;
+            }
+            Err(error) => return Err(error),
+        };
+        summary.parsed += 1;
+        listed.insert(entry.designator().to_string());
+        let reasons = entry.validate();
+        if !reasons.is_empty() {
+            warn!(designator = entry.designator(), ?reasons, "rejected entry");
+            summary.rejected.push(format!("{}: {}", entry.designator(), reasons.join(", ")));
+            continue;
+        }
+        let fields = entry.fields();
+        if fields.is_empty() {
+            continue;
+        }
+        if store.merge(doc! { &store.field_names().icao_code: entry.designator() }, fields).await? {
+            summary.enriched += 1;
+        } else {
+            summary.unknown.push(entry.designator().to_string());
+        }
+    }
+    let mut missing: Vec<String> =
+        store.find_all().await?.into_iter().map(|aircraft| aircraft.icao_code).filter(|code| !listed.contains(code)).collect();
+    missing.sort();
+    summary.missing = missing;
+    info!(
+        parsed = summary.parsed,
+        enriched = summary.enriched,
+        unknown = summary.unknown.len(),
+        missing = summary.missing.len(),
+        rejected = summary.rejected.len(),
+        elapsed_ms = started.elapsed().as_millis() as u64,
+        "enrich finished"
+    );
+    Ok(summary)
+}
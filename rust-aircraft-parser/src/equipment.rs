@@ -0,0 +1,48 @@
+//! Resolving the equipment strings of airline schedules, such as `"320 321 32N"`, to the
+//! aircraft their IATA codes stand for.
+
+use serde::Serialize;
+use crate::{Aircraft, Result, Storage};
+
+/// One IATA code of an equipment string with the aircraft stored under it; none when the
+/// code is unknown.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Equipment {
+    pub iata_code: String,
+    pub aircraft: Vec<Aircraft>,
+}
+
+impl Equipment {
+    pub fn is_known(&self) -> bool {
+        !self.aircraft.is_empty()
+    }
+}
+
+/// The IATA codes of `equipment`, upper case, in the order they first appear. Schedules
+/// separate them with spaces, commas, slashes or semicolons, e.g. `320/321` or `73H, 738`.
+pub fn split(equipment: &str) -> Vec<String> {
+    let mut codes: Vec<String> = Vec::new();
+    for code in equipment.split(|c: char| c.is_whitespace() || matches!(c, ',' | '/' | ';')) {
+        let code = code.to_ascii_uppercase();
+        if !code.is_empty() && !codes.contains(&code) {
+            codes.push(code);
+        }
+    }
+    codes
+}
+
+/// Looks up every code of `equipment` in `storage`, in order.
+pub async fn resolve(storage: &dyn Storage, equipment: &str) -> Result<Vec<Equipment>> {
+    let mut resolved = Vec::new();
+    for iata_code in split(equipment) {
+        let aircraft = storage.find_by_iata(&iata_code).await?;
+        resolved.push(Equipment { iata_code, aircraft });
+    }
+    Ok(resolved)
+}
+
+/// The codes of `resolved` no aircraft is stored under.
+pub fn unknown(resolved: &[Equipment]) -> Vec<&str> {
+    resolved.iter().filter(|equipment| !equipment.is_known()).map(|equipment| equipment.iata_code.as_str()).collect()
+}
@@ -0,0 +1,169 @@
+//! The crate-wide error type and how each variant maps onto a process exit code.
+
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+/// Everything that can go wrong while reading, converting, or storing aircraft.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The input file could not be opened or read.
+    #[error("cannot read {}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+
+    /// An output file could not be written.
+    #[error("cannot write {}: {source}", path.display())]
+    Write { path: PathBuf, source: io::Error },
+
+    /// The input is not valid JSON or does not match the expected shape.
+    #[error("cannot parse json: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// The input is not valid CSV.
+    #[error("cannot parse csv: {0}")]
+    Csv(#[from] csv::Error),
+
+    /// The input is well-formed but structurally wrong, e.g. a missing CSV column.
+    #[error("invalid input: {0}")]
+    InvalidInput(String),
+
+    /// A record could not be converted to BSON.
+    #[error("cannot convert to bson: {0}")]
+    BsonSerialization(#[from] mongodb::bson::ser::Error),
+
+    /// A stored document could not be converted back into a record.
+    #[error("cannot convert from bson: {0}")]
+    BsonDeserialization(#[from] mongodb::bson::de::Error),
+
+    /// MongoDB rejected an operation or could not be reached.
+    #[error("mongodb: {0}")]
+    Mongo(#[from] mongodb::error::Error),
+
+    /// A remote input could not be fetched.
+    #[error("http: {0}")]
+    Http(#[from] reqwest::Error),
+
+    /// Elasticsearch (or OpenSearch) refused a request.
+    #[cfg(feature = "elasticsearch")]
+    #[error("elasticsearch: {0}")]
+    Elasticsearch(String),
+
+    /// An S3 input could not be read.
+    #[cfg(feature = "s3")]
+    #[error("s3: {0}")]
+    S3(String),
+
+    /// DynamoDB rejected a request or could not be reached.
+    #[cfg(feature = "dynamodb")]
+    #[error("dynamodb: {0}")]
+    Dynamo(String),
+
+    /// A Kafka producer could not be created, or the broker refused a message.
+    #[cfg(feature = "kafka")]
+    #[error("kafka: {0}")]
+    Kafka(#[from] rdkafka::error::KafkaError),
+
+    /// Redis rejected a command or could not be reached.
+    #[cfg(feature = "redis")]
+    #[error("redis: {0}")]
+    Redis(#[from] redis::RedisError),
+
+    /// A Parquet export could not be encoded or written.
+    #[cfg(feature = "parquet")]
+    #[error("parquet: {0}")]
+    Parquet(String),
+
+    /// A SQL backend rejected an operation or could not be reached.
+    #[cfg(any(feature = "postgres", feature = "sqlite"))]
+    #[error("sql: {0}")]
+    Sql(#[from] sqlx::Error),
+
+    /// The API server could not listen on its address, or failed while serving.
+    #[error("cannot serve on {address}: {source}")]
+    Serve { address: SocketAddr, source: io::Error },
+
+    /// A secrets store refused to hand over a secret, or it is not the string looked for.
+    #[error("secret: {0}")]
+    Secret(String),
+
+    /// Required configuration is missing or invalid.
+    #[error("configuration: {0}")]
+    Config(String),
+
+    /// The disposable MongoDB container of `self-test --container` could not be started.
+    #[cfg(feature = "testcontainers")]
+    #[error("container: {0}")]
+    Container(String),
+
+    /// A `--hook` could not be started or gave an answer that is not an aircraft.
+    #[error("hook: {0}")]
+    Hook(String),
+
+    /// Checks of `self-test` did not pass.
+    #[error("{0} self-test checks failed")]
+    SelfTest(usize),
+
+    /// Another load or sync holds the lock on the collection.
+    #[error("{collection} is locked by load {owner} until {until}; break a stale lock with --force")]
+    Locked { collection: String, owner: String, until: String },
+
+    /// Ctrl-C or SIGTERM stopped the run before it finished.
+    #[error("interrupted by a shutdown signal")]
+    Interrupted,
+}
+
+/// Shorthand for results carrying an [`Error`].
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// The process exit code for this error, following the BSD `sysexits.h` conventions so
+    /// automation can tell a bad input file apart from an unreachable database:
+    ///
+    /// | code | meaning |
+    /// |------|---------|
+    /// | 65   | malformed input data (JSON, CSV, shape) |
+    /// | 66   | input file missing or unreadable |
+    /// | 69   | database or remote input unreachable, or rejected the operation |
+    /// | 70   | internal conversion failure (BSON), a failed hook, or failed self-test checks |
+    /// | 71   | the API server could not listen on its address |
+    /// | 73   | output file could not be written |
+    /// | 75   | stopped by Ctrl-C or SIGTERM, can be resumed |
+    /// | 77   | the collection is locked by another load or sync |
+    /// | 78   | missing or invalid configuration |
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Json(_) | Error::Csv(_) | Error::InvalidInput(_) => 65,
+            Error::Io { .. } => 66,
+            Error::Mongo(_) | Error::Http(_) | Error::Secret(_) => 69,
+            #[cfg(feature = "elasticsearch")]
+            Error::Elasticsearch(_) => 69,
+            #[cfg(feature = "s3")]
+            Error::S3(_) => 69,
+            #[cfg(any(feature = "postgres", feature = "sqlite"))]
+            Error::Sql(_) => 69,
+            #[cfg(feature = "dynamodb")]
+            Error::Dynamo(_) => 69,
+            #[cfg(feature = "kafka")]
+            Error::Kafka(_) => 69,
+            #[cfg(feature = "redis")]
+            Error::Redis(_) => 69,
+            #[cfg(feature = "testcontainers")]
+            Error::Container(_) => 69,
+            Error::BsonSerialization(_) | Error::BsonDeserialization(_) | Error::Hook(_) | Error::SelfTest(_) => 70,
+            Error::Serve { .. } => 71,
+            Error::Write { .. } => 73,
+            Error::Interrupted => 75,
+            Error::Locked { .. } => 77,
+            #[cfg(feature = "parquet")]
+            Error::Parquet(_) => 73,
+            Error::Config(_) => 78,
+        }
+    }
+}
+
+impl From<&Error> for ExitCode {
+    fn from(error: &Error) -> Self {
+        ExitCode::from(error.exit_code())
+    }
+}
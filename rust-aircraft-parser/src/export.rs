@@ -0,0 +1,536 @@
+//! Writing stored aircraft back out to files.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+#[cfg(feature = "parquet")]
+use std::{fs::File, path::Path, sync::Arc};
+#[cfg(feature = "parquet")]
+use arrow_array::{ArrayRef, RecordBatch, StringArray};
+#[cfg(feature = "parquet")]
+use arrow_schema::{DataType, Field, Schema};
+use clap::ValueEnum;
+use futures::{Stream, StreamExt};
+use mongodb::bson::{self, doc, Document};
+#[cfg(feature = "parquet")]
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+use serde::ser::{SerializeMap, Serializer};
+use serde::Serialize;
+use serde_json::{json, Value};
+use crate::fields::FieldNames;
+use crate::storage::StoredAircraft;
+use crate::{Aircraft, Error, Result};
+
+// Opens a mongodump archive, as a little-endian int32.
+const ARCHIVE_MAGIC: u32 = 0x8199_e26d;
+
+// Ends the prelude and every block of documents in an archive.
+const ARCHIVE_TERMINATOR: [u8; 4] = [0xff; 4];
+
+// mongorestore reads the layout of an archive by the server version it was dumped from; the
+// documents and metadata written here are laid out as a 4.4 server dumps them.
+const ARCHIVE_SERVER_VERSION: &str = "4.4.0";
+
+// The reflected ECMA-182 polynomial of the CRC-64 mongorestore checks each collection against.
+const CRC64_ECMA: u64 = 0xc96c_5795_d787_0f42;
+
+/// Layout of an export file.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// A pretty-printed JSON array, loadable again with `--format json`
+    #[default]
+    Json,
+    /// CSV with an icaoCode,iataCode,description header
+    Csv,
+    /// One JSON object per line, loadable again with `--format ndjson`
+    Ndjson,
+    /// Aligned columns for reading on a terminal
+    Table,
+    /// A mongodump archive of the collection, restorable with `mongorestore --archive`
+    BsonArchive,
+    /// A Snappy-compressed Parquet file with one string column per field, for analytics tools
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+/// The fields an export can be limited to with [`ExportOptions::fields`].
+pub const COLUMNS: [&str; 6] = ["_id", "icaoCode", "iataCode", "description", "descriptions", "aliases"];
+
+// The columns of CSV and tables unless limited.
+const DEFAULT_COLUMNS: [&str; 3] = ["icaoCode", "iataCode", "description"];
+
+/// Settings for [`export`].
+#[derive(Clone, Debug, Default)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    /// Include each record's stored identifier as `_id`.
+    pub keep_id: bool,
+    /// Write only these of the [`COLUMNS`], in this order; all of them when empty.
+    pub fields: Vec<String>,
+}
+
+impl ExportOptions {
+    // The columns written, `_id` first with `keep_id` unless the fields place it.
+    fn columns(&self) -> Vec<&str> {
+        let mut columns: Vec<&str> = match self.fields.is_empty() {
+            true => DEFAULT_COLUMNS.to_vec(),
+            false => self.fields.iter().map(String::as_str).collect(),
+        };
+        if self.keep_id && !columns.contains(&"_id") {
+            columns.insert(0, "_id");
+        }
+        columns
+    }
+}
+
+#[derive(Serialize)]
+struct ExportRecord<'a> {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    id: Option<&'a str>,
+    #[serde(flatten)]
+    aircraft: &'a Aircraft,
+}
+
+// A record limited to some columns, serialized as an object keeping their order.
+struct Projected(Vec<(String, Value)>);
+
+impl Serialize for Projected {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (column, value) in &self.0 {
+            map.serialize_entry(column, value)?;
+        }
+        map.end()
+    }
+}
+
+/// Writes `records` to `writer` sorted by ICAO code, so exports of the same data are
+/// byte-for-byte identical and diff cleanly in source control.
+pub fn export(writer: impl Write, mut records: Vec<StoredAircraft>, options: &ExportOptions) -> Result<()> {
+    records.sort_by(|left, right| left.aircraft.icao_code.cmp(&right.aircraft.icao_code));
+    write(writer, &records, options)
+}
+
+/// Writes `records` to `writer` in the order given.
+pub fn write(writer: impl Write, records: &[StoredAircraft], options: &ExportOptions) -> Result<()> {
+    match options.format {
+        ExportFormat::Json => write_json(writer, records, options),
+        ExportFormat::Csv => write_csv(writer, records, options),
+        ExportFormat::Ndjson => write_ndjson(writer, records, options),
+        ExportFormat::Table => write_table(writer, records, options),
+        ExportFormat::BsonArchive => Err(Error::Config("bson-archive is only written by export".to_string())),
+        #[cfg(feature = "parquet")]
+        ExportFormat::Parquet => Err(Error::Config("parquet is only written to a file".to_string())),
+    }
+}
+
+/// Writes `records` to `writer` as they arrive, in the order given, holding one record at a
+/// time, and returns how many were written. Only JSON, NDJSON and CSV can be written this
+/// way: the other layouts need every record before they can write the first.
+pub async fn export_stream(mut writer: impl Write, records: impl Stream<Item = Result<StoredAircraft>>, options: &ExportOptions) -> Result<u64> {
+    futures::pin_mut!(records);
+    let mut written = 0;
+    match options.format {
+        ExportFormat::Json => {
+            // Laid out as `write` lays out the whole array, element by element.
+            write!(writer, "[").map_err(serde_json::Error::io)?;
+            while let Some(record) = records.next().await {
+                let record = record?;
+                let element = json_record(&record, options)?;
+                let separator = if written == 0 { "\n" } else { ",\n" };
+                write!(writer, "{}  {}", separator, element.replace('\n', "\n  ")).map_err(serde_json::Error::io)?;
+                written += 1;
+            }
+            let end = if written == 0 { "]" } else { "\n]" };
+            writeln!(writer, "{}", end).map_err(serde_json::Error::io)?;
+        }
+        ExportFormat::Ndjson => {
+            while let Some(record) = records.next().await {
+                writer.write_all(json_line(&record?, options)?.as_bytes()).map_err(serde_json::Error::io)?;
+                written += 1;
+            }
+        }
+        ExportFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(writer);
+            let columns = options.columns();
+            writer.write_record(&columns)?;
+            while let Some(record) = records.next().await {
+                writer.write_record(row(&record?, &columns))?;
+                written += 1;
+            }
+            writer.flush().map_err(csv::Error::from)?;
+        }
+        format => {
+            let name = format.to_possible_value().map(|value| value.get_name().to_string()).unwrap_or_default();
+            return Err(Error::Config(format!("{} exports cannot be streamed", name)));
+        }
+    }
+    Ok(written)
+}
+
+/// Writes `records` to `writer` as a mongodump archive of `database.collection`, sorted by
+/// ICAO code like [`export`]. The documents are stored as the collection stores them, with
+/// their `_id` and fields named by `fields`, so `mongorestore --archive` recreates it
+/// without this tool.
+pub fn export_archive(
+    writer: impl Write,
+    mut records: Vec<StoredAircraft>,
+    database: &str,
+    collection: &str,
+    fields: &FieldNames,
+) -> Result<()> {
+    records.sort_by(|left, right| left.aircraft.icao_code.cmp(&right.aircraft.icao_code));
+    let documents: Vec<Document> = records
+        .iter()
+        .map(|record| {
+            let mut document = doc! { "_id": record.id.clone() };
+            document.extend(fields.document(&record.aircraft));
+            document
+        })
+        .collect();
+    write_archive(writer, database, collection, &documents)
+}
+
+/// Writes `records` to a new Parquet file at `path`, sorted by ICAO code like [`export`].
+#[cfg(feature = "parquet")]
+pub fn export_parquet(path: &Path, mut records: Vec<StoredAircraft>, keep_id: bool) -> Result<()> {
+    records.sort_by(|left, right| left.aircraft.icao_code.cmp(&right.aircraft.icao_code));
+    let file = File::create(path).map_err(|source| Error::Write { path: path.to_path_buf(), source })?;
+    write_parquet(file, &records, keep_id)
+}
+
+/// Writes `records` to `writer` as a single Parquet row group. A missing IATA code is
+/// written as null.
+#[cfg(feature = "parquet")]
+pub fn write_parquet(writer: impl Write + Send, records: &[StoredAircraft], keep_id: bool) -> Result<()> {
+    let mut fields = vec![
+        Field::new("icaoCode", DataType::Utf8, false),
+        Field::new("iataCode", DataType::Utf8, true),
+        Field::new("description", DataType::Utf8, false),
+    ];
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(records.iter().map(|record| record.aircraft.icao_code.as_str()))),
+        Arc::new(StringArray::from_iter(records.iter().map(|record| record.aircraft.iata_code.as_deref()))),
+        Arc::new(StringArray::from_iter_values(records.iter().map(|record| record.aircraft.description.as_str()))),
+    ];
+    if keep_id {
+        fields.insert(0, Field::new("_id", DataType::Utf8, false));
+        columns.insert(0, Arc::new(StringArray::from_iter_values(records.iter().map(|record| record.id.as_str()))));
+    }
+    let parquet = |error: parquet::errors::ParquetError| Error::Parquet(error.to_string());
+    let batch = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).map_err(|error| Error::Parquet(error.to_string()))?;
+    let properties = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+    let mut writer = ArrowWriter::try_new(writer, batch.schema(), Some(properties)).map_err(parquet)?;
+    writer.write(&batch).map_err(parquet)?;
+    writer.close().map_err(parquet)?;
+    Ok(())
+}
+
+/// Which codes a [`mapping`] is keyed by.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Direction {
+    /// Each IATA code to the ICAO codes of every type sharing it
+    #[default]
+    IataToIcao,
+    /// Each ICAO code to its IATA code, an empty list for types without one
+    IcaoToIata,
+}
+
+impl Direction {
+    // Header of the CSV mapping.
+    fn columns(self) -> [&'static str; 2] {
+        match self {
+            Direction::IataToIcao => ["iataCode", "icaoCode"],
+            Direction::IcaoToIata => ["icaoCode", "iataCode"],
+        }
+    }
+}
+
+/// The ICAO codes of the types sharing each IATA code, sorted and without repeats. Several
+/// types, such as the variants of a regional jet, can share one IATA code; types without
+/// an IATA code are left out.
+pub fn iata_to_icao<'a>(aircrafts: impl IntoIterator<Item = &'a Aircraft>) -> HashMap<String, Vec<String>> {
+    let mut mapping: HashMap<String, Vec<String>> = HashMap::new();
+    for aircraft in aircrafts {
+        if let Some(iata_code) = &aircraft.iata_code {
+            mapping.entry(iata_code.clone()).or_default().push(aircraft.icao_code.clone());
+        }
+    }
+    for icao_codes in mapping.values_mut() {
+        icao_codes.sort();
+        icao_codes.dedup();
+    }
+    mapping
+}
+
+/// The IATA code of each ICAO code, as a list like [`iata_to_icao`]'s: empty for types
+/// without one, and holding every code given when the input repeats a type.
+pub fn icao_to_iata<'a>(aircrafts: impl IntoIterator<Item = &'a Aircraft>) -> HashMap<String, Vec<String>> {
+    let mut mapping: HashMap<String, Vec<String>> = HashMap::new();
+    for aircraft in aircrafts {
+        let iata_codes = mapping.entry(aircraft.icao_code.clone()).or_default();
+        iata_codes.extend(aircraft.iata_code.clone());
+    }
+    for iata_codes in mapping.values_mut() {
+        iata_codes.sort();
+        iata_codes.dedup();
+    }
+    mapping
+}
+
+/// [`iata_to_icao`] or [`icao_to_iata`], by `direction`.
+pub fn mapping<'a>(aircrafts: impl IntoIterator<Item = &'a Aircraft>, direction: Direction) -> HashMap<String, Vec<String>> {
+    match direction {
+        Direction::IataToIcao => iata_to_icao(aircrafts),
+        Direction::IcaoToIata => icao_to_iata(aircrafts),
+    }
+}
+
+/// Writes `mapping` to `writer` sorted by key: as a JSON object of code lists, or as CSV
+/// with a row per pair of codes.
+pub fn write_mapping(mut writer: impl Write, mapping: &HashMap<String, Vec<String>>, direction: Direction, format: ExportFormat) -> Result<()> {
+    let sorted: BTreeMap<&String, &Vec<String>> = mapping.iter().collect();
+    match format {
+        ExportFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, &sorted)?;
+            writeln!(writer).map_err(serde_json::Error::io)?;
+        }
+        ExportFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(writer);
+            writer.write_record(direction.columns())?;
+            for (code, codes) in sorted {
+                for other in codes {
+                    writer.write_record([code, other])?;
+                }
+            }
+            writer.flush().map_err(csv::Error::from)?;
+        }
+        _ => return Err(Error::Config("a mapping is only written as json or csv".to_string())),
+    }
+    Ok(())
+}
+
+fn export_record(record: &StoredAircraft, keep_id: bool) -> ExportRecord<'_> {
+    ExportRecord { id: keep_id.then_some(record.id.as_str()), aircraft: &record.aircraft }
+}
+
+fn projected(record: &StoredAircraft, options: &ExportOptions) -> Projected {
+    Projected(options.columns().into_iter().map(|column| (column.to_string(), value(record, column))).collect())
+}
+
+// `record` as pretty-printed JSON, limited to the fields of `options` when there are some.
+fn json_record(record: &StoredAircraft, options: &ExportOptions) -> Result<String> {
+    Ok(match options.fields.is_empty() {
+        true => serde_json::to_string_pretty(&export_record(record, options.keep_id))?,
+        false => serde_json::to_string_pretty(&projected(record, options))?,
+    })
+}
+
+// `record` as a line of JSON, limited like `json_record`.
+fn json_line(record: &StoredAircraft, options: &ExportOptions) -> Result<String> {
+    let mut line = match options.fields.is_empty() {
+        true => serde_json::to_string(&export_record(record, options.keep_id))?,
+        false => serde_json::to_string(&projected(record, options))?,
+    };
+    line.push('\n');
+    Ok(line)
+}
+
+// The value of one of the `COLUMNS` of `record`, as JSON.
+fn value(record: &StoredAircraft, column: &str) -> Value {
+    let aircraft = &record.aircraft;
+    match column {
+        "_id" => json!(record.id),
+        "icaoCode" => json!(aircraft.icao_code),
+        "iataCode" => json!(aircraft.iata_code),
+        "description" => json!(aircraft.description),
+        "descriptions" => json!(aircraft.descriptions),
+        "aliases" => json!(aircraft.aliases),
+        _ => Value::Null,
+    }
+}
+
+// The cells of `record` in `columns`, as CSV and tables show them: a missing IATA code as
+// an empty cell, translations as `lang=description` and aliases separated by semicolons.
+fn row(record: &StoredAircraft, columns: &[&str]) -> Vec<String> {
+    columns
+        .iter()
+        .map(|column| match value(record, column) {
+            Value::Null => String::new(),
+            Value::String(value) => value,
+            Value::Array(values) => values.iter().filter_map(Value::as_str).collect::<Vec<_>>().join(";"),
+            Value::Object(values) => values.iter().map(|(lang, value)| format!("{}={}", lang, value.as_str().unwrap_or_default())).collect::<Vec<_>>().join(";"),
+            value => value.to_string(),
+        })
+        .collect()
+}
+
+fn write_json(mut writer: impl Write, records: &[StoredAircraft], options: &ExportOptions) -> Result<()> {
+    match options.fields.is_empty() {
+        true => {
+            let records: Vec<ExportRecord> = records.iter().map(|record| export_record(record, options.keep_id)).collect();
+            serde_json::to_writer_pretty(&mut writer, &records)?;
+        }
+        false => {
+            let records: Vec<Projected> = records.iter().map(|record| projected(record, options)).collect();
+            serde_json::to_writer_pretty(&mut writer, &records)?;
+        }
+    }
+    writeln!(writer).map_err(serde_json::Error::io)?;
+    Ok(())
+}
+
+fn write_ndjson(mut writer: impl Write, records: &[StoredAircraft], options: &ExportOptions) -> Result<()> {
+    for record in records {
+        writer.write_all(json_line(record, options)?.as_bytes()).map_err(serde_json::Error::io)?;
+    }
+    Ok(())
+}
+
+fn write_csv(writer: impl Write, records: &[StoredAircraft], options: &ExportOptions) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    let columns = options.columns();
+    writer.write_record(&columns)?;
+    for record in records {
+        writer.write_record(row(record, &columns))?;
+    }
+    writer.flush().map_err(csv::Error::from)?;
+    Ok(())
+}
+
+fn write_table(mut writer: impl Write, records: &[StoredAircraft], options: &ExportOptions) -> Result<()> {
+    let columns = options.columns();
+    let mut rows = vec![columns.iter().map(|column| column.to_string()).collect::<Vec<_>>()];
+    rows.extend(records.iter().map(|record| row(record, &columns)));
+    let mut widths = vec![0; columns.len()];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for row in &rows {
+        let cells: Vec<String> = row.iter().zip(&widths).map(|(cell, width)| format!("{:<width$}", cell, width = width)).collect();
+        writeln!(writer, "{}", cells.join("  ").trim_end()).map_err(serde_json::Error::io)?;
+    }
+    Ok(())
+}
+
+// The archive is a prelude naming the collection, then its documents in one block, then an
+// end-of-collection header carrying their checksum.
+fn write_archive(mut writer: impl Write, database: &str, collection: &str, documents: &[Document]) -> Result<()> {
+    let bodies = documents.iter().map(bson::to_vec).collect::<bson::ser::Result<Vec<_>>>()?;
+    let size: usize = bodies.iter().map(Vec::len).sum();
+    let header = doc! {
+        "concurrent_collections": 1,
+        "version": "0.1",
+        "server_version": ARCHIVE_SERVER_VERSION,
+        "tool_version": concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION")),
+    };
+    let metadata = json!({
+        "options": {},
+        "indexes": [{ "v": 2, "key": { "_id": 1 }, "name": "_id_" }],
+        "collectionName": collection,
+        "type": "collection",
+    });
+    let prelude = doc! {
+        "db": database,
+        "collection": collection,
+        "metadata": metadata.to_string(),
+        "size": size as i64,
+        "type": "collection",
+    };
+    let namespace = |eof: bool, crc: u64| doc! { "db": database, "collection": collection, "EOF": eof, "CRC": crc as i64 };
+
+    let mut out = ARCHIVE_MAGIC.to_le_bytes().to_vec();
+    out.extend(bson::to_vec(&header)?);
+    out.extend(bson::to_vec(&prelude)?);
+    out.extend(ARCHIVE_TERMINATOR);
+    out.extend(bson::to_vec(&namespace(false, 0))?);
+    let mut crc = 0;
+    for body in &bodies {
+        crc = crc64(crc, body);
+        out.extend(body);
+    }
+    out.extend(ARCHIVE_TERMINATOR);
+    out.extend(bson::to_vec(&namespace(true, crc))?);
+    out.extend(ARCHIVE_TERMINATOR);
+    writer.write_all(&out).map_err(serde_json::Error::io)?;
+    Ok(())
+}
+
+// Continues the CRC-64 `crc` over `bytes`, the way Go's `hash/crc64` does with its ECMA table.
+fn crc64(crc: u64, bytes: &[u8]) -> u64 {
+    let mut crc = !crc;
+    for &byte in bytes {
+        crc ^= u64::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ CRC64_ECMA } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::aircraft;
+
+    fn stored() -> Vec<StoredAircraft> {
+        vec![
+            StoredAircraft { id: "2".to_string(), aircraft: aircraft("E75L").iata("E75").description("Embraer 175").translation("fr", "Embraer 175 (long)").build() },
+            StoredAircraft { id: "1".to_string(), aircraft: aircraft("A320").description("Airbus, A320").build() },
+        ]
+    }
+
+    fn exported(options: &ExportOptions) -> String {
+        let mut output = Vec::new();
+        export(&mut output, stored(), options).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn writes_csv_sorted_by_icao_code_and_limited_to_the_fields() {
+        let options = ExportOptions { format: ExportFormat::Csv, keep_id: false, fields: Vec::new() };
+        assert_eq!(exported(&options), "icaoCode,iataCode,description\nA320,,\"Airbus, A320\"\nE75L,E75,Embraer 175\n");
+        let options = ExportOptions { format: ExportFormat::Csv, keep_id: true, fields: vec!["descriptions".to_string()] };
+        assert_eq!(exported(&options), "_id,descriptions\n1,\n2,fr=Embraer 175 (long)\n");
+    }
+
+    #[test]
+    fn writes_json_that_loads_back() {
+        let options = ExportOptions { format: ExportFormat::Json, keep_id: true, fields: Vec::new() };
+        let json = exported(&options);
+        assert!(json.contains("\"_id\": \"1\""));
+        let aircrafts: Vec<Aircraft> = serde_json::from_str(&json).unwrap();
+        let mut expected: Vec<Aircraft> = stored().into_iter().map(|record| record.aircraft).collect();
+        expected.reverse();
+        assert_eq!(aircrafts, expected);
+    }
+
+    #[tokio::test]
+    async fn streams_what_it_writes_whole_byte_for_byte() {
+        for format in [ExportFormat::Json, ExportFormat::Ndjson, ExportFormat::Csv] {
+            for fields in [Vec::new(), vec!["icaoCode".to_string(), "aliases".to_string()]] {
+                let options = ExportOptions { format, keep_id: false, fields };
+                let mut records = stored();
+                records.sort_by(|left, right| left.aircraft.icao_code.cmp(&right.aircraft.icao_code));
+                let mut streamed = Vec::new();
+                let written = export_stream(&mut streamed, futures::stream::iter(records.into_iter().map(Ok)), &options).await.unwrap();
+                assert_eq!(written, 2);
+                assert_eq!(String::from_utf8(streamed).unwrap(), exported(&options), "{:?}", options);
+            }
+        }
+        let options = ExportOptions { format: ExportFormat::Table, ..ExportOptions::default() };
+        assert!(export_stream(Vec::new(), futures::stream::empty(), &options).await.is_err());
+    }
+
+    #[test]
+    fn maps_shared_iata_codes_to_every_icao_code() {
+        let aircrafts = [aircraft("E75S").iata("E75").build(), aircraft("E75L").iata("E75").build(), aircraft("A320").build()];
+        let mapping = iata_to_icao(&aircrafts);
+        assert_eq!(mapping.len(), 1);
+        assert_eq!(mapping["E75"], ["E75L", "E75S"]);
+        let reverse = icao_to_iata(&aircrafts);
+        assert_eq!(reverse["A320"], Vec::<String>::new());
+        assert_eq!(reverse["E75S"], ["E75"]);
+    }
+}
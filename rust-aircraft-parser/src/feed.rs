@@ -0,0 +1,88 @@
+//! The changes a [`sync`](crate::sync::sync) made, as events downstream systems can apply
+//! one by one instead of re-reading the whole collection. Events are written as NDJSON, one
+//! [`FeedEvent`] per line, to a file or in the body of a POST.
+
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::SystemTime;
+use reqwest::header::CONTENT_TYPE;
+use serde::Serialize;
+use tracing::info;
+use crate::provenance::{rfc3339, Provenance};
+use crate::{Aircraft, Error, Result};
+
+/// What a sync did to an aircraft.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    /// Inserted, as it was not stored yet.
+    Add,
+    /// Stored with other values.
+    Update,
+    /// Deleted, with prune, as the input no longer lists it.
+    Delete,
+    /// Marked inactive, with retire, as the input no longer lists it.
+    Retire,
+    /// Made active again, as the input lists it again.
+    Reactivate,
+}
+
+/// A change to one aircraft, with its values before and after.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Change {
+    pub operation: Operation,
+    pub icao_code: String,
+    /// As stored before the sync; none for additions.
+    pub old: Option<Aircraft>,
+    /// As stored after the sync; none for deletions and retirements.
+    pub new: Option<Aircraft>,
+}
+
+/// A [`Change`] as the feed carries it, naming the run that made it.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FeedEvent<'a> {
+    pub load_id: &'a str,
+    /// When the sync finished.
+    pub at: &'a str,
+    #[serde(flatten)]
+    pub change: &'a Change,
+}
+
+// The changes as NDJSON, each line an event of the run of `provenance`.
+fn ndjson(provenance: &Provenance, changes: &[Change]) -> Result<Vec<u8>> {
+    let at = rfc3339(SystemTime::now());
+    let mut body = Vec::new();
+    for change in changes {
+        serde_json::to_writer(&mut body, &FeedEvent { load_id: &provenance.load_id, at: &at, change })?;
+        body.push(b'\n');
+    }
+    Ok(body)
+}
+
+/// Appends the events of `changes` to `path`, created if missing, so a file written by
+/// every run of a scheduled sync or watch holds them all in order.
+pub fn append(path: &Path, provenance: &Provenance, changes: &[Change]) -> Result<()> {
+    let write_error = |source| Error::Write { path: path.to_path_buf(), source };
+    let file = OpenOptions::new().create(true).append(true).open(path).map_err(write_error)?;
+    let mut writer = BufWriter::new(file);
+    writer.write_all(&ndjson(provenance, changes)?).map_err(write_error)?;
+    writer.flush().map_err(write_error)?;
+    info!(path = %path.display(), changes = changes.len(), "wrote change feed");
+    Ok(())
+}
+
+/// POSTs the events of `changes` to `url` in one NDJSON body, failing if the endpoint
+/// answers with an error status. Nothing is sent when there are no changes.
+pub async fn post(url: &str, provenance: &Provenance, changes: &[Change]) -> Result<()> {
+    if changes.is_empty() {
+        return Ok(());
+    }
+    let body = ndjson(provenance, changes)?;
+    let response = reqwest::Client::new().post(url).header(CONTENT_TYPE, "application/x-ndjson").body(body).send().await?;
+    response.error_for_status()?;
+    info!(url, changes = changes.len(), "posted change feed");
+    Ok(())
+}
@@ -0,0 +1,147 @@
+//! Names the aircraft fields are stored under in MongoDB.
+
+use clap::ValueEnum;
+use mongodb::bson::{self, Bson, Document};
+use crate::overrides::OVERRIDE;
+use crate::{Aircraft, Error, Result};
+
+// Fields stored documents carry besides the aircraft's own, the translations and aliases.
+const RESERVED: [&str; 9] = ["_id", "loadId", "sourceFile", "checksum", "createdAt", "updatedAt", DESCRIPTIONS, ALIASES, OVERRIDE];
+
+/// Field the descriptions in other languages are stored under, by locale.
+pub const DESCRIPTIONS: &str = "descriptions";
+
+/// Field the other designators of a type are stored under.
+pub const ALIASES: &str = "aliases";
+
+/// Names of the aircraft fields in stored documents. The default keeps the camelCase names
+/// of the input, e.g. `icaoCode`; [`snake_case`](Self::snake_case) matches collections
+/// using `icao_code`, and [`rename`](Self::rename) picks any other name per field.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldNames {
+    pub icao_code: String,
+    pub iata_code: String,
+    pub description: String,
+    /// How the IATA code of an aircraft without one is stored.
+    pub missing_iata: MissingCode,
+}
+
+/// How a missing code is stored.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MissingCode {
+    /// As an empty string
+    #[default]
+    Empty,
+    /// As null
+    Null,
+    /// By leaving the field out
+    Omit,
+}
+
+impl Default for FieldNames {
+    fn default() -> Self {
+        FieldNames {
+            icao_code: "icaoCode".to_string(),
+            iata_code: "iataCode".to_string(),
+            description: "description".to_string(),
+            missing_iata: MissingCode::default(),
+        }
+    }
+}
+
+impl FieldNames {
+    /// `icao_code`, `iata_code` and `description`.
+    pub fn snake_case() -> Self {
+        FieldNames {
+            icao_code: "icao_code".to_string(),
+            iata_code: "iata_code".to_string(),
+            description: "description".to_string(),
+            missing_iata: MissingCode::default(),
+        }
+    }
+
+    /// Stores the fields named in `mapping` by their camelCase name under the name they
+    /// map to, e.g. `("icaoCode", "icao")`. Unknown fields, names used twice and the names
+    /// of the provenance fields are rejected.
+    pub fn rename<'a>(mut self, mapping: impl IntoIterator<Item = (&'a str, &'a str)>) -> Result<Self> {
+        for (field, name) in mapping {
+            let target = match field {
+                "icaoCode" => &mut self.icao_code,
+                "iataCode" => &mut self.iata_code,
+                "description" => &mut self.description,
+                _ => return Err(Error::Config(format!("cannot rename unknown field {:?}", field))),
+            };
+            *target = name.to_string();
+        }
+        let names = [&self.icao_code, &self.iata_code, &self.description];
+        for (position, name) in names.iter().enumerate() {
+            if name.is_empty() || RESERVED.contains(&name.as_str()) || name.starts_with('$') || name.contains('.') {
+                return Err(Error::Config(format!("{:?} cannot be used as a field name", name)));
+            }
+            if names[..position].contains(name) {
+                return Err(Error::Config(format!("{:?} is used for two fields", name)));
+            }
+        }
+        Ok(self)
+    }
+
+    /// Stores missing IATA codes as `missing_iata` says.
+    pub fn with_missing_iata(mut self, missing_iata: MissingCode) -> Self {
+        self.missing_iata = missing_iata;
+        self
+    }
+
+    /// The stored document of `aircraft`, without `_id` or provenance.
+    pub fn document(&self, aircraft: &Aircraft) -> Document {
+        let mut document = Document::new();
+        document.insert(&self.icao_code, &aircraft.icao_code);
+        match (&aircraft.iata_code, self.missing_iata) {
+            (Some(iata_code), _) => {
+                document.insert(&self.iata_code, iata_code);
+            }
+            (None, MissingCode::Empty) => {
+                document.insert(&self.iata_code, "");
+            }
+            (None, MissingCode::Null) => {
+                document.insert(&self.iata_code, Bson::Null);
+            }
+            (None, MissingCode::Omit) => {}
+        }
+        document.insert(&self.description, &aircraft.description);
+        if !aircraft.descriptions.is_empty() {
+            let descriptions = aircraft.descriptions.iter().map(|(lang, description)| (lang.clone(), Bson::String(description.clone())));
+            document.insert(DESCRIPTIONS, descriptions.collect::<Document>());
+        }
+        if !aircraft.aliases.is_empty() {
+            document.insert(ALIASES, &aircraft.aliases);
+        }
+        document
+    }
+
+    /// The fields [`document`](Self::document) leaves out of the document of `aircraft`,
+    /// which an update has to unset.
+    pub fn omitted(&self, aircraft: &Aircraft) -> Document {
+        let mut omitted = Document::new();
+        if aircraft.iata_code.is_none() && self.missing_iata == MissingCode::Omit {
+            omitted.insert(&self.iata_code, "");
+        }
+        omitted
+    }
+
+    /// The aircraft stored in `document`, ignoring its other fields.
+    pub fn aircraft(&self, document: &Document) -> Result<Aircraft> {
+        let mut fields = Document::new();
+        for (field, name) in [
+            ("icaoCode", self.icao_code.as_str()),
+            ("iataCode", self.iata_code.as_str()),
+            ("description", self.description.as_str()),
+            (DESCRIPTIONS, DESCRIPTIONS),
+            (ALIASES, ALIASES),
+        ] {
+            if let Some(value) = document.get(name) {
+                fields.insert(field, value.clone());
+            }
+        }
+        Ok(bson::from_document(fields)?)
+    }
+}
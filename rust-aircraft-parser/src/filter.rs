@@ -0,0 +1,160 @@
+//! The `--filter` and `--fields` of query and export, compiled to the query and projection
+//! documents MongoDB reads.
+
+use std::str::FromStr;
+use mongodb::bson::{doc, Bson, Document, Regex};
+use crate::fields::{FieldNames, ALIASES, DESCRIPTIONS};
+
+/// Which aircraft to read: a MongoDB query as extended JSON, e.g. `{"iataCode": "738"}`, or
+/// comma-separated `field=value` terms that all have to match, e.g.
+/// `manufacturer=Boeing,icaoCode=B7*`. A value ending in `*` matches the values starting
+/// with the rest of it, and an empty one matches a missing field.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Filter {
+    Query(Document),
+    Terms(Vec<Term>),
+}
+
+/// One `field=value` term of a [`Filter`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Term {
+    /// The field by its camelCase name, or the name it is stored under for the fields
+    /// enrichment adds, such as `manufacturer`.
+    pub field: String,
+    pub value: String,
+    /// Whether the value ended in `*`, which is left out of `value`.
+    pub prefix: bool,
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let expression = expression.trim();
+        if expression.starts_with('{') {
+            let json: serde_json::Value = serde_json::from_str(expression).map_err(|error| error.to_string())?;
+            return match Bson::try_from(json).map_err(|error| error.to_string())? {
+                Bson::Document(query) => Ok(Filter::Query(query)),
+                _ => Err("the filter must be a JSON object".to_string()),
+            };
+        }
+        let terms = expression
+            .split(',')
+            .map(|term| {
+                let (field, value) = term.split_once('=').ok_or_else(|| format!("{:?} is not a field=value term", term))?;
+                let field = field.trim();
+                if field.is_empty() || field.starts_with('$') {
+                    return Err(format!("{:?} is not a field name", field));
+                }
+                let value = value.trim();
+                let (value, prefix) = match value.strip_suffix('*') {
+                    Some(value) => (value, true),
+                    None => (value, false),
+                };
+                Ok(Term { field: field.to_string(), value: value.to_string(), prefix })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Filter::Terms(terms))
+    }
+}
+
+impl Filter {
+    /// The query document, with the aircraft fields of the terms under the names `fields`
+    /// stores them under. A query given as JSON is passed through as it is.
+    pub fn to_document(&self, fields: &FieldNames) -> Document {
+        let terms = match self {
+            Filter::Query(query) => return query.clone(),
+            Filter::Terms(terms) => terms,
+        };
+        let mut query = Document::new();
+        for term in terms {
+            let condition = match (term.prefix, term.value.is_empty()) {
+                // Regular expressions ignore the collation, so say the case does not matter.
+                (true, _) => Bson::RegularExpression(Regex { pattern: format!("^{}", escape(&term.value)), options: "i".to_string() }),
+                (false, true) => Bson::Document(doc! { "$in": [Bson::Null, ""] }),
+                (false, false) => Bson::String(term.value.clone()),
+            };
+            query.insert(stored_name(&term.field, fields), condition);
+        }
+        query
+    }
+}
+
+/// The projection reading the `columns` of [`export`](crate::export) from documents stored
+/// with `fields`. The ICAO code and description are always read, as every aircraft has them.
+pub fn projection(columns: &[String], fields: &FieldNames) -> Document {
+    let mut projection = doc! { &fields.icao_code: 1, &fields.description: 1 };
+    for column in columns {
+        projection.insert(stored_name(column, fields), 1);
+    }
+    projection
+}
+
+// `value` with the characters regular expressions give a meaning escaped.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for character in value.chars() {
+        if "\\.+*?()|[]{}^$".contains(character) {
+            escaped.push('\\');
+        }
+        escaped.push(character);
+    }
+    escaped
+}
+
+fn stored_name<'a>(field: &'a str, fields: &'a FieldNames) -> &'a str {
+    match field {
+        "icaoCode" | "icao_code" => &fields.icao_code,
+        "iataCode" | "iata_code" => &fields.iata_code,
+        "description" => &fields.description,
+        DESCRIPTIONS => DESCRIPTIONS,
+        ALIASES => ALIASES,
+        field => field,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_terms() {
+        let filter: Filter = "manufacturer=Boeing, icaoCode=B7*,iataCode=".parse().unwrap();
+        assert_eq!(
+            filter,
+            Filter::Terms(vec![
+                Term { field: "manufacturer".to_string(), value: "Boeing".to_string(), prefix: false },
+                Term { field: "icaoCode".to_string(), value: "B7".to_string(), prefix: true },
+                Term { field: "iataCode".to_string(), value: String::new(), prefix: false },
+            ])
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_terms() {
+        assert!("manufacturer".parse::<Filter>().is_err());
+        assert!("=Boeing".parse::<Filter>().is_err());
+        assert!("$where=1".parse::<Filter>().is_err());
+        assert!("[1]".parse::<Filter>().is_err());
+    }
+
+    #[test]
+    fn compiles_terms_under_the_stored_names() {
+        let filter: Filter = "icaoCode=B7.*,iataCode=,manufacturer=Boeing".parse().unwrap();
+        let query = filter.to_document(&FieldNames::snake_case());
+        let prefix = Bson::RegularExpression(Regex { pattern: "^B7\\.".to_string(), options: "i".to_string() });
+        assert_eq!(query, doc! { "icao_code": prefix, "iata_code": { "$in": [Bson::Null, ""] }, "manufacturer": "Boeing" });
+    }
+
+    #[test]
+    fn passes_a_json_query_through() {
+        let filter: Filter = r#"{"iataCode": {"$in": ["738", "73H"]}}"#.parse().unwrap();
+        assert_eq!(filter.to_document(&FieldNames::snake_case()), doc! { "iataCode": { "$in": ["738", "73H"] } });
+    }
+
+    #[test]
+    fn projects_the_code_and_description_with_the_columns() {
+        let projection = projection(&["iataCode".to_string(), "manufacturer".to_string()], &FieldNames::default());
+        assert_eq!(projection, doc! { "icaoCode": 1, "description": 1, "iataCode": 1, "manufacturer": 1 });
+    }
+}
@@ -0,0 +1,236 @@
+//! A GraphQL schema over the stored reference data, served by the HTTP API at `/graphql`,
+//! in which a route resolves the airline, airports and aircraft types it refers to.
+//!
+//! Routes refer to them by ICAO or IATA code, matched as [`KnownCodes`](crate::references::KnownCodes)
+//! matches them: an ICAO code first, else the first record with that IATA code. The lookups of
+//! one query are batched into a single find per collection.
+
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use async_graphql::dataloader::{DataLoader, Loader};
+use async_graphql::{Context, EmptyMutation, EmptySubscription, Object};
+use mongodb::bson::{doc, Document};
+use tracing::error;
+use crate::metrics::metrics;
+use crate::record::Record;
+use crate::server::MAX_PAGE_SIZE;
+use crate::{Aircraft, AircraftStore, Airline, Airport, Error, Route};
+
+/// The schema served at `/graphql`.
+pub type Schema = async_graphql::Schema<Query, EmptyMutation, EmptySubscription>;
+
+// Deepest selection a query may make; the schema has no cycles, so this only bounds abuse.
+const MAX_DEPTH: usize = 8;
+
+const DEFAULT_ROUTES: u64 = 100;
+
+/// The schema reading from `store` and the collections next to it.
+pub fn schema(store: AircraftStore) -> Schema {
+    Schema::build(Query, EmptyMutation, EmptySubscription)
+        .data(DataLoader::new(AircraftLoader { store: store.clone() }, tokio::spawn))
+        .data(DataLoader::new(RecordLoader::<Airline>::new(store.clone()), tokio::spawn))
+        .data(DataLoader::new(RecordLoader::<Airport>::new(store.clone()), tokio::spawn))
+        .data(store)
+        .limit_depth(MAX_DEPTH)
+        .finish()
+}
+
+fn internal(error: impl Borrow<Error>) -> async_graphql::Error {
+    let error = error.borrow();
+    metrics().observe_error(error);
+    error!(%error, "request failed");
+    async_graphql::Error::new("internal error")
+}
+
+// Records by each requested code they have, an ICAO code winning over another record's IATA code.
+fn by_code<T>(records: Vec<T>, codes: &[String], code_of: impl Fn(&T) -> (String, String)) -> HashMap<String, T>
+where
+    T: Clone,
+{
+    let mut found = HashMap::new();
+    let coded: Vec<_> = records.into_iter().map(|record| (code_of(&record), record)).collect();
+    for ((_, iata_code), record) in &coded {
+        if !iata_code.is_empty() && codes.contains(iata_code) {
+            found.entry(iata_code.clone()).or_insert_with(|| record.clone());
+        }
+    }
+    for ((icao_code, _), record) in coded {
+        if codes.contains(&icao_code) {
+            found.insert(icao_code, record);
+        }
+    }
+    found
+}
+
+// What looking up airlines and airports by code needs besides their key.
+trait Coded: Record {
+    fn iata_code(&self) -> &str;
+}
+
+impl Coded for Airline {
+    fn iata_code(&self) -> &str {
+        &self.iata_code
+    }
+}
+
+impl Coded for Airport {
+    fn iata_code(&self) -> &str {
+        &self.iata_code
+    }
+}
+
+struct RecordLoader<T> {
+    store: AircraftStore,
+    kind: PhantomData<T>,
+}
+
+impl<T> RecordLoader<T> {
+    fn new(store: AircraftStore) -> Self {
+        RecordLoader { store, kind: PhantomData }
+    }
+}
+
+impl<T: Coded> Loader<String> for RecordLoader<T> {
+    type Value = T;
+    type Error = Arc<Error>;
+
+    async fn load(&self, codes: &[String]) -> Result<HashMap<String, T>, Arc<Error>> {
+        let filter = doc! { "$or": [{ "icaoCode": { "$in": codes } }, { "iataCode": { "$in": codes } }] };
+        let records = self.store.records::<T>(T::COLLECTION).find(filter).await?;
+        Ok(by_code(records, codes, |record| (record.key().into_owned(), record.iata_code().to_string())))
+    }
+}
+
+// Aircraft are read from the store's own collection, under its field names.
+struct AircraftLoader {
+    store: AircraftStore,
+}
+
+impl Loader<String> for AircraftLoader {
+    type Value = Aircraft;
+    type Error = Arc<Error>;
+
+    async fn load(&self, codes: &[String]) -> Result<HashMap<String, Aircraft>, Arc<Error>> {
+        let fields = self.store.field_names();
+        let filter = doc! { "$or": [{ &fields.icao_code: { "$in": codes } }, { &fields.iata_code: { "$in": codes } }] };
+        let documents = self.store.find(filter).await?;
+        let aircrafts = documents.iter().map(|document| fields.aircraft(document)).collect::<crate::Result<Vec<_>>>()?;
+        Ok(by_code(aircrafts, codes, |aircraft| (aircraft.icao_code.clone(), aircraft.iata_code.clone().unwrap_or_default())))
+    }
+}
+
+async fn load<T, L>(ctx: &Context<'_>, code: &str) -> async_graphql::Result<Option<T>>
+where
+    L: Loader<String, Value = T, Error = Arc<Error>>,
+{
+    ctx.data_unchecked::<DataLoader<L>>().load_one(code.to_ascii_uppercase()).await.map_err(internal)
+}
+
+/// The entry points of the schema.
+pub struct Query;
+
+#[Object]
+impl Query {
+    /// The aircraft type with this ICAO (or else IATA) code, e.g. `B38M`.
+    async fn aircraft(&self, ctx: &Context<'_>, code: String) -> async_graphql::Result<Option<Aircraft>> {
+        load::<_, AircraftLoader>(ctx, &code).await
+    }
+
+    /// The airline with this ICAO (or else IATA) designator, e.g. `BAW`.
+    async fn airline(&self, ctx: &Context<'_>, code: String) -> async_graphql::Result<Option<Airline>> {
+        load::<_, RecordLoader<Airline>>(ctx, &code).await
+    }
+
+    /// The airport with this ICAO (or else IATA) code, e.g. `EGLL`.
+    async fn airport(&self, ctx: &Context<'_>, code: String) -> async_graphql::Result<Option<Airport>> {
+        load::<_, RecordLoader<Airport>>(ctx, &code).await
+    }
+
+    /// Routes ordered by key, of one airline, origin and destination code (as stored) if given;
+    /// at most `limit` (100 by default, at most 1000) of them after skipping `offset`.
+    async fn routes(
+        &self,
+        ctx: &Context<'_>,
+        airline: Option<String>,
+        origin: Option<String>,
+        destination: Option<String>,
+        #[graphql(default)] offset: u64,
+        limit: Option<u64>,
+    ) -> async_graphql::Result<Vec<RouteNode>> {
+        let mut filter = Document::new();
+        for (field, code) in [("airline", airline), ("origin", origin), ("destination", destination)] {
+            if let Some(code) = code {
+                filter.insert(field, code.to_ascii_uppercase());
+            }
+        }
+        let limit = limit.unwrap_or(DEFAULT_ROUTES).min(MAX_PAGE_SIZE);
+        let store = ctx.data_unchecked::<AircraftStore>();
+        let (routes, _) = store.records::<Route>(Route::COLLECTION).page(filter, offset, limit).await.map_err(internal)?;
+        Ok(routes.into_iter().map(RouteNode).collect())
+    }
+}
+
+/// A [`Route`] with the records its codes refer to.
+pub struct RouteNode(Route);
+
+#[Object(name = "Route")]
+impl RouteNode {
+    /// `<airline>:<origin>-<destination>`, e.g. `BAW:EGLL-KJFK`.
+    async fn route_key(&self) -> String {
+        self.0.route_key()
+    }
+
+    /// Code of the operating airline as stored, e.g. `BAW` or `BA`.
+    async fn airline_code(&self) -> &str {
+        &self.0.airline
+    }
+
+    /// Code of the departure airport as stored, e.g. `EGLL` or `LHR`.
+    async fn origin_code(&self) -> &str {
+        &self.0.origin
+    }
+
+    /// Code of the arrival airport as stored, e.g. `KJFK` or `JFK`.
+    async fn destination_code(&self) -> &str {
+        &self.0.destination
+    }
+
+    /// Aircraft type codes flown on the route as stored, e.g. `["77W", "359"]`.
+    async fn equipment_codes(&self) -> &[String] {
+        &self.0.equipment
+    }
+
+    /// Great-circle distance between the airports in kilometres, null if it wasn't measured.
+    async fn distance_km(&self) -> Option<f64> {
+        self.0.distance_km
+    }
+
+    /// Great-circle distance between the airports in nautical miles, null if it wasn't measured.
+    async fn distance_nm(&self) -> Option<f64> {
+        self.0.distance_nm
+    }
+
+    /// The operating airline, null if it isn't loaded.
+    async fn airline(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Airline>> {
+        load::<_, RecordLoader<Airline>>(ctx, &self.0.airline).await
+    }
+
+    /// The departure airport, null if it isn't loaded.
+    async fn origin(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Airport>> {
+        load::<_, RecordLoader<Airport>>(ctx, &self.0.origin).await
+    }
+
+    /// The arrival airport, null if it isn't loaded.
+    async fn destination(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Airport>> {
+        load::<_, RecordLoader<Airport>>(ctx, &self.0.destination).await
+    }
+
+    /// The aircraft types flown, in the order of their codes, leaving out those not loaded.
+    async fn equipment(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Aircraft>> {
+        let codes: Vec<String> = self.0.equipment.iter().map(|code| code.to_ascii_uppercase()).collect();
+        let mut found = ctx.data_unchecked::<DataLoader<AircraftLoader>>().load_many(codes.clone()).await.map_err(internal)?;
+        Ok(codes.iter().filter_map(|code| found.remove(code)).collect())
+    }
+}
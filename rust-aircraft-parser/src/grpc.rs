@@ -0,0 +1,189 @@
+//! A gRPC service over the stored reference data, the `ReferenceData` service of
+//! `proto/reference_data.proto`, for services that speak gRPC rather than HTTP.
+//!
+//! It reads from the same collections as the [HTTP API](crate::server). Lookups answer
+//! `NOT_FOUND` for unknown codes; lists stream every match, fetched a page at a time.
+
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use mongodb::bson::Document;
+use tonic::transport::server::TcpIncoming;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+use tracing::{error, info};
+use crate::metrics::metrics;
+use crate::record::Record;
+use crate::server::MAX_PAGE_SIZE;
+use crate::{Aircraft, AircraftStore, Airline, Airport, Error, Result, Storage};
+
+/// The messages and service generated from `proto/reference_data.proto`.
+pub mod proto {
+    tonic::include_proto!("reference_data.v1");
+}
+
+use proto::reference_data_server::{ReferenceData, ReferenceDataServer};
+
+/// Serves the `ReferenceData` service on `address` until the process receives Ctrl-C.
+pub async fn serve(store: AircraftStore, address: SocketAddr) -> Result<()> {
+    let incoming = TcpIncoming::bind(address).map_err(|source| Error::Serve { address, source })?;
+    info!(%address, "serving gRPC");
+    Server::builder()
+        .add_service(ReferenceDataServer::new(Service { store }))
+        .serve_with_incoming_shutdown(incoming, async {
+            tokio::signal::ctrl_c().await.ok();
+        })
+        .await
+        .map_err(|error| Error::Serve { address, source: io::Error::other(error) })
+}
+
+type RpcResult<T> = std::result::Result<T, Status>;
+
+struct Service {
+    store: AircraftStore,
+}
+
+fn internal(error: Error) -> Status {
+    metrics().observe_error(&error);
+    error!(%error, "request failed");
+    Status::internal("internal error")
+}
+
+// The items of the pages `fetch` returns from each offset, until one comes back short of the total.
+fn paged<T, F, Fut>(fetch: F) -> BoxStream<'static, Result<T>>
+where
+    T: Send + 'static,
+    F: FnMut(u64) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<(Vec<T>, u64)>> + Send,
+{
+    stream::try_unfold((fetch, Some(0)), |(mut fetch, offset)| async move {
+        let Some(offset) = offset else { return Ok(None) };
+        let (items, total) = fetch(offset).await?;
+        let next = offset + items.len() as u64;
+        let more = !items.is_empty() && next < total;
+        Result::Ok(Some((items, (fetch, more.then_some(next)))))
+    })
+    .map_ok(|items| stream::iter(items.into_iter().map(Ok)))
+    .try_flatten()
+    .boxed()
+}
+
+fn place_filter(request: &proto::ListPlacesRequest) -> Document {
+    let mut filter = Document::new();
+    if !request.iata_code.is_empty() {
+        filter.insert("iataCode", request.iata_code.to_ascii_uppercase());
+    }
+    if !request.country.is_empty() {
+        filter.insert("country", &request.country);
+    }
+    filter
+}
+
+// Airlines and airports are listed the same way.
+fn list_records<T, M>(store: &AircraftStore, request: &proto::ListPlacesRequest) -> BoxStream<'static, RpcResult<M>>
+where
+    T: Record + Send + 'static,
+    M: From<T> + Send + 'static,
+{
+    let store = store.clone();
+    let filter = place_filter(request);
+    paged(move |offset| {
+        let records = store.records::<T>(T::COLLECTION);
+        let filter = filter.clone();
+        async move { records.page(filter, offset, MAX_PAGE_SIZE).await }
+    })
+    .map_ok(M::from)
+    .map_err(internal)
+    .boxed()
+}
+
+async fn get_record<T: Record, M: From<T>>(store: &AircraftStore, icao_code: &str) -> RpcResult<Response<M>> {
+    let icao_code = icao_code.to_ascii_uppercase();
+    match store.records::<T>(T::COLLECTION).find_by_key(&icao_code).await.map_err(internal)? {
+        Some(record) => Ok(Response::new(M::from(record))),
+        None => Err(Status::not_found(format!("nothing in {} under {}", T::COLLECTION, icao_code))),
+    }
+}
+
+#[tonic::async_trait]
+impl ReferenceData for Service {
+    type ListAircraftStream = BoxStream<'static, RpcResult<proto::Aircraft>>;
+    type ListAirlinesStream = BoxStream<'static, RpcResult<proto::Airline>>;
+    type ListAirportsStream = BoxStream<'static, RpcResult<proto::Airport>>;
+
+    async fn get_aircraft_by_icao(&self, request: Request<proto::GetByIcaoRequest>) -> RpcResult<Response<proto::Aircraft>> {
+        let icao_code = request.into_inner().icao_code.to_ascii_uppercase();
+        match self.store.find_by_icao(&icao_code).await.map_err(internal)? {
+            Some(aircraft) => Ok(Response::new(aircraft.into())),
+            None => Err(Status::not_found(format!("no aircraft {}", icao_code))),
+        }
+    }
+
+    async fn list_aircraft(&self, request: Request<proto::ListAircraftRequest>) -> RpcResult<Response<Self::ListAircraftStream>> {
+        let iata_code = Some(request.into_inner().iata_code.to_ascii_uppercase()).filter(|code| !code.is_empty());
+        let store = self.store.clone();
+        let aircraft = paged(move |offset| {
+            let store = store.clone();
+            let iata_code = iata_code.clone();
+            async move { store.page(iata_code.as_deref(), offset, MAX_PAGE_SIZE).await }
+        });
+        Ok(Response::new(aircraft.map_ok(proto::Aircraft::from).map_err(internal).boxed()))
+    }
+
+    async fn get_airline_by_icao(&self, request: Request<proto::GetByIcaoRequest>) -> RpcResult<Response<proto::Airline>> {
+        get_record::<Airline, _>(&self.store, &request.into_inner().icao_code).await
+    }
+
+    async fn list_airlines(&self, request: Request<proto::ListPlacesRequest>) -> RpcResult<Response<Self::ListAirlinesStream>> {
+        Ok(Response::new(list_records::<Airline, _>(&self.store, request.get_ref())))
+    }
+
+    async fn get_airport_by_icao(&self, request: Request<proto::GetByIcaoRequest>) -> RpcResult<Response<proto::Airport>> {
+        get_record::<Airport, _>(&self.store, &request.into_inner().icao_code).await
+    }
+
+    async fn list_airports(&self, request: Request<proto::ListPlacesRequest>) -> RpcResult<Response<Self::ListAirportsStream>> {
+        Ok(Response::new(list_records::<Airport, _>(&self.store, request.get_ref())))
+    }
+}
+
+impl From<Aircraft> for proto::Aircraft {
+    fn from(aircraft: Aircraft) -> Self {
+        proto::Aircraft {
+            icao_code: aircraft.icao_code,
+            iata_code: aircraft.iata_code.unwrap_or_default(),
+            description: aircraft.description,
+            aliases: aircraft.aliases,
+        }
+    }
+}
+
+impl From<Airline> for proto::Airline {
+    fn from(airline: Airline) -> Self {
+        proto::Airline {
+            icao_code: airline.icao_code,
+            iata_code: airline.iata_code,
+            name: airline.name,
+            callsign: airline.callsign,
+            country: airline.country,
+            active: airline.active,
+        }
+    }
+}
+
+impl From<Airport> for proto::Airport {
+    fn from(airport: Airport) -> Self {
+        proto::Airport {
+            icao_code: airport.icao_code,
+            iata_code: airport.iata_code,
+            name: airport.name,
+            city: airport.city,
+            country: airport.country,
+            latitude: airport.latitude,
+            longitude: airport.longitude,
+            elevation: airport.elevation,
+            timezone: airport.timezone,
+        }
+    }
+}
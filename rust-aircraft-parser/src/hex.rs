@@ -0,0 +1,54 @@
+use std::borrow::Cow;
+use mongodb::bson::doc;
+use mongodb::IndexModel;
+use serde::{Deserialize, Deserializer, Serialize};
+use crate::record::Record;
+
+/// An aircraft's 24-bit ICAO (Mode S) address with the registration and type broadcasting it,
+/// as in the readsb and Mictronics aircraft databases.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct HexEntry {
+    /// Address as 6 upper case hex digits, e.g. `A061D9`.
+    #[serde(alias = "icao", deserialize_with = "upper_case")]
+    pub hex: String,
+    /// Registration mark, e.g. `N12345`. Empty when unknown.
+    #[serde(default, alias = "reg", alias = "r")]
+    pub registration: String,
+    /// ICAO type designator, e.g. `B38M`. Empty when unknown.
+    #[serde(default, alias = "icaotype", alias = "t")]
+    pub type_code: String,
+    /// Whether the address belongs to a military aircraft.
+    #[serde(default, alias = "mil")]
+    pub military: bool,
+}
+
+fn upper_case<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(String::deserialize(deserializer)?.trim().to_ascii_uppercase())
+}
+
+impl Record for HexEntry {
+    const COLLECTION: &'static str = "hexdb";
+    const KEY_FIELD: &'static str = "hex";
+
+    fn key(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.hex)
+    }
+
+    /// * `hex` must be exactly 6 hex digits.
+    fn validate(&self) -> Vec<String> {
+        let mut reasons = Vec::new();
+        if self.hex.len() != 6 || !self.hex.chars().all(|character| character.is_ascii_hexdigit()) {
+            reasons.push(format!("hex {:?} is not 6 hex digits", self.hex));
+        }
+        reasons
+    }
+
+    /// Indexes on `registration` and on `typeCode`.
+    fn indexes() -> Vec<IndexModel> {
+        vec![
+            IndexModel::builder().keys(doc! { "registration": 1 }).build(),
+            IndexModel::builder().keys(doc! { "typeCode": 1 }).build(),
+        ]
+    }
+}
@@ -0,0 +1,196 @@
+//! Site-specific transforms run on every aircraft between parsing and writing, without
+//! forking the crate: an external command fed NDJSON, or a WebAssembly module with the
+//! `wasm` feature.
+//!
+//! Both see each aircraft as one JSON object, as it is exported, and answer with the
+//! aircraft to write instead, or `null` to leave it out.
+
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::warn;
+use crate::input::AircraftStream;
+use crate::{Aircraft, Error, Result};
+
+/// A transform registered with `--hook`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Hook {
+    /// A shell command started once per run, reading one aircraft per line on stdin and
+    /// writing its answer as one line on stdout, flushed, before reading the next.
+    Command(String),
+    /// A WebAssembly module exporting its `memory`, `alloc(len: i32) -> i32`, which
+    /// returns where to write `len` bytes of input, and `transform(ptr: i32, len: i32) -> i64`,
+    /// which returns where its answer starts in the high 32 bits and its length in the
+    /// low 32 bits.
+    Wasm(PathBuf),
+}
+
+impl Hook {
+    /// A path ending in `.wasm` is a module; anything else a command.
+    pub fn parse(spec: &str) -> Hook {
+        if spec.ends_with(".wasm") {
+            Hook::Wasm(PathBuf::from(spec))
+        } else {
+            Hook::Command(spec.to_string())
+        }
+    }
+
+    fn start(&self) -> Result<Running> {
+        match self {
+            Hook::Command(command) => CommandHook::start(command).map(Running::Command),
+            #[cfg(feature = "wasm")]
+            Hook::Wasm(path) => WasmHook::load(path).map(Running::Wasm),
+            #[cfg(not(feature = "wasm"))]
+            Hook::Wasm(path) => Err(Error::Config(format!("running {} needs the wasm feature", path.display()))),
+        }
+    }
+}
+
+/// Passes every aircraft of `aircrafts` through each of `hooks` in turn, leaving out those
+/// a hook answers `null` for. Every hook is started before the first aircraft is read.
+pub fn apply(mut aircrafts: AircraftStream, hooks: &[Hook]) -> Result<AircraftStream> {
+    for hook in hooks {
+        let mut running = hook.start()?;
+        aircrafts = Box::new(aircrafts.filter_map(move |aircraft| match aircraft {
+            Ok(aircraft) => running.transform(&aircraft).transpose(),
+            Err(error) => Some(Err(error)),
+        }));
+    }
+    Ok(aircrafts)
+}
+
+enum Running {
+    Command(CommandHook),
+    #[cfg(feature = "wasm")]
+    Wasm(WasmHook),
+}
+
+impl Running {
+    fn name(&self) -> &str {
+        match self {
+            Running::Command(hook) => &hook.command,
+            #[cfg(feature = "wasm")]
+            Running::Wasm(hook) => &hook.name,
+        }
+    }
+
+    // An empty answer leaves the aircraft out like `null`.
+    fn transform(&mut self, aircraft: &Aircraft) -> Result<Option<Aircraft>> {
+        let input = serde_json::to_vec(aircraft)?;
+        let output = match self {
+            Running::Command(hook) => hook.call(&input)?,
+            #[cfg(feature = "wasm")]
+            Running::Wasm(hook) => hook.call(&input)?,
+        };
+        let answer = String::from_utf8_lossy(&output);
+        if answer.trim().is_empty() {
+            return Ok(None);
+        }
+        serde_json::from_str(&answer).map_err(|error| {
+            Error::Hook(format!("{} answered {} with {}: {}", self.name(), aircraft.icao_code, answer.trim(), error))
+        })
+    }
+}
+
+// How long a command gets to exit once its input is closed before it is killed.
+const EXIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct CommandHook {
+    command: String,
+    child: Child,
+    // Closed first when the hook is dropped, so the command sees the end of its input.
+    stdin: Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl CommandHook {
+    fn start(command: &str) -> Result<CommandHook> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|error| Error::Hook(format!("cannot start {}: {}", command, error)))?;
+        let stdin = child.stdin.take();
+        let stdout = child.stdout.take().map(BufReader::new);
+        let stdout = stdout.ok_or_else(|| Error::Hook(format!("{} has no stdout", command)))?;
+        Ok(CommandHook { command: command.to_string(), child, stdin, stdout })
+    }
+
+    fn call(&mut self, input: &[u8]) -> Result<Vec<u8>> {
+        let failed = |error: std::io::Error| Error::Hook(format!("{}: {}", self.command, error));
+        let stdin = self.stdin.as_mut().ok_or_else(|| Error::Hook(format!("{} is closed", self.command)))?;
+        stdin.write_all(input).and_then(|_| stdin.write_all(b"\n")).and_then(|_| stdin.flush()).map_err(failed)?;
+        let mut line = Vec::new();
+        if self.stdout.read_until(b'\n', &mut line).map_err(failed)? == 0 {
+            return Err(Error::Hook(format!("{} exited without answering", self.command)));
+        }
+        Ok(line)
+    }
+}
+
+impl Drop for CommandHook {
+    // A command that does not exit when its input ends is killed rather than waited for.
+    fn drop(&mut self) {
+        drop(self.stdin.take());
+        let deadline = Instant::now() + EXIT_TIMEOUT;
+        while let Ok(None) = self.child.try_wait() {
+            if Instant::now() >= deadline {
+                warn!(hook = %self.command, "killing a hook that did not exit");
+                let _ = self.child.kill();
+                let _ = self.child.wait();
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+// How many instructions, roughly, a module may run per aircraft before the call is trapped.
+#[cfg(feature = "wasm")]
+const FUEL_PER_AIRCRAFT: u64 = 100_000_000;
+
+#[cfg(feature = "wasm")]
+struct WasmHook {
+    name: String,
+    store: wasmtime::Store<()>,
+    memory: wasmtime::Memory,
+    alloc: wasmtime::TypedFunc<i32, i32>,
+    transform: wasmtime::TypedFunc<(i32, i32), i64>,
+}
+
+#[cfg(feature = "wasm")]
+impl WasmHook {
+    fn load(path: &std::path::Path) -> Result<WasmHook> {
+        let name = path.display().to_string();
+        let failed = |error: wasmtime::Error| Error::Hook(format!("{}: {}", name, error));
+        let mut config = wasmtime::Config::new();
+        config.consume_fuel(true);
+        let engine = wasmtime::Engine::new(&config).map_err(failed)?;
+        let module = wasmtime::Module::from_file(&engine, path).map_err(failed)?;
+        let mut store = wasmtime::Store::new(&engine, ());
+        let instance = wasmtime::Instance::new(&mut store, &module, &[]).map_err(failed)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| Error::Hook(format!("{} exports no memory", name)))?;
+        let alloc = instance.get_typed_func(&mut store, "alloc").map_err(failed)?;
+        let transform = instance.get_typed_func(&mut store, "transform").map_err(failed)?;
+        Ok(WasmHook { name, store, memory, alloc, transform })
+    }
+
+    fn call(&mut self, input: &[u8]) -> Result<Vec<u8>> {
+        let name = &self.name;
+        let failed = |error: String| Error::Hook(format!("{}: {}", name, error));
+        // Refilled for every aircraft, so a module stuck in a loop fails the call instead of the run.
+        self.store.set_fuel(FUEL_PER_AIRCRAFT).map_err(|error| failed(error.to_string()))?;
+        let at = self.alloc.call(&mut self.store, input.len() as i32).map_err(|error| failed(error.to_string()))?;
+        self.memory.write(&mut self.store, at as u32 as usize, input).map_err(|error| failed(error.to_string()))?;
+        let answer = self.transform.call(&mut self.store, (at, input.len() as i32)).map_err(|error| failed(error.to_string()))?;
+        let mut output = vec![0; answer as u32 as usize];
+        self.memory.read(&self.store, (answer >> 32) as u32 as usize, &mut output).map_err(|error| failed(error.to_string()))?;
+        Ok(output)
+    }
+}
@@ -0,0 +1,107 @@
+//! How document identifiers are generated.
+
+use mongodb::bson::{self, Bson};
+use uuid::Uuid;
+use crate::Aircraft;
+
+/// Namespace used for UUIDv5 identifiers unless another one is configured.
+pub const DEFAULT_NAMESPACE: Uuid = Uuid::from_u128(0x01bc76dc_9d5a_4135_acf8_13f0262ca968);
+
+/// Strategy for the `_id` (or `id` column) of newly written records.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IdStrategy {
+    /// A random UUIDv4 per write, so reloading the same aircraft produces a new identifier.
+    #[default]
+    Random,
+    /// A UUIDv5 of `namespace` and the ICAO code, so every load produces the same identifier.
+    Uuid5 { namespace: Uuid },
+    /// The ICAO code itself, upper case, so the `_id` doubles as the unique key and reads
+    /// as what it identifies.
+    Natural,
+    /// The ICAO and IATA codes joined by a colon, e.g. `B738:738`, or the ICAO code alone
+    /// for aircraft without an IATA code. Fixed when a record is first written, like the
+    /// other strategies, so it is not changed by a later change of IATA code.
+    Composite,
+}
+
+impl IdStrategy {
+    /// The identifier a newly written `aircraft` gets.
+    pub fn id_for(&self, aircraft: &Aircraft) -> String {
+        let icao_code = aircraft.icao_code.trim().to_ascii_uppercase();
+        match (self, aircraft.iata_code.as_deref().map(str::trim)) {
+            (IdStrategy::Natural, _) => icao_code,
+            (IdStrategy::Composite, Some(iata_code)) if !iata_code.is_empty() => {
+                format!("{}:{}", icao_code, iata_code.to_ascii_uppercase())
+            }
+            (IdStrategy::Composite, _) => icao_code,
+            _ => self.id_for_key(&aircraft.icao_code),
+        }
+    }
+
+    /// The identifier a newly written record with natural key `key` gets.
+    pub fn id_for_key(&self, key: &str) -> String {
+        match self {
+            IdStrategy::Random => Uuid::new_v4().to_string(),
+            IdStrategy::Uuid5 { namespace } => Uuid::new_v5(namespace, key.as_bytes()).to_string(),
+            IdStrategy::Natural | IdStrategy::Composite => key.to_string(),
+        }
+    }
+
+    /// Whether the `_id` of an aircraft is its upper-case ICAO code, so lookups by ICAO
+    /// code can go through the `_id` index instead of one of their own.
+    pub fn is_natural(&self) -> bool {
+        matches!(self, IdStrategy::Natural)
+    }
+}
+
+/// How UUID identifiers are stored in MongoDB.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UuidEncoding {
+    /// BSON binaries of subtype 4, half the size of their strings in documents and indexes,
+    /// and what other drivers map to their UUID type.
+    #[default]
+    Binary,
+    /// Strings, as earlier versions wrote them.
+    String,
+}
+
+impl UuidEncoding {
+    /// `id` as stored: encoded as configured if it is a UUID, a string otherwise, as natural
+    /// keys are.
+    pub fn encode(&self, id: String) -> Bson {
+        match (self, bson::Uuid::parse_str(&id)) {
+            (UuidEncoding::Binary, Ok(uuid)) => Bson::from(uuid),
+            _ => Bson::String(id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::aircraft;
+
+    #[test]
+    fn derives_stable_identifiers_from_the_codes() {
+        let b738 = aircraft(" b738").iata("738 ").build();
+        let uuid5 = IdStrategy::Uuid5 { namespace: DEFAULT_NAMESPACE };
+        assert_eq!(uuid5.id_for(&b738), uuid5.id_for(&aircraft(" b738").build()));
+        assert_eq!(IdStrategy::Natural.id_for(&b738), "B738");
+        assert_eq!(IdStrategy::Composite.id_for(&b738), "B738:738");
+        assert_eq!(IdStrategy::Composite.id_for(&aircraft("A320").iata(" ").build()), "A320");
+    }
+
+    #[test]
+    fn draws_a_new_random_identifier_on_every_write() {
+        let a320 = aircraft("A320").build();
+        assert_ne!(IdStrategy::Random.id_for(&a320), IdStrategy::Random.id_for(&a320));
+    }
+
+    #[test]
+    fn stores_uuids_as_configured_and_anything_else_as_a_string() {
+        let uuid = Uuid::new_v5(&DEFAULT_NAMESPACE, b"B738").to_string();
+        assert!(matches!(UuidEncoding::Binary.encode(uuid.clone()), Bson::Binary(_)));
+        assert_eq!(UuidEncoding::String.encode(uuid.clone()), Bson::String(uuid));
+        assert_eq!(UuidEncoding::Binary.encode("B738".to_string()), Bson::String("B738".to_string()));
+    }
+}
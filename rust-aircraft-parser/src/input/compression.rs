@@ -0,0 +1,93 @@
+use std::io::{self, BufRead, BufReader, PipeReader, Read};
+use std::thread::{self, JoinHandle};
+use flate2::bufread::MultiGzDecoder;
+use zip::read::read_zipfile_from_stream;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// Wraps `reader` in a decoder when it starts with a gzip or zip signature, so compressed
+/// input reads like the plain file. Gzip is decoded as it is read; of a zip archive the first
+/// file is extracted, on a background thread, as it is read. Other input is returned as is.
+pub fn decompress(mut reader: impl BufRead + Send + 'static) -> io::Result<Box<dyn BufRead + Send>> {
+    let start = reader.fill_buf()?;
+    if start.starts_with(GZIP_MAGIC) {
+        return Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))));
+    }
+    if start.starts_with(ZIP_MAGIC) {
+        return Ok(Box::new(BufReader::new(extract_first_file(reader)?)));
+    }
+    Ok(Box::new(reader))
+}
+
+fn extract_first_file(mut reader: impl Read + Send + 'static) -> io::Result<Extracted> {
+    let (pipe, mut writer) = io::pipe()?;
+    let worker = thread::spawn(move || {
+        while let Some(mut file) = read_zipfile_from_stream(&mut reader).map_err(io::Error::other)? {
+            if file.is_file() {
+                io::copy(&mut file, &mut writer)?;
+                return Ok(());
+            }
+        }
+        Err(io::Error::new(io::ErrorKind::InvalidData, "zip archive contains no file"))
+    });
+    Ok(Extracted { pipe, worker: Some(worker) })
+}
+
+// The extracted bytes, followed by the extraction's error, if any, once they run out.
+struct Extracted {
+    pipe: PipeReader,
+    worker: Option<JoinHandle<io::Result<()>>>,
+}
+
+impl Read for Extracted {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let length = self.pipe.read(buffer)?;
+        if length == 0 && !buffer.is_empty() {
+            if let Some(worker) = self.worker.take() {
+                worker.join().map_err(|_| io::Error::other("zip extraction panicked"))??;
+            }
+        }
+        Ok(length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use zip::write::{SimpleFileOptions, ZipWriter};
+    use super::*;
+
+    const TEXT: &str = "[{\"icaoCode\": \"B738\"}]";
+
+    fn read(input: Vec<u8>) -> String {
+        let mut text = String::new();
+        decompress(io::Cursor::new(input)).unwrap().read_to_string(&mut text).unwrap();
+        text
+    }
+
+    #[test]
+    fn passes_plain_input_through() {
+        assert_eq!(read(TEXT.as_bytes().to_vec()), TEXT);
+    }
+
+    #[test]
+    fn decodes_gzip() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(TEXT.as_bytes()).unwrap();
+        assert_eq!(read(encoder.finish().unwrap()), TEXT);
+    }
+
+    #[test]
+    fn extracts_the_first_file_of_a_zip_archive() {
+        let mut archive = ZipWriter::new(io::Cursor::new(Vec::new()));
+        archive.add_directory("data/", SimpleFileOptions::default()).unwrap();
+        archive.start_file("data/aircraft.json", SimpleFileOptions::default()).unwrap();
+        archive.write_all(TEXT.as_bytes()).unwrap();
+        archive.start_file("data/other.json", SimpleFileOptions::default()).unwrap();
+        archive.write_all(b"[]").unwrap();
+        assert_eq!(read(archive.finish().unwrap().into_inner()), TEXT);
+    }
+}
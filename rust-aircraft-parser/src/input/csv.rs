@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+use std::io::Read;
+use serde::de::DeserializeOwned;
+use crate::{Aircraft, Error, Result};
+
+/// Header names of the CSV columns holding each [`Aircraft`] field.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CsvColumns {
+    pub icao_code: String,
+    pub iata_code: String,
+    pub description: String,
+}
+
+impl Default for CsvColumns {
+    fn default() -> Self {
+        CsvColumns {
+            icao_code: "icaoCode".to_string(),
+            iata_code: "iataCode".to_string(),
+            description: "description".to_string(),
+        }
+    }
+}
+
+/// Reads header-based CSV rows into [`Aircraft`], looking columns up by the names in `columns`.
+/// Columns not named in the mapping are ignored. Rows are parsed lazily as the iterator is advanced;
+/// a header missing one of the mapped columns is reported up front.
+pub fn read_aircraft_csv(reader: impl Read, columns: &CsvColumns) -> Result<impl Iterator<Item = Result<Aircraft>>> {
+    let mut reader = csv::Reader::from_reader(reader);
+    let headers = reader.headers()?.clone();
+    let position = |name: &str| {
+        headers
+            .iter()
+            .position(|header| header.trim() == name)
+            .ok_or_else(|| Error::InvalidInput(format!("csv header has no column named {}", name)))
+    };
+    let icao_code = position(&columns.icao_code)?;
+    let iata_code = position(&columns.iata_code)?;
+    let description = position(&columns.description)?;
+
+    Ok(reader.into_records().map(move |record| {
+        let record = record?;
+        let field = |index: usize| record.get(index).unwrap_or_default().trim().to_string();
+        Ok(Aircraft {
+            icao_code: field(icao_code),
+            iata_code: Some(field(iata_code)).filter(|code| !code.is_empty()),
+            description: field(description),
+            descriptions: BTreeMap::new(),
+            aliases: Vec::new(),
+        })
+    }))
+}
+
+/// Reads header-based CSV rows into any deserializable record type, matching headers to the
+/// record's (camelCase) field names. Surrounding whitespace is trimmed and empty cells of
+/// optional fields become `None`.
+pub fn read_csv<T: DeserializeOwned>(reader: impl Read) -> impl Iterator<Item = Result<T>> {
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader)
+        .into_deserialize()
+        .map(|record| Ok(record?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::aircraft;
+
+    #[test]
+    fn reads_renamed_columns_and_leaves_out_an_empty_iata_code() {
+        let csv = "type, iata ,name,extra\nB738,738,Boeing 737-800,x\n A320 ,,Airbus A320 ,y\n";
+        let columns = CsvColumns { icao_code: "type".to_string(), iata_code: "iata".to_string(), description: "name".to_string() };
+        let aircrafts: Vec<Aircraft> = read_aircraft_csv(csv.as_bytes(), &columns).unwrap().collect::<Result<_>>().unwrap();
+        assert_eq!(
+            aircrafts,
+            vec![aircraft("B738").iata("738").description("Boeing 737-800").build(), aircraft("A320").description("Airbus A320").build()]
+        );
+    }
+
+    #[test]
+    fn reports_a_missing_column_before_the_first_row() {
+        let error = read_aircraft_csv("icaoCode,description\nB738,Boeing 737-800\n".as_bytes(), &CsvColumns::default()).err();
+        assert!(matches!(error, Some(Error::InvalidInput(message)) if message == "csv header has no column named iataCode"));
+    }
+}
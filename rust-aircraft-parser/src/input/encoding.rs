@@ -0,0 +1,18 @@
+use std::io::{BufRead, BufReader};
+use encoding_rs::Encoding;
+use encoding_rs_io::DecodeReaderBytesBuilder;
+
+/// Wraps `reader` in a decoder turning its text into UTF-8, so files saved as UTF-16 or with
+/// a byte order mark, as Excel does, read like any other. A byte order mark gives the
+/// encoding and is dropped; without one, input is decoded from `encoding`, and passed on
+/// as is when none is given.
+pub fn transcode(reader: impl BufRead + Send + 'static, encoding: Option<&'static Encoding>) -> Box<dyn BufRead + Send> {
+    let decoder = DecodeReaderBytesBuilder::new().encoding(encoding).bom_override(true).strip_bom(true).build(reader);
+    Box::new(BufReader::new(decoder))
+}
+
+/// The encoding named `label`, e.g. `utf-16le` or `windows-1252`, as the WHATWG Encoding
+/// Standard names them.
+pub fn encoding_for(label: &str) -> Option<&'static Encoding> {
+    Encoding::for_label(label.trim().as_bytes())
+}
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::io::Read;
+use crate::{Aircraft, Error, Registration, Result};
+
+/// Resolves the manufacturer and model the FAA registers an aircraft under to an ICAO type
+/// designator, by comparing them with the descriptions of the known [`Aircraft`].
+#[derive(Clone, Debug, Default)]
+pub struct TypeIndex {
+    designators: HashMap<String, String>,
+}
+
+impl TypeIndex {
+    pub fn new(aircraft: &[Aircraft]) -> Self {
+        let designators = aircraft
+            .iter()
+            .map(|aircraft| (normalize(&aircraft.description), aircraft.icao_code.clone()))
+            .collect();
+        TypeIndex { designators }
+    }
+
+    /// The designator whose description reads the same as `manufacturer model` once case and
+    /// punctuation are ignored, where only the first word of the manufacturer counts
+    /// (`BOEING` `737-8` matches "Boeing 737-8", `AIRBUS INDUSTRIE` `A320-214` matches "Airbus A320-214").
+    pub fn resolve(&self, manufacturer: &str, model: &str) -> Option<&str> {
+        let maker = manufacturer.split_whitespace().next().unwrap_or_default();
+        [format!("{} {}", maker, model), format!("{} {}", manufacturer, model)]
+            .iter()
+            .find_map(|name| self.designators.get(&normalize(name)))
+            .map(String::as_str)
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.chars().filter(char::is_ascii_alphanumeric).map(|character| character.to_ascii_uppercase()).collect()
+}
+
+/// Reads the FAA releasable aircraft registry: `master` (`MASTER.txt`) holds one row per
+/// registered aircraft, `acftref` (`ACFTREF.txt`) the manufacturer and model of each
+/// `MFR MDL CODE`. Both are comma separated with padded, upper case headers. `ACFTREF.txt` is
+/// read up front; `MASTER.txt` rows are parsed lazily as the iterator is advanced.
+pub fn read_faa_registry(
+    master: impl Read,
+    acftref: impl Read,
+    types: TypeIndex,
+) -> Result<impl Iterator<Item = Result<Registration>>> {
+    let mut reference = reader(acftref);
+    let columns = Columns::new(reference.headers()?);
+    let [code, manufacturer, model] = columns.positions(["CODE", "MFR", "MODEL"])?;
+    let mut models = HashMap::new();
+    for row in reference.records() {
+        let row = row?;
+        let field = |index: usize| row.get(index).unwrap_or_default().to_string();
+        models.insert(field(code), (field(manufacturer), field(model)));
+    }
+
+    let mut master = reader(master);
+    let columns = Columns::new(master.headers()?);
+    let [n_number, serial_number, model_code, year, owner, mode_s_hex] =
+        columns.positions(["N-NUMBER", "SERIAL NUMBER", "MFR MDL CODE", "YEAR MFR", "NAME", "MODE S CODE HEX"])?;
+
+    Ok(master.into_records().map(move |row| {
+        let row = row?;
+        let field = |index: usize| row.get(index).unwrap_or_default().to_string();
+        let (manufacturer, model) = models.get(&field(model_code)).cloned().unwrap_or_default();
+        Ok(Registration {
+            registration: format!("N{}", field(n_number)),
+            mode_s_hex: field(mode_s_hex).to_ascii_uppercase(),
+            serial_number: field(serial_number),
+            type_designator: types.resolve(&manufacturer, &model).unwrap_or_default().to_string(),
+            manufacturer,
+            model,
+            year_built: field(year).parse().ok(),
+            owner: field(owner),
+            country_code: String::new(),
+        })
+    }))
+}
+
+fn reader(input: impl Read) -> csv::Reader<impl Read> {
+    csv::ReaderBuilder::new().trim(csv::Trim::All).flexible(true).from_reader(input)
+}
+
+struct Columns(Vec<String>);
+
+impl Columns {
+    // Some releases start with a byte order mark.
+    fn new(headers: &csv::StringRecord) -> Self {
+        Columns(headers.iter().map(|header| header.trim_start_matches('\u{feff}').trim().to_string()).collect())
+    }
+
+    fn positions<const N: usize>(&self, names: [&str; N]) -> Result<[usize; N]> {
+        let mut positions = [0; N];
+        for (position, name) in positions.iter_mut().zip(names) {
+            *position = self
+                .0
+                .iter()
+                .position(|header| header == name)
+                .ok_or_else(|| Error::InvalidInput(format!("faa registry header has no column named {}", name)))?;
+        }
+        Ok(positions)
+    }
+}
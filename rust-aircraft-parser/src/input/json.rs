@@ -0,0 +1,139 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::marker::PhantomData;
+use std::path::Path;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use crate::{Aircraft, Error, Result};
+
+/// Reads a JSON array of [`Aircraft`] from `path`.
+pub fn load_aircraft_file(path: impl AsRef<Path>) -> Result<Vec<Aircraft>> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|source| Error::Io { path: path.to_path_buf(), source })?;
+    read_aircraft_json(BufReader::new(file)).collect()
+}
+
+/// Lazily parses a JSON array of [`Aircraft`], deserializing one element at a time
+/// so neither the raw text nor the whole `Vec` has to be held in memory.
+///
+/// An element that is valid JSON but not a valid aircraft yields an error and iteration
+/// carries on with the next element; the iterator only ends early on a syntax error, after
+/// which the position of the next element is unknown.
+pub fn read_aircraft_json<R: BufRead>(reader: R) -> JsonArrayReader<R> {
+    read_json_array(reader)
+}
+
+/// [`read_aircraft_json`] for any deserializable record type.
+pub fn read_json_array<T: DeserializeOwned, R: BufRead>(reader: R) -> JsonArrayReader<R, T> {
+    JsonArrayReader { reader, started: false, finished: false, index: 0, record: PhantomData }
+}
+
+/// Iterator returned by [`read_aircraft_json`] and [`read_json_array`].
+pub struct JsonArrayReader<R, T = Aircraft> {
+    reader: R,
+    started: bool,
+    finished: bool,
+    index: usize,
+    record: PhantomData<fn() -> T>,
+}
+
+impl<R: BufRead, T> JsonArrayReader<R, T> {
+    // Skips whitespace and returns the next byte without consuming it.
+    fn peek(&mut self) -> Result<Option<u8>> {
+        loop {
+            let buffer = self.reader.fill_buf().map_err(serde_json::Error::io)?;
+            let Some(&byte) = buffer.first() else {
+                return Ok(None);
+            };
+            if !byte.is_ascii_whitespace() {
+                return Ok(Some(byte));
+            }
+            self.reader.consume(1);
+        }
+    }
+
+    fn expect_byte(&mut self, expected: &[u8]) -> Result<u8> {
+        match self.peek()? {
+            Some(byte) if expected.contains(&byte) => {
+                self.reader.consume(1);
+                Ok(byte)
+            }
+            Some(byte) => Err(Error::InvalidInput(format!("unexpected character '{}' in json array", byte as char))),
+            None => Err(Error::InvalidInput("unexpected end of json array".to_string())),
+        }
+    }
+
+    // Reads the next element as a generic JSON value. Errors here are fatal to the iteration.
+    fn next_value(&mut self) -> Result<Option<serde_json::Value>> {
+        if !self.started {
+            self.expect_byte(b"[")?;
+            self.started = true;
+            if self.peek()? == Some(b']') {
+                self.expect_byte(b"]")?;
+                return Ok(None);
+            }
+        } else if self.expect_byte(b",]")? == b']' {
+            return Ok(None);
+        }
+
+        // A number only ends at the byte after it, which the deserializer would take from
+        // the reader along with the number, so numbers are read up to the separator here.
+        if matches!(self.peek()?, Some(b'-' | b'0'..=b'9')) {
+            let mut number = Vec::new();
+            loop {
+                let buffer = self.reader.fill_buf().map_err(serde_json::Error::io)?;
+                match buffer.first() {
+                    Some(&byte) if byte.is_ascii_digit() || b"+-.eE".contains(&byte) => number.push(byte),
+                    _ => break,
+                }
+                self.reader.consume(1);
+            }
+            return Ok(Some(serde_json::from_slice(&number)?));
+        }
+
+        // Any other element ends with its closing brace, bracket, quote or letter, so the
+        // deserializer stops right after it and leaves the separator for the next call.
+        let mut deserializer = serde_json::Deserializer::from_reader(&mut self.reader);
+        Ok(Some(serde_json::Value::deserialize(&mut deserializer)?))
+    }
+}
+
+impl<R: BufRead, T: DeserializeOwned> Iterator for JsonArrayReader<R, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        if self.finished {
+            return None;
+        }
+        let value = match self.next_value() {
+            Ok(Some(value)) => value,
+            Ok(None) => {
+                self.finished = true;
+                return None;
+            }
+            Err(error) => {
+                self.finished = true;
+                return Some(Err(error));
+            }
+        };
+        self.index += 1;
+        Some(T::deserialize(value).map_err(|error| {
+            Error::InvalidInput(format!("element {}: {}", self.index, error))
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn carries_on_after_an_element_that_is_not_an_aircraft() {
+        let json = r#"[1, {"icaoCode": "A320", "iataCode": "320", "description": "Airbus A320"}, {"icaoCode": "B738", "description": "Boeing 737-800"}]"#;
+        let entries: Vec<Result<Aircraft>> = read_aircraft_json(json.as_bytes()).collect();
+        assert_eq!(entries.len(), 3);
+        assert!(matches!(&entries[0], Err(Error::InvalidInput(message)) if message.starts_with("element 1")));
+        assert_eq!(entries[1].as_ref().unwrap().icao_code, "A320");
+        assert_eq!(entries[2].as_ref().unwrap().icao_code, "B738");
+    }
+}
@@ -0,0 +1,35 @@
+use std::io::Read;
+use serde::de::DeserializeOwned;
+use crate::{Aircraft, Error, Result};
+
+/// Parses a JSON5 array of [`Aircraft`]: JSON that may also carry comments, trailing
+/// commas, unquoted keys and single-quoted strings, as hand-curated source files do.
+/// Unlike [`read_aircraft_json`](super::read_aircraft_json) the whole input is read up
+/// front, so a syntax error anywhere fails before the first element is returned.
+pub fn read_aircraft_json5(reader: impl Read) -> Result<impl Iterator<Item = Result<Aircraft>>> {
+    read_json5_array(reader)
+}
+
+/// [`read_aircraft_json5`] for any deserializable record type.
+pub fn read_json5_array<T: DeserializeOwned>(mut reader: impl Read) -> Result<impl Iterator<Item = Result<T>>> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text).map_err(serde_json::Error::io)?;
+    let values: Vec<serde_json::Value> =
+        ::json5::from_str(&text).map_err(|error| Error::InvalidInput(format!("cannot parse json5: {}", error)))?;
+    Ok(values.into_iter().enumerate().map(|(index, value)| {
+        T::deserialize(value).map_err(|error| Error::InvalidInput(format!("element {}: {}", index + 1, error)))
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::aircraft;
+
+    #[test]
+    fn reads_comments_trailing_commas_unquoted_keys_and_single_quotes() {
+        let json5 = "[\n  // the most common\n  { icaoCode: 'B738', iataCode: '738', description: 'Boeing 737-800', },\n]";
+        let aircrafts: Vec<Aircraft> = read_aircraft_json5(json5.as_bytes()).unwrap().collect::<Result<_>>().unwrap();
+        assert_eq!(aircrafts, vec![aircraft("B738").iata("738").description("Boeing 737-800").build()]);
+    }
+}
@@ -0,0 +1,19 @@
+use std::collections::BTreeMap;
+use std::io::Read;
+use crate::{HexEntry, Result};
+
+/// Reads a Mictronics `aircrafts.json`: one JSON object mapping each hex address to
+/// `[registration, type, flags]`, where flags starting with `1` mark military aircraft.
+/// The object is read up front, the entries come out in address order.
+pub fn read_mictronics(reader: impl Read) -> Result<impl Iterator<Item = Result<HexEntry>>> {
+    let entries: BTreeMap<String, Vec<Option<String>>> = serde_json::from_reader(reader)?;
+    Ok(entries.into_iter().map(|(hex, fields)| {
+        let field = |index: usize| fields.get(index).cloned().flatten().unwrap_or_default();
+        Ok(HexEntry {
+            hex: hex.trim().to_ascii_uppercase(),
+            registration: field(0),
+            type_code: field(1),
+            military: field(2).starts_with('1'),
+        })
+    }))
+}
@@ -0,0 +1,237 @@
+//! Readers turning source files into [`Aircraft`](crate::Aircraft) (or other [`Record`]) records.
+//! Files of every format can also be gzip or zip compressed, and `-` reads standard input.
+//! Text in UTF-16 or with a byte order mark is transcoded to UTF-8 before it is parsed.
+
+mod compression;
+mod csv;
+mod encoding;
+mod faa;
+mod json;
+mod json5;
+mod mictronics;
+mod ndjson;
+mod openflights;
+mod ourairports;
+mod xml;
+mod yaml;
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use clap::ValueEnum;
+use serde::de::DeserializeOwned;
+use crate::normalize::normalize;
+use crate::record::Record;
+use crate::{Aircraft, Airport, Error, HexEntry, Registration, Result};
+
+pub use self::compression::decompress;
+pub use self::csv::{read_aircraft_csv, read_csv, CsvColumns};
+pub use self::encoding::{encoding_for, transcode};
+pub use self::faa::{read_faa_registry, TypeIndex};
+pub use self::json::{load_aircraft_file, read_aircraft_json, read_json_array, JsonArrayReader};
+pub use self::json5::{read_aircraft_json5, read_json5_array};
+pub use self::mictronics::read_mictronics;
+pub use self::ndjson::{read_aircraft_ndjson, read_ndjson};
+pub use self::openflights::{read_openflights, OpenFlightsRow};
+pub use self::ourairports::{read_ourairports, OurAirportsFilter};
+pub use self::xml::{read_aircraft_xml, XmlMapping, XmlReader};
+pub use self::yaml::{read_aircraft_yaml, read_yaml_sequence};
+
+/// Layout of an input file.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    /// A single JSON array of objects
+    #[default]
+    Json,
+    /// A JSON5 array: JSON with comments, trailing commas, unquoted keys and single quotes
+    Json5,
+    /// Header-based CSV; aircraft columns can be renamed with the --csv-*-column options
+    Csv,
+    /// Newline-delimited JSON, one object per line
+    Ndjson,
+    /// A YAML sequence of mappings, which may carry comments
+    Yaml,
+    /// XML with one element per aircraft; elements and attributes can be mapped with the --xml-* options; aircraft only
+    Xml,
+    /// The OpenFlights .dat files (airports, airlines, routes, planes): headerless CSV with \N for null
+    Openflights,
+    /// The OurAirports airports.csv schema; airports only
+    Ourairports,
+    /// The FAA aircraft registry MASTER.txt, with ACFTREF.txt next to it; registrations only
+    Faa,
+    /// The Mictronics aircrafts.json object of hex address to registration and type; hexdb only
+    Mictronics,
+}
+
+impl Format {
+    /// The file extension conventionally used for the format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Json5 => "json5",
+            Format::Csv => "csv",
+            Format::Ndjson => "ndjson",
+            Format::Yaml => "yaml",
+            Format::Xml => "xml",
+            Format::Openflights => "dat",
+            Format::Ourairports => "csv",
+            Format::Faa => "txt",
+            Format::Mictronics => "json",
+        }
+    }
+}
+
+/// Settings for [`read_aircraft`] beyond the file format.
+#[derive(Clone, Debug, Default)]
+pub struct InputOptions {
+    pub format: Format,
+    pub csv_columns: CsvColumns,
+    pub xml_mapping: XmlMapping,
+    /// Clean up every aircraft read with [`normalize`](crate::normalize::normalize).
+    pub normalize: bool,
+    /// Encoding of input without a byte order mark; UTF-8 when none is given.
+    pub encoding: Option<&'static encoding_rs::Encoding>,
+}
+
+/// A lazily parsed sequence of aircraft, each of which may fail to parse.
+pub type AircraftStream = Box<dyn Iterator<Item = Result<Aircraft>> + Send>;
+
+/// Reads every aircraft in `path` according to `options`, stopping at the first error.
+pub fn read_aircraft(path: impl AsRef<Path>, options: &InputOptions) -> Result<Vec<Aircraft>> {
+    stream_aircraft(path, options)?.collect()
+}
+
+/// Iterates over the aircraft in `path` according to `options`. Input is parsed as the
+/// iterator is advanced, so callers can process files larger than memory.
+pub fn stream_aircraft(path: impl AsRef<Path>, options: &InputOptions) -> Result<AircraftStream> {
+    stream_aircraft_from(decompressed(path.as_ref())?, options)
+}
+
+/// [`stream_aircraft`] for input that is not a local file.
+pub fn stream_aircraft_from(reader: impl BufRead + Send + 'static, options: &InputOptions) -> Result<AircraftStream> {
+    let reader = transcode(reader, options.encoding);
+    let aircrafts: AircraftStream = match options.format {
+        Format::Json => Box::new(read_aircraft_json(reader)),
+        Format::Json5 => Box::new(read_aircraft_json5(reader)?),
+        Format::Csv => Box::new(read_aircraft_csv(reader, &options.csv_columns)?),
+        Format::Ndjson => Box::new(read_aircraft_ndjson(reader)),
+        Format::Yaml => Box::new(read_aircraft_yaml(reader)?),
+        Format::Xml => Box::new(read_aircraft_xml(reader, &options.xml_mapping)),
+        Format::Openflights => Box::new(read_openflights::<Aircraft>(reader)?),
+        Format::Ourairports => return Err(only_holds("ourairports", "airports")),
+        Format::Faa => return Err(only_holds("faa", "registrations")),
+        Format::Mictronics => return Err(only_holds("mictronics", "hexdb")),
+    };
+    if !options.normalize {
+        return Ok(aircrafts);
+    }
+    Ok(Box::new(aircrafts.map(|aircraft| aircraft.map(normalize))))
+}
+
+/// A lazily parsed sequence of records of any kind.
+pub type RecordStream<T> = Box<dyn Iterator<Item = Result<T>> + Send>;
+
+/// [`stream_aircraft`] for other record types. CSV headers must match the record's field names.
+pub fn stream_records<T: Record>(path: impl AsRef<Path>, format: Format) -> Result<RecordStream<T>> {
+    if format == Format::Openflights {
+        return Ok(Box::new(read_openflights::<T>(open(path.as_ref())?)?));
+    }
+    stream_deserialized(path, format)
+}
+
+/// Iterates over any deserializable type in a JSON, JSON5, CSV, NDJSON or YAML file, matching CSV headers
+/// to its field names.
+pub fn stream_deserialized<T: DeserializeOwned + Send + 'static>(path: impl AsRef<Path>, format: Format) -> Result<RecordStream<T>> {
+    let reader = open(path.as_ref())?;
+    Ok(match format {
+        Format::Json => Box::new(read_json_array(reader)),
+        Format::Json5 => Box::new(read_json5_array(reader)?),
+        Format::Csv => Box::new(read_csv(reader)),
+        Format::Ndjson => Box::new(read_ndjson(reader)),
+        Format::Yaml => Box::new(read_yaml_sequence(reader)?),
+        Format::Xml => return Err(only_holds("xml", "aircraft")),
+        Format::Openflights => return Err(only_holds("openflights", "airports, airlines, routes and planes")),
+        Format::Ourairports => return Err(only_holds("ourairports", "airports")),
+        Format::Faa => return Err(only_holds("faa", "registrations")),
+        Format::Mictronics => return Err(only_holds("mictronics", "hexdb")),
+    })
+}
+
+/// [`stream_records`] for airports, which can also be read from OurAirports files, keeping
+/// the rows `filter` selects.
+pub fn stream_airports(path: impl AsRef<Path>, format: Format, filter: OurAirportsFilter) -> Result<RecordStream<Airport>> {
+    match format {
+        Format::Ourairports => Ok(Box::new(read_ourairports(open(path.as_ref())?, filter))),
+        format => stream_records(path, format),
+    }
+}
+
+/// [`stream_records`] for registrations, which can also be read from the FAA registry at
+/// `path` with its `acftref` file, resolving type designators with `types`.
+pub fn stream_registrations(
+    path: impl AsRef<Path>,
+    format: Format,
+    acftref: &Path,
+    types: TypeIndex,
+) -> Result<RecordStream<Registration>> {
+    match format {
+        Format::Faa => Ok(Box::new(read_faa_registry(open(path.as_ref())?, open(acftref)?, types)?)),
+        format => stream_records(path, format),
+    }
+}
+
+/// [`stream_records`] for hex addresses, which can also be read from a Mictronics database.
+pub fn stream_hexdb(path: impl AsRef<Path>, format: Format) -> Result<RecordStream<HexEntry>> {
+    match format {
+        Format::Mictronics => Ok(Box::new(read_mictronics(open(path.as_ref())?)?)),
+        format => stream_records(path, format),
+    }
+}
+
+fn only_holds(format: &str, dataset: &str) -> Error {
+    Error::InvalidInput(format!("{} files only hold {}", format, dataset))
+}
+
+/// Input path standing for standard input.
+pub const STDIN: &str = "-";
+
+/// Whether `path` is [`STDIN`].
+pub fn is_stdin(path: &Path) -> bool {
+    path.as_os_str() == STDIN
+}
+
+static BYTES_READ: AtomicU64 = AtomicU64::new(0);
+
+/// Bytes the readers of this module have read off local files and standard input so far,
+/// before decompression: compared with the size of the file being read, how far into it a
+/// load is.
+pub fn bytes_read() -> u64 {
+    BYTES_READ.load(Ordering::Relaxed)
+}
+
+// Adds what it reads to BYTES_READ.
+struct Counted<R>(R);
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let read = self.0.read(buffer)?;
+        BYTES_READ.fetch_add(read as u64, Ordering::Relaxed);
+        Ok(read)
+    }
+}
+
+// Input decompressed and transcoded to UTF-8 as it is read.
+fn open(path: &Path) -> Result<Box<dyn BufRead + Send>> {
+    Ok(transcode(decompressed(path)?, None))
+}
+
+// Gzip and zip input is decompressed as it is read.
+fn decompressed(path: &Path) -> Result<Box<dyn BufRead + Send>> {
+    let io_error = |source| Error::Io { path: path.to_path_buf(), source };
+    if is_stdin(path) {
+        return decompress(BufReader::new(Counted(io::stdin()))).map_err(io_error);
+    }
+    let file = File::open(path).map_err(io_error)?;
+    decompress(BufReader::new(Counted(file))).map_err(io_error)
+}
@@ -0,0 +1,38 @@
+use std::io::BufRead;
+use serde::de::DeserializeOwned;
+use crate::{Aircraft, Error, Result};
+
+/// Lazily parses newline-delimited JSON, one [`Aircraft`] object per line.
+/// Blank lines are skipped so files with a trailing newline or spacing between records load cleanly.
+/// A malformed line yields an error mentioning its line number and does not stop the iteration.
+pub fn read_aircraft_ndjson(reader: impl BufRead) -> impl Iterator<Item = Result<Aircraft>> {
+    read_ndjson(reader)
+}
+
+/// [`read_aircraft_ndjson`] for any deserializable record type.
+pub fn read_ndjson<T: DeserializeOwned>(reader: impl BufRead) -> impl Iterator<Item = Result<T>> {
+    reader
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|(index, line)| {
+            let line = line.map_err(serde_json::Error::io)?;
+            serde_json::from_str::<T>(&line)
+                .map_err(|error| Error::InvalidInput(format!("line {}: {}", index + 1, error)))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_blank_lines_and_carries_on_after_a_malformed_one() {
+        let ndjson = "{\"icaoCode\":\"A320\",\"description\":\"Airbus A320\"}\n\n{\"icaoCode\":\n  \n{\"icaoCode\":\"B738\",\"description\":\"Boeing 737-800\"}\n";
+        let entries: Vec<Result<Aircraft>> = read_aircraft_ndjson(ndjson.as_bytes()).collect();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].as_ref().unwrap().icao_code, "A320");
+        assert!(matches!(&entries[1], Err(Error::InvalidInput(message)) if message.starts_with("line 3:")));
+        assert_eq!(entries[2].as_ref().unwrap().icao_code, "B738");
+    }
+}
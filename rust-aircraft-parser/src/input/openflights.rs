@@ -0,0 +1,90 @@
+use std::io::Read;
+use std::str::FromStr;
+use crate::record::Record;
+use crate::{Error, Result};
+
+/// One row of an OpenFlights `.dat` file (`airports.dat`, `airlines.dat`, `routes.dat`,
+/// `planes.dat`), whose columns are identified by position.
+#[derive(Clone, Debug)]
+pub struct OpenFlightsRow(csv::StringRecord);
+
+impl OpenFlightsRow {
+    /// The column at `index`, or `None` when it is missing, empty or the `\N` null marker.
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.0.get(index).map(str::trim).filter(|field| !field.is_empty() && *field != r"\N")
+    }
+
+    /// The column at `index`, empty when null.
+    pub fn text(&self, index: usize) -> String {
+        self.get(index).unwrap_or_default().to_string()
+    }
+
+    /// The column at `index` parsed as a `F`, `None` when null. `name` describes the column
+    /// in the error reported for an unparseable value.
+    pub fn parse<F: FromStr>(&self, index: usize, name: &str) -> Result<Option<F>> {
+        self.get(index)
+            .map(|field| {
+                field.parse().map_err(|_| {
+                    let line = self.0.position().map_or(0, |position| position.line());
+                    Error::InvalidInput(format!("line {}: {} {:?} is not a number", line, name, field))
+                })
+            })
+            .transpose()
+    }
+
+    /// Like [`parse`](Self::parse) for a column that must not be null.
+    pub fn require<F: FromStr>(&self, index: usize, name: &str) -> Result<F> {
+        self.parse(index, name)?.ok_or_else(|| {
+            let line = self.0.position().map_or(0, |position| position.line());
+            Error::InvalidInput(format!("line {}: {} is missing", line, name))
+        })
+    }
+}
+
+/// Reads the headerless rows of an OpenFlights `.dat` file into `T`, using the record type's
+/// [`openflights`](Record::openflights) layout. Quotes inside quoted fields are escaped with a
+/// backslash, and rows may have a varying number of columns. Rows are parsed lazily; a record
+/// type without an OpenFlights layout is reported up front.
+pub fn read_openflights<T: Record>(reader: impl Read) -> Result<impl Iterator<Item = Result<T>>> {
+    let parse = T::openflights()
+        .ok_or_else(|| Error::InvalidInput(format!("{} cannot be read from openflights files", T::COLLECTION)))?;
+    let rows = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .escape(Some(b'\\'))
+        .from_reader(reader)
+        .into_records();
+    Ok(rows.map(move |row| parse(&OpenFlightsRow(row?))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::aircraft;
+    use crate::{Aircraft, Country};
+
+    #[test]
+    fn reads_planes_with_null_markers_and_escaped_quotes() {
+        let dat = "\"Boeing 737-800\",\"738\",\"B738\"\n\"Airbus \\\"Neo\\\" A320\",\\N,\"A20N\"\n";
+        let aircrafts: Vec<Aircraft> = read_openflights(dat.as_bytes()).unwrap().collect::<Result<_>>().unwrap();
+        assert_eq!(
+            aircrafts,
+            vec![aircraft("B738").iata("738").description("Boeing 737-800").build(), aircraft("A20N").description("Airbus \"Neo\" A320").build()]
+        );
+    }
+
+    #[test]
+    fn reports_an_unparseable_number_with_its_line() {
+        let row = OpenFlightsRow(csv::StringRecord::from(vec!["1", "north"]));
+        assert!(row.parse::<f64>(1, "latitude").is_err());
+        assert_eq!(row.parse::<u32>(0, "id").unwrap(), Some(1));
+        assert_eq!(row.parse::<u32>(2, "altitude").unwrap(), None);
+        assert!(matches!(row.require::<u32>(2, "altitude"), Err(Error::InvalidInput(message)) if message.ends_with("altitude is missing")));
+    }
+
+    #[test]
+    fn rejects_a_record_type_without_a_layout() {
+        let error = read_openflights::<Country>("".as_bytes()).err();
+        assert!(matches!(error, Some(Error::InvalidInput(message)) if message.ends_with("cannot be read from openflights files")));
+    }
+}
@@ -0,0 +1,69 @@
+use std::io::Read;
+use serde::Deserialize;
+use crate::{Airport, Result};
+
+/// Which rows of an OurAirports `airports.csv` to keep. An empty list keeps everything.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OurAirportsFilter {
+    /// Values of the `type` column, e.g. `large_airport`.
+    pub types: Vec<String>,
+    /// ISO 3166-1 alpha-2 codes matched against the `iso_country` column, e.g. `US`.
+    pub countries: Vec<String>,
+}
+
+impl OurAirportsFilter {
+    fn keeps(&self, row: &Row) -> bool {
+        (self.types.is_empty() || self.types.contains(&row.kind))
+            && (self.countries.is_empty() || self.countries.iter().any(|country| country.eq_ignore_ascii_case(&row.iso_country)))
+    }
+}
+
+// The columns of airports.csv that map onto Airport; the others are ignored.
+#[derive(Deserialize)]
+struct Row {
+    ident: String,
+    #[serde(rename = "type")]
+    kind: String,
+    name: String,
+    latitude_deg: f64,
+    longitude_deg: f64,
+    elevation_ft: Option<i32>,
+    iso_country: String,
+    #[serde(default)]
+    municipality: String,
+    #[serde(default)]
+    icao_code: String,
+    #[serde(default)]
+    gps_code: String,
+    #[serde(default)]
+    iata_code: String,
+}
+
+impl From<Row> for Airport {
+    // Older exports have no icao_code column; gps_code, then the OurAirports ident, stand in.
+    fn from(row: Row) -> Self {
+        let icao_code = [row.icao_code, row.gps_code].into_iter().find(|code| !code.is_empty()).unwrap_or(row.ident);
+        Airport {
+            icao_code,
+            iata_code: row.iata_code,
+            name: row.name,
+            city: row.municipality,
+            country: row.iso_country,
+            latitude: row.latitude_deg,
+            longitude: row.longitude_deg,
+            elevation: row.elevation_ft,
+            timezone: None,
+        }
+    }
+}
+
+/// Reads the OurAirports `airports.csv` schema into [`Airport`]s, dropping the rows `filter`
+/// does not keep. Rows are parsed lazily as the iterator is advanced.
+pub fn read_ourairports(reader: impl Read, filter: OurAirportsFilter) -> impl Iterator<Item = Result<Airport>> {
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader)
+        .into_deserialize::<Row>()
+        .filter(move |row| !matches!(row, Ok(row) if !filter.keeps(row)))
+        .map(|row| Ok(Airport::from(row?)))
+}
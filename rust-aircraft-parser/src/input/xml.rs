@@ -0,0 +1,193 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use std::io::BufRead;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use crate::{Aircraft, Error, Result};
+
+/// Names of the XML element holding each [`Aircraft`] and of the child elements, or its
+/// attributes when written `@name`, holding each field.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct XmlMapping {
+    pub record: String,
+    pub icao_code: String,
+    pub iata_code: String,
+    pub description: String,
+}
+
+impl Default for XmlMapping {
+    fn default() -> Self {
+        XmlMapping {
+            record: "aircraft".to_string(),
+            icao_code: "icaoCode".to_string(),
+            iata_code: "iataCode".to_string(),
+            description: "description".to_string(),
+        }
+    }
+}
+
+/// Lazily reads the elements named by `mapping` anywhere in an XML document into [`Aircraft`],
+/// ignoring everything around them. Namespace prefixes are ignored. A record without an ICAO
+/// code or description yields an error and iteration carries on; the iterator only ends early
+/// on malformed XML.
+pub fn read_aircraft_xml<R: BufRead>(reader: R, mapping: &XmlMapping) -> XmlReader<R> {
+    let mut reader = Reader::from_reader(reader);
+    reader.config_mut().trim_text(true);
+    XmlReader { reader, mapping: mapping.clone(), buffer: Vec::new(), finished: false, index: 0 }
+}
+
+/// Iterator returned by [`read_aircraft_xml`].
+pub struct XmlReader<R> {
+    reader: Reader<R>,
+    mapping: XmlMapping,
+    buffer: Vec<u8>,
+    finished: bool,
+    index: usize,
+}
+
+impl<R: BufRead> XmlReader<R> {
+    // The attributes, as `@name`, and child element texts of the next record element.
+    fn next_record(&mut self) -> Result<Option<HashMap<String, String>>> {
+        let mut fields: Option<HashMap<String, String>> = None;
+        // The child element of the record being read and its text so far.
+        let mut child: Option<(String, String)> = None;
+        // Elements open inside the record.
+        let mut depth = 0;
+        loop {
+            self.buffer.clear();
+            match self.reader.read_event_into(&mut self.buffer).map_err(xml_error)? {
+                Event::Start(element) if fields.is_some() => {
+                    depth += 1;
+                    if depth == 1 {
+                        child = Some((local_name(&element), String::new()));
+                    }
+                }
+                Event::Start(element) if local_name(&element) == self.mapping.record => {
+                    fields = Some(attributes(&element)?);
+                }
+                Event::Empty(element) if fields.is_none() && local_name(&element) == self.mapping.record => {
+                    return Ok(Some(attributes(&element)?));
+                }
+                Event::Empty(element) if depth == 0 => {
+                    if let Some(fields) = &mut fields {
+                        fields.insert(local_name(&element), String::new());
+                    }
+                }
+                Event::Text(text) if depth == 1 => {
+                    if let Some((_, value)) = &mut child {
+                        value.push_str(&text.unescape().map_err(xml_error)?);
+                    }
+                }
+                Event::CData(data) if depth == 1 => {
+                    if let Some((_, value)) = &mut child {
+                        value.push_str(&String::from_utf8_lossy(&data));
+                    }
+                }
+                Event::End(_) if fields.is_some() => {
+                    if depth == 0 {
+                        return Ok(fields);
+                    }
+                    if let (1, Some((name, value)), Some(fields)) = (depth, child.take(), &mut fields) {
+                        fields.insert(name, value.trim().to_string());
+                    }
+                    depth -= 1;
+                }
+                Event::Eof if fields.is_some() => return Err(Error::InvalidInput("unexpected end of xml".to_string())),
+                Event::Eof => return Ok(None),
+                _ => {}
+            }
+        }
+    }
+
+    fn aircraft(&self, fields: &HashMap<String, String>) -> Result<Aircraft> {
+        let required = |name: &str| {
+            fields
+                .get(name)
+                .cloned()
+                .ok_or_else(|| Error::InvalidInput(format!("element {}: no {} in <{}>", self.index, name, self.mapping.record)))
+        };
+        Ok(Aircraft {
+            icao_code: required(&self.mapping.icao_code)?,
+            iata_code: fields.get(&self.mapping.iata_code).filter(|code| !code.is_empty()).cloned(),
+            description: required(&self.mapping.description)?,
+            descriptions: BTreeMap::new(),
+            aliases: Vec::new(),
+        })
+    }
+}
+
+impl<R: BufRead> Iterator for XmlReader<R> {
+    type Item = Result<Aircraft>;
+
+    fn next(&mut self) -> Option<Result<Aircraft>> {
+        if self.finished {
+            return None;
+        }
+        match self.next_record() {
+            Ok(Some(fields)) => {
+                self.index += 1;
+                Some(self.aircraft(&fields))
+            }
+            Ok(None) => {
+                self.finished = true;
+                None
+            }
+            Err(error) => {
+                self.finished = true;
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+fn local_name(element: &BytesStart) -> String {
+    String::from_utf8_lossy(element.local_name().as_ref()).into_owned()
+}
+
+fn attributes(element: &BytesStart) -> Result<HashMap<String, String>> {
+    element
+        .attributes()
+        .map(|attribute| {
+            let attribute = attribute.map_err(xml_error)?;
+            let value = attribute.unescape_value().map_err(xml_error)?;
+            Ok((format!("@{}", String::from_utf8_lossy(attribute.key.local_name().as_ref())), value.trim().to_string()))
+        })
+        .collect()
+}
+
+fn xml_error(error: impl Display) -> Error {
+    Error::InvalidInput(format!("cannot parse xml: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::aircraft;
+
+    #[test]
+    fn reads_attributes_and_child_elements_anywhere_in_the_document() {
+        let xml = r#"<fleet xmlns:f="urn:fleet"><types>
+            <f:type code="B738"><iata>738</iata><name><![CDATA[Boeing 737-800]]></name><note><b>ignored</b></note></f:type>
+            <f:type code="A320" name="Airbus A320"/>
+        </types></fleet>"#;
+        let mapping = XmlMapping {
+            record: "type".to_string(),
+            icao_code: "@code".to_string(),
+            iata_code: "iata".to_string(),
+            description: "name".to_string(),
+        };
+        let entries: Vec<Result<Aircraft>> = read_aircraft_xml(xml.as_bytes(), &mapping).collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].as_ref().unwrap(), &aircraft("B738").iata("738").description("Boeing 737-800").build());
+        assert!(matches!(&entries[1], Err(Error::InvalidInput(message)) if message == "element 2: no name in <type>"));
+    }
+
+    #[test]
+    fn stops_at_malformed_xml() {
+        let xml = "<aircraft><icaoCode>B738</icaoCode><description>Boeing 737-800</description></aircraft><aircraft><icaoCode>A320";
+        let entries: Vec<Result<Aircraft>> = read_aircraft_xml(xml.as_bytes(), &XmlMapping::default()).collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].as_ref().unwrap().icao_code, "B738");
+        assert!(entries[1].is_err());
+    }
+}
@@ -0,0 +1,40 @@
+use std::io::Read;
+use serde::de::DeserializeOwned;
+use crate::{Aircraft, Error, Result};
+
+/// Parses a YAML sequence of [`Aircraft`], such as curators keep with comments next to the
+/// entries. The whole input is read up front, so a syntax error anywhere fails before the
+/// first element is returned; an element that is not a valid aircraft yields an error and
+/// iteration carries on with the next one.
+pub fn read_aircraft_yaml(reader: impl Read) -> Result<impl Iterator<Item = Result<Aircraft>>> {
+    read_yaml_sequence(reader)
+}
+
+/// [`read_aircraft_yaml`] for any deserializable record type.
+pub fn read_yaml_sequence<T: DeserializeOwned>(reader: impl Read) -> Result<impl Iterator<Item = Result<T>>> {
+    let values: Vec<serde_yaml::Value> =
+        serde_yaml::from_reader(reader).map_err(|error| Error::InvalidInput(format!("cannot parse yaml: {}", error)))?;
+    Ok(values.into_iter().enumerate().map(|(index, value)| {
+        serde_yaml::from_value(value).map_err(|error| Error::InvalidInput(format!("element {}: {}", index + 1, error)))
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_a_commented_sequence_and_carries_on_after_an_invalid_element() {
+        let yaml = "# curated\n- icaoCode: B738 # the most common\n  iataCode: '738'\n  description: Boeing 737-800\n- icaoCode: A320\n- icaoCode: E175\n  description: Embraer 175\n";
+        let entries: Vec<Result<Aircraft>> = read_aircraft_yaml(yaml.as_bytes()).unwrap().collect();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].as_ref().unwrap().iata_code.as_deref(), Some("738"));
+        assert!(matches!(&entries[1], Err(Error::InvalidInput(message)) if message.starts_with("element 2:")));
+        assert_eq!(entries[2].as_ref().unwrap().icao_code, "E175");
+    }
+
+    #[test]
+    fn fails_up_front_on_a_syntax_error() {
+        assert!(read_aircraft_yaml("- icaoCode: B738\n  description: [unclosed\n".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,92 @@
+//! Parsing and MongoDB loading of aircraft reference data.
+//!
+//! [`load_aircraft_file`] reads the source JSON (and [`input::read_aircraft`] the other
+//! supported formats) into [`Aircraft`] records and [`AircraftStore`] writes them to
+//! (and reads them back from) a collection; [`query`] reads them back as typed
+//! [`Aircraft`]s from nothing but a client. Other backends can be plugged in by
+//! implementing [`Storage`]. [`load::load`] ties the two together, writing the records
+//! in batches. Other reference data, such as [`Airport`]s, [`Airline`]s, [`Route`]s,
+//! [`Country`]s, [`Registration`]s and [`HexEntry`]s, implements [`Record`] and is loaded
+//! into MongoDB through a [`storage::RecordStore`], after [`references::KnownCodes`] has
+//! flagged records referring to codes that are not loaded.
+
+mod aircraft;
+mod airline;
+pub mod airport;
+pub mod bench;
+pub mod cache;
+pub mod checkpoint;
+pub mod classify;
+mod country;
+#[cfg(feature = "server")]
+pub mod daemon;
+pub mod dedup;
+#[cfg(feature = "embedded")]
+pub mod embedded;
+pub mod diff;
+mod error;
+pub mod enrich;
+pub mod equipment;
+pub mod export;
+pub mod feed;
+pub mod fields;
+pub mod filter;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+mod hex;
+pub mod hooks;
+pub mod ids;
+pub mod input;
+pub mod load;
+pub mod merge;
+pub mod metrics;
+pub mod migrations;
+pub mod nationality;
+pub mod normalize;
+pub mod overrides;
+pub mod provenance;
+pub mod quality;
+pub mod query;
+pub mod record;
+pub mod references;
+mod registration;
+pub mod remote;
+pub mod report;
+pub mod resolve;
+pub mod retry;
+mod route;
+pub mod schedule;
+pub mod schema;
+pub mod search;
+pub mod secrets;
+pub mod selftest;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod shutdown;
+#[cfg(feature = "s3")]
+pub mod s3;
+pub mod stats;
+pub mod storage;
+pub mod sync;
+pub mod tail;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod timezones;
+pub mod validate;
+pub mod watch;
+pub mod webhook;
+pub mod wikidata;
+
+pub use aircraft::Aircraft;
+pub use airline::Airline;
+pub use airport::Airport;
+pub use country::Country;
+pub use error::{Error, Result};
+pub use hex::HexEntry;
+pub use input::load_aircraft_file;
+pub use record::Record;
+pub use registration::Registration;
+pub use route::Route;
+pub use storage::{AircraftStore, Storage};
@@ -0,0 +1,820 @@
+//! Writing a stream of parsed records into a [`Storage`] backend (or any other [`Sink`]) in batches.
+
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::panic;
+use std::path::Path;
+use std::pin::pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use futures::{future, stream, StreamExt};
+use mongodb::bson::Document;
+use serde::Serialize;
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio::task::{self, JoinError};
+use tracing::{debug, error, field, info, info_span, instrument, warn, Instrument, Span};
+use crate::fields::FieldNames;
+use crate::ids::{IdStrategy, UuidEncoding};
+use crate::metrics::metrics;
+use crate::record::Record;
+use crate::shutdown;
+use crate::storage::{aircraft_document, AircraftStore, FailedWrite, Sink};
+use crate::validate::{check, Rejection};
+use crate::{Aircraft, Error, Result, Storage};
+
+/// Number of aircraft handed to the backend at a time unless configured otherwise.
+pub const DEFAULT_BATCH_SIZE: usize = 1000;
+
+/// Batches each stage of a load holds for the next unless configured otherwise.
+pub const DEFAULT_BUFFER: usize = 2;
+
+/// How [`load`] writes records.
+#[derive(Clone, Debug)]
+pub struct LoadOptions {
+    /// Maximum number of aircraft per write.
+    pub batch_size: usize,
+    /// Upsert keyed on ICAO code instead of inserting new records.
+    pub upsert: bool,
+    /// Skip entries that fail to parse instead of aborting the load.
+    pub lenient: bool,
+    /// Maximum number of batches being written at once.
+    pub concurrency: usize,
+    /// Batches parsed, and batches checked, that may wait for the next stage of the load
+    /// before the stage stops reading: with `batch_size` and `concurrency`, what bounds the
+    /// records a load holds at once.
+    pub buffer: usize,
+    /// Insert the records of a batch independently of each other, so the ones the backend
+    /// refuses are listed in [`LoadSummary::failed`] instead of failing the batch.
+    pub unordered: bool,
+    /// Keys of the records already stored, which are counted in [`LoadSummary::existing`]
+    /// instead of being written.
+    pub existing: HashSet<String>,
+    /// Records written per second at most, on average over every batch, so loads on a
+    /// shared cluster leave room for other traffic.
+    pub max_docs_per_sec: Option<u32>,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        LoadOptions {
+            batch_size: DEFAULT_BATCH_SIZE,
+            upsert: false,
+            lenient: false,
+            concurrency: 1,
+            buffer: DEFAULT_BUFFER,
+            unordered: false,
+            existing: HashSet::new(),
+            max_docs_per_sec: None,
+        }
+    }
+}
+
+/// Totals aggregated over every batch of a [`load`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoadSummary<T = Aircraft> {
+    /// Records read from the input.
+    pub parsed: u64,
+    /// Records the backend reported as written.
+    pub written: u64,
+    /// Of `written`, the records that were already stored; unknown for upserts.
+    pub updated: Option<u64>,
+    /// Number of batches sent.
+    pub batches: u64,
+    /// Records left out because their key was already stored.
+    pub existing: u64,
+    /// Records that failed validation and were not written.
+    pub rejected: Vec<Rejection<T>>,
+    /// Messages for entries skipped because they failed to parse, in lenient mode.
+    pub skipped: Vec<String>,
+    /// Records the backend refused in an unordered load.
+    pub failed: Vec<FailedWrite<T>>,
+    /// Time from reading the first record to writing the last.
+    pub elapsed: Duration,
+    /// Of `elapsed`, the time batches waited for [`LoadOptions::max_docs_per_sec`], added
+    /// up over batches written at once.
+    pub throttled: Duration,
+    /// Whether a shutdown signal stopped the load before it read all of its input.
+    pub interrupted: bool,
+    /// How each stage of the load spent its time.
+    pub stages: Stages,
+}
+
+/// How a stage of a load spent its time. The stages run at once, so a stage mostly
+/// `starved` waits on the one before it and a stage mostly `blocked` on the one after it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StageMetrics {
+    /// Batches the stage handled.
+    pub batches: u64,
+    /// Time spent on its own work; for writes, added up over batches written at once.
+    pub busy: Duration,
+    /// Time waiting for a batch from the stage before it.
+    pub starved: Duration,
+    /// Time waiting for room in the channel to the stage after it.
+    pub blocked: Duration,
+}
+
+/// The stages of a [`load`]: parsing the input, checking the records parsed against
+/// validation and those already stored, and writing the rest.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stages {
+    pub parse: StageMetrics,
+    pub check: StageMetrics,
+    pub write: StageMetrics,
+}
+
+impl<T> Default for LoadSummary<T> {
+    fn default() -> Self {
+        LoadSummary {
+            parsed: 0,
+            written: 0,
+            updated: Some(0),
+            batches: 0,
+            existing: 0,
+            rejected: Vec::new(),
+            skipped: Vec::new(),
+            failed: Vec::new(),
+            elapsed: Duration::ZERO,
+            throttled: Duration::ZERO,
+            interrupted: false,
+            stages: Stages::default(),
+        }
+    }
+}
+
+/// Told about each batch of a [`load_with_progress`] as it goes, e.g. to draw a progress bar.
+/// Batches are written out of order when several are written at once.
+pub trait Progress {
+    /// Batch `batch` was read off the input, with `parsed` records of which `rejected` failed validation.
+    fn read(&self, _batch: u64, _parsed: u64, _rejected: u64) {}
+
+    /// Batch `batch` was written: `written` records, and `failed` refused in an unordered load.
+    fn written(&self, _batch: u64, _written: u64, _failed: u64) {}
+
+    /// The first `consumed` entries of the input have been dealt with: written, left out or
+    /// set aside. Only grows, even when batches are written out of order.
+    fn committed(&self, _consumed: u64) {}
+}
+
+/// Reports nothing.
+impl Progress for () {}
+
+/// Splits `records` into batches of `options.batch_size` and writes them to `sink` (usually
+/// a [`Storage`]), up to `options.concurrency` batches at a time, so the input never has to
+/// be collected up front. Records failing [`Record::validate`] are collected in
+/// [`LoadSummary::rejected`] instead of being written. The first write error aborts the
+/// load, as does the first parse error unless `options.lenient` is set, in which case
+/// unparseable entries are logged and listed in [`LoadSummary::skipped`]. On an error no
+/// further batch is started, the batches already being written are finished, every failed
+/// batch is logged and the first error is returned once every stage stopped; batches
+/// written before the error stay written. A shutdown signal
+/// stops the load the same way, without an error but with [`LoadSummary::interrupted`] set.
+pub async fn load<T: Record, S: Sink<T> + ?Sized>(
+    sink: &S,
+    records: impl Iterator<Item = Result<T>> + Send + 'static,
+    options: &LoadOptions,
+) -> Result<LoadSummary<T>> {
+    load_with_progress(sink, records, options, &()).await
+}
+
+/// [`load`], telling `progress` about each batch.
+///
+/// The load is a pipeline of three stages connected by channels of up to
+/// [`LoadOptions::buffer`] batches: parsing the input on a blocking thread, checking the
+/// records parsed on a task of its own, and writing the records checked on the load's
+/// task. The next batches are parsed and checked while the last are being written, and a
+/// stage waits for the next one when its channel is full, so the memory a load holds does
+/// not grow with its input. How each stage spent its time is in [`LoadSummary::stages`].
+#[instrument(name = "load", skip_all, fields(collection = T::COLLECTION))]
+pub async fn load_with_progress<T: Record, S: Sink<T> + ?Sized>(
+    sink: &S,
+    records: impl Iterator<Item = Result<T>> + Send + 'static,
+    options: &LoadOptions,
+    progress: &dyn Progress,
+) -> Result<LoadSummary<T>> {
+    let started = Instant::now();
+    let (parsed, to_check) = mpsc::channel(options.buffer.max(1));
+    let (checked, to_write) = mpsc::channel(options.buffer.max(1));
+    let (batch_size, lenient) = (options.batch_size.max(1), options.lenient);
+    let span = Span::current();
+    let parsing = task::spawn_blocking(move || span.in_scope(|| parse_stage(records, batch_size, lenient, parsed)));
+    let checking = tokio::spawn(check_stage(to_check, options.existing.clone(), checked).in_current_span());
+    let written = write_stage(sink, to_write, options, progress).await;
+    // Without the writes, the checks are of no use: aborting them closes the channel the
+    // parse stage hands its batches on, which ends it once it read the batch it is on.
+    if written.is_err() {
+        checking.abort();
+    }
+    let stages = (joined(parsing.await), checking.await);
+    let mut summary = written?;
+    summary.stages.parse = stages.0;
+    summary.stages.check = joined(stages.1);
+    summary.elapsed = started.elapsed();
+    if summary.interrupted {
+        warn!(written = summary.written, batches = summary.batches, "load interrupted");
+    }
+    info!(
+        collection = T::COLLECTION,
+        parsed = summary.parsed,
+        written = summary.written,
+        existing = summary.existing,
+        rejected = summary.rejected.len(),
+        skipped = summary.skipped.len(),
+        failed = summary.failed.len(),
+        batches = summary.batches,
+        elapsed_ms = summary.elapsed.as_millis() as u64,
+        throttled_ms = summary.throttled.as_millis() as u64,
+        parse_ms = summary.stages.parse.busy.as_millis() as u64,
+        check_ms = summary.stages.check.busy.as_millis() as u64,
+        write_ms = summary.stages.write.busy.as_millis() as u64,
+        "load finished"
+    );
+    Ok(summary)
+}
+
+// The metrics a stage returned, passing on its panic if it panicked. Stages are only
+// cancelled once the load failed, which leaves their metrics unused.
+fn joined(stage: std::result::Result<StageMetrics, JoinError>) -> StageMetrics {
+    stage.unwrap_or_else(|error| panic::resume_unwind(error.into_panic()))
+}
+
+// Reads `records` in batches of up to `batch_size` parsed records and hands them on,
+// blocking while the channel is full. The first parse error is handed on and ends the
+// stage unless `lenient` is set; so does the next stage no longer taking batches.
+fn parse_stage<T: Record>(
+    records: impl Iterator<Item = Result<T>>,
+    batch_size: usize,
+    lenient: bool,
+    output: Sender<Result<Parsed<T>>>,
+) -> StageMetrics {
+    let mut metrics = StageMetrics::default();
+    let mut records = records.fuse();
+    let mut number = 0;
+    loop {
+        let reading = Instant::now();
+        let batch = read_batch(&mut records, batch_size, lenient, number + 1);
+        metrics.busy += reading.elapsed();
+        let Some(batch) = batch else { break };
+        let failed = batch.is_err();
+        number += 1;
+        metrics.batches += 1;
+        let sending = Instant::now();
+        if output.blocking_send(batch).is_err() {
+            break;
+        }
+        metrics.blocked += sending.elapsed();
+        if failed {
+            break;
+        }
+    }
+    metrics
+}
+
+// Sets aside the records of each batch that are already stored, keyed in `existing`, or
+// fail validation, and hands on the rest to be written. Parse errors are handed on as is.
+async fn check_stage<T: Record>(
+    mut input: Receiver<Result<Parsed<T>>>,
+    existing: HashSet<String>,
+    output: Sender<Result<Batch<T>>>,
+) -> StageMetrics {
+    let mut metrics = StageMetrics::default();
+    loop {
+        let waiting = Instant::now();
+        let parsed = input.recv().await;
+        metrics.starved += waiting.elapsed();
+        let Some(parsed) = parsed else { break };
+        let checking = Instant::now();
+        let batch = parsed.map(|parsed| check_batch(parsed, &existing));
+        metrics.busy += checking.elapsed();
+        metrics.batches += 1;
+        let sending = Instant::now();
+        if output.send(batch).await.is_err() {
+            break;
+        }
+        metrics.blocked += sending.elapsed();
+    }
+    metrics
+}
+
+// Writes the batches checked to `sink` as `options` say, up to `options.concurrency` at a
+// time, and adds them up. Dropping the channel when done, as on the first error or a
+// shutdown signal, ends the stages before it.
+async fn write_stage<T: Record, S: Sink<T> + ?Sized>(
+    sink: &S,
+    input: Receiver<Result<Batch<T>>>,
+    options: &LoadOptions,
+    progress: &dyn Progress,
+) -> Result<LoadSummary<T>> {
+    let mut summary = LoadSummary::default();
+    let failed = AtomicBool::new(false);
+    let failed = &failed;
+    let interrupted = AtomicBool::new(false);
+    let bucket = options.max_docs_per_sec.map(TokenBucket::new);
+    let bucket = &bucket;
+    let starved = Cell::new(Duration::ZERO);
+    let starved = &starved;
+    let batches = stream::unfold(input, |mut input| async move {
+        let waiting = Instant::now();
+        let batch = input.recv().await;
+        starved.set(starved.get() + waiting.elapsed());
+        batch.map(|batch| (batch, input))
+    });
+    let writes = batches
+        .take_while(|_| {
+            // After a shutdown signal, the batch just read is the first one left unwritten.
+            let stop = shutdown::requested();
+            if stop {
+                interrupted.store(true, Ordering::Relaxed);
+            }
+            future::ready(!stop && !failed.load(Ordering::Relaxed))
+        })
+        .map(|batch| async move {
+            let batch = batch.inspect_err(|_| failed.store(true, Ordering::Relaxed))?;
+            progress.read(batch.number, batch.parsed, batch.rejected.len() as u64);
+            if batch.records.is_empty() {
+                return Ok((batch, 0, Vec::new(), Duration::ZERO, Duration::ZERO));
+            }
+            let throttled = match bucket {
+                Some(bucket) => bucket.take(batch.records.len() as u64).await,
+                None => Duration::ZERO,
+            };
+            let batch_started = Instant::now();
+            let written = async {
+                if options.upsert {
+                    sink.upsert(&batch.records).await.map(|written| (written, Vec::new()))
+                } else if options.unordered {
+                    sink.insert_unordered(&batch.records).await
+                } else {
+                    sink.insert_batch(&batch.records).await.map(|written| (written, Vec::new()))
+                }
+            }
+            .instrument(info_span!("write_batch", batch = batch.number, size = batch.records.len()))
+            .await;
+            match written {
+                Ok((written, failed)) => {
+                    metrics().records_loaded.with_label_values(&[T::COLLECTION]).inc_by(written);
+                    metrics().batch_insert_seconds.with_label_values(&[T::COLLECTION]).observe(batch_started.elapsed().as_secs_f64());
+                    for failure in &failed {
+                        warn!(batch = batch.number, key = %failure.record.key(), error = %failure.error, "record not written");
+                    }
+                    debug!(
+                        batch = batch.number,
+                        size = batch.records.len(),
+                        written,
+                        failed = failed.len(),
+                        elapsed_ms = batch_started.elapsed().as_millis() as u64,
+                        "wrote batch"
+                    );
+                    progress.written(batch.number, written, failed.len() as u64);
+                    Ok((batch, written, failed, throttled, batch_started.elapsed()))
+                }
+                Err(error) => {
+                    metrics().observe_error(&error);
+                    failed.store(true, Ordering::Relaxed);
+                    let (first, last) = (&batch.records[0], &batch.records[batch.records.len() - 1]);
+                    error!(batch = batch.number, size = batch.records.len(), first = %first.key(), last = %last.key(), %error, "batch failed");
+                    Err(error)
+                }
+            }
+        })
+        .buffer_unordered(options.concurrency.max(1));
+    let mut writes = pin!(writes);
+    let mut failure = None;
+    // Entries read for each batch written, until the batches before it are written too.
+    let mut done = BTreeMap::new();
+    let (mut next, mut consumed) = (1, 0);
+    while let Some(write) = writes.next().await {
+        match write {
+            Ok((batch, written, failed, throttled, busy)) => {
+                done.insert(batch.number, batch.parsed + batch.skipped.len() as u64);
+                if done.contains_key(&next) {
+                    while let Some(entries) = done.remove(&next) {
+                        consumed += entries;
+                        next += 1;
+                    }
+                    progress.committed(consumed);
+                }
+                summary.failed.extend(failed);
+                summary.parsed += batch.parsed;
+                summary.written += written;
+                summary.batches += u64::from(!batch.records.is_empty());
+                summary.existing += batch.existing;
+                summary.rejected.extend(batch.rejected);
+                summary.skipped.extend(batch.skipped);
+                summary.throttled += throttled;
+                summary.stages.write.batches += 1;
+                summary.stages.write.busy += busy;
+            }
+            Err(error) => {
+                failure.get_or_insert(error);
+            }
+        }
+    }
+    if let Some(error) = failure {
+        warn!(written = summary.written, batches = summary.batches, "load aborted");
+        return Err(error);
+    }
+    summary.stages.write.starved = starved.get();
+    summary.updated = (!options.upsert).then_some(0);
+    summary.interrupted = interrupted.load(Ordering::Relaxed);
+    Ok(summary)
+}
+
+/// Hands out permission to write a number of records a second, letting up to a second's
+/// worth through at once after a pause.
+#[derive(Debug)]
+pub struct TokenBucket {
+    rate: f64,
+    // Records that may be written now, negative while batches larger than that are paid
+    // back, and when it was last refilled.
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    /// A full bucket refilling at `rate` records a second.
+    pub fn new(rate: u32) -> Self {
+        let rate = f64::from(rate.max(1));
+        TokenBucket { rate, state: Mutex::new((rate, Instant::now())) }
+    }
+
+    /// Waits until `count` records may be written and returns how long that took. A batch
+    /// larger than the bucket goes through once it is full, and the next waits for the
+    /// difference.
+    pub async fn take(&self, count: u64) -> Duration {
+        let wait = {
+            let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let (tokens, refilled) = &mut *state;
+            let now = Instant::now();
+            *tokens = (*tokens + now.duration_since(*refilled).as_secs_f64() * self.rate).min(self.rate);
+            *refilled = now;
+            let wait = (self.rate.min(count as f64) - *tokens).max(0.0) / self.rate;
+            *tokens -= count as f64;
+            Duration::from_secs_f64(wait)
+        };
+        tokio::time::sleep(wait).await;
+        wait
+    }
+}
+
+/// Writes the records of `failures` to `path` as a pretty-printed JSON array, which can be
+/// loaded again once whatever refused them is fixed.
+pub fn write_failures<T: Serialize>(path: &Path, failures: &[FailedWrite<T>]) -> Result<()> {
+    let records: Vec<&T> = failures.iter().map(|failure| &failure.record).collect();
+    let json = serde_json::to_string_pretty(&records)?;
+    fs::write(path, json).map_err(|source| Error::Write { path: path.to_path_buf(), source })
+}
+
+// A batch read off the input: the records parsed and the entries skipped.
+struct Parsed<T> {
+    number: u64,
+    records: Vec<T>,
+    skipped: Vec<String>,
+}
+
+// A batch checked, with the records set aside while reading and checking it.
+struct Batch<T> {
+    number: u64,
+    parsed: u64,
+    existing: u64,
+    records: Vec<T>,
+    rejected: Vec<Rejection<T>>,
+    skipped: Vec<String>,
+}
+
+// Reads batch `number` of up to `batch_size` parsed records off `records`; none at the end
+// of the input. A parse error is returned instead of the batch unless `lenient` is set.
+fn read_batch<T: Record>(
+    records: &mut impl Iterator<Item = Result<T>>,
+    batch_size: usize,
+    lenient: bool,
+    number: u64,
+) -> Option<Result<Parsed<T>>> {
+    let span = info_span!("read_batch", batch = number, parsed = field::Empty).entered();
+    let mut batch = Parsed { number, records: Vec::with_capacity(batch_size), skipped: Vec::new() };
+    for record in records.by_ref() {
+        match record {
+            Ok(record) => batch.records.push(record),
+            Err(error) if lenient => {
+                metrics().parse_failures.with_label_values(&[T::COLLECTION]).inc();
+                warn!(%error, "skipped unparseable record");
+                batch.skipped.push(error.to_string());
+            }
+            Err(error) => {
+                metrics().parse_failures.with_label_values(&[T::COLLECTION]).inc();
+                return Some(Err(error));
+            }
+        }
+        if batch.records.len() == batch_size {
+            break;
+        }
+    }
+    span.record("parsed", batch.records.len());
+    if batch.records.is_empty() && batch.skipped.is_empty() {
+        return None;
+    }
+    Some(Ok(batch))
+}
+
+// The records of `parsed` to write: those not keyed in `existing` that pass validation.
+fn check_batch<T: Record>(parsed: Parsed<T>, existing: &HashSet<String>) -> Batch<T> {
+    let span = info_span!("check_batch", batch = parsed.number, rejected = field::Empty).entered();
+    let mut batch = Batch {
+        number: parsed.number,
+        parsed: parsed.records.len() as u64,
+        existing: 0,
+        records: Vec::with_capacity(parsed.records.len()),
+        rejected: Vec::new(),
+        skipped: parsed.skipped,
+    };
+    for record in parsed.records {
+        if existing.contains(record.key().as_ref()) {
+            batch.existing += 1;
+            debug!(key = %record.key(), "already stored");
+            continue;
+        }
+        match check(record) {
+            Ok(record) => batch.records.push(record),
+            Err(rejection) => {
+                metrics().records_rejected.with_label_values(&[T::COLLECTION]).inc();
+                warn!(key = %rejection.record.key(), reasons = ?rejection.reasons, "rejected record");
+                batch.rejected.push(rejection);
+            }
+        }
+    }
+    span.record("rejected", batch.rejected.len());
+    batch
+}
+
+/// Runs a full reload of `store` without readers ever seeing a half-written collection:
+/// loads `aircrafts` into a fresh [staging](AircraftStore::staging) collection, creating its
+/// indexes first unless `skip_indexes` is set, then renames it over the live one. If the
+/// load fails the staging collection is dropped and the live one is left as it was.
+/// `progress` is told about each batch as for [`load_with_progress`].
+pub async fn load_swapped(
+    store: &AircraftStore,
+    aircrafts: impl Iterator<Item = Result<Aircraft>> + Send + 'static,
+    options: &LoadOptions,
+    skip_indexes: bool,
+    progress: &dyn Progress,
+) -> Result<LoadSummary> {
+    let staging = store.staging();
+    info!(collection = staging.collection().name(), "loading into staging collection");
+    let loaded = async {
+        if !skip_indexes {
+            staging.ensure_indexes().await?;
+        }
+        load_with_progress(&staging, aircrafts, options, progress).await
+    }
+    .await;
+    // An interrupted reload is as incomplete as a failed one.
+    let loaded = loaded.and_then(|summary| if summary.interrupted { Err(Error::Interrupted) } else { Ok(summary) });
+    match loaded {
+        Ok(summary) => {
+            staging.replace(store.collection().name()).await?;
+            Ok(summary)
+        }
+        Err(error) => {
+            if let Err(drop_error) = staging.drop_collection().await {
+                warn!(error = %drop_error, collection = staging.collection().name(), "could not drop staging collection");
+            }
+            Err(error)
+        }
+    }
+}
+
+/// What a load would do, as computed by [`dry_run`].
+#[derive(Clone, Debug, Default)]
+pub struct DryRunReport {
+    /// Aircraft that parsed successfully.
+    pub parsed: u64,
+    /// Messages for entries that failed to parse.
+    pub malformed: Vec<String>,
+    /// Aircraft that parsed but failed validation.
+    pub rejected: Vec<Rejection>,
+    /// ICAO codes appearing more than once, with how often each appears.
+    pub duplicates: Vec<(String, u64)>,
+    /// The first documents that would be inserted into MongoDB.
+    pub sample: Vec<Document>,
+}
+
+/// Parses and checks `aircrafts` without touching any backend: counts records, collects
+/// parse failures, validation failures and duplicate ICAO codes, and converts the first `sample_size` records to
+/// the BSON documents a load would insert. Readers that cannot resume after a parse error
+/// (such as a JSON array) stop at the first malformed entry.
+pub fn dry_run(
+    aircrafts: impl Iterator<Item = Result<Aircraft>>,
+    sample_size: usize,
+    ids: &IdStrategy,
+    uuids: UuidEncoding,
+    fields: &FieldNames,
+) -> Result<DryRunReport> {
+    let mut report = DryRunReport::default();
+    let mut occurrences: HashMap<String, u64> = HashMap::new();
+    for aircraft in aircrafts {
+        let aircraft = match aircraft {
+            Ok(aircraft) => aircraft,
+            Err(error) => {
+                warn!(%error, "malformed record");
+                report.malformed.push(error.to_string());
+                continue;
+            }
+        };
+        report.parsed += 1;
+        *occurrences.entry(aircraft.icao_code.clone()).or_default() += 1;
+        let aircraft = match check(aircraft) {
+            Ok(aircraft) => aircraft,
+            Err(rejection) => {
+                report.rejected.push(rejection);
+                continue;
+            }
+        };
+        if report.sample.len() < sample_size {
+            report.sample.push(aircraft_document(&aircraft, ids, uuids, fields));
+        }
+    }
+    report.duplicates = occurrences.into_iter().filter(|(_, count)| *count > 1).collect();
+    report.duplicates.sort();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+    use async_trait::async_trait;
+    use crate::checkpoint::{Checkpoint, Checkpointer};
+    use crate::storage::InMemoryStorage;
+    use crate::testing::aircraft;
+    use super::*;
+
+    // An in-memory store whose writes of odd batches of `BATCH` take longer, so batches
+    // written at once finish out of order, which counts the writes under way and fails the
+    // batch holding `failing`.
+    #[derive(Default)]
+    struct Slow {
+        storage: InMemoryStorage,
+        failing: Option<String>,
+        writing: AtomicUsize,
+        most_writing: AtomicUsize,
+    }
+
+    const BATCH: usize = 10;
+
+    #[async_trait]
+    impl Sink<Aircraft> for Slow {
+        async fn insert_batch(&self, records: &[Aircraft]) -> Result<u64> {
+            let writing = self.writing.fetch_add(1, Ordering::SeqCst) + 1;
+            self.most_writing.fetch_max(writing, Ordering::SeqCst);
+            let number: usize = records[0].icao_code[1..].parse().unwrap();
+            tokio::time::sleep(Duration::from_millis(if (number / BATCH).is_multiple_of(2) { 30 } else { 5 })).await;
+            let written = match records.iter().any(|record| Some(&record.icao_code) == self.failing.as_ref()) {
+                true => Err(Error::InvalidInput("write refused".to_string())),
+                false => Storage::insert_batch(&self.storage, records).await,
+            };
+            self.writing.fetch_sub(1, Ordering::SeqCst);
+            written
+        }
+
+        async fn insert_unordered(&self, records: &[Aircraft]) -> Result<(u64, Vec<FailedWrite>)> {
+            Storage::insert_unordered(&self.storage, records).await
+        }
+
+        async fn upsert(&self, records: &[Aircraft]) -> Result<u64> {
+            Storage::upsert(&self.storage, records).await
+        }
+    }
+
+    // Aircraft A000, A001 and so on.
+    fn numbered(count: usize) -> Vec<Result<Aircraft>> {
+        (0..count).map(|number| Ok(aircraft(&format!("A{:03}", number)).build())).collect()
+    }
+
+    fn options(concurrency: usize) -> LoadOptions {
+        LoadOptions { batch_size: BATCH, concurrency, ..LoadOptions::default() }
+    }
+
+    // The batches written and the entries committed, in the order reported.
+    #[derive(Default)]
+    struct Reported {
+        written: Mutex<Vec<u64>>,
+        committed: Mutex<Vec<u64>>,
+    }
+
+    impl Progress for Reported {
+        fn written(&self, batch: u64, _written: u64, _failed: u64) {
+            self.written.lock().unwrap().push(batch);
+        }
+
+        fn committed(&self, consumed: u64) {
+            self.committed.lock().unwrap().push(consumed);
+        }
+    }
+
+    #[tokio::test]
+    async fn progress_is_committed_in_input_order() {
+        let sink = Slow::default();
+        let reported = Reported::default();
+        let summary = load_with_progress(&sink, numbered(100).into_iter(), &options(4), &reported).await.unwrap();
+        assert_eq!((summary.parsed, summary.written, summary.batches), (100, 100, 10));
+
+        let written = reported.written.into_inner().unwrap();
+        assert!(written.windows(2).any(|pair| pair[0] > pair[1]), "batches written in order: {:?}", written);
+        // Only whole runs of batches from the start count, however they finished.
+        let committed = reported.committed.into_inner().unwrap();
+        assert!(committed.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", committed);
+        assert!(committed.iter().all(|consumed| (*consumed as usize).is_multiple_of(BATCH)), "{:?}", committed);
+        assert_eq!(committed.last(), Some(&100));
+    }
+
+    #[tokio::test]
+    async fn no_more_batches_are_written_at_once_than_the_concurrency() {
+        for concurrency in [1, 3] {
+            let sink = Slow::default();
+            load(&sink, numbered(100).into_iter(), &options(concurrency)).await.unwrap();
+            assert_eq!(sink.most_writing.load(Ordering::SeqCst), concurrency);
+        }
+    }
+
+    #[tokio::test]
+    async fn lenient_loads_skip_the_entries_that_fail_to_parse() {
+        let mut entries = numbered(25);
+        entries[3] = Err(Error::InvalidInput("line 4 is not an aircraft".to_string()));
+        entries[17] = Err(Error::InvalidInput("line 18 is not an aircraft".to_string()));
+        let reported = Reported::default();
+        let lenient = LoadOptions { lenient: true, ..options(2) };
+
+        let summary = load_with_progress(&Slow::default(), entries.into_iter(), &lenient, &reported).await.unwrap();
+        assert_eq!((summary.parsed, summary.written), (23, 23));
+        let mut skipped = summary.skipped;
+        skipped.sort();
+        assert_eq!(skipped, ["invalid input: line 18 is not an aircraft", "invalid input: line 4 is not an aircraft"]);
+        // The skipped entries are consumed too.
+        assert_eq!(reported.committed.into_inner().unwrap().last(), Some(&25));
+
+        let mut entries = numbered(25);
+        entries[17] = Err(Error::InvalidInput("line 18 is not an aircraft".to_string()));
+        let failed = load(&Slow::default(), entries.into_iter(), &options(2)).await;
+        assert!(matches!(failed, Err(Error::InvalidInput(message)) if message.contains("line 18")));
+    }
+
+    #[tokio::test]
+    async fn a_failed_load_resumes_from_its_checkpoint() {
+        let input = std::env::temp_dir().join(format!("load-{}.json", uuid::Uuid::new_v4()));
+        let path = Checkpoint::path(&input);
+        let checkpointer = |base| Checkpointer { path: path.clone(), load_id: "first".to_string(), checksum: "sum".to_string(), base, inner: &() };
+        let sink = Slow { failing: Some("A055".to_string()), ..Slow::default() };
+
+        let failed = load_with_progress(&sink, numbered(100).into_iter(), &options(1), &checkpointer(0)).await;
+        assert!(matches!(failed, Err(Error::InvalidInput(_))));
+        let checkpoint = Checkpoint::read(&path).unwrap().expect("a checkpoint is saved");
+        assert_eq!(checkpoint.offset, 50);
+
+        // Carrying on after the checkpoint writes every aircraft once.
+        let sink = Slow { failing: None, ..sink };
+        let rest = numbered(100).into_iter().skip(checkpoint.offset as usize);
+        let summary = load_with_progress(&sink, rest, &options(1), &checkpointer(checkpoint.offset)).await.unwrap();
+        assert_eq!(summary.written, 50);
+        assert_eq!(sink.storage.find_all().await.unwrap().len(), 100);
+        assert_eq!(Checkpoint::read(&path).unwrap().map(|checkpoint| checkpoint.offset), Some(100));
+        Checkpoint::remove(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn a_failed_write_stops_every_stage_before_the_load_returns() {
+        // An endless input, dropped by the parse stage as it stops.
+        let input = Arc::new(());
+        let held = Arc::clone(&input);
+        let endless = (0..).map(move |number| {
+            let _ = &held;
+            Ok(aircraft(&format!("A{:03}", number % 1000)).build())
+        });
+        let sink = Slow { failing: Some("A025".to_string()), ..Slow::default() };
+
+        let failed = load(&sink, endless, &options(2)).await;
+        assert!(matches!(failed, Err(Error::InvalidInput(message)) if message == "write refused"));
+        assert_eq!(Arc::strong_count(&input), 1);
+    }
+
+    #[tokio::test]
+    async fn a_full_bucket_lets_a_second_of_records_through() {
+        let bucket = TokenBucket::new(100);
+        assert_eq!(bucket.take(100).await, Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn an_empty_bucket_waits_for_the_records_to_refill() {
+        let bucket = TokenBucket::new(100);
+        bucket.take(100).await;
+        let waited = bucket.take(10).await;
+        assert!(waited > Duration::from_millis(90) && waited <= Duration::from_millis(100), "{:?}", waited);
+    }
+
+    #[tokio::test]
+    async fn a_batch_larger_than_the_bucket_is_paid_back_by_the_next() {
+        let bucket = TokenBucket::new(100);
+        assert_eq!(bucket.take(150).await, Duration::ZERO);
+        let waited = bucket.take(50).await;
+        assert!(waited > Duration::from_millis(900) && waited <= Duration::from_secs(1), "{:?}", waited);
+    }
+}
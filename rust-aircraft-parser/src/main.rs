@@ -1,52 +1,2112 @@
-use std::{env, fs};
-use std::ops::Deref;
+mod batch;
+mod cli;
+mod browse;
+mod config;
+mod progress;
+mod telemetry;
+
+use std::env;
+use std::fs::{self, File};
+use std::future::Future;
+use std::io::{self, BufWriter, IsTerminal, Write};
+use std::mem;
+#[cfg(feature = "grpc")]
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use chrono::{DateTime, Utc};
 use dotenv::dotenv;
-use serde::{Deserialize, Serialize};
-use mongodb::{bson, bson::doc, Client, Collection};
-use mongodb::bson::Document;
-use uuid::Uuid;
+use futures::{future, StreamExt, TryStreamExt};
+use mongodb::bson::{self, doc};
+use serde::Serialize;
+use tracing::{error, info, warn};
+use tracing::Instrument;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+use progress::LoadProgress;
+use rust_aircraft_parser::airport::AirportIndex;
+use rust_aircraft_parser::checkpoint::{Checkpoint, Checkpointer};
+use rust_aircraft_parser::dedup::DedupStrategy;
+use rust_aircraft_parser::filter::{self, Filter};
+use rust_aircraft_parser::enrich::{Alias, Performance, Translation, TypeData, TypeDetails};
+use rust_aircraft_parser::input::{Format, RecordStream};
+use rust_aircraft_parser::merge::{self, FieldConflict};
+use rust_aircraft_parser::nationality::{self, NationalityMarks};
+use rust_aircraft_parser::overrides::Overrides;
+use rust_aircraft_parser::provenance::{LoadRecord, Provenance};
+use rust_aircraft_parser::references::KnownCodes;
+use rust_aircraft_parser::report::RunReport;
+use rust_aircraft_parser::retry::RetryPolicy;
+#[cfg(feature = "embedded")]
+use rust_aircraft_parser::embedded;
+#[cfg(feature = "grpc")]
+use rust_aircraft_parser::grpc;
+#[cfg(feature = "server")]
+use rust_aircraft_parser::{cache, daemon, server};
+#[cfg(feature = "s3")]
+use rust_aircraft_parser::s3::S3Object;
+use rust_aircraft_parser::schedule::Schedule;
+use rust_aircraft_parser::storage::{Inactive, LoadLock, StoredAircraft, DEFAULT_LEASE};
+use rust_aircraft_parser::watch::FileWatcher;
+use rust_aircraft_parser::webhook::{RunSummary, Webhook, WebhookFormat};
+use rust_aircraft_parser::{bench, classify, dedup, diff, enrich, equipment, wikidata, export, feed, hooks, input, load, migrations, quality, remote, resolve, schema, search, secrets, selftest, shutdown, sync, timezones, validate, Aircraft, AircraftStore, Airline, Airport, Country, Error, HexEntry, Record, Registration, Result, Route, Storage};
+
+fn env_var(name: &str) -> Result<String> {
+    env::var(name).map_err(|_| Error::Config(format!("cannot get env var {}", name)))
+}
+
+async fn connect_mongo(global: &cli::GlobalArgs, provenance: &Provenance) -> Result<AircraftStore> {
+    let uri = match (&global.mongo_uri_secret, &global.mongodb_url) {
+        (Some(secret), _) => secrets::resolve(secret).await?,
+        (None, Some(uri)) => uri.clone(),
+        (None, None) => env_var("MONGODB_URL")?,
+    };
+    let retry = RetryPolicy { max_retries: global.max_retries, ..RetryPolicy::default() };
+    let collection = global.staged.get(&global.collection).unwrap_or(&global.collection);
+    let store = AircraftStore::connect(&uri, &global.client_settings(), &global.database, collection, retry).await?;
+    Ok(store
+        .with_staged(global.staged.clone())
+        .with_id_strategy(global.id_strategy())
+        .with_uuid_encoding(global.uuid_encoding())
+        .with_provenance(Provenance { tenant: global.tenant.clone(), ..provenance.clone() })
+        .with_field_names(global.field_names()?)
+        .with_history(global.history)
+        .with_audit(global.actor())
+        .with_staging_ttl(Duration::from_secs(global.staging_ttl * 60 * 60))
+        .with_inactive(global.inactive))
+}
+
+#[cfg(feature = "dynamodb")]
+async fn connect_dynamodb(global: &cli::GlobalArgs, provenance: &Provenance) -> Result<rust_aircraft_parser::storage::DynamoStorage> {
+    let storage = rust_aircraft_parser::storage::DynamoStorage::connect(&global.collection).await?;
+    let retry = RetryPolicy { max_retries: global.max_retries, ..RetryPolicy::default() };
+    Ok(storage.with_retry_policy(retry).with_id_strategy(global.id_strategy()).with_provenance(provenance.clone()))
+}
+
+// Every record written through the returned storage is stamped with `provenance`. With a
+// --topic the aircraft written are published to Kafka too.
+async fn create_storage(global: &cli::GlobalArgs, provenance: &Provenance) -> Result<Box<dyn Storage>> {
+    #[cfg(feature = "kafka")]
+    if let Some(topic) = &global.topic {
+        let kafka = rust_aircraft_parser::storage::KafkaStorage::connect(&env_var("KAFKA_BROKERS")?, topic)?;
+        let kafka = kafka.with_provenance(provenance.clone());
+        if global.backend == cli::Backend::Kafka {
+            return Ok(Box::new(kafka));
+        }
+        return Ok(Box::new(kafka.mirroring(backend_storage(global, provenance).await?)));
+    }
+    backend_storage(global, provenance).await
+}
+
+async fn backend_storage(global: &cli::GlobalArgs, provenance: &Provenance) -> Result<Box<dyn Storage>> {
+    Ok(match global.backend {
+        cli::Backend::Mongo => Box::new(connect_mongo(global, provenance).await?),
+        #[cfg(feature = "embedded")]
+        cli::Backend::Embedded => Box::new(embedded::storage()?),
+        #[cfg(feature = "postgres")]
+        cli::Backend::Postgres => {
+            let url = env_var("POSTGRES_URL")?;
+            let storage = rust_aircraft_parser::storage::PostgresStorage::connect(&url, &global.collection).await?;
+            Box::new(storage.with_id_strategy(global.id_strategy()).with_provenance(provenance.clone()))
+        }
+        #[cfg(feature = "sqlite")]
+        cli::Backend::Sqlite => {
+            let storage = rust_aircraft_parser::storage::SqliteStorage::open(&global.db, &global.collection).await?;
+            Box::new(storage.with_id_strategy(global.id_strategy()).with_provenance(provenance.clone()))
+        }
+        #[cfg(feature = "elasticsearch")]
+        cli::Backend::Elasticsearch => {
+            let storage = rust_aircraft_parser::storage::ElasticsearchStorage::connect(&env_var("ELASTICSEARCH_URL")?, &global.collection).await?;
+            Box::new(storage.with_id_strategy(global.id_strategy()).with_provenance(provenance.clone()))
+        }
+        #[cfg(feature = "dynamodb")]
+        cli::Backend::Dynamodb => Box::new(connect_dynamodb(global, provenance).await?),
+        #[cfg(feature = "redis")]
+        cli::Backend::Redis => {
+            let storage = rust_aircraft_parser::storage::RedisStorage::connect(&env_var("REDIS_URL")?).await?;
+            let storage = storage.with_ttl(global.redis_ttl.map(std::time::Duration::from_secs));
+            Box::new(storage.with_id_strategy(global.id_strategy()).with_provenance(provenance.clone()))
+        }
+        #[cfg(feature = "kafka")]
+        cli::Backend::Kafka => return Err(Error::Config("the kafka backend needs a --topic".to_string())),
+    })
+}
 
-#[derive(Serialize, Deserialize, Debug)]
-struct Aircraft {
-    icaoCode: String,
-    iataCode: String,
-    description: String,
+// Logs go to stderr, around any progress bar, so stdout stays clean for exported data. The -v/-q level applies to
+// this crate only, dependencies log warnings and errors; RUST_LOG, when set, replaces both.
+// Spans are also exported when the environment names an OTLP collector.
+fn init_logging(global: &cli::GlobalArgs) -> telemetry::Telemetry {
+    let level = global.log_level();
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| {
+        let dependencies = level.min(tracing::Level::WARN);
+        EnvFilter::new(format!("{},rust_aircraft_parser={}", dependencies, level))
+    });
+    let logs = tracing_subscriber::fmt::layer().with_writer(|| progress::LogWriter);
+    let logs = match global.log_format {
+        cli::LogFormat::Text => logs.with_filter(filter).boxed(),
+        cli::LogFormat::Json => logs.json().with_filter(filter).boxed(),
+    };
+    // Spans of this crate are exported whatever the log level.
+    let (traces, telemetry) = telemetry::layer();
+    let traces = traces.map(|traces| traces.with_filter(Targets::new().with_target("rust_aircraft_parser", tracing::Level::INFO)));
+    tracing_subscriber::registry().with(logs).with(traces).init();
+    if let Some(problem) = telemetry.problem() {
+        warn!("{}", problem);
+    }
+    telemetry
+}
+
+fn print_dry_run(report: &load::DryRunReport) -> Result<()> {
+    println!("parsed {} aircraft", report.parsed);
+    println!("malformed {} records", report.malformed.len());
+    for message in &report.malformed {
+        println!("  {}", message);
+    }
+    println!("invalid {} records", report.rejected.len());
+    for rejection in &report.rejected {
+        println!("  {}: {}", rejection.record.icao_code, rejection.reasons.join("; "));
+    }
+    println!("duplicate {} icao codes", report.duplicates.len());
+    for (icao_code, count) in &report.duplicates {
+        println!("  {} x{}", icao_code, count);
+    }
+    println!("sample of {} documents:", report.sample.len());
+    for document in &report.sample {
+        let json = bson::Bson::Document(document.clone()).into_relaxed_extjson();
+        println!("{}", serde_json::to_string_pretty(&json)?);
+    }
+    Ok(())
 }
 
-fn read_aircraft_json() -> Vec<Aircraft> {
-    let aircraft_string = fs::read_to_string("aircraft.json").expect("TODO: cannot unwrap string");
+// Writes the rejects and failures files and, unless the summary is printed as JSON by
+// notify_finished, tells how the load went.
+fn print_load_summary<T: Record>(
+    summary: &load::LoadSummary<T>,
+    load_id: &str,
+    rejects: &Path,
+    failures: &Path,
+    output: cli::RunOutput,
+) -> Result<()> {
+    if !summary.rejected.is_empty() {
+        validate::write_rejects(rejects, &summary.rejected)?;
+    }
+    if !summary.failed.is_empty() {
+        load::write_failures(failures, &summary.failed)?;
+    }
+    if output == cli::RunOutput::Text {
+        let seconds = summary.elapsed.as_secs_f64();
+        println!(
+            "loaded {} of {} records in {} batches in {:.1}s, {:.0} records/s (load id {})",
+            summary.written,
+            summary.parsed,
+            summary.batches,
+            seconds,
+            summary.written as f64 / seconds.max(0.001),
+            load_id
+        );
+        let stages = [("parsing", &summary.stages.parse), ("checking", &summary.stages.check), ("writing", &summary.stages.write)];
+        let timings: Vec<String> = stages
+            .iter()
+            .map(|(stage, metrics)| {
+                let waited = metrics.starved + metrics.blocked;
+                format!("{} {:.1}s (waited {:.1}s)", stage, metrics.busy.as_secs_f64(), waited.as_secs_f64())
+            })
+            .collect();
+        println!("{}", timings.join(", "));
+        if summary.throttled > Duration::ZERO {
+            println!("waited {:.1}s in all to stay under --max-docs-per-sec", summary.throttled.as_secs_f64());
+        }
+        if summary.existing > 0 {
+            println!("skipped {} records already stored", summary.existing);
+        }
+        if !summary.skipped.is_empty() {
+            println!("skipped {} unparseable entries:", summary.skipped.len());
+            for message in &summary.skipped {
+                println!("  {}", message);
+            }
+        }
+        if !summary.rejected.is_empty() {
+            println!("rejected {} records, see {}", summary.rejected.len(), rejects.display());
+        }
+        if !summary.failed.is_empty() {
+            println!("failed to write {} records, see {}:", summary.failed.len(), failures.display());
+            for failure in &summary.failed {
+                println!("  {}: {}", failure.record.key(), failure.error);
+            }
+        }
+        if summary.interrupted {
+            println!("interrupted before the end of the input");
+        }
+    }
+    if summary.interrupted {
+        return Err(Error::Interrupted);
+    }
+    Ok(())
+}
 
-    // Parse the string of data into a Person object. This is exactly the
-    // same function as the one that produced serde_json::Value above, but
-    // now we are asking it for a Person as output.
-    let aircrafts: Vec<Aircraft> = serde_json::from_str::<Vec<Aircraft>>(&*aircraft_string).expect("cannot parse json");
+// Whether --skip-unchanged applies: the input matches what the last recorded run read.
+fn unchanged(last_checksum: Option<String>, provenance: &Provenance) -> bool {
+    if last_checksum.is_some() && last_checksum == provenance.checksum {
+        println!(
+            "{} is unchanged since the last load, skipping",
+            provenance.source_file.as_deref().unwrap_or_default()
+        );
+        return true;
+    }
+    false
+}
+
+// An interrupted load is recorded without its checksum, so --skip-unchanged doesn't take it
+// for a complete load of the input.
+fn load_record<T>(provenance: &Provenance, command: &str, started_at: SystemTime, summary: &load::LoadSummary<T>) -> LoadRecord {
+    let mut provenance = provenance.clone();
+    if summary.interrupted {
+        provenance.checksum = None;
+    }
+    LoadRecord {
+        parsed: summary.parsed,
+        written: summary.written,
+        updated: summary.updated,
+        rejected: (summary.rejected.len() + summary.failed.len()) as u64,
+        skipped: summary.skipped.len() as u64 + summary.existing,
+        interrupted: summary.interrupted,
+        ..LoadRecord::finished(provenance, command, started_at)
+    }
+}
+
+// `aircraft` if it passes the checks every load applies, refused with every reason otherwise.
+fn validated(aircraft: Aircraft) -> Result<Aircraft> {
+    let reasons = validate::validate(&aircraft);
+    if !reasons.is_empty() {
+        return Err(Error::InvalidInput(format!("{}: {}", aircraft.icao_code, reasons.join("; "))));
+    }
+    Ok(aircraft)
+}
+
+// Records a single-record add, edit or delete in load_history like a run of one record.
+async fn record_single(storage: &dyn Storage, provenance: &Provenance, command: &str, started_at: SystemTime, written: u64, deleted: u64) -> Result<()> {
+    let updated = (command == "edit").then_some(written);
+    let parsed = written.max(deleted);
+    storage.record_load(&LoadRecord { parsed, written, updated, deleted, ..LoadRecord::finished(provenance.clone(), command, started_at) }).await
+}
+
+// Tells --notify-url, if given, about a finished run, and prints its summary with --output json.
+async fn notify_finished(global: &cli::GlobalArgs, record: &LoadRecord) {
+    let summary = RunSummary::finished(record);
+    if global.run_output == cli::RunOutput::Json {
+        match serde_json::to_string(&summary) {
+            Ok(json) => println!("{}", json),
+            Err(error) => error!("cannot print run summary: {}", error),
+        }
+    }
+    if let Some(webhook) = global.webhook() {
+        webhook.notify(&summary).await;
+    }
+}
+
+// Tells --notify-url, if given, about a run of `command` that failed.
+async fn notify_failed(global: &cli::GlobalArgs, command: &str, started_at: SystemTime, error: &Error) {
+    if let Some(webhook) = global.webhook() {
+        webhook.notify(&RunSummary::failed(command, started_at, error)).await;
+    }
+}
+
+// Destructive commands go ahead with --yes, otherwise only after the user confirms on a
+// terminal, unless the config requires --yes.
+fn confirm(global: &cli::GlobalArgs, yes: bool, question: &str) -> Result<bool> {
+    if yes {
+        return Ok(true);
+    }
+    if global.require_yes {
+        let profile = global.profile.as_deref().map(|profile| format!(" of profile {}", profile)).unwrap_or_default();
+        return Err(Error::Config(format!("{} needs --yes, as the config{} requires", question, profile)));
+    }
+    if !io::stdin().is_terminal() {
+        return Err(Error::Config(format!("{} needs --yes when not running interactively", question)));
+    }
+    eprint!("{} [y/N] ", question);
+    io::stderr().flush().map_err(|source| Error::Write { path: PathBuf::from("terminal"), source })?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).map_err(|source| Error::Io { path: PathBuf::from("terminal"), source })?;
+    Ok(matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes"))
+}
 
-    return aircrafts;
+async fn stream<T: Record>(path: PathBuf, format: Format, _: &AircraftStore) -> Result<RecordStream<T>> {
+    input::stream_records(path, format)
 }
 
-async fn create_mongodb() -> mongodb::Collection<Document> {
-    // Replace the placeholder with your Atlas connection string
-    let uri = env::var("MONGODB_URL").expect("cannot get env var MONGODB_URL");
-    // Create a new client and connect to the server
-    let client = Client::with_uri_str(uri).await.expect("cannot create mongo client");
-    // Get a handle on the movies collection
-    let database = client.database("flights-admin");
-    let aircraft_collection = database.collection::<Document>("aircraft");
-    return aircraft_collection
+// Routes with the distance between their airports, measured against the airports already
+// loaded; routes whose airports are missing are set aside as orphans.
+async fn stream_routes(path: PathBuf, format: Format, mongo: &AircraftStore) -> Result<RecordStream<Route>> {
+    let airports = AirportIndex::new(mongo.records::<Airport>(Airport::COLLECTION).find(doc! {}).await?);
+    let routes = input::stream_records::<Route>(path, format)?.map(move |route| {
+        route.map(|mut route| {
+            route.measure(&airports);
+            route
+        })
+    });
+    Ok(Box::new(routes))
+}
+
+// Input paths of the form s3://bucket/key are read from S3, given the s3 feature.
+fn s3_url(path: &Path) -> Option<&str> {
+    path.to_str().filter(|path| path.starts_with("s3://"))
+}
+
+#[cfg(not(feature = "s3"))]
+fn needs_s3() -> Error {
+    Error::Config("reading s3:// inputs needs the s3 feature".to_string())
+}
+
+#[cfg(not(feature = "embedded"))]
+fn needs_embedded() -> Error {
+    Error::Config("--from-embedded needs the embedded feature".to_string())
+}
+
+// The aircraft a load reads, corrected by `overrides`: the embedded dataset with
+// --from-embedded, its sources merged with --source, its input otherwise; and the fields
+// merged sources disagreed on.
+async fn load_input(global: &cli::GlobalArgs, args: &cli::LoadArgs, overrides: Option<&Overrides>) -> Result<(input::AircraftStream, Vec<FieldConflict>)> {
+    let (aircrafts, conflicts) = if args.from_embedded {
+        (hooks::apply(embedded_input(&args.source)?, &args.source.hooks)?, Vec::new())
+    } else if !args.sources.is_empty() {
+        merge_sources(args)?
+    } else {
+        (open_input(global, &args.source).await?, Vec::new())
+    };
+    Ok((override_input(aircrafts, overrides), conflicts))
+}
+
+fn override_input(aircrafts: input::AircraftStream, overrides: Option<&Overrides>) -> input::AircraftStream {
+    match overrides {
+        Some(overrides) => overrides.apply(aircrafts),
+        None => aircrafts,
+    }
+}
+
+// Locks the collection a command writes for the run, so no other run writes to it
+// meanwhile. Only the mongo backend keeps locks.
+async fn lock_collection(global: &cli::GlobalArgs, provenance: &Provenance, force: bool) -> Result<Option<LoadLock>> {
+    if global.backend != cli::Backend::Mongo {
+        return Ok(None);
+    }
+    Ok(Some(connect_mongo(global, provenance).await?.lock(DEFAULT_LEASE, force).await?))
+}
+
+// Runs the writes of a command under `locks`, failing them with Error::Locked once another
+// run takes one of the leases, and gives the locks up however they end.
+async fn locked<T>(locks: impl IntoIterator<Item = LoadLock>, writes: impl Future<Output = Result<T>>) -> Result<T> {
+    let locks: Vec<LoadLock> = locks.into_iter().collect();
+    let written = match locks.is_empty() {
+        true => writes.await,
+        false => tokio::select! {
+            written = writes => written,
+            (lost, _, _) = future::select_all(locks.iter().map(|lock| Box::pin(lock.lost()))) => Err(lost),
+        },
+    };
+    let mut released = Ok(());
+    for lock in locks {
+        released = released.and(lock.release().await);
+    }
+    let written = written?;
+    released.map(|()| written)
+}
+
+// Records the overrides on the documents a load or sync wrote, as its last step; other
+// backends only store the corrected fields.
+async fn stamp_overrides(global: &cli::GlobalArgs, provenance: &Provenance, overrides: Option<&Overrides>) -> Result<()> {
+    let Some(overrides) = overrides.filter(|_| global.backend == cli::Backend::Mongo) else { return Ok(()) };
+    let stamped = connect_mongo(global, provenance).await?.stamp_overrides(overrides).await?;
+    info!(stamped, file = %overrides.source_file, "applied overrides");
+    Ok(())
+}
+
+// Merges the --source files, writing where every field was taken from to --origins.
+fn merge_sources(args: &cli::LoadArgs) -> Result<(input::AircraftStream, Vec<FieldConflict>)> {
+    let merged = merge::merge(merge::read_sources(&args.sources, &args.source.input_options())?);
+    merge::write_origins(&args.origins, &merged.aircraft)?;
+    if !merged.conflicts.is_empty() {
+        warn!(fields = merged.conflicts.len(), "sources disagree, keeping the value of the first source giving each field");
+    }
+    let aircrafts = merged.aircraft.into_iter().map(|merged| Ok(merged.aircraft));
+    Ok((hooks::apply(Box::new(aircrafts), &args.source.hooks)?, merged.conflicts))
+}
+
+#[cfg(feature = "embedded")]
+fn embedded_input(source: &cli::SourceArgs) -> Result<input::AircraftStream> {
+    embedded::stream(&source.input_options())
+}
+
+#[cfg(not(feature = "embedded"))]
+fn embedded_input(_: &cli::SourceArgs) -> Result<input::AircraftStream> {
+    Err(needs_embedded())
+}
+
+#[cfg(feature = "embedded")]
+fn embedded_provenance() -> Result<Provenance> {
+    Ok(embedded::provenance())
+}
+
+#[cfg(not(feature = "embedded"))]
+fn embedded_provenance() -> Result<Provenance> {
+    Err(needs_embedded())
+}
+
+// Serves the aircraft of the embedded dataset, for demos without a database.
+#[cfg(all(feature = "embedded", feature = "server"))]
+async fn serve_embedded(args: &cli::ServeArgs) -> Result<()> {
+    if args.access_options() != server::AccessOptions::default() || args.grpc_port.is_some() {
+        return Err(Error::Config("API keys, rate limits and gRPC need the mongo backend".to_string()));
+    }
+    let storage: std::sync::Arc<dyn Storage> = std::sync::Arc::new(embedded::storage()?);
+    server::serve_storage(storage, args.address()).await
+}
+
+async fn open_input(global: &cli::GlobalArgs, source: &cli::SourceArgs) -> Result<input::AircraftStream> {
+    let path = source.path(&global.input);
+    let aircrafts = match s3_url(path) {
+        #[cfg(feature = "s3")]
+        Some(url) => {
+            let object = S3Object::connect(url, &source.s3_options()).await?;
+            input::stream_aircraft_from(object.open().await?, &source.input_options())?
+        }
+        #[cfg(not(feature = "s3"))]
+        Some(_) => return Err(needs_s3()),
+        None => input::stream_aircraft(path, &source.input_options())?,
+    };
+    hooks::apply(aircrafts, &source.hooks)
+}
+
+// An S3 object's ETag stands in for the checksum of a local file.
+async fn input_provenance(global: &cli::GlobalArgs, source: &cli::SourceArgs) -> Result<Provenance> {
+    let path = source.path(&global.input);
+    match s3_url(path) {
+        #[cfg(feature = "s3")]
+        Some(url) => {
+            let object = S3Object::connect(url, &source.s3_options()).await?;
+            Ok(Provenance { source_file: Some(url.to_string()), checksum: object.etag().await?, ..Provenance::new() })
+        }
+        #[cfg(not(feature = "s3"))]
+        Some(_) => Err(needs_s3()),
+        None => Provenance::for_file(path),
+    }
+}
+
+// Loads one of the non-aircraft datasets into its own collection of the MongoDB database,
+// reading the input file with `open`, which can look up what it needs through the store.
+async fn load_records<T: Record>(
+    global: &cli::GlobalArgs,
+    args: &cli::DatasetArgs,
+    force: bool,
+    command: &str,
+    open: impl AsyncFnOnce(PathBuf, Format, &AircraftStore) -> Result<RecordStream<T>>,
+) -> Result<()> {
+    if global.backend != cli::Backend::Mongo {
+        return Err(Error::Config(format!("{} is only supported by the mongo backend", command)));
+    }
+    let started_at = SystemTime::now();
+    let path = args.path(T::COLLECTION);
+    let provenance = Provenance::for_file(&path)?;
+    info!(load_id = %provenance.load_id, "starting run");
+    let mongo = connect_mongo(global, &provenance).await?;
+    let store = mongo.records::<T>(T::COLLECTION);
+    if args.run.skip_unchanged && unchanged(store.last_checksum().await?, &provenance) {
+        return Ok(());
+    }
+    let lock = mongo.lock_collection(T::COLLECTION, DEFAULT_LEASE, force).await?;
+    // Records referring to codes missing from the collections they depend on are set
+    // aside before loading and written to the orphans report.
+    let known = KnownCodes::fetch(&mongo, T::REFERENCES).await?;
+    // Filled as the records are parsed, on a thread of the load's own.
+    let orphaned = Arc::new(Mutex::new(Vec::new()));
+    let progress = LoadProgress::start(&path, global.progress());
+    let setting_aside = Arc::clone(&orphaned);
+    let records = open(path, args.format, &mongo).await?.filter(move |record| {
+        let Ok(record) = record else { return true };
+        let reasons = known.orphans(record);
+        if reasons.is_empty() {
+            return true;
+        }
+        warn!(key = %record.key(), ?reasons, "orphaned record");
+        setting_aside.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(validate::Rejection { record: record.clone(), reasons });
+        false
+    });
+    let summary = locked(Some(lock), async {
+        if !args.skip_indexes {
+            store.ensure_indexes().await?;
+        }
+        load::load_with_progress(&store, records, &args.load_options(), &progress).await
+    })
+    .await?;
+    drop(progress);
+    let orphans = mem::take(&mut *orphaned.lock().unwrap_or_else(|poisoned| poisoned.into_inner()));
+    let record = LoadRecord {
+        rejected: (summary.rejected.len() + summary.failed.len() + orphans.len()) as u64,
+        ..load_record(&provenance, command, started_at, &summary)
+    };
+    store.record_load(&record).await?;
+    notify_finished(global, &record).await;
+    if let Some(report) = &args.run.report {
+        RunReport::load(record, &summary, Vec::new()).write(report)?;
+    }
+    print_load_summary(&summary, &provenance.load_id, &args.run.rejects, &args.write.failures, global.run_output)?;
+    if !orphans.is_empty() {
+        validate::write_rejects(&args.orphans, &orphans)?;
+        if global.run_output == cli::RunOutput::Text {
+            println!("set aside {} orphaned records, see {}", orphans.len(), args.orphans.display());
+        }
+    }
+    Ok(())
+}
+
+// Runs one of the enrichments, holding the lock of the collection it writes.
+async fn enrich(global: &cli::GlobalArgs, args: &cli::EnrichArgs) -> Result<()> {
+    let force = args.lock.force;
+    match &args.enrichment {
+        cli::Enrichment::Doc8643(args) => enrich_file::<TypeDetails>(global, &args.path("doc8643"), args.format, args.parse.lenient, force, "enrich doc8643").await,
+        cli::Enrichment::Performance(args) => enrich_file::<Performance>(global, &args.path("performance"), args.format, args.parse.lenient, force, "enrich performance").await,
+        cli::Enrichment::Translations(args) => enrich_file::<Translation>(global, &args.path("translations"), args.format, args.parse.lenient, force, "enrich translations").await,
+        cli::Enrichment::Aliases(args) => enrich_file::<Alias>(global, &args.path("aliases"), args.format, args.parse.lenient, force, "enrich aliases").await,
+        cli::Enrichment::Classify => enrich_classify(global, force).await,
+        cli::Enrichment::Wikidata(args) => enrich_wikidata(global, args, force).await,
+        cli::Enrichment::Timezones => enrich_timezones(global, force).await,
+        cli::Enrichment::Nationality => enrich_nationality(global, force).await,
+    }
+}
+
+// Merges the type data of `file`, DOC 8643 details or performance figures, into the stored
+// aircraft, recording the run in the load history as `command`.
+async fn enrich_file<T: TypeData + Send + 'static>(global: &cli::GlobalArgs, file: &Path, format: Format, lenient: bool, force: bool, command: &str) -> Result<()> {
+    if global.backend != cli::Backend::Mongo {
+        return Err(Error::Config(format!("{} is only supported by the mongo backend", command)));
+    }
+    let started_at = SystemTime::now();
+    let provenance = Provenance::for_file(file)?;
+    info!(load_id = %provenance.load_id, "starting run");
+    let store = connect_mongo(global, &provenance).await?;
+    let lock = store.lock(DEFAULT_LEASE, force).await?;
+    let entries = input::stream_deserialized::<T>(file, format)?;
+    let summary = locked(Some(lock), enrich::enrich(&store, entries, lenient)).await?;
+    let record = LoadRecord {
+        parsed: summary.parsed,
+        written: summary.enriched,
+        updated: Some(summary.enriched),
+        rejected: summary.rejected.len() as u64,
+        skipped: summary.skipped.len() as u64,
+        ..LoadRecord::finished(provenance.clone(), command, started_at)
+    };
+    store.record_load(&record).await?;
+    notify_finished(global, &record).await;
+    println!(
+        "enriched {} aircraft from {} entries (load id {})",
+        summary.enriched, summary.parsed, provenance.load_id
+    );
+    if !summary.unknown.is_empty() {
+        println!("{} designators are not loaded: {}", summary.unknown.len(), summary.unknown.join(", "));
+    }
+    if !summary.missing.is_empty() {
+        println!("{} loaded aircraft have no entry: {}", summary.missing.len(), summary.missing.join(", "));
+    }
+    if !summary.rejected.is_empty() {
+        println!("rejected {} invalid entries:", summary.rejected.len());
+        for message in &summary.rejected {
+            println!("  {}", message);
+        }
+    }
+    if !summary.skipped.is_empty() {
+        println!("skipped {} unparseable entries:", summary.skipped.len());
+        for message in &summary.skipped {
+            println!("  {}", message);
+        }
+    }
+    Ok(())
+}
+
+// Stores the fields derived from the descriptions of the stored aircraft, recording the run
+// in the load history.
+async fn enrich_classify(global: &cli::GlobalArgs, force: bool) -> Result<()> {
+    if global.backend != cli::Backend::Mongo {
+        return Err(Error::Config("enrich classify is only supported by the mongo backend".to_string()));
+    }
+    let started_at = SystemTime::now();
+    let provenance = Provenance::new();
+    info!(load_id = %provenance.load_id, "starting run");
+    let store = connect_mongo(global, &provenance).await?;
+    let lock = store.lock(DEFAULT_LEASE, force).await?;
+    let summary = locked(Some(lock), classify::classify_stored(&store)).await?;
+    let record = LoadRecord {
+        parsed: summary.parsed,
+        written: summary.classified,
+        updated: Some(summary.classified),
+        ..LoadRecord::finished(provenance.clone(), "enrich classify", started_at)
+    };
+    store.record_load(&record).await?;
+    notify_finished(global, &record).await;
+    println!(
+        "classified {} of {} aircraft (load id {})",
+        summary.classified, summary.parsed, provenance.load_id
+    );
+    if !summary.unclassified.is_empty() {
+        println!("{} aircraft match no body type rule: {}", summary.unclassified.len(), summary.unclassified.join(", "));
+    }
+    Ok(())
+}
+
+// Merges what Wikidata knows about each stored aircraft type into it, recording the run in
+// the load history.
+async fn enrich_wikidata(global: &cli::GlobalArgs, args: &cli::WikidataArgs, force: bool) -> Result<()> {
+    if global.backend != cli::Backend::Mongo {
+        return Err(Error::Config("enrich wikidata is only supported by the mongo backend".to_string()));
+    }
+    let started_at = SystemTime::now();
+    let provenance = Provenance { source_file: Some(args.endpoint.clone()), ..Provenance::new() };
+    info!(load_id = %provenance.load_id, "starting run");
+    let store = connect_mongo(global, &provenance).await?;
+    let mut client = wikidata::Wikidata::new(&args.endpoint, &args.cache_dir, Duration::from_millis(args.delay))?;
+    let lock = store.lock(DEFAULT_LEASE, force).await?;
+    let summary = locked(Some(lock), wikidata::enrich(&store, &mut client)).await?;
+    let record = LoadRecord {
+        parsed: summary.parsed,
+        written: summary.enriched,
+        updated: Some(summary.enriched),
+        ..LoadRecord::finished(provenance.clone(), "enrich wikidata", started_at)
+    };
+    store.record_load(&record).await?;
+    notify_finished(global, &record).await;
+    println!(
+        "enriched {} of {} aircraft from wikidata (load id {})",
+        summary.enriched, summary.parsed, provenance.load_id
+    );
+    if !summary.unknown.is_empty() {
+        println!("{} designators are not on wikidata: {}", summary.unknown.len(), summary.unknown.join(", "));
+    }
+    Ok(())
+}
+
+// Sets the time zone of every stored airport from its coordinates, recording the run in the
+// load history of the airports collection.
+async fn enrich_timezones(global: &cli::GlobalArgs, force: bool) -> Result<()> {
+    if global.backend != cli::Backend::Mongo {
+        return Err(Error::Config("enrich timezones is only supported by the mongo backend".to_string()));
+    }
+    let finder = timezones::TimezoneFinder::new()?;
+    let started_at = SystemTime::now();
+    let provenance = Provenance::new();
+    info!(load_id = %provenance.load_id, "starting run");
+    let store = connect_mongo(global, &provenance).await?;
+    let airports = store.records::<Airport>(Airport::COLLECTION);
+    let lock = store.lock_collection(Airport::COLLECTION, DEFAULT_LEASE, force).await?;
+    let summary = locked(Some(lock), timezones::enrich_airports(&airports, &finder)).await?;
+    let record = LoadRecord {
+        parsed: summary.parsed,
+        written: summary.updated,
+        updated: Some(summary.updated),
+        ..LoadRecord::finished(provenance.clone(), "enrich timezones", started_at)
+    };
+    airports.record_load(&record).await?;
+    notify_finished(global, &record).await;
+    println!(
+        "set the time zone of {} of {} airports, correcting {} (load id {})",
+        summary.updated,
+        summary.parsed,
+        summary.corrected.len(),
+        provenance.load_id
+    );
+    if !summary.unresolved.is_empty() {
+        println!("{} airports lie in no time zone: {}", summary.unresolved.len(), summary.unresolved.join(", "));
+    }
+    Ok(())
+}
+
+// Tags the stored registrations with the country of their nationality mark, recording the run
+// in the load history of the registrations collection.
+async fn enrich_nationality(global: &cli::GlobalArgs, force: bool) -> Result<()> {
+    if global.backend != cli::Backend::Mongo {
+        return Err(Error::Config("enrich nationality is only supported by the mongo backend".to_string()));
+    }
+    let started_at = SystemTime::now();
+    let provenance = Provenance::new();
+    info!(load_id = %provenance.load_id, "starting run");
+    let store = connect_mongo(global, &provenance).await?;
+    let marks = NationalityMarks::with_countries(&store.records::<Country>(Country::COLLECTION).find(doc! {}).await?);
+    let registrations = store.records::<Registration>(Registration::COLLECTION);
+    let lock = store.lock_collection(Registration::COLLECTION, DEFAULT_LEASE, force).await?;
+    let summary = locked(Some(lock), nationality::tag_registrations(&registrations, &marks)).await?;
+    let record = LoadRecord {
+        parsed: summary.parsed,
+        written: summary.tagged,
+        updated: Some(summary.tagged),
+        ..LoadRecord::finished(provenance.clone(), "enrich nationality", started_at)
+    };
+    registrations.record_load(&record).await?;
+    notify_finished(global, &record).await;
+    println!("tagged {} of {} registrations (load id {})", summary.tagged, summary.parsed, provenance.load_id);
+    if !summary.unknown.is_empty() {
+        let examples: Vec<&str> = summary.unknown.iter().take(10).map(String::as_str).collect();
+        println!("{} registrations have no known nationality mark, e.g. {}", summary.unknown.len(), examples.join(", "));
+    }
+    Ok(())
+}
+
+// A registration's country, from its nationality mark, with the registration as stored.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RegistrationLookup {
+    registration: String,
+    country_code: Option<String>,
+    country: Option<Country>,
+    record: Option<Registration>,
+}
+
+async fn query_registration(global: &cli::GlobalArgs, registration: &str) -> Result<()> {
+    if global.backend != cli::Backend::Mongo {
+        return Err(Error::Config("query registration is only supported by the mongo backend".to_string()));
+    }
+    let store = connect_mongo(global, &Provenance::new()).await?;
+    let countries = store.records::<Country>(Country::COLLECTION).find(doc! {}).await?;
+    let registration = registration.trim().to_ascii_uppercase();
+    let country_code = NationalityMarks::with_countries(&countries).country(&registration).map(str::to_string);
+    let country = countries.into_iter().find(|country| Some(&country.iso_code) == country_code.as_ref());
+    let record = store.records::<Registration>(Registration::COLLECTION).find_by_key(&registration).await?;
+    println!("{}", serde_json::to_string_pretty(&RegistrationLookup { registration, country_code, country, record })?);
+    Ok(())
+}
+
+// A hexdb entry with the catalog entry of the type it reports.
+#[derive(Serialize)]
+struct HexLookup {
+    #[serde(flatten)]
+    entry: HexEntry,
+    aircraft: Option<Aircraft>,
+}
+
+// The codes listed in `path`, or stdin for -, separated by lines, spaces or commas.
+fn read_codes(path: &Path) -> Result<Vec<String>> {
+    let read = match path == Path::new("-") {
+        true => io::read_to_string(io::stdin()),
+        false => fs::read_to_string(path),
+    };
+    let text = read.map_err(|source| Error::Io { path: path.to_path_buf(), source })?;
+    let codes = resolve::normalize(text.split(|c: char| c.is_whitespace() || c == ','));
+    if codes.len() > resolve::MAX_CODES {
+        return Err(Error::InvalidInput(format!("{} lists {} codes, at most {} are resolved at once", path.display(), codes.len(), resolve::MAX_CODES)));
+    }
+    Ok(codes)
+}
+
+fn print_resolution(resolution: &resolve::Resolution) {
+    for matched in &resolution.matches {
+        let aircraft = &matched.aircraft;
+        println!("{:<5} {:<5} {:<4} {}", matched.code, aircraft.icao_code, aircraft.iata_code.as_deref().unwrap_or("-"), aircraft.description);
+    }
+    for ambiguous in &resolution.ambiguities {
+        let types: Vec<&str> = ambiguous.aircraft.iter().map(|aircraft| aircraft.icao_code.as_str()).collect();
+        println!("{:<5} ambiguous: {}", ambiguous.code, types.join(" "));
+    }
+    for code in &resolution.misses {
+        println!("{:<5} unknown", code);
+    }
+    println!(
+        "{} matched, {} ambiguous, {} unknown",
+        resolution.matches.len(),
+        resolution.ambiguities.len(),
+        resolution.misses.len()
+    );
+}
+
+// One line per code: its ICAO type designators, or that it is unknown.
+fn print_equipment(resolved: &[equipment::Equipment]) {
+    for equipment in resolved {
+        let types: Vec<&str> = equipment.aircraft.iter().map(|aircraft| aircraft.icao_code.as_str()).collect();
+        match types.is_empty() {
+            true => println!("{}  unknown", equipment.iata_code),
+            false => println!("{}  {}", equipment.iata_code, types.join(" ")),
+        }
+    }
+    let unknown = equipment::unknown(resolved);
+    if !unknown.is_empty() {
+        println!("{} of {} codes unknown: {}", unknown.len(), resolved.len(), unknown.join(" "));
+    }
+}
+
+async fn query_hex(global: &cli::GlobalArgs, hex: &str) -> Result<()> {
+    if global.backend != cli::Backend::Mongo {
+        return Err(Error::Config("query hex is only supported by the mongo backend".to_string()));
+    }
+    let store = connect_mongo(global, &Provenance::new()).await?;
+    let hexdb = store.records::<HexEntry>(HexEntry::COLLECTION);
+    if let Some(entry) = hexdb.find_by_key(&hex.trim().to_ascii_uppercase()).await? {
+        let aircraft = store.find_by_icao(&entry.type_code).await?;
+        println!("{}", serde_json::to_string_pretty(&HexLookup { entry, aircraft })?);
+    }
+    Ok(())
+}
+
+async fn query_airports(global: &cli::GlobalArgs, near: &cli::Point, radius_km: f64, limit: Option<i64>, json: bool) -> Result<()> {
+    if global.backend != cli::Backend::Mongo {
+        return Err(Error::Config("query airports is only supported by the mongo backend".to_string()));
+    }
+    let store = connect_mongo(global, &Provenance::new()).await?;
+    let airports = store.records::<Airport>(Airport::COLLECTION).near(near.latitude, near.longitude, radius_km, limit).await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&airports)?);
+        return Ok(());
+    }
+    for nearby in &airports {
+        let airport = &nearby.airport;
+        println!("{:>8.1} km  {}  {:<3}  {}", nearby.distance_km, airport.icao_code, airport.iata_code, airport.name);
+    }
+    Ok(())
+}
+
+// Prints the aircraft as aircraft_history recorded them at `at`.
+async fn query_as_of(global: &cli::GlobalArgs, args: &cli::QueryArgs, at: &DateTime<Utc>) -> Result<()> {
+    if global.backend != cli::Backend::Mongo {
+        return Err(Error::Config("query --as-of is only supported by the mongo backend".to_string()));
+    }
+    let store = connect_mongo(global, &Provenance::new()).await?;
+    let fields = store.field_names();
+    let filter = match (&args.icao, &args.iata) {
+        (Some(icao), _) => doc! { &fields.icao_code: icao.to_ascii_uppercase() },
+        (None, Some(iata)) => doc! { &fields.iata_code: iata.to_ascii_uppercase() },
+        (None, None) => unreachable!("clap requires --icao, --iata or a lookup"),
+    };
+    let aircrafts = store.as_of(filter, bson::DateTime::from_millis(at.timestamp_millis())).await?;
+    let records = aircrafts.into_iter().map(|aircraft| StoredAircraft { id: String::new(), aircraft }).collect();
+    let records = localize(records, args.lang.as_deref());
+    let options = args.print.export_options();
+    export::export(io::stdout().lock(), records, &options)
+}
+
+// Prints the aircraft matching --filter, which is compiled to a MongoDB query.
+async fn query_filtered(global: &cli::GlobalArgs, args: &cli::QueryArgs, filter: &Filter) -> Result<()> {
+    if global.backend != cli::Backend::Mongo {
+        return Err(Error::Config("--filter is only supported by the mongo backend".to_string()));
+    }
+    let store = connect_mongo(global, &Provenance::new()).await?.with_inactive(args.inactive);
+    let projection = (!args.print.fields.is_empty()).then(|| filter::projection(&args.print.fields, store.field_names()));
+    let records: Vec<StoredAircraft> = store.stream_with_ids(filter.to_document(store.field_names()), projection).await?.try_collect().await?;
+    let records = records.into_iter().map(|record| StoredAircraft { id: String::new(), ..record }).collect();
+    let options = args.print.export_options();
+    // Already in ICAO order.
+    export::write(io::stdout().lock(), &localize(records, args.lang.as_deref()), &options)
+}
+
+// Loads airports into the airports table of DynamoDB. Nothing is checked against the
+// other datasets, which only MongoDB holds.
+#[cfg(feature = "dynamodb")]
+async fn load_airports_dynamodb(global: &cli::GlobalArgs, args: &cli::AirportArgs) -> Result<()> {
+    let started_at = SystemTime::now();
+    let path = args.dataset.path(Airport::COLLECTION);
+    let provenance = Provenance::for_file(&path)?;
+    info!(load_id = %provenance.load_id, "starting run");
+    let storage = connect_dynamodb(global, &provenance).await?;
+    let progress = LoadProgress::start(&path, global.progress());
+    let airports = input::stream_airports(path, args.dataset.format, args.filter())?;
+    let summary = load::load_with_progress(&storage, airports, &args.dataset.load_options(), &progress).await?;
+    drop(progress);
+    let record = load_record(&provenance, "load airports", started_at, &summary);
+    storage.record_load(&record).await?;
+    notify_finished(global, &record).await;
+    print_load_summary(&summary, &provenance.load_id, &args.dataset.run.rejects, &args.dataset.write.failures, global.run_output)
+}
+
+async fn load_dataset(global: &cli::GlobalArgs, dataset: &cli::Dataset, force: bool) -> Result<()> {
+    match dataset {
+        #[cfg(feature = "dynamodb")]
+        cli::Dataset::Airports(args) if global.backend == cli::Backend::Dynamodb => load_airports_dynamodb(global, args).await,
+        cli::Dataset::Airports(args) => {
+            let open = async |path, format, _: &AircraftStore| input::stream_airports(path, format, args.filter());
+            load_records::<Airport>(global, &args.dataset, force, "load airports", open).await
+        }
+        cli::Dataset::Airlines(args) => load_records(global, args, force, "load airlines", stream::<Airline>).await,
+        cli::Dataset::Routes(args) => load_records(global, args, force, "load routes", stream_routes).await,
+        cli::Dataset::Countries(args) => load_records(global, args, force, "load countries", stream::<Country>).await,
+        cli::Dataset::Registrations(args) => {
+            // Type designators are resolved against the aircraft already loaded.
+            let open = async |path: PathBuf, format, mongo: &AircraftStore| {
+                let types = input::TypeIndex::new(&mongo.find_all().await?);
+                input::stream_registrations(&path, format, &args.acftref(&path), types)
+            };
+            load_records::<Registration>(global, &args.dataset, force, "load registrations", open).await
+        }
+        cli::Dataset::Hexdb(args) => {
+            let open = async |path, format, _: &AircraftStore| input::stream_hexdb(path, format);
+            load_records::<HexEntry>(global, args, force, "load hexdb", open).await
+        }
+    }
+}
+
+// Loads an aircraft file found by a multi-file load, with its own provenance and rejects file.
+async fn load_aircraft_file(global: &cli::GlobalArgs, args: &cli::LoadArgs, file: &batch::Input) -> Result<()> {
+    let started_at = SystemTime::now();
+    let provenance = Provenance::for_file(&file.path)?;
+    info!(load_id = %provenance.load_id, "starting run");
+    let storage = create_storage(global, &provenance).await?;
+    if args.run.skip_unchanged && unchanged(storage.last_checksum().await?, &provenance) {
+        return Ok(());
+    }
+    let lock = lock_collection(global, &provenance, args.lock.force).await?;
+    let (summary, conflicts) = locked(lock, async {
+        if args.snapshot {
+            take_snapshot(global, &provenance).await?;
+        }
+        let progress = LoadProgress::start(&file.path, global.progress());
+        let options = input::InputOptions { format: file.format, ..args.source.input_options() };
+        let aircrafts = hooks::apply(input::stream_aircraft(&file.path, &options)?, &args.source.hooks)?;
+        let overrides = Overrides::discover(global.overrides.as_deref())?;
+        let (aircrafts, conflicts) = dedup_input(args, &file.report(&args.conflicts), override_input(aircrafts, overrides.as_ref()))?;
+        if !args.skip_indexes {
+            storage.ensure_indexes().await?;
+        }
+        let options = load_options(args, storage.as_ref()).await?;
+        let summary = load::load_with_progress(storage.as_ref(), aircrafts, &options, &progress).await?;
+        drop(progress);
+        stamp_overrides(global, &provenance, overrides.as_ref()).await?;
+        Ok((summary, conflicts))
+    })
+    .await?;
+    let record = load_record(&provenance, "load", started_at, &summary);
+    storage.record_load(&record).await?;
+    notify_finished(global, &record).await;
+    if let Some(report) = &args.run.report {
+        RunReport::load(record, &summary, conflicts).write(&file.report(report))?;
+    }
+    print_load_summary(&summary, &provenance.load_id, &file.report(&args.run.rejects), &file.report(&args.write.failures), global.run_output)
+}
+
+// The options of an aircraft load, with the ICAO codes already stored when --skip-existing
+// leaves them out.
+async fn load_options(args: &cli::LoadArgs, storage: &dyn Storage) -> Result<load::LoadOptions> {
+    let mut options = args.load_options();
+    if args.skip_existing {
+        options.existing = storage.find_all().await?.into_iter().map(|aircraft| aircraft.icao_code).collect();
+    }
+    Ok(options)
+}
+
+// Keeps one entry per ICAO code with --dedup, reading the whole input first, and writes the
+// codes listed with different fields to `conflicts`, returning them too.
+fn dedup_input(args: &cli::LoadArgs, conflicts: &Path, aircrafts: input::AircraftStream) -> Result<(input::AircraftStream, Vec<String>)> {
+    let Some(strategy) = args.dedup else { return Ok((aircrafts, Vec::new())) };
+    let deduped = dedup::dedup(aircrafts, strategy);
+    if !deduped.conflicts.is_empty() {
+        dedup::write_conflicts(conflicts, &deduped.conflicts)?;
+        println!("found {} ICAO codes listed with different fields, see {}", deduped.conflicts.len(), conflicts.display());
+        if strategy == DedupStrategy::Error {
+            return Err(Error::InvalidInput(format!("{} ICAO codes are listed with different fields", deduped.conflicts.len())));
+        }
+    }
+    let conflicting = deduped.conflicts.into_iter().map(|conflict| conflict.icao_code).collect();
+    Ok((Box::new(deduped.aircrafts.into_iter()), conflicting))
+}
+
+// Copies the collection into aircraft_snapshots under the load id before a --snapshot load
+// writes to it, so `restore --snapshot <load id>` can undo the load.
+async fn take_snapshot(global: &cli::GlobalArgs, provenance: &Provenance) -> Result<()> {
+    let copied = connect_mongo(global, provenance).await?.snapshot(&provenance.load_id).await?;
+    println!("snapshot {} holds {} documents", provenance.load_id, copied);
+    Ok(())
+}
+
+// Loads every file found through the positional paths as the dataset it holds, going on
+// past files that fail and returning the first error once all have been tried.
+// With --atomic every file is loaded into a staging collection and the first failure stops
+// the batch; the staging collections replace the live ones only if none failed.
+async fn load_batch(global: &cli::GlobalArgs, args: &cli::LoadArgs) -> Result<()> {
+    if args.dry_run || args.swap || args.remote.url.is_some() {
+        return Err(Error::Config("--dry-run, --swap and --url load a single file".to_string()));
+    }
+    let (files, skipped) = batch::discover(&args.paths())?;
+    // With --output json only the summary of each file loaded is printed.
+    let text = global.run_output == cli::RunOutput::Text;
+    for path in skipped.iter().filter(|_| text) {
+        println!("skipping {}: unknown dataset or format", path.display());
+    }
+    let mut global = global.clone();
+    if args.atomic {
+        if global.backend != cli::Backend::Mongo {
+            return Err(Error::Config("--atomic is only supported by the mongo backend".to_string()));
+        }
+        if global.history || global.audit {
+            return Err(Error::Config("--history and --audit cannot follow an --atomic load".to_string()));
+        }
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+        for file in &files {
+            let live = if file.kind == batch::Kind::Aircraft { global.collection.clone() } else { file.kind.name().to_string() };
+            let staging = format!("{}_staging_{}", live, seconds);
+            global.staged.insert(live, staging);
+        }
+    }
+    let mut first_error = None;
+    let mut failed = 0;
+    for file in &files {
+        if text {
+            println!("{} ({}):", file.path.display(), file.kind.name());
+        }
+        let loaded = match file.dataset(args) {
+            Some(dataset) => load_dataset(&global, &dataset, args.lock.force).await,
+            None => load_aircraft_file(&global, args, file).await,
+        };
+        if let Err(error) = loaded {
+            error!(path = %file.path.display(), "{}", error);
+            if text {
+                println!("failed: {}", error);
+            }
+            failed += 1;
+            let interrupted = matches!(error, Error::Interrupted);
+            first_error.get_or_insert(error);
+            if args.atomic || interrupted {
+                break;
+            }
+        }
+    }
+    if text {
+        println!("loaded {} of {} files, {} failed, {} skipped", files.len() - failed, files.len(), failed, skipped.len());
+    }
+    if args.atomic {
+        let staged = std::mem::take(&mut global.staged);
+        let store = connect_mongo(&global, &Provenance::new()).await?;
+        for (live, staging) in &staged {
+            if first_error.is_some() {
+                store.sibling(staging).drop(None).await?;
+            } else {
+                store.rename(staging, live).await?;
+            }
+        }
+        let collections = staged.keys().map(String::as_str).collect::<Vec<_>>().join(", ");
+        match (text, first_error.is_some()) {
+            (true, true) => println!("left {} unchanged", collections),
+            (true, false) => println!("replaced {}", collections),
+            (false, _) => {}
+        }
+    }
+    first_error.map_or(Ok(()), Err)
+}
+
+async fn stats(global: &cli::GlobalArgs, args: &cli::StatsArgs) -> Result<()> {
+    if global.backend != cli::Backend::Mongo {
+        return Err(Error::Config("stats is only supported by the mongo backend".to_string()));
+    }
+    let store = connect_mongo(global, &Provenance::new()).await?;
+    let stats = rust_aircraft_parser::stats::collect(&store).await?;
+    match args.json {
+        true => println!("{}", serde_json::to_string_pretty(&stats)?),
+        false => print!("{}", stats.table()),
+    }
+    Ok(())
+}
+
+async fn gc(global: &cli::GlobalArgs, args: &cli::GcArgs) -> Result<()> {
+    if global.backend != cli::Backend::Mongo {
+        return Err(Error::Config("gc is only supported by the mongo backend".to_string()));
+    }
+    let store = connect_mongo(global, &Provenance::new()).await?;
+    let garbage = store.gc(args.dry_run).await?;
+    let (drop, delete) = if args.dry_run { ("would drop", "would delete") } else { ("dropped", "deleted") };
+    for collection in &garbage.dropped {
+        println!("{} {}", drop, collection);
+    }
+    println!(
+        "{} {} abandoned staging collections, {} {} expired documents from {} in use",
+        drop,
+        garbage.dropped.len(),
+        delete,
+        garbage.expired,
+        garbage.kept.len()
+    );
+    Ok(())
+}
+
+// A preflight for loads: connecting pings the server, then the store checks the rest.
+async fn check(global: &cli::GlobalArgs) -> Result<()> {
+    if global.backend != cli::Backend::Mongo {
+        return Err(Error::Config("check is only supported by the mongo backend".to_string()));
+    }
+    let store = connect_mongo(global, &Provenance::new()).await?;
+    println!("ping ok");
+    let report = store.check().await?;
+    println!("databases: {}", report.databases.join(", "));
+    println!("collections in {}: {}", global.database, report.collections.join(", "));
+    println!("write to {}.{} ok", global.database, global.collection);
+    Ok(())
+}
+
+// Runs the self-test in a new collection next to --collection, or in a disposable container
+// with --container, printing each check.
+async fn self_test(global: &cli::GlobalArgs, args: &cli::SelfTestArgs) -> Result<()> {
+    if global.backend != cli::Backend::Mongo {
+        return Err(Error::Config("self-test is only supported by the mongo backend".to_string()));
+    }
+    #[cfg(not(feature = "testcontainers"))]
+    if args.container {
+        return Err(Error::Config("self-test --container needs the testcontainers feature".to_string()));
+    }
+    #[cfg(feature = "testcontainers")]
+    if args.container {
+        let (store, _container) = selftest::container(&global.database, &global.collection).await?;
+        return report_self_test(selftest::run(&store.with_field_names(global.field_names()?)).await?);
+    }
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+    let mut scratch = global.clone();
+    scratch.collection = format!("{}_self_test_{}", global.collection, seconds);
+    let store = connect_mongo(&scratch, &Provenance::new()).await?;
+    report_self_test(selftest::run(&store).await?)
+}
+
+fn report_self_test(checks: Vec<selftest::Check>) -> Result<()> {
+    for check in &checks {
+        println!("{} {}: {}", if check.passed { "ok" } else { "FAILED" }, check.name, check.detail);
+    }
+    match checks.iter().filter(|check| !check.passed).count() {
+        0 => Ok(()),
+        failed => Err(Error::SelfTest(failed)),
+    }
+}
+
+// Times each stage over the same synthetic aircraft, inserting into a scratch collection
+// next to --collection once per batch size and concurrency, then prints the comparison.
+async fn run_bench(global: &cli::GlobalArgs, args: &cli::BenchArgs) -> Result<()> {
+    if !args.no_insert && global.backend != cli::Backend::Mongo {
+        return Err(Error::Config("bench inserts are only supported by the mongo backend, see --no-insert".to_string()));
+    }
+    let aircrafts = bench::synthetic(args.records as usize);
+    let fields = global.field_names()?;
+    let mut measurements = vec![bench::parse(&aircrafts)?, bench::convert(&aircrafts, &fields)];
+    if !args.no_insert {
+        let store = connect_mongo(global, &Provenance::new()).await?;
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+        let scratch = format!("{}_bench_{}", global.collection, seconds);
+        for &batch_size in &args.batch_size {
+            for &concurrency in &args.concurrency {
+                info!(batch_size, concurrency, "timing inserts");
+                measurements.push(bench::insert(&store, &scratch, &aircrafts, batch_size, concurrency).await?);
+            }
+        }
+    }
+    print!("{}", bench::table(&measurements));
+    Ok(())
+}
+
+// Installs the validators of the models, with the field names the loads write.
+async fn schema_apply(global: &cli::GlobalArgs, args: &cli::SchemaArgs) -> Result<()> {
+    if global.backend != cli::Backend::Mongo {
+        return Err(Error::Config("schema is only supported by the mongo backend".to_string()));
+    }
+    let store = connect_mongo(global, &Provenance::new()).await?;
+    for collection in schema::apply(&store, args.validation_level).await? {
+        println!("validator installed on {}.{} ({})", global.database, collection, args.validation_level.as_str());
+    }
+    Ok(())
+}
+
+// Applies the pending migrations, or lists them all with --list.
+async fn migrate(global: &cli::GlobalArgs, args: &cli::MigrateArgs) -> Result<()> {
+    if global.backend != cli::Backend::Mongo {
+        return Err(Error::Config("migrate is only supported by the mongo backend".to_string()));
+    }
+    let store = connect_mongo(global, &Provenance::new()).await?;
+    if let Some(cli::Migration::Ids(ids)) = &args.command {
+        let verb = if ids.dry_run { "would convert" } else { "converted" };
+        let encoding = if global.string_ids { "strings" } else { "binaries" };
+        // Converting rewrites every reference collection, so all of them are locked.
+        let mut locks = Vec::new();
+        if !ids.dry_run {
+            locks.push(store.lock(DEFAULT_LEASE, args.lock.force).await?);
+            for collection in migrations::REFERENCE_COLLECTIONS {
+                locks.push(store.lock_collection(collection, DEFAULT_LEASE, args.lock.force).await?);
+            }
+        }
+        for (collection, converted) in locked(locks, migrations::convert_all_ids(&store, global.uuid_encoding(), ids.dry_run)).await? {
+            println!("{}: {} {} UUID _ids to {}", collection, verb, converted, encoding);
+        }
+        return Ok(());
+    }
+    if args.list {
+        for migration in migrations::status(&store).await? {
+            println!("{:>3}  {:<7}  {}", migration.version, if migration.applied { "applied" } else { "pending" }, migration.name);
+        }
+        return Ok(());
+    }
+    let lock = store.lock(DEFAULT_LEASE, args.lock.force).await?;
+    let applied = locked(Some(lock), migrations::migrate(&store, args.to)).await?;
+    if applied.is_empty() {
+        println!("{} is up to date", global.collection);
+    }
+    for migration in applied {
+        println!("applied {} ({}): {} documents modified", migration.version, migration.name, migration.modified);
+    }
+    Ok(())
+}
+
+// Serves the HTTP API, and gRPC with --grpc-port, over the configured MongoDB collection or
+// the embedded dataset.
+#[cfg(feature = "server")]
+async fn serve(global: &cli::GlobalArgs, args: &cli::ServeArgs) -> Result<()> {
+    #[cfg(feature = "embedded")]
+    if global.backend == cli::Backend::Embedded {
+        return serve_embedded(args).await;
+    }
+    if global.backend != cli::Backend::Mongo {
+        return Err(Error::Config("serve is only supported by the mongo backend".to_string()));
+    }
+    #[cfg(not(feature = "grpc"))]
+    if args.grpc_port.is_some() {
+        return Err(Error::Config("serving gRPC needs the grpc feature".to_string()));
+    }
+    let mut store = connect_mongo(global, &Provenance::new()).await?;
+    if let Some(cache) = args.lookup_cache() {
+        store = store.with_lookup_cache(cache.clone());
+        tokio::spawn(cache::invalidate_on_change(store.clone(), cache));
+    }
+    let http = server::serve(store.clone(), args.address(), args.access_options());
+    #[cfg(feature = "grpc")]
+    if let Some(port) = args.grpc_port {
+        return tokio::try_join!(http, grpc::serve(store, SocketAddr::new(args.listen.bind, port))).map(|_| ());
+    }
+    http.await
+}
+
+#[cfg(not(feature = "server"))]
+async fn serve(_: &cli::GlobalArgs, _: &cli::ServeArgs) -> Result<()> {
+    Err(Error::Config("serve needs the server feature".to_string()))
+}
+
+// Serves the daemon, whose loads are given the global options this process was given.
+#[cfg(feature = "server")]
+async fn run_daemon(global: &cli::GlobalArgs, args: &cli::DaemonArgs) -> Result<()> {
+    let program = env::current_exe().map_err(|source| Error::Io { path: PathBuf::from("the running executable"), source })?;
+    let launcher = daemon::Launcher { program, args: global.typed.clone(), cache_dir: args.cache_dir.clone() };
+    daemon::serve(launcher, args.daemon_options(), args.address()).await
+}
+
+#[cfg(not(feature = "server"))]
+async fn run_daemon(_: &cli::GlobalArgs, _: &cli::DaemonArgs) -> Result<()> {
+    Err(Error::Config("daemon needs the server feature".to_string()))
+}
+
+// Writes the differences between the input file read through `source` and the stored
+// aircraft, recording the run as reading `url` if the file was fetched from there.
+async fn sync_file(global: &cli::GlobalArgs, args: &cli::SyncArgs, source: &cli::SourceArgs, url: Option<&str>) -> Result<()> {
+    if args.retire && global.backend != cli::Backend::Mongo {
+        return Err(Error::Config("--retire is only supported by the mongo backend".to_string()));
+    }
+    let started_at = SystemTime::now();
+    let mut provenance = input_provenance(global, source).await?;
+    provenance.source_file = url.map(str::to_string).or(provenance.source_file);
+    let load_id = &provenance.load_id;
+    info!(%load_id, "starting run");
+    let storage = create_storage(global, &provenance).await?;
+    if args.run.skip_unchanged && unchanged(storage.last_checksum().await?, &provenance) {
+        return Ok(());
+    }
+    let lock = lock_collection(global, &provenance, args.lock.force).await?;
+    let summary = locked(lock, async {
+        let overrides = Overrides::discover(global.overrides.as_deref())?;
+        let aircrafts = override_input(open_input(global, source).await?, overrides.as_ref());
+        let summary = sync::sync(storage.as_ref(), aircrafts, &args.sync_options()).await?;
+        stamp_overrides(global, &provenance, overrides.as_ref()).await?;
+        Ok(summary)
+    })
+    .await?;
+    let record = LoadRecord {
+        parsed: summary.parsed,
+        written: (summary.added.len() + summary.updated.len() + summary.reactivated.len() + summary.retired.len()) as u64,
+        updated: Some((summary.updated.len() + summary.reactivated.len() + summary.retired.len()) as u64),
+        deleted: summary.deleted.len() as u64,
+        rejected: summary.rejected.len() as u64,
+        skipped: summary.skipped.len() as u64,
+        ..LoadRecord::finished(provenance.clone(), "sync", started_at)
+    };
+    storage.record_load(&record).await?;
+    notify_finished(global, &record).await;
+    if let Some(path) = &args.change_feed {
+        feed::append(path, &provenance, &summary.changes)?;
+    }
+    if let Some(url) = &args.change_feed_url {
+        feed::post(url, &provenance, &summary.changes).await?;
+    }
+    if let Some(report) = &args.run.report {
+        RunReport::sync(record, &summary).write(report)?;
+    }
+    if !summary.rejected.is_empty() {
+        validate::write_rejects(&args.run.rejects, &summary.rejected)?;
+    }
+    if global.run_output == cli::RunOutput::Json {
+        return Ok(());
+    }
+    println!(
+        "{} added, {} updated, {} deleted, {} retired, {} reactivated, {} unchanged (load id {})",
+        summary.added.len(),
+        summary.updated.len(),
+        summary.deleted.len(),
+        summary.retired.len(),
+        summary.reactivated.len(),
+        summary.unchanged,
+        load_id
+    );
+    let labelled = [
+        ("added", &summary.added),
+        ("updated", &summary.updated),
+        ("deleted", &summary.deleted),
+        ("retired", &summary.retired),
+        ("reactivated", &summary.reactivated),
+    ];
+    for (label, icao_codes) in labelled {
+        if !icao_codes.is_empty() {
+            println!("  {}: {}", label, icao_codes.join(", "));
+        }
+    }
+    if !summary.skipped.is_empty() {
+        println!("skipped {} unparseable entries:", summary.skipped.len());
+        for message in &summary.skipped {
+            println!("  {}", message);
+        }
+    }
+    if !summary.rejected.is_empty() {
+        println!("rejected {} aircraft, see {}", summary.rejected.len(), args.run.rejects.display());
+    }
+    Ok(())
+}
+
+// Syncs the input file, fetching it first with --url, in which case nothing is synced if it
+// is not modified since the last fetch.
+async fn sync_input(global: &cli::GlobalArgs, args: &cli::SyncArgs) -> Result<()> {
+    let Some(url) = &args.remote.url else {
+        return sync_file(global, args, &args.source, None).await;
+    };
+    let remote = remote::fetch(url, &args.remote.cache_dir, !args.remote.no_cache).await?;
+    if remote.not_modified {
+        println!("{} is not modified since the last fetch, skipping", url);
+        return Ok(());
+    }
+    let source = cli::SourceArgs { file: Some(remote.path.clone()), ..args.source.clone() };
+    sync_file(global, args, &source, Some(url)).await?;
+    remote.keep()
+}
+
+// Syncs whenever the schedule matches until Ctrl-C, each run recorded in the load history
+// like any other. A failed run is logged and the next one waited for.
+async fn sync_scheduled(global: &cli::GlobalArgs, args: &cli::SyncArgs, schedule: &Schedule) -> Result<()> {
+    loop {
+        tokio::select! {
+            waited = schedule.wait() => waited?,
+            _ = tokio::signal::ctrl_c() => {
+                info!("stopped the schedule");
+                return Ok(());
+            }
+        };
+        let started_at = SystemTime::now();
+        if let Err(error) = sync_input(global, args).await {
+            error!("scheduled sync failed: {}", error);
+            notify_failed(global, "sync", started_at, &error).await;
+        }
+    }
+}
+
+// Syncs the input file once and then after every change to it until Ctrl-C. A failed sync,
+// e.g. of a file saved halfway through an edit, is logged and the next change tried.
+async fn watch(global: &cli::GlobalArgs, args: &cli::WatchArgs) -> Result<()> {
+    let path = args.sync.source.path(&global.input);
+    if input::is_stdin(path) || s3_url(path).is_some() || args.sync.remote.url.is_some() {
+        return Err(Error::Config("watch needs a local input file".to_string()));
+    }
+    if args.sync.schedule.is_some() {
+        return Err(Error::Config("watch syncs on changes, not on a --schedule".to_string()));
+    }
+    let mut watcher = FileWatcher::new(path, args.debounce())?;
+    info!(path = %path.display(), "watching");
+    loop {
+        let started_at = SystemTime::now();
+        if let Err(error) = sync_file(global, &args.sync, &args.sync.source, None).await {
+            error!(path = %path.display(), "sync failed: {}", error);
+            notify_failed(global, "watch", started_at, &error).await;
+        }
+        tokio::select! {
+            changed = watcher.changed() => changed?,
+            _ = tokio::signal::ctrl_c() => {
+                info!("stopped watching");
+                return Ok(());
+            }
+        }
+    }
+}
+
+// Writes `records` to --out, or stdout for the formats that can be streamed.
+fn export_records(global: &cli::GlobalArgs, args: &cli::ExportArgs, records: Vec<StoredAircraft>) -> Result<()> {
+    if let Some(cli::ExportMapping::Mapping { direction }) = args.mapping {
+        let mapping = export::mapping(records.iter().map(|record| &record.aircraft), direction);
+        return match &args.out {
+            Some(out) => {
+                let file = File::create(out).map_err(|source| Error::Write { path: out.clone(), source })?;
+                export::write_mapping(BufWriter::new(file), &mapping, direction, args.format)
+            }
+            None => export::write_mapping(io::stdout().lock(), &mapping, direction, args.format),
+        };
+    }
+    if args.format == export::ExportFormat::BsonArchive {
+        let fields = global.field_names()?;
+        return match &args.out {
+            Some(out) => {
+                let file = File::create(out).map_err(|source| Error::Write { path: out.clone(), source })?;
+                export::export_archive(BufWriter::new(file), records, &global.database, &global.collection, &fields)
+            }
+            None => export::export_archive(io::stdout().lock(), records, &global.database, &global.collection, &fields),
+        };
+    }
+    let records = localize(records, args.lang.as_deref());
+    #[cfg(feature = "parquet")]
+    if args.format == export::ExportFormat::Parquet {
+        let out = args.out.as_ref().ok_or_else(|| Error::Config("--format parquet needs --out".to_string()))?;
+        return export::export_parquet(out, records, args.keep_id);
+    }
+    match &args.out {
+        Some(out) => {
+            let file = File::create(out).map_err(|source| Error::Write { path: out.clone(), source })?;
+            export::export(BufWriter::new(file), records, &args.export_options())
+        }
+        None => export::export(io::stdout().lock(), records, &args.export_options()),
+    }
+}
+
+// `records` with the description in `lang` where they have a translation, for --lang.
+fn localize(records: Vec<StoredAircraft>, lang: Option<&str>) -> Vec<StoredAircraft> {
+    let Some(lang) = lang else { return records };
+    records.into_iter().map(|record| localize_one(record, lang)).collect()
+}
+
+fn localize_one(record: StoredAircraft, lang: &str) -> StoredAircraft {
+    StoredAircraft { aircraft: record.aircraft.localized(lang), ..record }
+}
+
+// Exports the stored aircraft matching --filter. JSON, NDJSON and CSV are written as the
+// cursor returns them, in constant memory; the other layouts need them all first.
+async fn export_mongo(global: &cli::GlobalArgs, args: &cli::ExportArgs) -> Result<()> {
+    let store = connect_mongo(global, &Provenance::new()).await?.with_inactive(args.inactive);
+    let filter = args.filter.as_ref().map(|filter| filter.to_document(store.field_names())).unwrap_or_default();
+    let projection = (!args.fields.is_empty()).then(|| filter::projection(&args.fields, store.field_names()));
+    let streamed = matches!(args.format, export::ExportFormat::Json | export::ExportFormat::Ndjson | export::ExportFormat::Csv);
+    if args.mapping.is_some() || !streamed {
+        let records = store.stream_with_ids(filter, projection).await?.try_collect().await?;
+        return export_records(global, args, records);
+    }
+    let records = store.stream_with_ids(filter, projection).await?.map_ok(|record| match args.lang.as_deref() {
+        Some(lang) => localize_one(record, lang),
+        None => record,
+    });
+    let written = match &args.out {
+        Some(out) => {
+            let file = File::create(out).map_err(|source| Error::Write { path: out.clone(), source })?;
+            export::export_stream(BufWriter::new(file), records, &args.export_options()).await?
+        }
+        None => export::export_stream(BufWriter::new(io::stdout()), records, &args.export_options()).await?,
+    };
+    info!(written, "exported");
+    Ok(())
+}
+
+// Exports the aircraft of the --input file as a load would write them, ids included.
+fn export_input(global: &cli::GlobalArgs, args: &cli::ExportArgs) -> Result<()> {
+    let options = input::InputOptions { format: args.input.input_format, normalize: true, ..input::InputOptions::default() };
+    let ids = global.id_strategy();
+    let records = input::stream_aircraft(&global.input, &options)?
+        .map(|aircraft| aircraft.map(|aircraft| StoredAircraft { id: ids.id_for(&aircraft), aircraft }))
+        .collect::<Result<Vec<_>>>()?;
+    export_records(global, args, records)
+}
+
+// Writes the quality report of `aircrafts` to --out or stdout.
+fn write_quality(args: &cli::QualityArgs, aircrafts: &[Aircraft]) -> Result<()> {
+    let report = quality::assess(aircrafts, args.min_description);
+    let text = match args.format {
+        quality::ReportFormat::Json => serde_json::to_string_pretty(&report)? + "\n",
+        quality::ReportFormat::Html => quality::html(&report),
+    };
+    match &args.out {
+        Some(out) => fs::write(out, text).map_err(|source| Error::Write { path: out.clone(), source }),
+        None => {
+            print!("{}", text);
+            Ok(())
+        }
+    }
+}
+
+// quality --from-input: assesses the aircraft parsed from --input, as export --from-input.
+fn quality_input(global: &cli::GlobalArgs, args: &cli::QualityArgs) -> Result<()> {
+    let options = input::InputOptions { format: args.input.input_format, normalize: true, ..input::InputOptions::default() };
+    let aircrafts = input::stream_aircraft(&global.input, &options)?.collect::<Result<Vec<_>>>()?;
+    write_quality(args, &aircrafts)
+}
+
+// Prints the differences between two input files, read like --input.
+fn diff_inputs(args: &cli::DiffArgs) -> Result<()> {
+    let options = input::InputOptions { format: args.format, normalize: true, ..input::InputOptions::default() };
+    let differences = diff::diff(input::stream_aircraft(&args.old, &options)?, input::stream_aircraft(&args.new, &options)?)?;
+    match args.json {
+        true => println!("{}", serde_json::to_string_pretty(&differences)?),
+        false => print!("{}", differences.text()),
+    }
+    Ok(())
+}
+
+// Replaces the collection with a snapshot, after confirmation.
+async fn restore(global: &cli::GlobalArgs, args: &cli::RestoreArgs) -> Result<()> {
+    if global.backend != cli::Backend::Mongo {
+        return Err(Error::Config("restore is only supported by the mongo backend".to_string()));
+    }
+    let question = format!("replace every record in {} with snapshot {}?", global.collection, args.snapshot);
+    if !confirm(global, args.confirm.yes, &question)? {
+        eprintln!("aborted");
+        return Ok(());
+    }
+    let store = connect_mongo(global, &Provenance::new()).await?;
+    let lock = store.lock(DEFAULT_LEASE, args.lock.force).await?;
+    println!("restored {} documents", locked(Some(lock), store.restore(&args.snapshot)).await?);
+    Ok(())
+}
+
+// Prints every audited write to an aircraft, oldest first, with the fields it changed.
+async fn history(global: &cli::GlobalArgs, args: &cli::HistoryArgs) -> Result<()> {
+    if global.backend != cli::Backend::Mongo {
+        return Err(Error::Config("history is only supported by the mongo backend".to_string()));
+    }
+    let store = connect_mongo(global, &Provenance::new()).await?;
+    let entries = store.audit_entries(&args.icao).await?;
+    if entries.is_empty() {
+        println!("no audited writes to {} in {}", args.icao, global.collection);
+    }
+    let show = |value: Option<&bson::Bson>| value.map_or_else(|| "-".to_string(), bson::Bson::to_string);
+    for entry in entries {
+        let at = entry.at.try_to_rfc3339_string().unwrap_or_else(|_| entry.at.to_string());
+        println!("{}  {}  {}  load {}", at, entry.operation, entry.actor, entry.load_id);
+        let (before, after) = (entry.before.unwrap_or_default(), entry.after.unwrap_or_default());
+        let mut fields: Vec<&String> = after.keys().collect();
+        fields.extend(before.keys().filter(|field| !after.contains_key(field)));
+        for field in fields {
+            let (old, new) = (before.get(field), after.get(field));
+            if old != new {
+                println!("    {}: {} -> {}", field, show(old), show(new));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Prints every change to the collection until Ctrl-C, forwarding it to --forward-url too. A
+// change that cannot be forwarded is logged and the next one followed.
+async fn tail(global: &cli::GlobalArgs, args: &cli::TailArgs) -> Result<()> {
+    if global.backend != cli::Backend::Mongo {
+        return Err(Error::Config("tail is only supported by the mongo backend".to_string()));
+    }
+    let store = connect_mongo(global, &Provenance::new()).await?;
+    let forward = args.forward_url.as_deref().map(|url| Webhook::new(url, WebhookFormat::Json));
+    let changes = rust_aircraft_parser::tail::changes(&store).await?;
+    futures::pin_mut!(changes);
+    loop {
+        let change = tokio::select! {
+            change = changes.next() => match change {
+                Some(change) => change?,
+                None => return Ok(()),
+            },
+            _ = tokio::signal::ctrl_c() => {
+                info!("stopped tailing");
+                return Ok(());
+            }
+        };
+        println!("{}", serde_json::to_string(&change)?);
+        if let Some(forward) = &forward {
+            if let Err(error) = forward.post(&change).await {
+                error!(operation = %change.operation, "could not forward change: {}", error);
+            }
+        }
+    }
+}
+
+// Runs the command, once for each tenant of the config with --all-tenants. A tenant failing
+// does not stop the others, but being interrupted does; the first failure is returned.
+async fn run_tenants(cli: cli::Cli) -> Result<()> {
+    if !cli.global.all_tenants {
+        return run(cli).await;
+    }
+    let tenants: Vec<String> = cli.global.tenants.keys().cloned().collect();
+    if tenants.is_empty() {
+        return Err(Error::Config("--all-tenants needs [tenants] in the config".to_string()));
+    }
+    let mut failed = None;
+    for tenant in tenants {
+        // Running a command consumes it, so each tenant parses its own.
+        let mut cli = cli::Cli::load()?;
+        cli.global = cli.global.for_tenant(&tenant)?;
+        cli.global.run_output = cli.command.run_output();
+        match run(cli).instrument(tracing::info_span!("tenant", %tenant)).await {
+            Ok(()) => info!(%tenant, "finished tenant"),
+            Err(Error::Interrupted) => return Err(Error::Interrupted),
+            Err(error) => {
+                error!(%tenant, "{}", error);
+                failed.get_or_insert(error);
+            }
+        }
+    }
+    failed.map_or(Ok(()), Err)
+}
+
+// Loads a single aircraft file, --url or --source, or a --dataset or batch of files.
+async fn run_load(global: &cli::GlobalArgs, mut args: cli::LoadArgs) -> Result<()> {
+    // Loads finish the batches being written on Ctrl-C or SIGTERM; other commands just stop.
+    shutdown::listen();
+    if args.write.unordered && global.backend != cli::Backend::Mongo {
+        return Err(Error::Config("--unordered is only supported by the mongo backend".to_string()));
+    }
+    if args.snapshot && global.backend != cli::Backend::Mongo {
+        return Err(Error::Config("--snapshot is only supported by the mongo backend".to_string()));
+    }
+    if args.atomic && !batch::is_batch(&args.paths()) {
+        return Err(Error::Config("--atomic loads several files; --swap replaces the collection of one".to_string()));
+    }
+    if args.resume && (args.dataset.is_some() || batch::is_batch(&args.paths())) {
+        return Err(Error::Config("--resume continues the load of a single aircraft file".to_string()));
+    }
+    if (args.from_embedded || !args.sources.is_empty()) && (args.dataset.is_some() || args.resume) {
+        return Err(Error::Config("--from-embedded and --source load the aircraft from the start".to_string()));
+    }
+    if let Some(dataset) = &args.dataset {
+        return load_dataset(global, dataset, args.lock.force).await;
+    }
+    if batch::is_batch(&args.paths()) {
+        return load_batch(global, &args).await;
+    }
+    // A --url input is fetched into the cache and then read like a local file.
+    let mut fetched = None;
+    if let Some(url) = &args.remote.url {
+        let remote = remote::fetch(url, &args.remote.cache_dir, !args.remote.no_cache).await?;
+        if remote.not_modified && !args.dry_run {
+            println!("{} is not modified since the last fetch, skipping", url);
+            return Ok(());
+        }
+        args.source.file = Some(remote.path.clone());
+        fetched = Some(remote);
+    }
+    if args.dry_run {
+        let overrides = Overrides::discover(global.overrides.as_deref())?;
+        let (aircrafts, _) = load_input(global, &args, overrides.as_ref()).await?;
+        return print_dry_run(&load::dry_run(aircrafts, args.sample, &global.id_strategy(), global.uuid_encoding(), &global.field_names()?)?);
+    }
+    // Identifies this run and its input in the records it writes, for auditing, purge
+    // --load-id and rollback.
+    let started_at = SystemTime::now();
+    let mut provenance = if args.from_embedded {
+        embedded_provenance()?
+    } else if !args.sources.is_empty() {
+        merge::provenance(&args.sources)?
+    } else {
+        input_provenance(global, &args.source).await?
+    };
+    provenance.source_file = args.remote.url.clone().or(provenance.source_file);
+    // Loads of a file save how far they got after every batch; --resume carries on from
+    // there under the interrupted load's id, so rollback still undoes all of it.
+    let mut resumed_at = 0;
+    if args.resume {
+        let path = Checkpoint::path(args.source.path(&global.input));
+        match (Checkpoint::read(&path)?, &provenance.checksum) {
+            (Some(checkpoint), Some(checksum)) if &checkpoint.checksum == checksum => {
+                println!("resuming load {} after {} entries", checkpoint.load_id, checkpoint.offset);
+                provenance.load_id = checkpoint.load_id;
+                resumed_at = checkpoint.offset;
+            }
+            (Some(_), _) => return Err(Error::Config(format!("{} was saved for another version of the input", path.display()))),
+            (None, _) => println!("no checkpoint at {}, loading from the start", path.display()),
+        }
+    }
+    let load_id = &provenance.load_id;
+    info!(%load_id, "starting run");
+    match args.swap {
+        true => load_swap(global, &args, &provenance, started_at, fetched.as_ref()).await,
+        false => load_in_place(global, &args, &provenance, started_at, resumed_at, fetched.as_ref()).await,
+    }
+}
+
+// Loads the aircraft into a new collection that then replaces the configured one, for --swap.
+async fn load_swap(global: &cli::GlobalArgs, args: &cli::LoadArgs, provenance: &Provenance, started_at: SystemTime, fetched: Option<&remote::Fetched>) -> Result<()> {
+    if global.backend != cli::Backend::Mongo {
+        return Err(Error::Config("--swap is only supported by the mongo backend".to_string()));
+    }
+    if global.history || global.audit {
+        return Err(Error::Config("--history and --audit cannot follow a --swap load".to_string()));
+    }
+    let store = connect_mongo(global, provenance).await?;
+    if args.run.skip_unchanged && unchanged(store.last_checksum().await?, provenance) {
+        return Ok(());
+    }
+    let lock = store.lock(DEFAULT_LEASE, args.lock.force).await?;
+    let (summary, field_conflicts, conflicts) = locked(Some(lock), async {
+        if args.snapshot {
+            take_snapshot(global, provenance).await?;
+        }
+        let progress = LoadProgress::start(args.source.path(&global.input), global.progress());
+        let overrides = Overrides::discover(global.overrides.as_deref())?;
+        let (aircrafts, field_conflicts) = load_input(global, args, overrides.as_ref()).await?;
+        let (aircrafts, conflicts) = dedup_input(args, &args.conflicts, aircrafts)?;
+        let summary = load::load_swapped(&store, aircrafts, &args.load_options(), args.skip_indexes, &progress).await?;
+        drop(progress);
+        stamp_overrides(global, provenance, overrides.as_ref()).await?;
+        Ok((summary, field_conflicts, conflicts))
+    })
+    .await?;
+    let record = load_record(provenance, "load", started_at, &summary);
+    store.record_load(&record).await?;
+    notify_finished(global, &record).await;
+    if let Some(report) = &args.run.report {
+        RunReport::load(record, &summary, conflicts).with_field_conflicts(field_conflicts).write(report)?;
+    }
+    fetched.map(remote::Fetched::keep).transpose()?;
+    print_load_summary(&summary, &provenance.load_id, &args.run.rejects, &args.write.failures, global.run_output)
+}
+
+// Writes the aircraft into the configured collection, after the `resumed_at` entries an
+// interrupted load already wrote.
+async fn load_in_place(global: &cli::GlobalArgs, args: &cli::LoadArgs, provenance: &Provenance, started_at: SystemTime, resumed_at: u64, fetched: Option<&remote::Fetched>) -> Result<()> {
+    let storage = create_storage(global, provenance).await?;
+    if args.run.skip_unchanged && unchanged(storage.last_checksum().await?, provenance) {
+        return Ok(());
+    }
+    let lock = lock_collection(global, provenance, args.lock.force).await?;
+    let (summary, field_conflicts, conflicts) = locked(lock, async {
+        if args.snapshot {
+            take_snapshot(global, provenance).await?;
+        }
+        let path = args.source.path(&global.input);
+        let progress = LoadProgress::start(path, global.progress());
+        let overrides = Overrides::discover(global.overrides.as_deref())?;
+        let (aircrafts, field_conflicts) = load_input(global, args, overrides.as_ref()).await?;
+        let (aircrafts, conflicts) = dedup_input(args, &args.conflicts, aircrafts)?;
+        let aircrafts = aircrafts.skip(resumed_at as usize);
+        if !args.skip_indexes {
+            storage.ensure_indexes().await?;
+        }
+        let options = load_options(args, storage.as_ref()).await?;
+        // Standard input has no checksum to tell a resume it is reading the same input, and
+        // the embedded dataset and merged sources are read whole anyway.
+        let checkpointer = provenance.checksum.clone().filter(|_| !args.from_embedded && args.sources.is_empty()).map(|checksum| Checkpointer {
+            path: Checkpoint::path(path),
+            load_id: provenance.load_id.clone(),
+            checksum,
+            base: resumed_at,
+            inner: &progress,
+        });
+        let reported: &dyn load::Progress = match &checkpointer {
+            Some(checkpointer) => checkpointer,
+            None => &progress,
+        };
+        let summary = load::load_with_progress(storage.as_ref(), aircrafts, &options, reported).await?;
+        match checkpointer {
+            Some(checkpointer) if summary.interrupted => println!("continue with load --resume, see {}", checkpointer.path.display()),
+            Some(checkpointer) => Checkpoint::remove(&checkpointer.path)?,
+            None => {}
+        }
+        drop(progress);
+        stamp_overrides(global, provenance, overrides.as_ref()).await?;
+        Ok((summary, field_conflicts, conflicts))
+    })
+    .await?;
+    let record = load_record(provenance, "load", started_at, &summary);
+    storage.record_load(&record).await?;
+    notify_finished(global, &record).await;
+    if let Some(report) = &args.run.report {
+        RunReport::load(record, &summary, conflicts).with_field_conflicts(field_conflicts).write(report)?;
+    }
+    fetched.map(remote::Fetched::keep).transpose()?;
+    print_load_summary(&summary, &provenance.load_id, &args.run.rejects, &args.write.failures, global.run_output)
+}
+
+// The storage of the commands reading the stored aircraft, which sees those retired by sync
+// --retire as their --inactive says.
+async fn lookup_storage(global: &cli::GlobalArgs, inactive: Inactive) -> Result<Box<dyn Storage>> {
+    if inactive != Inactive::Include && global.backend != cli::Backend::Mongo {
+        return Err(Error::Config("--inactive is only supported by the mongo backend".to_string()));
+    }
+    create_storage(&cli::GlobalArgs { inactive, ..global.clone() }, &Provenance::new()).await
+}
+
+async fn query(global: &cli::GlobalArgs, args: &cli::QueryArgs) -> Result<()> {
+    match &args.lookup {
+        Some(cli::Lookup::Hex { hex }) => return query_hex(global, hex).await,
+        Some(cli::Lookup::Registration { registration }) => return query_registration(global, registration).await,
+        Some(cli::Lookup::Airports { near, radius_km, limit, json }) => return query_airports(global, near, *radius_km, *limit, *json).await,
+        Some(cli::Lookup::Equipment { .. }) | None => {}
+    }
+    if let Some(at) = &args.as_of {
+        return query_as_of(global, args, at).await;
+    }
+    if let Some(filter) = &args.filter {
+        return query_filtered(global, args, filter).await;
+    }
+    let storage = lookup_storage(global, args.inactive).await?;
+    if let Some(cli::Lookup::Equipment { equipment: codes, json }) = &args.lookup {
+        let resolved = equipment::resolve(storage.as_ref(), codes).await?;
+        match json {
+            true => println!("{}", serde_json::to_string_pretty(&resolved)?),
+            false => print_equipment(&resolved),
+        }
+        return Ok(());
+    }
+    let aircrafts = match (&args.icao, &args.iata) {
+        (Some(icao), _) => storage.find_by_icao(icao).await?.into_iter().collect(),
+        (None, Some(iata)) => storage.find_by_iata(iata).await?,
+        (None, None) => unreachable!("clap requires --icao, --iata, --filter or a lookup"),
+    };
+    let records = aircrafts.into_iter().map(|aircraft| StoredAircraft { id: String::new(), aircraft }).collect();
+    export::export(io::stdout().lock(), localize(records, args.lang.as_deref()), &args.print.export_options())
+}
+
+async fn run_export(global: &cli::GlobalArgs, args: &cli::ExportArgs) -> Result<()> {
+    if args.input.from_input {
+        return export_input(global, args);
+    }
+    if !args.fields.is_empty() && (args.mapping.is_some() || !matches!(args.format, export::ExportFormat::Json | export::ExportFormat::Ndjson | export::ExportFormat::Csv | export::ExportFormat::Table)) {
+        return Err(Error::Config("--fields is only supported by json, ndjson, csv and table exports".to_string()));
+    }
+    if global.backend == cli::Backend::Mongo {
+        return export_mongo(global, args).await;
+    }
+    if args.filter.is_some() {
+        return Err(Error::Config("--filter is only supported by the mongo backend".to_string()));
+    }
+    let storage = lookup_storage(global, args.inactive).await?;
+    export_records(global, args, storage.find_all_with_ids().await?)
+}
+
+async fn run_quality(global: &cli::GlobalArgs, args: &cli::QualityArgs) -> Result<()> {
+    if args.input.from_input {
+        return quality_input(global, args);
+    }
+    let storage = create_storage(global, &Provenance::new()).await?;
+    write_quality(args, &storage.find_all().await?)
+}
+
+async fn run_resolve(global: &cli::GlobalArgs, args: &cli::ResolveArgs) -> Result<()> {
+    let storage = create_storage(global, &Provenance::new()).await?;
+    let resolution = resolve::resolve(storage.as_ref(), &read_codes(&args.file)?).await?;
+    match args.json {
+        true => println!("{}", serde_json::to_string_pretty(&resolution)?),
+        false => print_resolution(&resolution),
+    }
+    Ok(())
+}
+
+async fn run_search(global: &cli::GlobalArgs, args: &cli::SearchArgs) -> Result<()> {
+    let storage = create_storage(global, &Provenance::new()).await?;
+    let hits = search::search(storage.find_all().await?, &args.query, args.limit);
+    let records: Vec<_> = hits.into_iter().map(|hit| StoredAircraft { id: String::new(), aircraft: hit.aircraft }).collect();
+    export::write(io::stdout().lock(), &records, &args.print.export_options())
+}
+
+async fn run_browse(global: &cli::GlobalArgs) -> Result<()> {
+    if global.backend != cli::Backend::Mongo {
+        return Err(Error::Config("browse is only supported by the mongo backend".to_string()));
+    }
+    let store = connect_mongo(global, &Provenance::new()).await?;
+    browse::browse(&store).await
+}
+
+async fn run_sync(global: &cli::GlobalArgs, args: &cli::SyncArgs) -> Result<()> {
+    match &args.schedule {
+        Some(schedule) => sync_scheduled(global, args, schedule).await,
+        None => sync_input(global, args).await,
+    }
+}
+
+// Nothing else tells who made a single-record write, so they are always audited.
+async fn audited_storage(global: &cli::GlobalArgs, provenance: &Provenance) -> Result<Box<dyn Storage>> {
+    create_storage(&cli::GlobalArgs { audit: true, ..global.clone() }, provenance).await
+}
+
+async fn add_aircraft(global: &cli::GlobalArgs, args: cli::AddAircraftArgs) -> Result<()> {
+    let (started_at, provenance) = (SystemTime::now(), Provenance::new());
+    let storage = audited_storage(global, &provenance).await?;
+    let aircraft = Aircraft {
+        icao_code: args.icao.to_ascii_uppercase(),
+        iata_code: args.iata.map(|iata| iata.to_ascii_uppercase()),
+        description: args.description,
+        descriptions: Default::default(),
+        aliases: Vec::new(),
+    };
+    if storage.find_by_icao(&aircraft.icao_code).await?.is_some() {
+        return Err(Error::InvalidInput(format!("{} is already stored, see edit", aircraft.icao_code)));
+    }
+    let icao_code = aircraft.icao_code.clone();
+    let written = storage.insert_batch(&[validated(aircraft)?]).await?;
+    record_single(storage.as_ref(), &provenance, "add", started_at, written, 0).await?;
+    println!("added {} (load {})", icao_code, provenance.load_id);
+    Ok(())
+}
+
+async fn edit_aircraft(global: &cli::GlobalArgs, args: cli::EditAircraftArgs) -> Result<()> {
+    let (started_at, provenance) = (SystemTime::now(), Provenance::new());
+    let storage = audited_storage(global, &provenance).await?;
+    let icao_code = args.icao.to_ascii_uppercase();
+    let Some(mut aircraft) = storage.find_by_icao(&icao_code).await? else {
+        return Err(Error::InvalidInput(format!("{} is not stored, see add", icao_code)));
+    };
+    if args.no_iata {
+        aircraft.iata_code = None;
+    }
+    if let Some(iata) = args.iata {
+        aircraft.iata_code = Some(iata.to_ascii_uppercase());
+    }
+    if let Some(description) = args.description {
+        aircraft.description = description;
+    }
+    let written = storage.upsert(&[validated(aircraft)?]).await?;
+    record_single(storage.as_ref(), &provenance, "edit", started_at, written, 0).await?;
+    println!("edited {} (load {})", icao_code, provenance.load_id);
+    Ok(())
+}
+
+async fn delete_aircraft(global: &cli::GlobalArgs, args: &cli::DeleteAircraftArgs) -> Result<()> {
+    let (started_at, provenance) = (SystemTime::now(), Provenance::new());
+    let storage = audited_storage(global, &provenance).await?;
+    let icao_code = args.icao.to_ascii_uppercase();
+    if !confirm(global, args.confirm.yes, &format!("delete {} from {}?", icao_code, global.collection))? {
+        eprintln!("aborted");
+        return Ok(());
+    }
+    let deleted = storage.delete_by_icao(std::slice::from_ref(&icao_code)).await?;
+    if deleted == 0 {
+        return Err(Error::InvalidInput(format!("{} is not stored", icao_code)));
+    }
+    record_single(storage.as_ref(), &provenance, "delete", started_at, 0, deleted).await?;
+    println!("deleted {} (load {})", icao_code, provenance.load_id);
+    Ok(())
+}
+
+async fn purge(global: &cli::GlobalArgs, args: &cli::PurgeArgs) -> Result<()> {
+    let provenance = Provenance::new();
+    let storage = create_storage(global, &provenance).await?;
+    let question = match &args.load_id {
+        Some(load_id) => format!("delete every record written by load {} from {}?", load_id, global.collection),
+        None => format!("delete every record in {}?", global.collection),
+    };
+    if !confirm(global, args.confirm.yes, &question)? {
+        eprintln!("aborted");
+        return Ok(());
+    }
+    let lock = lock_collection(global, &provenance, args.lock.force).await?;
+    let deleted = locked(lock, async {
+        match &args.load_id {
+            Some(load_id) => storage.delete_by_load(load_id).await,
+            None => storage.delete_all().await,
+        }
+    })
+    .await?;
+    println!("deleted {} documents", deleted);
+    Ok(())
+}
+
+async fn rollback(global: &cli::GlobalArgs, args: &cli::RollbackArgs) -> Result<()> {
+    let provenance = Provenance::new();
+    let storage = create_storage(global, &provenance).await?;
+    let question = format!("roll back load {} in {}?", args.load_id, global.collection);
+    if !confirm(global, args.confirm.yes, &question)? {
+        eprintln!("aborted");
+        return Ok(());
+    }
+    let lock = lock_collection(global, &provenance, args.lock.force).await?;
+    println!("rolled back {} documents", locked(lock, storage.delete_by_load(&args.load_id)).await?);
+    Ok(())
+}
+
+// Lookups read the embedded dataset when no database is configured.
+#[cfg(feature = "embedded")]
+fn lookup_backend(global: &cli::GlobalArgs, command: &cli::Command) -> cli::Backend {
+    if global.backend == cli::Backend::Mongo && !global.has_mongodb() && command.is_lookup() {
+        warn!("no MongoDB connection string is configured, reading the embedded dataset");
+        return cli::Backend::Embedded;
+    }
+    global.backend
+}
+
+#[cfg(not(feature = "embedded"))]
+fn lookup_backend(global: &cli::GlobalArgs, _: &cli::Command) -> cli::Backend {
+    global.backend
+}
+
+async fn run(mut cli: cli::Cli) -> Result<()> {
+    cli.global.backend = lookup_backend(&cli.global, &cli.command);
+    let global = &cli.global;
+    match cli.command {
+        cli::Command::Load(args) => run_load(global, *args).await,
+        cli::Command::Export(args) => run_export(global, &args).await,
+        cli::Command::Query(args) => query(global, &args).await,
+        cli::Command::Resolve(args) => run_resolve(global, &args).await,
+        cli::Command::Search(args) => run_search(global, &args).await,
+        cli::Command::Quality(args) => run_quality(global, &args).await,
+        cli::Command::Add(cli::AddRecord::Aircraft(args)) => add_aircraft(global, args).await,
+        cli::Command::Edit(cli::EditRecord::Aircraft(args)) => edit_aircraft(global, args).await,
+        cli::Command::Delete(cli::DeleteRecord::Aircraft(args)) => delete_aircraft(global, &args).await,
+        cli::Command::Purge(args) => purge(global, &args).await,
+        cli::Command::Rollback(args) => rollback(global, &args).await,
+        cli::Command::Sync(args) => run_sync(global, &args).await,
+        cli::Command::Watch(args) => watch(global, &args).await,
+        cli::Command::Tail(args) => tail(global, &args).await,
+        cli::Command::Restore(args) => restore(global, &args).await,
+        cli::Command::History(args) => history(global, &args).await,
+        cli::Command::Enrich(args) => enrich(global, &args).await,
+        cli::Command::Check => check(global).await,
+        cli::Command::Schema(cli::SchemaCommand::Apply(args)) => schema_apply(global, &args).await,
+        cli::Command::Migrate(args) => migrate(global, &args).await,
+        cli::Command::SelfTest(args) => self_test(global, &args).await,
+        cli::Command::Bench(args) => run_bench(global, &args).await,
+        cli::Command::Browse => run_browse(global).await,
+        cli::Command::Diff(args) => diff_inputs(&args),
+        cli::Command::Stats(args) => stats(global, &args).await,
+        cli::Command::Gc(args) => gc(global, &args).await,
+        cli::Command::Completions { shell } => {
+            cli::write_completions(shell);
+            Ok(())
+        }
+        cli::Command::Serve(args) => serve(global, &args).await,
+        cli::Command::Daemon(args) => run_daemon(global, &args).await,
+    }
 }
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     dotenv().ok();
-    let aircraft_collection: Collection<Document> = create_mongodb().await;
-    let aircrafts: Vec<Aircraft> = read_aircraft_json();
-    let mut aircraft_documents: Vec<Document> = Vec::new();
-    for aircraft in aircrafts.iter() {
-        // Convert `captain_marvel` to a Bson instance:
-        let aircraft_bson = bson::to_bson(&aircraft).expect("unwrap bson");
-        let mut document: Document = aircraft_bson.as_document().unwrap().clone();
-        document.insert("_id", Uuid::new_v4().to_string());
-        aircraft_documents.push(document);
-    }
-    aircraft_collection.insert_many(aircraft_documents, None).await.expect("insert into mongodb");
+    let mut cli = match cli::Cli::load() {
+        Ok(cli) => cli,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            return ExitCode::from(&error);
+        }
+    };
+    let _telemetry = init_logging(&cli.global);
+    let span = tracing::info_span!("run", command = cli.command.name());
+    // Runs that fail are reported to --notify-url, and printed with --output json, here, as
+    // they never record a finished run.
+    let started_at = SystemTime::now();
+    let (command, notify) = (cli.command.name(), cli.command.is_run());
+    let webhook = cli.global.webhook().filter(|_| notify);
+    cli.global.run_output = cli.command.run_output();
+    let json = cli.global.run_output == cli::RunOutput::Json;
+    match run_tenants(cli).instrument(span).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            error!("{}", error);
+            // Interrupted runs are recorded, and reported, like finished ones.
+            if !matches!(error, Error::Interrupted) {
+                let summary = RunSummary::failed(command, started_at, &error);
+                if json {
+                    println!("{}", serde_json::to_string(&summary).unwrap_or_default());
+                }
+                if let Some(webhook) = webhook {
+                    webhook.notify(&summary).await;
+                }
+            }
+            ExitCode::from(&error)
+        }
+    }
 }
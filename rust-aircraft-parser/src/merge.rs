@@ -0,0 +1,215 @@
+//! Merging the aircraft of several sources, such as ICAO DOC 8643, OpenFlights and a file of
+//! manual overrides, into one entry per ICAO code. Sources are listed highest priority
+//! first: each field is taken from the first source giving it, and sources giving another
+//! value are reported as conflicts.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use crate::input::{self, Format, InputOptions};
+use crate::provenance::Provenance;
+use crate::{Aircraft, Error, Result};
+
+/// A source of a merge, as `--source NAME=PATH` or the `[[sources]]` of the config give it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Source {
+    /// What the origins and conflicts call the source, e.g. `doc8643`.
+    pub name: String,
+    pub path: PathBuf,
+    /// Layout of the file; the load's --format when none is given.
+    pub format: Option<Format>,
+}
+
+/// A merged aircraft with the source each of its fields was taken from.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Merged {
+    pub aircraft: Aircraft,
+    /// Source names by camelCase field, e.g. `iataCode`, with translations as
+    /// `descriptions.fr`. Fields no source gives are left out; `aliases`, which are taken
+    /// from every source, names each source contributing some.
+    pub origins: BTreeMap<String, String>,
+}
+
+/// A value one source gave for a field.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct SourceValue {
+    pub source: String,
+    pub value: String,
+}
+
+/// A field sources gave different values for.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldConflict {
+    pub icao_code: String,
+    pub field: String,
+    /// The value of each source giving one, in priority order; the first was kept.
+    pub values: Vec<SourceValue>,
+}
+
+/// The merged aircraft, in the order their codes were first listed, and the conflicts found.
+#[derive(Debug, Default)]
+pub struct Merge {
+    pub aircraft: Vec<Merged>,
+    pub conflicts: Vec<FieldConflict>,
+}
+
+/// Reads every source with `options`, in its own format if it has one, stopping at the first
+/// entry that fails to parse.
+pub fn read_sources(sources: &[Source], options: &InputOptions) -> Result<Vec<(String, Vec<Aircraft>)>> {
+    sources
+        .iter()
+        .map(|source| {
+            let options = InputOptions { format: source.format.unwrap_or(options.format), ..options.clone() };
+            let aircraft = input::read_aircraft(&source.path, &options)
+                .map_err(|error| Error::InvalidInput(format!("source {}: {}", source.name, error)))?;
+            Ok((source.name.clone(), aircraft))
+        })
+        .collect()
+}
+
+/// Merges the aircraft of each named source, highest priority first. A code listed twice by
+/// one source is merged as if the second entry came from a source of lower priority.
+pub fn merge(sources: Vec<(String, Vec<Aircraft>)>) -> Merge {
+    // Entries of each code in priority order, and the codes in the order first listed.
+    let mut entries: HashMap<String, Vec<(&str, Aircraft)>> = HashMap::new();
+    let mut codes = Vec::new();
+    for (name, aircraft) in &sources {
+        for aircraft in aircraft {
+            let listed = entries.entry(aircraft.icao_code.clone()).or_default();
+            if listed.is_empty() {
+                codes.push(aircraft.icao_code.clone());
+            }
+            listed.push((name.as_str(), aircraft.clone()));
+        }
+    }
+    let mut merge = Merge::default();
+    for icao_code in codes {
+        let listed = &entries[&icao_code];
+        let mut origins = BTreeMap::from([("icaoCode".to_string(), listed[0].0.to_string())]);
+        let mut pick = |field: &str, values: Vec<(&str, &str)>| {
+            let given: Vec<SourceValue> = values
+                .into_iter()
+                .filter(|(_, value)| !value.is_empty())
+                .map(|(source, value)| SourceValue { source: source.to_string(), value: value.to_string() })
+                .collect();
+            let kept = given.first()?.clone();
+            origins.insert(field.to_string(), kept.source.clone());
+            if given.iter().any(|other| other.value != kept.value) {
+                merge.conflicts.push(FieldConflict { icao_code: icao_code.clone(), field: field.to_string(), values: given });
+            }
+            Some(kept.value)
+        };
+        let iata_code = pick("iataCode", listed.iter().map(|(source, aircraft)| (*source, aircraft.iata_code.as_deref().unwrap_or(""))).collect());
+        let description = pick("description", listed.iter().map(|(source, aircraft)| (*source, aircraft.description.as_str())).collect()).unwrap_or_default();
+        let mut locales: Vec<&String> = listed.iter().flat_map(|(_, aircraft)| aircraft.descriptions.keys()).collect();
+        locales.sort();
+        locales.dedup();
+        let mut descriptions = BTreeMap::new();
+        for locale in locales {
+            let values = listed
+                .iter()
+                .filter_map(|(source, aircraft)| aircraft.descriptions.get(locale).map(|description| (*source, description.as_str())))
+                .collect();
+            if let Some(description) = pick(&format!("descriptions.{}", locale), values) {
+                descriptions.insert(locale.clone(), description);
+            }
+        }
+        let mut aliases: Vec<String> = Vec::new();
+        let mut contributors: Vec<&str> = Vec::new();
+        for (source, aircraft) in listed {
+            for alias in &aircraft.aliases {
+                if !aliases.contains(alias) {
+                    aliases.push(alias.clone());
+                    if !contributors.contains(source) {
+                        contributors.push(*source);
+                    }
+                }
+            }
+        }
+        if !contributors.is_empty() {
+            origins.insert("aliases".to_string(), contributors.join(", "));
+        }
+        let aircraft = Aircraft { icao_code: icao_code.clone(), iata_code, description, descriptions, aliases };
+        merge.aircraft.push(Merged { aircraft, origins });
+    }
+    merge
+}
+
+/// The provenance of a load of `sources`, whose checksum changes with any of them. Sources
+/// without a checksum, such as standard input, leave the load without one.
+pub fn provenance(sources: &[Source]) -> Result<Provenance> {
+    let mut digest = Sha256::new();
+    let mut checksummed = true;
+    for source in sources {
+        match Provenance::for_file(&source.path)?.checksum {
+            Some(checksum) => digest.update(format!("{}={}\n", source.name, checksum)),
+            None => checksummed = false,
+        }
+    }
+    let names: Vec<&str> = sources.iter().map(|source| source.name.as_str()).collect();
+    Ok(Provenance {
+        source_file: Some(names.join("+")),
+        checksum: checksummed.then(|| format!("{:x}", digest.finalize())),
+        ..Provenance::new()
+    })
+}
+
+/// Writes the merged aircraft with their origins to `path` as a pretty-printed JSON array.
+pub fn write_origins(path: &Path, merged: &[Merged]) -> Result<()> {
+    let json = serde_json::to_string_pretty(merged)?;
+    fs::write(path, json).map_err(|source| Error::Write { path: path.to_path_buf(), source })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::aircraft;
+
+    fn sources() -> Vec<(String, Vec<Aircraft>)> {
+        let doc8643 = vec![
+            aircraft("B738").description("Boeing 737-800").build(),
+            Aircraft { aliases: vec!["B38".to_string()], ..aircraft("A320").description("Airbus A320").build() },
+        ];
+        let openflights = vec![
+            aircraft("B738").iata("738").description("Boeing 737-800 Winglets").translation("fr", "Boeing 737-800").build(),
+            aircraft("E175").iata("E75").build(),
+        ];
+        vec![("doc8643".to_string(), doc8643), ("openflights".to_string(), openflights)]
+    }
+
+    #[test]
+    fn takes_each_field_from_the_first_source_giving_it() {
+        let merge = merge(sources());
+        let codes: Vec<&str> = merge.aircraft.iter().map(|merged| merged.aircraft.icao_code.as_str()).collect();
+        assert_eq!(codes, ["B738", "A320", "E175"]);
+        let b738 = &merge.aircraft[0];
+        assert_eq!(b738.aircraft, aircraft("B738").iata("738").description("Boeing 737-800").translation("fr", "Boeing 737-800").build());
+        assert_eq!(b738.origins["icaoCode"], "doc8643");
+        assert_eq!(b738.origins["description"], "doc8643");
+        assert_eq!(b738.origins["iataCode"], "openflights");
+        assert_eq!(b738.origins["descriptions.fr"], "openflights");
+        assert_eq!(merge.aircraft[1].origins["aliases"], "doc8643");
+    }
+
+    #[test]
+    fn reports_fields_sources_disagree_on() {
+        let merge = merge(sources());
+        assert_eq!(merge.conflicts.len(), 1);
+        let conflict = &merge.conflicts[0];
+        assert_eq!((conflict.icao_code.as_str(), conflict.field.as_str()), ("B738", "description"));
+        let values: Vec<&str> = conflict.values.iter().map(|value| value.value.as_str()).collect();
+        assert_eq!(values, ["Boeing 737-800", "Boeing 737-800 Winglets"]);
+    }
+
+    #[test]
+    fn a_code_listed_twice_by_a_source_merges_like_another_source() {
+        let twice = vec![aircraft("B738").build(), aircraft("B738").iata("738").build()];
+        let merge = merge(vec![("doc8643".to_string(), twice)]);
+        assert_eq!(merge.aircraft.len(), 1);
+        assert_eq!(merge.aircraft[0].aircraft.iata_code.as_deref(), Some("738"));
+        assert!(merge.conflicts.is_empty());
+    }
+}
@@ -0,0 +1,66 @@
+//! Prometheus metrics of what this process has loaded and looked up, served by the HTTP API
+//! at `/metrics`.
+
+use std::sync::OnceLock;
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, Opts, Registry, TextEncoder};
+use crate::Error;
+
+/// The process' metrics, see [`metrics`].
+pub struct Metrics {
+    registry: Registry,
+    /// Records written, by collection.
+    pub records_loaded: IntCounterVec,
+    /// Input entries that failed to parse, by collection.
+    pub parse_failures: IntCounterVec,
+    /// Records that failed validation, by collection.
+    pub records_rejected: IntCounterVec,
+    /// Operations MongoDB rejected or that could not reach it.
+    pub mongo_errors: IntCounter,
+    /// Time taken to write a batch, by collection.
+    pub batch_insert_seconds: HistogramVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let by_collection = |name: &str, help: &str| IntCounterVec::new(Opts::new(name, help), &["collection"]).expect("valid metric");
+        let metrics = Metrics {
+            registry: Registry::new_custom(Some("aircraft_parser".to_string()), None).expect("valid prefix"),
+            records_loaded: by_collection("records_loaded_total", "Records written"),
+            parse_failures: by_collection("parse_failures_total", "Input entries that failed to parse"),
+            records_rejected: by_collection("records_rejected_total", "Records that failed validation"),
+            mongo_errors: IntCounter::new("mongo_errors_total", "MongoDB operations that failed").expect("valid metric"),
+            batch_insert_seconds: HistogramVec::new(
+                HistogramOpts::new("batch_insert_seconds", "Time taken to write a batch"),
+                &["collection"],
+            )
+            .expect("valid metric"),
+        };
+        let registry = &metrics.registry;
+        for collector in [&metrics.records_loaded, &metrics.parse_failures, &metrics.records_rejected] {
+            registry.register(Box::new(collector.clone())).expect("unique metric");
+        }
+        registry.register(Box::new(metrics.mongo_errors.clone())).expect("unique metric");
+        registry.register(Box::new(metrics.batch_insert_seconds.clone())).expect("unique metric");
+        metrics
+    }
+
+    /// Counts `error` among the MongoDB errors if it is one.
+    pub fn observe_error(&self, error: &Error) {
+        if let Error::Mongo(_) = error {
+            self.mongo_errors.inc();
+        }
+    }
+
+    /// Every metric in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut text = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut text).expect("metrics encode as text");
+        String::from_utf8(text).expect("metrics are UTF-8")
+    }
+}
+
+/// The metrics of this process, created on first use.
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(Metrics::new)
+}
@@ -0,0 +1,25 @@
+use async_trait::async_trait;
+use mongodb::bson::doc;
+use crate::{AircraftStore, Result};
+use super::Migration;
+
+/// Sets `createdAt` on documents written before loads stamped it, to their `updatedAt`
+/// where they have one and to the time of the migration otherwise.
+pub(super) struct BackfillCreatedAt;
+
+#[async_trait]
+impl Migration for BackfillCreatedAt {
+    fn version(&self) -> u32 {
+        1
+    }
+
+    fn name(&self) -> &'static str {
+        "backfill createdAt"
+    }
+
+    async fn up(&self, store: &AircraftStore) -> Result<u64> {
+        let filter = doc! { "createdAt": { "$exists": false } };
+        let update = vec![doc! { "$set": { "createdAt": { "$ifNull": ["$updatedAt", "$$NOW"] } } }];
+        Ok(store.collection().update_many(filter, update, None).await?.modified_count)
+    }
+}
@@ -0,0 +1,39 @@
+use async_trait::async_trait;
+use mongodb::bson::doc;
+use crate::fields::FieldNames;
+use crate::{AircraftStore, Result};
+use super::Migration;
+
+/// Renames the aircraft fields of documents still stored under the default names to the
+/// names configured with --field-case or --rename-field, e.g. `icaoCode` to `icao`.
+/// Nothing changes with the default names.
+pub(super) struct RenameFields;
+
+#[async_trait]
+impl Migration for RenameFields {
+    fn version(&self) -> u32 {
+        2
+    }
+
+    fn name(&self) -> &'static str {
+        "rename fields to the configured names"
+    }
+
+    async fn up(&self, store: &AircraftStore) -> Result<u64> {
+        let (default, configured) = (FieldNames::default(), store.field_names());
+        let mut modified = 0;
+        for (from, to) in [
+            (&default.icao_code, &configured.icao_code),
+            (&default.iata_code, &configured.iata_code),
+            (&default.description, &configured.description),
+        ] {
+            if from == to {
+                continue;
+            }
+            let filter = doc! { from: { "$exists": true } };
+            let update = doc! { "$rename": { from: to } };
+            modified += store.collection().update_many(filter, update, None).await?.modified_count;
+        }
+        Ok(modified)
+    }
+}
@@ -0,0 +1,61 @@
+use async_trait::async_trait;
+use futures::TryStreamExt;
+use mongodb::bson::{doc, spec::BinarySubtype, Bson, Document, Uuid};
+use mongodb::Collection;
+use crate::ids::UuidEncoding;
+use crate::{AircraftStore, Result};
+use super::Migration;
+
+/// Converts `_id`s holding a UUID as a string to UUID binaries (subtype 4), half the size
+/// and what other drivers map to their UUID type. Other string `_id`s, and those loads
+/// write afterwards, are left alone.
+pub(super) struct UuidIds;
+
+#[async_trait]
+impl Migration for UuidIds {
+    fn version(&self) -> u32 {
+        3
+    }
+
+    fn name(&self) -> &'static str {
+        "convert string _ids to UUID binaries"
+    }
+
+    async fn up(&self, store: &AircraftStore) -> Result<u64> {
+        convert_ids(store.collection(), UuidEncoding::Binary, false).await
+    }
+}
+
+/// Stores the UUID `_id`s of `collection` as `encoding` says, returning how many documents
+/// were converted, or would be with `dry_run`. As an `_id` cannot be changed, each document
+/// is deleted and inserted again under the new one, in that order for the unique indexes
+/// on its codes. `_id`s that are no UUIDs, such as natural keys, are left alone.
+pub async fn convert_ids(collection: &Collection<Document>, encoding: UuidEncoding, dry_run: bool) -> Result<u64> {
+    let filter = match encoding {
+        UuidEncoding::Binary => doc! { "_id": { "$type": "string" } },
+        UuidEncoding::String => doc! { "_id": { "$type": "binData" } },
+    };
+    let documents: Vec<Document> = collection.find(filter, None).await?.try_collect().await?;
+    let mut converted = 0;
+    for mut document in documents {
+        let Some(id) = document.get("_id").cloned() else {
+            continue;
+        };
+        let uuid = match &id {
+            Bson::String(id) => Uuid::parse_str(id).ok(),
+            Bson::Binary(binary) if binary.subtype == BinarySubtype::Uuid => binary.to_uuid().ok(),
+            _ => None,
+        };
+        let Some(uuid) = uuid else {
+            continue;
+        };
+        converted += 1;
+        if dry_run {
+            continue;
+        }
+        document.insert("_id", encoding.encode(uuid.to_string()));
+        collection.delete_one(doc! { "_id": id }, None).await?;
+        collection.insert_one(document, None).await?;
+    }
+    Ok(converted)
+}
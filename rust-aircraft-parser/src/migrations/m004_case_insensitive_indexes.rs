@@ -0,0 +1,35 @@
+use async_trait::async_trait;
+use tracing::info;
+use crate::{AircraftStore, Result};
+use super::Migration;
+
+/// Drops the case-sensitive indexes on the ICAO and IATA codes that earlier versions
+/// created, now that loads create case-insensitive ones under other names. Modifies no
+/// documents.
+pub(super) struct CaseInsensitiveIndexes;
+
+#[async_trait]
+impl Migration for CaseInsensitiveIndexes {
+    fn version(&self) -> u32 {
+        4
+    }
+
+    fn name(&self) -> &'static str {
+        "drop the case-sensitive code indexes"
+    }
+
+    async fn up(&self, store: &AircraftStore) -> Result<u64> {
+        let collection = store.collection();
+        let fields = store.field_names();
+        let existing = collection.list_index_names().await?;
+        for field in [&fields.icao_code, &fields.iata_code] {
+            // The name the server gives an ascending index on one field.
+            let name = format!("{}_1", field);
+            if existing.contains(&name) {
+                collection.drop_index(&name, None).await?;
+                info!(index = %name, "dropped case-sensitive index");
+            }
+        }
+        Ok(0)
+    }
+}
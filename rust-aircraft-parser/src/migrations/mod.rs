@@ -0,0 +1,133 @@
+//! Versioned changes to the documents already stored, applied in order by `migrate` and
+//! recorded per collection in `schema_versions` so each runs once.
+
+mod m001_backfill_created_at;
+mod m002_rename_fields;
+mod m003_uuid_ids;
+mod m004_case_insensitive_indexes;
+
+use std::collections::BTreeSet;
+use async_trait::async_trait;
+use futures::TryStreamExt;
+use mongodb::bson::{self, doc, Document};
+use mongodb::Collection;
+use tracing::info;
+use crate::ids::UuidEncoding;
+use crate::{Airline, AircraftStore, Airport, Country, HexEntry, Record, Registration, Result, Route};
+
+pub use m003_uuid_ids::convert_ids;
+
+/// Collection the applied migrations are recorded in, next to the aircraft collection.
+pub const SCHEMA_VERSIONS: &str = "schema_versions";
+
+/// One change to the stored aircraft.
+#[async_trait]
+pub trait Migration: Send + Sync {
+    /// Position in [`registry`], starting at 1.
+    fn version(&self) -> u32;
+    /// What the migration does, e.g. `backfill createdAt`.
+    fn name(&self) -> &'static str;
+    /// Changes the documents of `store`, returning how many were modified.
+    async fn up(&self, store: &AircraftStore) -> Result<u64>;
+}
+
+/// Every migration, oldest first.
+pub fn registry() -> Vec<Box<dyn Migration>> {
+    vec![
+        Box::new(m001_backfill_created_at::BackfillCreatedAt),
+        Box::new(m002_rename_fields::RenameFields),
+        Box::new(m003_uuid_ids::UuidIds),
+        Box::new(m004_case_insensitive_indexes::CaseInsensitiveIndexes),
+    ]
+}
+
+/// Whether one migration has been applied to the store's collection.
+#[derive(Debug)]
+pub struct MigrationStatus {
+    pub version: u32,
+    pub name: &'static str,
+    pub applied: bool,
+}
+
+/// Outcome of one migration run by [`migrate`].
+#[derive(Debug)]
+pub struct Applied {
+    pub version: u32,
+    pub name: &'static str,
+    /// Documents the migration modified.
+    pub modified: u64,
+}
+
+fn schema_versions(store: &AircraftStore) -> Collection<Document> {
+    store.sibling(SCHEMA_VERSIONS)
+}
+
+// Versions recorded as applied to the store's collection.
+async fn applied_versions(store: &AircraftStore) -> Result<BTreeSet<u32>> {
+    let filter = doc! { "collection": store.collection().name() };
+    let records: Vec<Document> = schema_versions(store).find(filter, None).await?.try_collect().await?;
+    Ok(records.iter().filter_map(|record| record.get_i64("version").ok()).map(|version| version as u32).collect())
+}
+
+/// Every migration of the registry, with whether it has been applied.
+pub async fn status(store: &AircraftStore) -> Result<Vec<MigrationStatus>> {
+    let applied = applied_versions(store).await?;
+    Ok(registry()
+        .iter()
+        .map(|migration| MigrationStatus {
+            version: migration.version(),
+            name: migration.name(),
+            applied: applied.contains(&migration.version()),
+        })
+        .collect())
+}
+
+/// Applies the migrations not applied yet up to version `to`, or all of them, in order,
+/// recording each once it finished. A migration that fails stops the run unrecorded, so
+/// the next run starts over with it.
+pub async fn migrate(store: &AircraftStore, to: Option<u32>) -> Result<Vec<Applied>> {
+    let applied = applied_versions(store).await?;
+    let mut run = Vec::new();
+    for migration in registry() {
+        let version = migration.version();
+        if applied.contains(&version) || to.is_some_and(|to| version > to) {
+            continue;
+        }
+        let modified = migration.up(store).await?;
+        let record = doc! {
+            "_id": format!("{}:{}", store.collection().name(), version),
+            "collection": store.collection().name(),
+            "version": version as i64,
+            "name": migration.name(),
+            "modified": modified as i64,
+            "appliedAt": bson::DateTime::now(),
+        };
+        schema_versions(store).insert_one(record, None).await?;
+        info!(version, name = migration.name(), modified, "migration applied");
+        run.push(Applied { version, name: migration.name(), modified });
+    }
+    Ok(run)
+}
+
+/// The collections of reference data other than aircraft, which [`convert_all_ids`] converts
+/// next to the store's collection.
+pub const REFERENCE_COLLECTIONS: [&str; 6] =
+    [Airline::COLLECTION, Airport::COLLECTION, Country::COLLECTION, Route::COLLECTION, Registration::COLLECTION, HexEntry::COLLECTION];
+
+/// Stores the UUID `_id`s of the store's collection and of the other reference collections
+/// next to it as `encoding` says, returning how many documents of each collection were
+/// converted, or would be with `dry_run`. Unlike the registered migrations this can run
+/// any number of times, in either direction.
+pub async fn convert_all_ids(store: &AircraftStore, encoding: UuidEncoding, dry_run: bool) -> Result<Vec<(String, u64)>> {
+    let mut collections = vec![store.collection().clone()];
+    for name in REFERENCE_COLLECTIONS {
+        collections.push(store.sibling(name));
+    }
+    let mut converted = Vec::new();
+    for collection in collections {
+        let count = convert_ids(&collection, encoding, dry_run).await?;
+        info!(collection = collection.name(), converted = count, dry_run, "converted _ids");
+        converted.push((collection.name().to_string(), count));
+    }
+    Ok(converted)
+}
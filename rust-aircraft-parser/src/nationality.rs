@@ -0,0 +1,175 @@
+//! Telling the registering state of an aircraft from the nationality mark its registration
+//! starts with, e.g. `N` for the United States or `VT-` for India.
+
+use std::collections::HashMap;
+use std::time::Instant;
+use mongodb::bson::doc;
+use tracing::info;
+use crate::storage::RecordStore;
+use crate::{Country, Registration, Result};
+
+// ICAO nationality marks and the ISO 3166-1 alpha-2 code of the state they are allocated to.
+// Marks shared out within a state's block, such as VP-B for Bermuda, keep their dash.
+const BUILTIN: &[(&str, &str)] = &[
+    ("2", "GG"), ("3A", "MC"), ("3B", "MU"), ("3C", "GQ"), ("3D", "SZ"), ("3X", "GN"), ("4K", "AZ"),
+    ("4L", "GE"), ("4O", "ME"), ("4R", "LK"), ("4X", "IL"), ("5A", "LY"), ("5B", "CY"), ("5H", "TZ"),
+    ("5N", "NG"), ("5R", "MG"), ("5T", "MR"), ("5U", "NE"), ("5V", "TG"), ("5W", "WS"), ("5X", "UG"),
+    ("5Y", "KE"), ("6O", "SO"), ("6V", "SN"), ("6Y", "JM"), ("7O", "YE"), ("7P", "LS"), ("7Q", "MW"),
+    ("7T", "DZ"), ("8P", "BB"), ("8Q", "MV"), ("8R", "GY"), ("9A", "HR"), ("9G", "GH"), ("9H", "MT"),
+    ("9J", "ZM"), ("9K", "KW"), ("9L", "SL"), ("9M", "MY"), ("9N", "NP"), ("9Q", "CD"), ("9U", "BI"),
+    ("9V", "SG"), ("9XR", "RW"), ("9Y", "TT"), ("A2", "BW"), ("A3", "TO"), ("A4O", "OM"), ("A5", "BT"),
+    ("A6", "AE"), ("A7", "QA"), ("A8", "LR"), ("A9C", "BH"), ("AP", "PK"), ("B", "CN"), ("B-H", "HK"),
+    ("B-K", "HK"), ("B-L", "HK"), ("B-M", "MO"), ("C", "CA"), ("C2", "NR"), ("C3", "AD"), ("C5", "GM"),
+    ("C6", "BS"), ("C9", "MZ"), ("CC", "CL"), ("CN", "MA"), ("CP", "BO"), ("CS", "PT"), ("CU", "CU"),
+    ("CX", "UY"), ("D", "DE"), ("D2", "AO"), ("D4", "CV"), ("D6", "KM"), ("DQ", "FJ"), ("E3", "ER"),
+    ("E5", "CK"), ("E7", "BA"), ("EC", "ES"), ("EI", "IE"), ("EK", "AM"), ("EP", "IR"), ("ER", "MD"),
+    ("ES", "EE"), ("ET", "ET"), ("EW", "BY"), ("EX", "KG"), ("EY", "TJ"), ("EZ", "TM"), ("F", "FR"),
+    ("G", "GB"), ("H4", "SB"), ("HA", "HU"), ("HB", "CH"), ("HC", "EC"), ("HH", "HT"), ("HI", "DO"),
+    ("HK", "CO"), ("HL", "KR"), ("HP", "PA"), ("HR", "HN"), ("HS", "TH"), ("HZ", "SA"), ("I", "IT"),
+    ("J2", "DJ"), ("J3", "GD"), ("J5", "GW"), ("J6", "LC"), ("J7", "DM"), ("J8", "VC"), ("JA", "JP"),
+    ("JU", "MN"), ("JY", "JO"), ("LN", "NO"), ("LV", "AR"), ("LX", "LU"), ("LY", "LT"), ("LZ", "BG"),
+    ("M", "IM"), ("N", "US"), ("OB", "PE"), ("OD", "LB"), ("OE", "AT"), ("OH", "FI"), ("OK", "CZ"),
+    ("OM", "SK"), ("OO", "BE"), ("OY", "DK"), ("P", "KP"), ("P2", "PG"), ("P4", "AW"), ("PH", "NL"),
+    ("PJ", "CW"), ("PK", "ID"), ("PP", "BR"), ("PR", "BR"), ("PS", "BR"), ("PT", "BR"), ("PU", "BR"),
+    ("PZ", "SR"), ("RA", "RU"), ("RDPL", "LA"), ("RP", "PH"), ("S2", "BD"), ("S5", "SI"), ("S7", "SC"),
+    ("S9", "ST"), ("SE", "SE"), ("SP", "PL"), ("ST", "SD"), ("SU", "EG"), ("SX", "GR"), ("T2", "TV"),
+    ("T3", "KI"), ("T7", "SM"), ("T8A", "PW"), ("TC", "TR"), ("TF", "IS"), ("TG", "GT"), ("TI", "CR"),
+    ("TJ", "CM"), ("TL", "CF"), ("TN", "CG"), ("TR", "GA"), ("TS", "TN"), ("TT", "TD"), ("TU", "CI"),
+    ("TY", "BJ"), ("TZ", "ML"), ("UK", "UZ"), ("UN", "KZ"), ("UP", "KZ"), ("UR", "UA"), ("V2", "AG"),
+    ("V3", "BZ"), ("V4", "KN"), ("V5", "NA"), ("V6", "FM"), ("V7", "MH"), ("V8", "BN"), ("VH", "AU"),
+    ("VN", "VN"), ("VP-A", "AI"), ("VP-B", "BM"), ("VP-C", "KY"), ("VP-F", "FK"), ("VP-L", "VG"),
+    ("VP-M", "MS"), ("VQ-B", "BM"), ("VQ-T", "TC"), ("VT", "IN"), ("XA", "MX"), ("XB", "MX"), ("XC", "MX"),
+    ("XT", "BF"), ("XU", "KH"), ("XY", "MM"), ("YA", "AF"), ("YI", "IQ"), ("YJ", "VU"), ("YK", "SY"),
+    ("YL", "LV"), ("YN", "NI"), ("YR", "RO"), ("YS", "SV"), ("YU", "RS"), ("YV", "VE"), ("Z", "ZW"),
+    ("Z3", "MK"), ("ZA", "AL"), ("ZJ", "JE"), ("ZK", "NZ"), ("ZP", "PY"), ("ZS", "ZA"), ("ZT", "ZA"),
+    ("ZU", "ZA"),
+];
+
+/// Nationality marks and the countries they belong to: the built-in ICAO allocations, plus
+/// or instead of those the `icaoPrefixes` of loaded [`Country`] records give.
+#[derive(Clone, Debug)]
+pub struct NationalityMarks {
+    // Longest marks first, so `VP-B` is tried before `V` would be.
+    marks: Vec<(String, String)>,
+}
+
+impl Default for NationalityMarks {
+    fn default() -> Self {
+        NationalityMarks::new(BUILTIN.iter().map(|(mark, country)| (mark.to_string(), country.to_string())))
+    }
+}
+
+impl NationalityMarks {
+    fn new(marks: impl IntoIterator<Item = (String, String)>) -> Self {
+        let mut marks: Vec<(String, String)> = marks.into_iter().collect();
+        marks.sort_by(|left, right| right.0.len().cmp(&left.0.len()).then_with(|| left.0.cmp(&right.0)));
+        NationalityMarks { marks }
+    }
+
+    /// The built-in marks, with those of `countries` replacing the ones they list too.
+    pub fn with_countries(countries: &[Country]) -> Self {
+        let mut marks: HashMap<String, String> = BUILTIN.iter().map(|(mark, country)| (mark.to_string(), country.to_string())).collect();
+        for country in countries {
+            for mark in &country.icao_prefixes {
+                marks.insert(mark.to_ascii_uppercase(), country.iso_code.clone());
+            }
+        }
+        NationalityMarks::new(marks)
+    }
+
+    /// The ISO 3166-1 alpha-2 code of the state `registration` is from, e.g. `GB` for
+    /// `G-EUPT`. A dash ends the mark; without one, as in `N12345` or `JA801A`, the longest
+    /// mark the registration starts with wins.
+    pub fn country(&self, registration: &str) -> Option<&str> {
+        let registration = registration.trim().to_ascii_uppercase();
+        let leading = registration.split_once('-').map(|(mark, _)| mark);
+        let undashed = registration.replace('-', "");
+        self.marks
+            .iter()
+            .find(|(mark, _)| match (mark.contains('-'), leading) {
+                (true, Some(_)) => registration.starts_with(mark.as_str()),
+                (true, None) => undashed.starts_with(&mark.replace('-', "")),
+                (false, Some(leading)) => leading == mark,
+                (false, None) => undashed.starts_with(mark.as_str()),
+            })
+            .map(|(_, country)| country.as_str())
+    }
+}
+
+/// Outcome of [`tag_registrations`].
+#[derive(Debug, Default)]
+pub struct NationalitySummary {
+    /// Stored registrations read.
+    pub parsed: u64,
+    /// Registrations whose country code was written, because it was missing or different.
+    pub tagged: u64,
+    /// Registrations no nationality mark matched.
+    pub unknown: Vec<String>,
+}
+
+/// Sets the `countryCode` of every stored registration to the country its nationality mark
+/// belongs to, writing the registrations of each country together.
+pub async fn tag_registrations(registrations: &RecordStore<Registration>, marks: &NationalityMarks) -> Result<NationalitySummary> {
+    let started = Instant::now();
+    let mut summary = NationalitySummary::default();
+    let mut by_country: HashMap<String, Vec<String>> = HashMap::new();
+    for registration in registrations.find(doc! {}).await? {
+        summary.parsed += 1;
+        match marks.country(&registration.registration) {
+            Some(country) if country == registration.country_code => {}
+            Some(country) => by_country.entry(country.to_string()).or_default().push(registration.registration),
+            None => summary.unknown.push(registration.registration),
+        }
+    }
+    for (country, keys) in by_country {
+        summary.tagged += registrations.merge_many(&keys, doc! { "countryCode": country }).await?;
+    }
+    info!(
+        parsed = summary.parsed,
+        tagged = summary.tagged,
+        unknown = summary.unknown.len(),
+        elapsed_ms = started.elapsed().as_millis() as u64,
+        "nationality finished"
+    );
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tells_the_country_of_a_registration() {
+        let marks = NationalityMarks::default();
+        assert_eq!(marks.country("G-EUPT"), Some("GB"));
+        assert_eq!(marks.country(" vt-ana "), Some("IN"));
+        assert_eq!(marks.country("N12345"), Some("US"));
+        assert_eq!(marks.country("JA801A"), Some("JP"));
+        assert_eq!(marks.country("QQ-123"), None);
+    }
+
+    #[test]
+    fn a_dash_ends_the_mark() {
+        let marks = NationalityMarks::default();
+        // D- is Germany, though D2 is Angola.
+        assert_eq!(marks.country("D-AIMA"), Some("DE"));
+        assert_eq!(marks.country("D2-TEE"), Some("AO"));
+    }
+
+    #[test]
+    fn marks_within_a_block_keep_their_dash() {
+        let marks = NationalityMarks::default();
+        assert_eq!(marks.country("VP-BJA"), Some("BM"));
+        assert_eq!(marks.country("VPBJA"), Some("BM"));
+        assert_eq!(marks.country("B-HNR"), Some("HK"));
+        assert_eq!(marks.country("B-1234"), Some("CN"));
+    }
+
+    #[test]
+    fn loaded_countries_replace_the_marks_they_list() {
+        let country = Country { iso_code: "XX".to_string(), iso3_code: String::new(), name: "Test".to_string(), icao_prefixes: vec!["g".to_string()] };
+        let marks = NationalityMarks::with_countries(&[country]);
+        assert_eq!(marks.country("G-EUPT"), Some("XX"));
+        assert_eq!(marks.country("N12345"), Some("US"));
+    }
+}
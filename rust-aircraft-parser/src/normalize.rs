@@ -0,0 +1,69 @@
+//! Cleaning up aircraft as they are read, before they are validated and written.
+
+use unicode_normalization::UnicodeNormalization;
+use crate::Aircraft;
+
+/// `aircraft` with its codes and aliases trimmed and uppercased, a blank IATA code dropped, and its
+/// descriptions trimmed with every run of whitespace collapsed to a single space, all in
+/// Unicode NFC.
+pub fn normalize(aircraft: Aircraft) -> Aircraft {
+    Aircraft {
+        icao_code: code(&aircraft.icao_code),
+        iata_code: aircraft.iata_code.map(|iata_code| code(&iata_code)).filter(|code| !code.is_empty()),
+        description: text(&aircraft.description),
+        descriptions: aircraft.descriptions.iter().map(|(lang, description)| (lang.trim().to_string(), text(description))).collect(),
+        aliases: aircraft.aliases.iter().map(|alias| code(alias)).filter(|alias| !alias.is_empty()).collect(),
+    }
+}
+
+fn code(value: &str) -> String {
+    value.trim().to_uppercase().nfc().collect()
+}
+
+fn text(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ").nfc().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use super::*;
+
+    fn aircraft(icao_code: &str, iata_code: Option<&str>, description: &str) -> Aircraft {
+        Aircraft {
+            icao_code: icao_code.to_string(),
+            iata_code: iata_code.map(str::to_string),
+            description: description.to_string(),
+            descriptions: BTreeMap::new(),
+            aliases: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn trims_and_uppercases_codes() {
+        let messy = Aircraft { aliases: vec![" b38 ".to_string(), "  ".to_string()], ..aircraft(" b738 ", Some("738 "), "Boeing 737-800") };
+        let normalized = normalize(messy);
+        assert_eq!(normalized.icao_code, "B738");
+        assert_eq!(normalized.iata_code.as_deref(), Some("738"));
+        assert_eq!(normalized.aliases, vec!["B38".to_string()]);
+    }
+
+    #[test]
+    fn drops_a_blank_iata_code() {
+        assert_eq!(normalize(aircraft("B738", Some("   "), "Boeing 737-800")).iata_code, None);
+    }
+
+    #[test]
+    fn collapses_whitespace_in_descriptions() {
+        let mut messy = aircraft("A320", Some("320"), "  Airbus \t A320\n ");
+        messy.descriptions.insert(" fr ".to_string(), "Airbus  A320 ".to_string());
+        let normalized = normalize(messy);
+        assert_eq!(normalized.description, "Airbus A320");
+        assert_eq!(normalized.descriptions.get("fr").map(String::as_str), Some("Airbus A320"));
+    }
+
+    #[test]
+    fn composes_to_nfc() {
+        assert_eq!(normalize(aircraft("A320", Some("320"), "Ae\u{301}rospatiale")).description, "A\u{e9}rospatiale");
+    }
+}
@@ -0,0 +1,122 @@
+//! Curated corrections to the upstream data, kept in `overrides.json` and applied to every
+//! aircraft a load or sync reads, so upstream data never clobbers them. Stored documents
+//! record the override they carry under [`OVERRIDE`].
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::input::AircraftStream;
+use crate::provenance::file_checksum;
+use crate::{Aircraft, Error, Result};
+
+/// The overrides file used when none is given, if it is in the working directory.
+pub const DEFAULT_PATH: &str = "overrides.json";
+
+/// Field of a stored document recording the override applied to it.
+pub const OVERRIDE: &str = "override";
+
+/// The corrections to one aircraft. Fields left out keep the upstream value.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Override {
+    pub iata_code: Option<String>,
+    pub description: Option<String>,
+    /// Translations by locale, set over those of upstream one by one.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub descriptions: BTreeMap<String, String>,
+    /// Replace the aliases of upstream.
+    pub aliases: Option<Vec<String>>,
+    /// Why the correction was made, for whoever reads the stored document.
+    pub reason: Option<String>,
+}
+
+impl Override {
+    /// The camelCase names of the fields the override sets.
+    pub fn fields(&self) -> Vec<String> {
+        let mut fields = Vec::new();
+        if self.iata_code.is_some() {
+            fields.push("iataCode".to_string());
+        }
+        if self.description.is_some() {
+            fields.push("description".to_string());
+        }
+        fields.extend(self.descriptions.keys().map(|locale| format!("descriptions.{}", locale)));
+        if self.aliases.is_some() {
+            fields.push("aliases".to_string());
+        }
+        fields
+    }
+
+    /// Sets the fields of the override on `aircraft`. An empty IATA code removes it.
+    pub fn patch(&self, aircraft: &mut Aircraft) {
+        if let Some(iata_code) = &self.iata_code {
+            aircraft.iata_code = Some(iata_code.clone()).filter(|iata_code| !iata_code.is_empty());
+        }
+        if let Some(description) = &self.description {
+            aircraft.description = description.clone();
+        }
+        aircraft.descriptions.extend(self.descriptions.clone());
+        if let Some(aliases) = &self.aliases {
+            aircraft.aliases = aliases.clone();
+        }
+    }
+}
+
+/// An overrides file: a JSON object of [`Override`]s by ICAO code, e.g.
+/// `{"B738": {"iataCode": "73H", "reason": "missing upstream"}}`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Overrides {
+    /// The file as given, which stored documents name.
+    pub source_file: String,
+    /// Hex SHA-256 of the file.
+    pub checksum: String,
+    /// By upper-case ICAO code.
+    pub entries: BTreeMap<String, Override>,
+}
+
+impl Overrides {
+    pub fn read(path: &Path) -> Result<Overrides> {
+        let text = fs::read_to_string(path).map_err(|source| Error::Io { path: path.to_path_buf(), source })?;
+        let entries: BTreeMap<String, Override> =
+            serde_json::from_str(&text).map_err(|error| Error::InvalidInput(format!("{}: {}", path.display(), error)))?;
+        Ok(Overrides {
+            source_file: path.display().to_string(),
+            checksum: file_checksum(path)?,
+            entries: entries.into_iter().map(|(icao_code, entry)| (icao_code.trim().to_ascii_uppercase(), entry)).collect(),
+        })
+    }
+
+    /// Reads `path`, or [`DEFAULT_PATH`] if there is such a file when no path is given.
+    pub fn discover(path: Option<&Path>) -> Result<Option<Overrides>> {
+        let default = PathBuf::from(DEFAULT_PATH);
+        match path {
+            Some(path) => Overrides::read(path).map(Some),
+            None if default.is_file() => Overrides::read(&default).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Applies the override of `aircraft`'s ICAO code, if there is one, returning whether
+    /// there was.
+    pub fn patch(&self, aircraft: &mut Aircraft) -> bool {
+        match self.entries.get(&aircraft.icao_code.to_ascii_uppercase()) {
+            Some(entry) => {
+                entry.patch(aircraft);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// `aircrafts` with the overrides applied as they are read.
+    pub fn apply(&self, aircrafts: AircraftStream) -> AircraftStream {
+        let overrides = self.clone();
+        Box::new(aircrafts.map(move |aircraft| {
+            aircraft.map(|mut aircraft| {
+                overrides.patch(&mut aircraft);
+                aircraft
+            })
+        }))
+    }
+}
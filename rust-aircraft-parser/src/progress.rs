@@ -0,0 +1,110 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use rust_aircraft_parser::input;
+use rust_aircraft_parser::load::Progress;
+
+// The bar being drawn, which log lines are written around.
+static ACTIVE: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+/// A progress bar on stderr for one load: records parsed and written, the latest batch and
+/// the write rate, with how far into the input file the load is and the time left when its
+/// size is known. Hidden when stderr isn't a terminal. Cleared when dropped.
+pub struct LoadProgress {
+    bar: ProgressBar,
+    // Bytes read off inputs before this load started.
+    base: u64,
+    started: Instant,
+    parsed: AtomicU64,
+    written: AtomicU64,
+    batch: AtomicU64,
+}
+
+impl LoadProgress {
+    /// A bar for reading `path`, or a hidden one unless `enabled`.
+    pub fn start(path: &Path, enabled: bool) -> Self {
+        let size = fs::metadata(path).ok().filter(|metadata| metadata.is_file() && !input::is_stdin(path)).map(|metadata| metadata.len());
+        let bar = match size {
+            Some(size) => ProgressBar::new(size).with_style(style("{spinner} [{elapsed_precise}] [{bar:30}] {percent}% {msg} · ETA {eta}")),
+            None => ProgressBar::new_spinner().with_style(style("{spinner} [{elapsed_precise}] {msg}")),
+        };
+        if enabled {
+            bar.set_draw_target(ProgressDrawTarget::stderr());
+            bar.enable_steady_tick(Duration::from_millis(200));
+        } else {
+            bar.set_draw_target(ProgressDrawTarget::hidden());
+        }
+        *active() = Some(bar.clone());
+        LoadProgress {
+            bar,
+            base: input::bytes_read(),
+            started: Instant::now(),
+            parsed: AtomicU64::new(0),
+            written: AtomicU64::new(0),
+            batch: AtomicU64::new(0),
+        }
+    }
+
+    fn update(&self) {
+        let written = self.written.load(Ordering::Relaxed);
+        let rate = written as f64 / self.started.elapsed().as_secs_f64().max(0.001);
+        self.bar.set_position(input::bytes_read() - self.base);
+        self.bar.set_message(format!(
+            "parsed {} · written {} · batch {} · {:.0} docs/s",
+            self.parsed.load(Ordering::Relaxed),
+            written,
+            self.batch.load(Ordering::Relaxed),
+            rate
+        ));
+    }
+}
+
+impl Progress for LoadProgress {
+    fn read(&self, batch: u64, parsed: u64, _rejected: u64) {
+        self.parsed.fetch_add(parsed, Ordering::Relaxed);
+        self.batch.fetch_max(batch, Ordering::Relaxed);
+        self.update();
+    }
+
+    fn written(&self, _batch: u64, written: u64, _failed: u64) {
+        self.written.fetch_add(written, Ordering::Relaxed);
+        self.update();
+    }
+}
+
+impl Drop for LoadProgress {
+    fn drop(&mut self) {
+        self.bar.finish_and_clear();
+        *active() = None;
+    }
+}
+
+fn style(template: &str) -> ProgressStyle {
+    ProgressStyle::with_template(template).expect("valid progress template").progress_chars("=> ")
+}
+
+fn active() -> std::sync::MutexGuard<'static, Option<ProgressBar>> {
+    ACTIVE.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Stderr for log lines, which hides the progress bar being drawn while writing so the
+/// two don't garble each other.
+pub struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        let bar = active().clone();
+        match bar {
+            Some(bar) => bar.suspend(|| io::stderr().write(buffer)),
+            None => io::stderr().write(buffer),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
@@ -0,0 +1,108 @@
+//! Where written records came from: the run that wrote them and the file they were read from.
+
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::time::SystemTime;
+use mongodb::bson;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+use crate::{input, Error, Result};
+
+/// Stamped onto every record a backend writes, so bad imports can be traced and rolled back.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Provenance {
+    /// Identifies the run; a random UUID unless set otherwise.
+    pub load_id: String,
+    /// Path of the input file, as given on the command line.
+    pub source_file: Option<String>,
+    /// Hex SHA-256 of the input file, or the ETag of an S3 object.
+    pub checksum: Option<String>,
+    /// The tenant the run writes for, recorded in the load history.
+    pub tenant: Option<String>,
+}
+
+impl Provenance {
+    /// A new run with a random load id and no input file.
+    pub fn new() -> Self {
+        Provenance { load_id: Uuid::new_v4().to_string(), source_file: None, checksum: None, tenant: None }
+    }
+
+    /// A new run reading `path`, whose checksum is computed up front. Standard input has
+    /// no checksum, as reading it ahead would consume it.
+    pub fn for_file(path: &Path) -> Result<Self> {
+        if input::is_stdin(path) {
+            return Ok(Provenance { source_file: Some(input::STDIN.to_string()), ..Provenance::new() });
+        }
+        Ok(Provenance {
+            source_file: Some(path.display().to_string()),
+            checksum: Some(file_checksum(path)?),
+            ..Provenance::new()
+        })
+    }
+}
+
+impl Default for Provenance {
+    fn default() -> Self {
+        Provenance::new()
+    }
+}
+
+/// Hex SHA-256 of the file at `path`, read in chunks.
+pub fn file_checksum(path: &Path) -> Result<String> {
+    let io_error = |source| Error::Io { path: path.to_path_buf(), source };
+    let mut file = File::open(path).map_err(io_error)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).map_err(io_error)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// `time` as an RFC 3339 string in UTC with millisecond precision, as stored by the SQL backends.
+pub fn rfc3339(time: SystemTime) -> String {
+    let time = bson::DateTime::from_system_time(time);
+    time.try_to_rfc3339_string().unwrap_or_else(|_| time.to_string())
+}
+
+/// One finished run, as kept in the `load_history` collection (or table).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoadRecord {
+    pub provenance: Provenance,
+    /// The subcommand that ran, e.g. `load` or `sync`.
+    pub command: String,
+    pub started_at: SystemTime,
+    pub finished_at: SystemTime,
+    /// Aircraft read from the input.
+    pub parsed: u64,
+    /// Records inserted or updated.
+    pub written: u64,
+    /// Of `written`, the records that were already stored, when the run can tell: upserts
+    /// can't. Not kept in the load history.
+    pub updated: Option<u64>,
+    /// Records deleted.
+    pub deleted: u64,
+    /// Aircraft that failed validation.
+    pub rejected: u64,
+    /// Entries that failed to parse and were skipped.
+    pub skipped: u64,
+    /// Whether a shutdown signal stopped the run part way, after writing what is counted.
+    pub interrupted: bool,
+}
+
+impl LoadRecord {
+    /// A run of `command` that started at `started_at` and finished now, with all counts zero.
+    pub fn finished(provenance: Provenance, command: &str, started_at: SystemTime) -> Self {
+        LoadRecord {
+            provenance,
+            command: command.to_string(),
+            started_at,
+            finished_at: SystemTime::now(),
+            parsed: 0,
+            written: 0,
+            updated: Some(0),
+            deleted: 0,
+            rejected: 0,
+            skipped: 0,
+            interrupted: false,
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! Scoring the aircraft reference data and reporting what looks wrong with it, ahead of
+//! each data review.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use clap::ValueEnum;
+use serde::Serialize;
+use crate::Aircraft;
+
+/// Descriptions shorter than this many characters are flagged unless `--min-description`
+/// says otherwise.
+pub const MIN_DESCRIPTION: usize = 5;
+
+/// Layout of a [`QualityReport`].
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReportFormat {
+    #[default]
+    Json,
+    /// A standalone page with a table per check
+    Html,
+}
+
+/// Something a [`Finding`] flags.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum Check {
+    MissingIata,
+    ShortDescription,
+    SharedIata,
+    NonAsciiCode,
+    DuplicateIcao,
+}
+
+impl Check {
+    /// What the check looks for, as a heading of the HTML report.
+    pub fn title(self) -> &'static str {
+        match self {
+            Check::MissingIata => "Missing IATA codes",
+            Check::ShortDescription => "Suspiciously short descriptions",
+            Check::SharedIata => "IATA codes shared by several ICAO types",
+            Check::NonAsciiCode => "Codes with non-ASCII characters",
+            Check::DuplicateIcao => "ICAO codes given more than once",
+        }
+    }
+}
+
+/// One problem with one aircraft.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Finding {
+    pub check: Check,
+    pub icao_code: String,
+    pub detail: String,
+}
+
+/// What [`assess`] found. `score` is the percentage of aircraft with no findings.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct QualityReport {
+    pub aircraft: u64,
+    pub flagged: u64,
+    pub score: f64,
+    pub counts: BTreeMap<Check, u64>,
+    /// ICAO codes of the types sharing each IATA code.
+    pub shared_iata: BTreeMap<String, Vec<String>>,
+    pub findings: Vec<Finding>,
+}
+
+/// Runs every [`Check`] over `aircrafts`, flagging descriptions shorter than
+/// `min_description` characters.
+pub fn assess(aircrafts: &[Aircraft], min_description: usize) -> QualityReport {
+    let mut findings = Vec::new();
+    let mut by_iata: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    let mut seen = BTreeSet::new();
+    for aircraft in aircrafts {
+        let icao_code = &aircraft.icao_code;
+        let mut flag = |check: Check, detail: String| findings.push(Finding { check, icao_code: icao_code.clone(), detail });
+        match aircraft.iata_code.as_deref() {
+            Some(iata_code) => {
+                by_iata.entry(iata_code).or_default().insert(icao_code.as_str());
+                if !iata_code.is_ascii() {
+                    flag(Check::NonAsciiCode, format!("IATA code {:?}", iata_code));
+                }
+            }
+            None => flag(Check::MissingIata, String::new()),
+        }
+        if !icao_code.is_ascii() {
+            flag(Check::NonAsciiCode, format!("ICAO code {:?}", icao_code));
+        }
+        let length = aircraft.description.trim().chars().count();
+        if length < min_description {
+            flag(Check::ShortDescription, format!("{:?}", aircraft.description));
+        }
+        if !seen.insert(icao_code.as_str()) {
+            flag(Check::DuplicateIcao, String::new());
+        }
+    }
+
+    let shared_iata: BTreeMap<String, Vec<String>> = by_iata
+        .into_iter()
+        .filter(|(_, icao_codes)| icao_codes.len() > 1)
+        .map(|(iata_code, icao_codes)| (iata_code.to_string(), icao_codes.into_iter().map(str::to_string).collect()))
+        .collect();
+    for (iata_code, icao_codes) in &shared_iata {
+        for icao_code in icao_codes {
+            let detail = format!("{} also used by {}", iata_code, others(icao_codes, icao_code));
+            findings.push(Finding { check: Check::SharedIata, icao_code: icao_code.clone(), detail });
+        }
+    }
+    findings.sort_by(|left, right| (left.check, &left.icao_code).cmp(&(right.check, &right.icao_code)));
+
+    let mut counts = BTreeMap::new();
+    for finding in &findings {
+        *counts.entry(finding.check).or_insert(0) += 1;
+    }
+    let flagged = findings.iter().map(|finding| finding.icao_code.as_str()).collect::<BTreeSet<_>>().len() as u64;
+    let total = aircrafts.len() as u64;
+    let score = if total == 0 { 100.0 } else { (1000.0 * (total - flagged) as f64 / total as f64).round() / 10.0 };
+    QualityReport { aircraft: total, flagged, score, counts, shared_iata, findings }
+}
+
+fn others(icao_codes: &[String], icao_code: &str) -> String {
+    icao_codes.iter().filter(|other| *other != icao_code).cloned().collect::<Vec<_>>().join(", ")
+}
+
+/// `report` as a standalone HTML page: the score, then a table of findings per check.
+pub fn html(report: &QualityReport) -> String {
+    let mut page = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Aircraft data quality</title>\n");
+    page.push_str("<style>body{font-family:sans-serif}table{border-collapse:collapse}td,th{border:1px solid #ccc;padding:2px 8px;text-align:left}</style>\n");
+    page.push_str("</head>\n<body>\n<h1>Aircraft data quality</h1>\n");
+    // Writing to a String cannot fail.
+    let _ = writeln!(page, "<p>Score <strong>{:.1}%</strong>: {} of {} aircraft flagged.</p>", report.score, report.flagged, report.aircraft);
+    let checks: BTreeSet<Check> = report.findings.iter().map(|finding| finding.check).collect();
+    for check in checks {
+        let _ = writeln!(page, "<h2>{} ({})</h2>", check.title(), report.counts.get(&check).copied().unwrap_or(0));
+        page.push_str("<table>\n<tr><th>ICAO code</th><th>Detail</th></tr>\n");
+        for finding in report.findings.iter().filter(|finding| finding.check == check) {
+            let _ = writeln!(page, "<tr><td>{}</td><td>{}</td></tr>", escape(&finding.icao_code), escape(&finding.detail));
+        }
+        page.push_str("</table>\n");
+    }
+    page.push_str("</body>\n</html>\n");
+    page
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(character),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,83 @@
+//! Typed lookups of stored aircraft for library users: the driver deserializes the documents
+//! straight into [`Aircraft`]s through a `Collection<Aircraft>`, so callers never handle a
+//! raw `Document`. Documents in the camelCase and snake_case field layouts are read alike;
+//! collections with other field names need an [`AircraftStore`] set up with them instead.
+
+use futures::{Stream, TryStreamExt};
+use mongodb::bson::{doc, Document};
+use mongodb::options::{FindOneOptions, FindOptions};
+use mongodb::{Client, Collection};
+use crate::storage::case_insensitive;
+use crate::{Aircraft, AircraftStore, Error, Result};
+
+/// Database the command line loads into unless told otherwise.
+pub const DEFAULT_DATABASE: &str = "flights-admin";
+
+/// Collection the command line loads aircraft into unless told otherwise.
+pub const DEFAULT_COLLECTION: &str = "aircraft";
+
+/// Typed reads of one aircraft collection.
+#[derive(Clone, Debug)]
+pub struct AircraftQuery {
+    collection: Collection<Aircraft>,
+}
+
+impl AircraftQuery {
+    /// Reads the aircraft in `collection` of `database`.
+    pub fn new(client: &Client, database: &str, collection: &str) -> Self {
+        AircraftQuery { collection: client.database(database).collection(collection) }
+    }
+
+    /// Reads the aircraft in the default collection of the default database.
+    pub fn default_collection(client: &Client) -> Self {
+        AircraftQuery::new(client, DEFAULT_DATABASE, DEFAULT_COLLECTION)
+    }
+
+    /// Reads the aircraft of the collection `store` writes to.
+    pub fn for_store(store: &AircraftStore) -> Self {
+        AircraftQuery { collection: store.collection().clone_with_type() }
+    }
+
+    /// The typed collection, for queries of one's own.
+    pub fn collection(&self) -> &Collection<Aircraft> {
+        &self.collection
+    }
+
+    /// The aircraft stored under `icao_code`, ignoring case.
+    pub async fn find_by_icao(&self, icao_code: &str) -> Result<Option<Aircraft>> {
+        let options = FindOneOptions::builder().collation(case_insensitive()).build();
+        Ok(self.collection.find_one(either("icaoCode", "icao_code", icao_code), options).await?)
+    }
+
+    /// The aircraft stored under `iata_code`, ignoring case; several types can share one.
+    pub async fn find_by_iata(&self, iata_code: &str) -> Result<Vec<Aircraft>> {
+        let options = FindOptions::builder().collation(case_insensitive()).build();
+        Ok(self.collection.find(either("iataCode", "iata_code", iata_code), options).await?.try_collect().await?)
+    }
+
+    /// Every stored aircraft, read as the stream is polled, so collections larger than
+    /// memory can be walked.
+    pub async fn stream_all(&self) -> Result<impl Stream<Item = Result<Aircraft>>> {
+        Ok(self.collection.find(None, None).await?.map_err(Error::from))
+    }
+}
+
+// Matches `value` under the field's camelCase or snake_case name.
+fn either(camel: &str, snake: &str, value: &str) -> Document {
+    doc! { "$or": [{ camel: value }, { snake: value }] }
+}
+
+/// [`AircraftQuery::find_by_icao`] on the default collection.
+pub async fn find_by_icao(client: &Client, icao_code: &str) -> Result<Option<Aircraft>> {
+    AircraftQuery::default_collection(client).find_by_icao(icao_code).await
+}
+
+/// [`AircraftQuery::find_by_iata`] on the default collection.
+pub async fn find_by_iata(client: &Client, iata_code: &str) -> Result<Vec<Aircraft>> {
+    AircraftQuery::default_collection(client).find_by_iata(iata_code).await
+}
+
+/// [`AircraftQuery::stream_all`] on the default collection.
+pub async fn stream_all(client: &Client) -> Result<impl Stream<Item = Result<Aircraft>>> {
+    AircraftQuery::default_collection(client).stream_all().await
+}
@@ -0,0 +1,77 @@
+//! What the loader needs to know about each kind of reference data it handles.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use mongodb::bson::{self, Document};
+use mongodb::IndexModel;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use crate::input::OpenFlightsRow;
+use crate::{validate, Aircraft, Result};
+
+/// A kind of reference record (aircraft, airports, ...) with its own collection, natural
+/// key and validation rules. Parsing, validation, batching and upserts are written once
+/// against this trait.
+pub trait Record: Serialize + DeserializeOwned + Clone + Send + Sync + 'static {
+    /// Collection the records are loaded into.
+    const COLLECTION: &'static str;
+
+    /// Stored field identifying a record, that upserts and the unique index are keyed on.
+    const KEY_FIELD: &'static str;
+
+    /// Collections holding the records this kind refers to by code, which must be loaded first.
+    const REFERENCES: &'static [&'static str] = &[];
+
+    /// The value of [`KEY_FIELD`](Self::KEY_FIELD).
+    fn key(&self) -> Cow<'_, str>;
+
+    /// Checks the record and returns why it is invalid; an empty list means it is valid.
+    fn validate(&self) -> Vec<String>;
+
+    /// The codes this record refers to, as `(collection, code)` pairs over
+    /// [`REFERENCES`](Self::REFERENCES); a code matches a record's ICAO or IATA code.
+    fn references(&self) -> Vec<(&'static str, &str)> {
+        Vec::new()
+    }
+
+    /// The fields stored for the record, without `_id` or provenance.
+    fn to_document(&self) -> Result<Document> {
+        Ok(bson::to_document(self)?)
+    }
+
+    /// How to read the record from a row of the OpenFlights `.dat` files, if they publish it.
+    fn openflights() -> Option<fn(&OpenFlightsRow) -> Result<Self>> {
+        None
+    }
+
+    /// Indexes to create besides the unique one on [`KEY_FIELD`](Self::KEY_FIELD).
+    fn indexes() -> Vec<IndexModel> {
+        Vec::new()
+    }
+}
+
+impl Record for Aircraft {
+    const COLLECTION: &'static str = "aircraft";
+    const KEY_FIELD: &'static str = "icaoCode";
+
+    fn key(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.icao_code)
+    }
+
+    fn validate(&self) -> Vec<String> {
+        validate::validate(self)
+    }
+
+    /// `planes.dat`: name, IATA code, ICAO code.
+    fn openflights() -> Option<fn(&OpenFlightsRow) -> Result<Self>> {
+        Some(|row| {
+            Ok(Aircraft {
+                icao_code: row.text(2),
+                iata_code: row.get(1).map(str::to_string),
+                description: row.text(0),
+                descriptions: BTreeMap::new(),
+                aliases: Vec::new(),
+            })
+        })
+    }
+}
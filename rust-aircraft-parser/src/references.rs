@@ -0,0 +1,33 @@
+//! Checking the codes records refer to against the collections already loaded.
+
+use std::collections::{HashMap, HashSet};
+use crate::record::Record;
+use crate::{AircraftStore, Result};
+
+/// The ICAO and IATA codes stored in each referenced collection.
+#[derive(Debug, Default)]
+pub struct KnownCodes {
+    codes: HashMap<&'static str, HashSet<String>>,
+}
+
+impl KnownCodes {
+    /// Reads the codes of each of `collections` from the database `store` belongs to.
+    pub async fn fetch(store: &AircraftStore, collections: &[&'static str]) -> Result<Self> {
+        let mut codes = HashMap::new();
+        for &collection in collections {
+            codes.insert(collection, store.codes(collection).await?);
+        }
+        Ok(KnownCodes { codes })
+    }
+
+    /// Why `record` is an orphan, one reason per code it refers to that is not stored;
+    /// an empty list means every reference resolves.
+    pub fn orphans<T: Record>(&self, record: &T) -> Vec<String> {
+        record
+            .references()
+            .into_iter()
+            .filter(|(collection, code)| !self.codes.get(collection).is_some_and(|codes| codes.contains(*code)))
+            .map(|(collection, code)| format!("{:?} is not in {}", code, collection))
+            .collect()
+    }
+}
@@ -0,0 +1,74 @@
+use std::borrow::Cow;
+use mongodb::bson::doc;
+use mongodb::IndexModel;
+use serde::{Deserialize, Serialize};
+use crate::record::Record;
+use crate::validate::is_alphanumeric;
+
+/// A registered aircraft, keyed by its registration mark and linked to its type designator.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Registration {
+    /// Registration mark, e.g. `N12345`.
+    pub registration: String,
+    /// 24-bit Mode S address as 6 upper case hex digits, e.g. `A061D9`. Empty when not assigned.
+    #[serde(default)]
+    pub mode_s_hex: String,
+    /// Manufacturer's serial number.
+    #[serde(default)]
+    pub serial_number: String,
+    /// Manufacturer as registered, e.g. `BOEING`.
+    #[serde(default)]
+    pub manufacturer: String,
+    /// Model as registered, e.g. `737-8`.
+    #[serde(default)]
+    pub model: String,
+    /// ICAO type designator from the `aircraft` collection, e.g. `B38M`. Empty when unresolved.
+    #[serde(default)]
+    pub type_designator: String,
+    /// Year of manufacture.
+    #[serde(default)]
+    pub year_built: Option<u16>,
+    /// Name of the registered owner.
+    #[serde(default)]
+    pub owner: String,
+    /// ISO 3166-1 alpha-2 code of the state of registry, e.g. `US`, from the nationality
+    /// mark. Empty until `enrich nationality` tags it.
+    #[serde(default)]
+    pub country_code: String,
+}
+
+impl Record for Registration {
+    const COLLECTION: &'static str = "registrations";
+    const KEY_FIELD: &'static str = "registration";
+
+    fn key(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.registration)
+    }
+
+    /// * `registration` must be 2 to 10 letters, digits or dashes.
+    /// * `modeSHex` must be exactly 6 hex digits, or empty.
+    fn validate(&self) -> Vec<String> {
+        let mut reasons = Vec::new();
+        let mark = self.registration.replace('-', "");
+        if !(2..=10).contains(&self.registration.chars().count()) || !is_alphanumeric(&mark) {
+            reasons.push(format!("registration {:?} is not 2 to 10 letters, digits or dashes", self.registration));
+        }
+        if !self.mode_s_hex.is_empty()
+            && (self.mode_s_hex.len() != 6 || !self.mode_s_hex.chars().all(|character| character.is_ascii_hexdigit()))
+        {
+            reasons.push(format!("modeSHex {:?} is neither empty nor 6 hex digits", self.mode_s_hex));
+        }
+        reasons
+    }
+
+    /// Indexes on `modeSHex`, for lookups from ADS-B traffic, on `typeDesignator` and on
+    /// `countryCode`.
+    fn indexes() -> Vec<IndexModel> {
+        vec![
+            IndexModel::builder().keys(doc! { "modeSHex": 1 }).build(),
+            IndexModel::builder().keys(doc! { "typeDesignator": 1 }).build(),
+            IndexModel::builder().keys(doc! { "countryCode": 1 }).build(),
+        ]
+    }
+}
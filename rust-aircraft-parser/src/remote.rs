@@ -0,0 +1,110 @@
+//! Fetching input files over HTTP(S) into a local cache.
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use reqwest::header::{HeaderMap, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::info;
+use crate::{Error, Result};
+
+/// A remote file as fetched by [`fetch`].
+#[derive(Debug)]
+pub struct Fetched {
+    /// Local copy of the file.
+    pub path: PathBuf,
+    /// Whether the server reported the file unchanged since it was cached.
+    pub not_modified: bool,
+    // Validators of a new download, not yet recorded.
+    validators: Option<Validators>,
+}
+
+impl Fetched {
+    /// Records the `ETag` and `Last-Modified` of a new download so the next [`fetch`] can
+    /// find it not modified. Called once the file has been processed, so a failed load is
+    /// retried in full rather than skipped.
+    pub fn keep(&self) -> Result<()> {
+        let Some(validators) = &self.validators else { return Ok(()) };
+        let meta = meta_path(&self.path);
+        fs::write(&meta, serde_json::to_vec(validators)?).map_err(|source| Error::Write { path: meta, source })
+    }
+}
+
+// Validators the server sent with the cached copy, kept next to it as <file>.meta.json.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+struct Validators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// Downloads `url` into `cache_dir`, unless the copy cached by an earlier fetch is still
+/// current: the request carries the `ETag` and `Last-Modified` the server sent for that copy,
+/// and a `304 Not Modified` answer keeps it. Without `revalidate` any cached copy is
+/// ignored and the file downloaded again. The body is written to disk as it arrives.
+pub async fn fetch(url: &str, cache_dir: &Path, revalidate: bool) -> Result<Fetched> {
+    let write_error = |path: &Path| {
+        let path = path.to_path_buf();
+        move |source| Error::Write { path, source }
+    };
+    fs::create_dir_all(cache_dir).map_err(write_error(cache_dir))?;
+    let path = cache_dir.join(cache_name(url));
+
+    let mut request = reqwest::Client::new().get(url);
+    if revalidate && path.is_file() {
+        let validators: Validators = fs::read(meta_path(&path))
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        if let Some(etag) = &validators.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &validators.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+    let mut response = request.send().await?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        info!(url, path = %path.display(), "remote file not modified");
+        return Ok(Fetched { path, not_modified: true, validators: None });
+    }
+    response.error_for_status_ref()?;
+
+    let validators = validators(response.headers());
+    // Written next to the cached copy first, so a failed download leaves it intact.
+    let partial = path.with_file_name(format!("{}.partial", file_name(&path)));
+    let mut file = File::create(&partial).map_err(write_error(&partial))?;
+    let mut bytes = 0;
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk).map_err(write_error(&partial))?;
+        bytes += chunk.len();
+    }
+    fs::rename(&partial, &path).map_err(write_error(&path))?;
+    info!(url, path = %path.display(), bytes, "fetched remote file");
+    Ok(Fetched { path, not_modified: false, validators: Some(validators) })
+}
+
+fn validators(headers: &HeaderMap) -> Validators {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok()).map(str::to_string);
+    Validators { etag: header(ETAG), last_modified: header(LAST_MODIFIED) }
+}
+
+// The last path segment of the URL, prefixed with a hash of the whole URL so different
+// sources with the same file name don't share a cache entry. The extension is kept for
+// format detection.
+fn cache_name(url: &str) -> String {
+    let hash = format!("{:x}", Sha256::digest(url.as_bytes()));
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let name = path.rsplit('/').next().filter(|name| !name.is_empty()).unwrap_or("download");
+    format!("{}-{}", &hash[..16], name)
+}
+
+fn meta_path(path: &Path) -> PathBuf {
+    path.with_file_name(format!("{}.meta.json", file_name(path)))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+}
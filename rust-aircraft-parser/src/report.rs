@@ -0,0 +1,214 @@
+//! Human-readable reports of loads and syncs, written with `--report` for attaching to
+//! change-management tickets.
+
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use crate::load::LoadSummary;
+use crate::merge::FieldConflict;
+use crate::provenance::{rfc3339, LoadRecord};
+use crate::quality::escape;
+use crate::sync::SyncSummary;
+use crate::{Error, Record, Result};
+
+/// How many entries of each list a report shows, the rest being counted.
+pub const SAMPLE: usize = 20;
+
+/// What a load or sync did, as [`write`](RunReport::write) renders it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RunReport {
+    pub record: LoadRecord,
+    /// Keys of the records changed, by what happened to them, e.g. `added`.
+    pub changes: Vec<(&'static str, Vec<String>)>,
+    /// Keys of the records failing validation, with the reasons.
+    pub rejected: Vec<(String, Vec<String>)>,
+    /// ICAO codes listed more than once with different fields, with `--dedup`.
+    pub conflicts: Vec<String>,
+    /// Fields the sources of a merged load gave different values for.
+    pub field_conflicts: Vec<FieldConflict>,
+    /// Messages for entries skipped because they failed to parse.
+    pub skipped: Vec<String>,
+    /// Keys of the records the backend refused, with its error.
+    pub failed: Vec<(String, String)>,
+}
+
+// A titled table of a report.
+struct Section {
+    title: String,
+    header: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+    // Entries left out of `rows`.
+    more: usize,
+}
+
+impl Section {
+    fn new(title: &str, header: Vec<&'static str>, rows: Vec<Vec<String>>) -> Section {
+        let more = rows.len().saturating_sub(SAMPLE);
+        let title = format!("{} ({})", title, rows.len());
+        Section { title, header, rows: rows.into_iter().take(SAMPLE).collect(), more }
+    }
+}
+
+impl RunReport {
+    /// The report of a load, with the ICAO codes `--dedup` found conflicting entries for.
+    pub fn load<T: Record>(record: LoadRecord, summary: &LoadSummary<T>, conflicts: Vec<String>) -> RunReport {
+        RunReport {
+            record,
+            changes: Vec::new(),
+            rejected: summary.rejected.iter().map(|rejection| (rejection.record.key().into_owned(), rejection.reasons.clone())).collect(),
+            conflicts,
+            field_conflicts: Vec::new(),
+            skipped: summary.skipped.clone(),
+            failed: summary.failed.iter().map(|failure| (failure.record.key().into_owned(), failure.error.clone())).collect(),
+        }
+    }
+
+    /// The report of a sync, listing the aircraft it added, updated, deleted, retired and
+    /// reactivated.
+    pub fn sync(record: LoadRecord, summary: &SyncSummary) -> RunReport {
+        RunReport {
+            record,
+            changes: vec![
+                ("added", summary.added.clone()),
+                ("updated", summary.updated.clone()),
+                ("deleted", summary.deleted.clone()),
+                ("retired", summary.retired.clone()),
+                ("reactivated", summary.reactivated.clone()),
+            ],
+            rejected: summary.rejected.iter().map(|rejection| (rejection.record.icao_code.clone(), rejection.reasons.clone())).collect(),
+            conflicts: Vec::new(),
+            field_conflicts: Vec::new(),
+            skipped: summary.skipped.clone(),
+            failed: Vec::new(),
+        }
+    }
+
+    /// The report with the fields the sources of a merged load disagreed on.
+    pub fn with_field_conflicts(mut self, field_conflicts: Vec<FieldConflict>) -> RunReport {
+        self.field_conflicts = field_conflicts;
+        self
+    }
+
+    /// Writes the report to `path`: as Markdown if it ends in `.md` or `.markdown`, as a
+    /// standalone HTML page otherwise.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let markdown = path.extension().is_some_and(|extension| extension == "md" || extension == "markdown");
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).map_err(|source| Error::Write { path: parent.to_owned(), source })?;
+        }
+        fs::write(path, if markdown { self.markdown() } else { self.html() }).map_err(|source| Error::Write { path: path.to_owned(), source })
+    }
+
+    fn title(&self) -> String {
+        format!("{} {}", self.record.command, self.record.provenance.load_id)
+    }
+
+    fn sections(&self) -> Vec<Section> {
+        let record = &self.record;
+        let mut summary = vec![
+            ("command", record.command.clone()),
+            ("load id", record.provenance.load_id.clone()),
+            ("source file", record.provenance.source_file.clone().unwrap_or_else(|| "-".to_string())),
+            ("checksum", record.provenance.checksum.clone().unwrap_or_else(|| "-".to_string())),
+            ("started", rfc3339(record.started_at)),
+            ("finished", rfc3339(record.finished_at)),
+            ("parsed", record.parsed.to_string()),
+            ("written", record.written.to_string()),
+        ];
+        if let Some(updated) = record.updated {
+            summary.push(("updated", updated.to_string()));
+        }
+        summary.extend([
+            ("deleted", record.deleted.to_string()),
+            ("rejected", record.rejected.to_string()),
+            ("skipped", record.skipped.to_string()),
+            ("interrupted", if record.interrupted { "yes" } else { "no" }.to_string()),
+        ]);
+        let summary = summary.into_iter().map(|(field, value)| vec![field.to_string(), value]).collect();
+        let mut sections = vec![Section { title: "Summary".to_string(), header: vec!["", ""], rows: summary, more: 0 }];
+        let changes: Vec<Vec<String>> = self
+            .changes
+            .iter()
+            .filter(|(_, keys)| !keys.is_empty())
+            .map(|(change, keys)| {
+                let mut sample = keys.iter().take(SAMPLE).cloned().collect::<Vec<_>>().join(", ");
+                if keys.len() > SAMPLE {
+                    let _ = write!(sample, " and {} more", keys.len() - SAMPLE);
+                }
+                vec![change.to_string(), keys.len().to_string(), sample]
+            })
+            .collect();
+        if !changes.is_empty() {
+            sections.push(Section { title: "Changes".to_string(), header: vec!["Change", "Records", "Sample"], rows: changes, more: 0 });
+        }
+        let rejected = self.rejected.iter().map(|(key, reasons)| vec![key.clone(), reasons.join("; ")]).collect::<Vec<_>>();
+        let conflicts = self.conflicts.iter().map(|icao_code| vec![icao_code.clone()]).collect::<Vec<_>>();
+        // The kept value comes first.
+        let field_conflicts = self
+            .field_conflicts
+            .iter()
+            .map(|conflict| {
+                let values: Vec<String> = conflict.values.iter().map(|value| format!("{}: {}", value.source, value.value)).collect();
+                vec![conflict.icao_code.clone(), conflict.field.clone(), values.join("; ")]
+            })
+            .collect::<Vec<_>>();
+        let skipped = self.skipped.iter().map(|message| vec![message.clone()]).collect::<Vec<_>>();
+        let failed = self.failed.iter().map(|(key, error)| vec![key.clone(), error.clone()]).collect::<Vec<_>>();
+        for (title, header, rows) in [
+            ("Rejected", vec!["Key", "Reasons"], rejected),
+            ("Conflicts", vec!["ICAO code"], conflicts),
+            ("Source conflicts", vec!["ICAO code", "Field", "Values"], field_conflicts),
+            ("Skipped", vec!["Error"], skipped),
+            ("Failed writes", vec!["Key", "Error"], failed),
+        ] {
+            if !rows.is_empty() {
+                sections.push(Section::new(title, header, rows));
+            }
+        }
+        sections
+    }
+
+    /// The report as a standalone HTML page, a table per section.
+    pub fn html(&self) -> String {
+        let title = escape(&self.title());
+        let mut page = format!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n", title);
+        page.push_str("<style>body{font-family:sans-serif}table{border-collapse:collapse}td,th{border:1px solid #ccc;padding:2px 8px;text-align:left}</style>\n");
+        // Writing to a String cannot fail.
+        let _ = writeln!(page, "</head>\n<body>\n<h1>{}</h1>", title);
+        for section in self.sections() {
+            let _ = writeln!(page, "<h2>{}</h2>\n<table>", escape(&section.title));
+            if section.header.iter().any(|column| !column.is_empty()) {
+                let header: String = section.header.iter().map(|column| format!("<th>{}</th>", escape(column))).collect();
+                let _ = writeln!(page, "<tr>{}</tr>", header);
+            }
+            for row in &section.rows {
+                let cells: String = row.iter().map(|cell| format!("<td>{}</td>", escape(cell))).collect();
+                let _ = writeln!(page, "<tr>{}</tr>", cells);
+            }
+            page.push_str("</table>\n");
+            if section.more > 0 {
+                let _ = writeln!(page, "<p>and {} more</p>", section.more);
+            }
+        }
+        page.push_str("</body>\n</html>\n");
+        page
+    }
+
+    /// The report as Markdown, a table per section.
+    pub fn markdown(&self) -> String {
+        let mut text = format!("# {}\n", self.title());
+        for section in self.sections() {
+            let _ = writeln!(text, "\n## {}\n", section.title);
+            let _ = writeln!(text, "| {} |", section.header.join(" | "));
+            let _ = writeln!(text, "|{}", "---|".repeat(section.header.len()));
+            for row in &section.rows {
+                let cells: Vec<String> = row.iter().map(|cell| cell.replace('|', "\\|").replace('\n', " ")).collect();
+                let _ = writeln!(text, "| {} |", cells.join(" | "));
+            }
+            if section.more > 0 {
+                let _ = writeln!(text, "\nand {} more", section.more);
+            }
+        }
+        text
+    }
+}
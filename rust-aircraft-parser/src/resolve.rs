@@ -0,0 +1,88 @@
+//! Resolving many ICAO and IATA codes at once, for callers that would otherwise look them
+//! up one query at a time.
+
+use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use crate::{Aircraft, Result, Storage};
+
+/// Most codes one request resolves.
+pub const MAX_CODES: usize = 10_000;
+
+/// The codes to resolve, as `POST /resolve` takes them.
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct ResolveRequest {
+    /// ICAO type designators, IATA codes or aliases, in any case, e.g. `["B38M", "738"]`.
+    pub codes: Vec<String>,
+}
+
+/// A code only one aircraft is known by.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct Match {
+    pub code: String,
+    pub aircraft: Aircraft,
+}
+
+/// A code several aircraft are known by, such as an IATA code shared by the variants of a
+/// type, or the ICAO designator of one type that is the IATA code of another.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct Ambiguous {
+    pub code: String,
+    pub aircraft: Vec<Aircraft>,
+}
+
+/// What [`resolve`] made of each code, upper case, in the order the codes were given and
+/// without repeats.
+#[derive(Serialize, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "server", derive(utoipa::ToSchema))]
+pub struct Resolution {
+    pub matches: Vec<Match>,
+    /// Codes no aircraft is known by.
+    pub misses: Vec<String>,
+    pub ambiguities: Vec<Ambiguous>,
+}
+
+/// `codes` upper case and trimmed, in order, without blanks or repeats.
+pub fn normalize<S: AsRef<str>>(codes: impl IntoIterator<Item = S>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    codes
+        .into_iter()
+        .map(|code| code.as_ref().trim().to_ascii_uppercase())
+        .filter(|code| !code.is_empty() && seen.insert(code.clone()))
+        .collect()
+}
+
+/// Whether `aircraft` is known by the upper-case `code`, as its ICAO designator, its IATA
+/// code or one of its aliases.
+pub fn known_by(aircraft: &Aircraft, code: &str) -> bool {
+    aircraft.icao_code.eq_ignore_ascii_case(code)
+        || aircraft.iata_code.as_deref().is_some_and(|iata_code| iata_code.eq_ignore_ascii_case(code))
+        || aircraft.aliases.iter().any(|alias| alias.eq_ignore_ascii_case(code))
+}
+
+/// Resolves `codes` against the `aircraft` known by any of them, as
+/// [`Storage::find_by_codes`] returns them.
+pub fn resolve_in(codes: &[String], aircraft: &[Aircraft]) -> Resolution {
+    let mut resolution = Resolution::default();
+    for code in normalize(codes) {
+        let mut candidates: Vec<Aircraft> = aircraft.iter().filter(|aircraft| known_by(aircraft, &code)).cloned().collect();
+        match candidates.len() {
+            0 => resolution.misses.push(code),
+            1 => resolution.matches.push(Match { code, aircraft: candidates.remove(0) }),
+            _ => {
+                candidates.sort_by(|left, right| left.icao_code.cmp(&right.icao_code));
+                resolution.ambiguities.push(Ambiguous { code, aircraft: candidates });
+            }
+        }
+    }
+    resolution
+}
+
+/// Resolves `codes` with one read of `storage`.
+pub async fn resolve(storage: &dyn Storage, codes: &[String]) -> Result<Resolution> {
+    let codes = normalize(codes);
+    let aircraft = storage.find_by_codes(&codes).await?;
+    Ok(resolve_in(&codes, &aircraft))
+}
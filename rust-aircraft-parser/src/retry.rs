@@ -0,0 +1,121 @@
+//! Exponential backoff with jitter for operations that can fail transiently.
+
+use std::future::Future;
+use std::time::Duration;
+use rand::Rng;
+use tracing::warn;
+
+/// How often and how patiently [`retry`] re-runs a failing operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts made after the first failure; `0` disables retrying.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled on every following one.
+    pub base_delay: Duration,
+    /// Upper bound for a single delay.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries.
+    pub fn none() -> Self {
+        RetryPolicy { max_retries: 0, ..RetryPolicy::default() }
+    }
+
+    /// The delay before retry number `attempt` (starting at 0): exponential growth capped at
+    /// `max_delay`, with equal jitter, a random delay between half of it and all of it, so
+    /// concurrent clients don't retry in lockstep.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponential = self.base_delay.saturating_mul(2u32.saturating_pow(attempt));
+        let capped = exponential.min(self.max_delay);
+        let millis = capped.as_millis() as u64;
+        Duration::from_millis(rand::thread_rng().gen_range(millis / 2..=millis))
+    }
+}
+
+/// Runs `operation` until it succeeds, returns an error `is_transient` rejects, or the
+/// policy's retries are used up. The last error is returned in the latter two cases.
+pub async fn retry<T, E, F, Fut>(policy: &RetryPolicy, is_transient: impl Fn(&E) -> bool, mut operation: F) -> Result<T, E>
+where
+    E: std::fmt::Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut attempt = 0;
+    loop {
+        match operation().await {
+            Ok(value) => return Ok(value),
+            Err(error) if attempt < policy.max_retries && is_transient(&error) => {
+                let delay = policy.delay(attempt);
+                warn!(attempt = attempt + 1, delay_ms = delay.as_millis() as u64, %error, "transient failure, retrying");
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use super::*;
+
+    fn quick(max_retries: u32) -> RetryPolicy {
+        RetryPolicy { max_retries, base_delay: Duration::from_millis(1), max_delay: Duration::from_millis(2) }
+    }
+
+    #[test]
+    fn delays_grow_exponentially_with_jitter_up_to_the_cap() {
+        let policy = RetryPolicy::default();
+        for attempt in 0..8 {
+            let full = (policy.base_delay * 2u32.pow(attempt)).min(policy.max_delay);
+            let delay = policy.delay(attempt);
+            assert!(delay >= full / 2 && delay <= full, "attempt {}: {:?}", attempt, delay);
+        }
+        assert!(policy.delay(u32::MAX) <= policy.max_delay);
+    }
+
+    #[tokio::test]
+    async fn retries_transient_failures_until_the_operation_succeeds() {
+        let attempts = Cell::new(0);
+        let result = retry(&quick(3), |_: &String| true, || {
+            attempts.set(attempts.get() + 1);
+            let attempt = attempts.get();
+            async move { if attempt < 3 { Err(format!("attempt {}", attempt)) } else { Ok(attempt) } }
+        })
+        .await;
+        assert_eq!(result, Ok(3));
+    }
+
+    #[tokio::test]
+    async fn gives_up_on_a_permanent_failure_or_once_the_retries_are_used_up() {
+        let attempts = Cell::new(0);
+        let permanent = retry(&quick(3), |error: &String| error != "permanent", || {
+            attempts.set(attempts.get() + 1);
+            async { Err::<(), _>("permanent".to_string()) }
+        })
+        .await;
+        assert_eq!(permanent, Err("permanent".to_string()));
+        assert_eq!(attempts.get(), 1);
+
+        attempts.set(0);
+        let transient = retry(&quick(2), |_: &String| true, || {
+            attempts.set(attempts.get() + 1);
+            async { Err::<(), _>("transient".to_string()) }
+        })
+        .await;
+        assert_eq!(transient, Err("transient".to_string()));
+        assert_eq!(attempts.get(), 3);
+    }
+}
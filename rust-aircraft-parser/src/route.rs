@@ -0,0 +1,122 @@
+use std::borrow::Cow;
+use mongodb::bson::{self, doc, Document};
+use mongodb::IndexModel;
+use serde::{Deserialize, Serialize};
+use crate::airport::AirportIndex;
+use crate::input::OpenFlightsRow;
+use crate::record::Record;
+use crate::validate::is_alphanumeric;
+use crate::{Airline, Airport, Result};
+
+// Kilometres in a nautical mile.
+const KM_PER_NM: f64 = 1.852;
+
+/// A scheduled route flown by an airline between two airports, keyed by all three codes.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Route {
+    /// ICAO or IATA code of the operating airline, e.g. `BAW` or `BA`.
+    pub airline: String,
+    /// ICAO or IATA code of the departure airport, e.g. `EGLL` or `LHR`.
+    pub origin: String,
+    /// ICAO or IATA code of the arrival airport, e.g. `KJFK` or `JFK`.
+    pub destination: String,
+    /// Aircraft type codes flown on the route, e.g. `["B77W", "A35K"]`.
+    #[serde(default)]
+    pub equipment: Vec<String>,
+    /// Great-circle distance between the two airports in kilometres, set by [`measure`](Self::measure).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distance_km: Option<f64>,
+    /// The same distance in nautical miles.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distance_nm: Option<f64>,
+}
+
+impl Route {
+    /// `<airline>:<origin>-<destination>`, e.g. `BAW:EGLL-KJFK`.
+    pub fn route_key(&self) -> String {
+        format!("{}:{}-{}", self.airline, self.origin, self.destination)
+    }
+
+    /// Sets the distances to the great-circle distance between the origin and destination
+    /// found in `airports`, to a tenth, and returns whether both were found.
+    pub fn measure(&mut self, airports: &AirportIndex) -> bool {
+        let (Some(origin), Some(destination)) = (airports.get(&self.origin), airports.get(&self.destination)) else {
+            return false;
+        };
+        let distance_km = origin.distance_km(destination.latitude, destination.longitude);
+        self.distance_km = Some((distance_km * 10.0).round() / 10.0);
+        self.distance_nm = Some((distance_km / KM_PER_NM * 10.0).round() / 10.0);
+        true
+    }
+}
+
+impl Record for Route {
+    const COLLECTION: &'static str = "routes";
+    const KEY_FIELD: &'static str = "routeKey";
+    const REFERENCES: &'static [&'static str] = &[Airline::COLLECTION, Airport::COLLECTION];
+
+    fn key(&self) -> Cow<'_, str> {
+        Cow::Owned(self.route_key())
+    }
+
+    /// * `airline` must be 2 or 3 letters or digits.
+    /// * `origin` and `destination` must be 3 or 4 letters or digits, and differ.
+    /// * `equipment` entries must not be blank.
+    fn validate(&self) -> Vec<String> {
+        let mut reasons = Vec::new();
+        if !(2..=3).contains(&self.airline.chars().count()) || !is_alphanumeric(&self.airline) {
+            reasons.push(format!("airline {:?} is not 2 or 3 alphanumeric characters", self.airline));
+        }
+        for (field, code) in [("origin", &self.origin), ("destination", &self.destination)] {
+            if !(3..=4).contains(&code.chars().count()) || !is_alphanumeric(code) {
+                reasons.push(format!("{} {:?} is not 3 or 4 alphanumeric characters", field, code));
+            }
+        }
+        if self.origin == self.destination {
+            reasons.push(format!("origin and destination are both {:?}", self.origin));
+        }
+        if self.equipment.iter().any(|code| code.trim().is_empty()) {
+            reasons.push("equipment contains an empty code".to_string());
+        }
+        reasons
+    }
+
+    fn references(&self) -> Vec<(&'static str, &str)> {
+        vec![
+            (Airline::COLLECTION, &self.airline),
+            (Airport::COLLECTION, &self.origin),
+            (Airport::COLLECTION, &self.destination),
+        ]
+    }
+
+    /// `routes.dat`: airline, airline id, origin, origin id, destination, destination id,
+    /// codeshare, stops, space separated equipment.
+    fn openflights() -> Option<fn(&OpenFlightsRow) -> Result<Self>> {
+        Some(|row| {
+            Ok(Route {
+                airline: row.text(0),
+                origin: row.text(2),
+                destination: row.text(4),
+                equipment: row.get(8).unwrap_or_default().split_whitespace().map(str::to_string).collect(),
+                distance_km: None,
+                distance_nm: None,
+            })
+        })
+    }
+
+    /// The route's fields plus its `routeKey`.
+    fn to_document(&self) -> Result<Document> {
+        let mut document = bson::to_document(self)?;
+        document.insert(Self::KEY_FIELD, self.route_key());
+        Ok(document)
+    }
+
+    /// Indexes on `origin` and on `destination`, for departures and arrivals of an airport.
+    fn indexes() -> Vec<IndexModel> {
+        vec![
+            IndexModel::builder().keys(doc! { "origin": 1 }).build(),
+            IndexModel::builder().keys(doc! { "destination": 1 }).build(),
+        ]
+    }
+}
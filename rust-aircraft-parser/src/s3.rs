@@ -0,0 +1,93 @@
+//! Reading input objects straight from Amazon S3.
+
+use std::io::{self, BufReader, Read};
+use aws_sdk_s3::config::Region;
+use aws_sdk_s3::error::DisplayErrorContext;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::Client;
+use bytes::{Buf, Bytes};
+use tokio::runtime::Handle;
+use tracing::info;
+use crate::input::decompress;
+use crate::{Error, Result};
+
+/// Overrides for the AWS configuration otherwise taken from the environment, the shared
+/// config files and instance metadata.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct S3Options {
+    pub region: Option<String>,
+    /// Named profile of the shared config files.
+    pub profile: Option<String>,
+}
+
+/// An object addressed as `s3://bucket/key`.
+#[derive(Clone, Debug)]
+pub struct S3Object {
+    client: Client,
+    bucket: String,
+    key: String,
+}
+
+impl S3Object {
+    /// A handle on the object at `url`, with a client configured from `options`.
+    pub async fn connect(url: &str, options: &S3Options) -> Result<Self> {
+        let (bucket, key) = url
+            .strip_prefix("s3://")
+            .and_then(|location| location.split_once('/'))
+            .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+            .ok_or_else(|| Error::Config(format!("{} is not of the form s3://bucket/key", url)))?;
+        let mut loader = aws_config::from_env();
+        if let Some(region) = &options.region {
+            loader = loader.region(Region::new(region.clone()));
+        }
+        if let Some(profile) = &options.profile {
+            loader = loader.profile_name(profile);
+        }
+        let client = Client::new(&loader.load().await);
+        Ok(S3Object { client, bucket: bucket.to_string(), key: key.to_string() })
+    }
+
+    /// The object's ETag, which changes whenever its content does.
+    pub async fn etag(&self) -> Result<Option<String>> {
+        let head = self.client.head_object().bucket(&self.bucket).key(&self.key).send().await.map_err(s3_error)?;
+        Ok(head.e_tag().map(|etag| etag.trim_matches('"').to_string()))
+    }
+
+    /// Starts downloading the object and returns a reader over its body, which yields the
+    /// bytes as they arrive rather than buffering the object, decompressing gzip and zip
+    /// objects. The reader blocks on the current Tokio runtime, so it must be used on a
+    /// multi-threaded one.
+    pub async fn open(&self) -> Result<Box<dyn io::BufRead + Send>> {
+        let object = self.client.get_object().bucket(&self.bucket).key(&self.key).send().await.map_err(s3_error)?;
+        info!(bucket = %self.bucket, key = %self.key, bytes = object.content_length(), "reading s3 object");
+        let body = BodyReader { body: object.body, chunk: Bytes::new(), runtime: Handle::current() };
+        decompress(BufReader::new(body)).map_err(|error| Error::S3(error.to_string()))
+    }
+}
+
+fn s3_error(error: impl std::error::Error + 'static) -> Error {
+    Error::S3(DisplayErrorContext(error).to_string())
+}
+
+// Adapts the asynchronous body stream to the synchronous readers of the parsers.
+struct BodyReader {
+    body: ByteStream,
+    chunk: Bytes,
+    runtime: Handle,
+}
+
+impl Read for BodyReader {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        while self.chunk.is_empty() {
+            let next = tokio::task::block_in_place(|| self.runtime.block_on(self.body.try_next()));
+            match next.map_err(io::Error::other)? {
+                Some(chunk) => self.chunk = chunk,
+                None => return Ok(0),
+            }
+        }
+        let length = buffer.len().min(self.chunk.len());
+        buffer[..length].copy_from_slice(&self.chunk[..length]);
+        self.chunk.advance(length);
+        Ok(length)
+    }
+}
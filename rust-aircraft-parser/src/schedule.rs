@@ -0,0 +1,43 @@
+//! Cron schedules for runs repeated by a long-lived process, such as `sync --schedule`.
+
+use std::str::FromStr;
+use std::time::Duration;
+use chrono::{DateTime, Local};
+use croner::Cron;
+use tracing::info;
+use crate::{Error, Result};
+
+/// A cron expression of five fields (minute, hour, day of month, month, day of week), or six
+/// with leading seconds, matched against local time; e.g. `0 3 * * *` for 03:00 every night.
+#[derive(Clone, Debug)]
+pub struct Schedule {
+    expression: String,
+    cron: Cron,
+}
+
+impl FromStr for Schedule {
+    type Err = Error;
+
+    fn from_str(expression: &str) -> Result<Self> {
+        let cron = Cron::from_str(expression).map_err(|error| Error::Config(format!("invalid schedule {:?}: {}", expression, error)))?;
+        Ok(Schedule { expression: expression.to_string(), cron })
+    }
+}
+
+impl Schedule {
+    /// The first time after `time` the schedule matches.
+    pub fn next_after(&self, time: &DateTime<Local>) -> Result<DateTime<Local>> {
+        self.cron
+            .find_next_occurrence(time, false)
+            .map_err(|error| Error::Config(format!("schedule {:?} never matches again: {}", self.expression, error)))
+    }
+
+    /// Sleeps until the next time the schedule matches, which it returns.
+    pub async fn wait(&self) -> Result<DateTime<Local>> {
+        let now = Local::now();
+        let next = self.next_after(&now)?;
+        info!(next = %next.to_rfc3339(), "waiting for the next scheduled run");
+        tokio::time::sleep((next - now).to_std().unwrap_or(Duration::ZERO)).await;
+        Ok(next)
+    }
+}
@@ -0,0 +1,127 @@
+//! `$jsonSchema` validators matching the models, installed on their collections so writes
+//! from other tools cannot change the shape of the documents.
+
+use clap::ValueEnum;
+use mongodb::bson::{doc, Bson, Document};
+use tracing::info;
+use crate::fields::{FieldNames, MissingCode, DESCRIPTIONS};
+use crate::record::Record;
+use crate::{Airline, Airport, AircraftStore, Result};
+
+/// Which existing documents a validator holds to, as MongoDB's `validationLevel`.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ValidationLevel {
+    /// Every insert and update
+    #[default]
+    Strict,
+    /// Inserts, and updates of documents that already pass
+    Moderate,
+}
+
+impl ValidationLevel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ValidationLevel::Strict => "strict",
+            ValidationLevel::Moderate => "moderate",
+        }
+    }
+}
+
+// Fields every loader stamps onto the documents it writes.
+fn provenance(properties: &mut Document) {
+    for name in ["loadId", "sourceFile", "checksum"] {
+        properties.insert(name, doc! { "bsonType": "string" });
+    }
+    for name in ["createdAt", "updatedAt"] {
+        properties.insert(name, doc! { "bsonType": "date" });
+    }
+}
+
+fn code() -> Document {
+    doc! { "bsonType": "string", "minLength": 1 }
+}
+
+fn string() -> Document {
+    doc! { "bsonType": "string" }
+}
+
+/// The validator of aircraft stored under `fields`. The IATA code may be null or left out
+/// as --missing-iata stores it, and fields added by enrichment are allowed.
+pub fn aircraft(fields: &FieldNames) -> Document {
+    let iata_code = match fields.missing_iata {
+        MissingCode::Null => doc! { "bsonType": ["string", "null"] },
+        MissingCode::Empty | MissingCode::Omit => string(),
+    };
+    let mut properties = doc! {
+        &fields.icao_code: code(),
+        &fields.iata_code: iata_code,
+        &fields.description: string(),
+        DESCRIPTIONS: { "bsonType": "object", "additionalProperties": string() },
+    };
+    provenance(&mut properties);
+    let mut required = vec![Bson::from(&fields.icao_code), Bson::from(&fields.description)];
+    if fields.missing_iata != MissingCode::Omit {
+        required.push(Bson::from(&fields.iata_code));
+    }
+    doc! { "$jsonSchema": { "bsonType": "object", "required": required, "properties": properties } }
+}
+
+/// The validator of [`Airport`] documents.
+pub fn airports() -> Document {
+    let mut properties = doc! {
+        "icaoCode": code(),
+        "iataCode": string(),
+        "name": string(),
+        "city": string(),
+        "country": string(),
+        "latitude": { "bsonType": "double", "minimum": -90, "maximum": 90 },
+        "longitude": { "bsonType": "double", "minimum": -180, "maximum": 180 },
+        "elevation": { "bsonType": ["int", "null"] },
+        "timezone": { "bsonType": ["string", "null"] },
+    };
+    provenance(&mut properties);
+    doc! { "$jsonSchema": { "bsonType": "object", "required": ["icaoCode", "name", "latitude", "longitude"], "properties": properties } }
+}
+
+/// The validator of [`Airline`] documents.
+pub fn airlines() -> Document {
+    let mut properties = doc! {
+        "icaoCode": code(),
+        "iataCode": string(),
+        "name": string(),
+        "callsign": string(),
+        "country": string(),
+        "active": { "bsonType": "bool" },
+    };
+    provenance(&mut properties);
+    doc! { "$jsonSchema": { "bsonType": "object", "required": ["icaoCode", "name"], "properties": properties } }
+}
+
+/// Installs the validators on the store's aircraft collection and on the airports and
+/// airlines collections next to it, creating those that don't exist yet. Returns the
+/// collections validated.
+pub async fn apply(store: &AircraftStore, level: ValidationLevel) -> Result<Vec<String>> {
+    let collection = store.collection();
+    let database = collection.client().database(&collection.namespace().db);
+    let existing = database.list_collection_names(None).await?;
+    let validators = [
+        (collection.name().to_string(), aircraft(store.field_names())),
+        (Airport::COLLECTION.to_string(), airports()),
+        (Airline::COLLECTION.to_string(), airlines()),
+    ];
+    let mut applied = Vec::new();
+    for (name, validator) in validators {
+        // collMod only changes collections that exist; create takes the same options.
+        let verb = if existing.contains(&name) { "collMod" } else { "create" };
+        let command = doc! {
+            verb: &name,
+            "validator": validator,
+            "validationLevel": level.as_str(),
+            "validationAction": "error",
+        };
+        database.run_command(command, None).await?;
+        info!(collection = %name, level = level.as_str(), "schema validator installed");
+        applied.push(name);
+    }
+    Ok(applied)
+}
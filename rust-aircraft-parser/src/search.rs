@@ -0,0 +1,97 @@
+//! Finding aircraft by free text rather than by code.
+
+use std::cmp::Ordering;
+use crate::Aircraft;
+
+// Share of a query word's letters that may differ from a description word it matches.
+const TYPO_TOLERANCE: f64 = 0.25;
+
+/// An aircraft matching a [`search`], with how well it matches: 1 for an exact ICAO or
+/// IATA code, less the looser the match.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchHit {
+    pub aircraft: Aircraft,
+    pub score: f64,
+}
+
+/// The aircraft matching `query`, best first, at most `limit` of them. Matching ignores
+/// case and punctuation: a code equal to the query ranks first, then descriptions containing
+/// it, then descriptions whose words each start like, or differ by a typo or so from, a word
+/// of the query.
+pub fn search(aircrafts: impl IntoIterator<Item = Aircraft>, query: &str, limit: usize) -> Vec<SearchHit> {
+    let query = normalize(query);
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let mut hits: Vec<SearchHit> = aircrafts
+        .into_iter()
+        .filter_map(|aircraft| score(&aircraft, &query).map(|score| SearchHit { aircraft, score }))
+        .collect();
+    hits.sort_by(|left, right| {
+        right
+            .score
+            .partial_cmp(&left.score)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| left.aircraft.icao_code.cmp(&right.aircraft.icao_code))
+    });
+    hits.truncate(limit);
+    hits
+}
+
+fn score(aircraft: &Aircraft, query: &str) -> Option<f64> {
+    if query == normalize(&aircraft.icao_code) || aircraft.iata_code.as_deref().is_some_and(|iata_code| query == normalize(iata_code)) {
+        return Some(1.0);
+    }
+    let description = normalize(&aircraft.description);
+    if description.contains(query) {
+        // A query covering more of the description is the closer match.
+        return Some(0.8 + 0.1 * query.len() as f64 / description.len() as f64);
+    }
+    let words: Vec<&str> = description.split(' ').collect();
+    let terms: Vec<&str> = query.split(' ').collect();
+    let mut total = 0.0;
+    for term in &terms {
+        let best = words.iter().map(|word| similarity(term, word)).fold(0.0, f64::max);
+        if best == 0.0 {
+            return None;
+        }
+        total += best;
+    }
+    Some(0.7 * total / terms.len() as f64)
+}
+
+// 1 for a word starting with `term`, less for one a few edits away, 0 for anything further.
+fn similarity(term: &str, word: &str) -> f64 {
+    if word.starts_with(term) {
+        return 1.0;
+    }
+    let length = term.chars().count().max(word.chars().count());
+    let distance = levenshtein(term, word);
+    if distance as f64 > TYPO_TOLERANCE * length as f64 {
+        return 0.0;
+    }
+    1.0 - distance as f64 / length as f64
+}
+
+fn levenshtein(left: &str, right: &str) -> usize {
+    let right: Vec<char> = right.chars().collect();
+    let mut previous: Vec<usize> = (0..=right.len()).collect();
+    for (row, left_character) in left.chars().enumerate() {
+        let mut current = vec![row + 1];
+        for (column, right_character) in right.iter().enumerate() {
+            let substitution = previous[column] + usize::from(left_character != *right_character);
+            current.push(substitution.min(previous[column + 1] + 1).min(current[column] + 1));
+        }
+        previous = current;
+    }
+    previous[right.len()]
+}
+
+// Lowercase words separated by single spaces, e.g. "boeing 737 max 8" for "Boeing 737-MAX 8".
+fn normalize(text: &str) -> String {
+    let text: String = text
+        .chars()
+        .map(|character| if character.is_alphanumeric() { character.to_ascii_lowercase() } else { ' ' })
+        .collect();
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
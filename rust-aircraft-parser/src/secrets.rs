@@ -0,0 +1,88 @@
+//! Fetching the MongoDB connection string at run time from a secrets store, so credentials
+//! never have to be written to a `.env` file.
+
+use std::env;
+use serde_json::Value;
+use crate::{Error, Result};
+
+/// Fetches the secret `reference` names:
+///
+/// * `arn:aws:secretsmanager:…`: a secret of AWS Secrets Manager, with the `secretsmanager`
+///   feature, read with the AWS configuration of the environment, such as an ECS task role.
+/// * `vault://PATH`: the secret at `PATH` of the HashiCorp Vault HTTP API at `VAULT_ADDR`,
+///   read with `VAULT_TOKEN` (and `VAULT_NAMESPACE` if set). Paths of a KV version 2
+///   engine include `data/`, e.g. `vault://secret/data/aircraft`.
+///
+/// A secret holding several fields is narrowed down to one by ending the reference with
+/// `#FIELD`, e.g. `vault://secret/data/aircraft#mongodb_url`.
+pub async fn resolve(reference: &str) -> Result<String> {
+    let (location, field) = match reference.rsplit_once('#') {
+        Some((location, field)) => (location, Some(field)),
+        None => (reference, None),
+    };
+    let secret = if let Some(path) = location.strip_prefix("vault://") {
+        vault(path).await?
+    } else if location.starts_with("arn:aws:secretsmanager:") {
+        secrets_manager(location).await?
+    } else {
+        return Err(Error::Config(format!("{} is neither an arn:aws:secretsmanager: ARN nor a vault:// path", reference)));
+    };
+    let value = match (secret, field) {
+        (Value::Object(mut fields), Some(field)) => {
+            fields.remove(field).ok_or_else(|| Error::Secret(format!("{} has no field {}", location, field)))?
+        }
+        (Value::Object(fields), None) if fields.len() == 1 => fields.into_values().next().unwrap_or_default(),
+        (Value::Object(_), None) => return Err(Error::Secret(format!("{} holds several fields, pick one with #FIELD", location))),
+        (_, Some(field)) => return Err(Error::Secret(format!("{} holds no fields to pick {} from", location, field))),
+        (value, None) => value,
+    };
+    match value {
+        Value::String(secret) => Ok(secret),
+        _ => Err(Error::Secret(format!("{} is not a string", reference))),
+    }
+}
+
+// The fields of the secret at `path`, without the metadata KV version 2 keeps next to them.
+async fn vault(path: &str) -> Result<Value> {
+    let address = env::var("VAULT_ADDR").map_err(|_| Error::Config("vault:// secrets need VAULT_ADDR".to_string()))?;
+    let token = env::var("VAULT_TOKEN").map_err(|_| Error::Config("vault:// secrets need VAULT_TOKEN".to_string()))?;
+    let url = format!("{}/v1/{}", address.trim_end_matches('/'), path.trim_start_matches('/'));
+    let mut request = reqwest::Client::new().get(url).header("X-Vault-Token", token);
+    if let Ok(namespace) = env::var("VAULT_NAMESPACE") {
+        request = request.header("X-Vault-Namespace", namespace);
+    }
+    let response = request.send().await?.error_for_status()?;
+    let mut body: Value = serde_json::from_slice(&response.bytes().await?)?;
+    let mut data = body.get_mut("data").map(Value::take).unwrap_or_default();
+    if data.get("metadata").is_some() && data.get("data").is_some_and(Value::is_object) {
+        return Ok(data["data"].take());
+    }
+    Ok(data)
+}
+
+// The secret string of `arn`, as a JSON object when it holds one, read in the region of the
+// ARN.
+#[cfg(feature = "secretsmanager")]
+async fn secrets_manager(arn: &str) -> Result<Value> {
+    use aws_sdk_secretsmanager::config::Region;
+    use aws_sdk_secretsmanager::error::DisplayErrorContext;
+
+    let mut loader = aws_config::from_env();
+    if let Some(region) = arn.split(':').nth(3).filter(|region| !region.is_empty()) {
+        loader = loader.region(Region::new(region.to_string()));
+    }
+    let client = aws_sdk_secretsmanager::Client::new(&loader.load().await);
+    let output = client
+        .get_secret_value()
+        .secret_id(arn)
+        .send()
+        .await
+        .map_err(|error| Error::Secret(DisplayErrorContext(error).to_string()))?;
+    let text = output.secret_string().ok_or_else(|| Error::Secret(format!("{} holds no secret string", arn)))?;
+    Ok(serde_json::from_str(text).ok().filter(Value::is_object).unwrap_or_else(|| Value::String(text.to_string())))
+}
+
+#[cfg(not(feature = "secretsmanager"))]
+async fn secrets_manager(arn: &str) -> Result<Value> {
+    Err(Error::Config(format!("reading {} needs the secretsmanager feature", arn)))
+}
@@ -0,0 +1,113 @@
+//! An end-to-end run of load, sync and export against a scratch MongoDB collection, for
+//! `self-test` and the integration tests.
+
+use std::collections::BTreeMap;
+use futures::TryStreamExt;
+use mongodb::bson::doc;
+use mongodb::IndexModel;
+use serde::Serialize;
+#[cfg(feature = "testcontainers")]
+use testcontainers_modules::{mongo::Mongo, testcontainers::runners::AsyncRunner, testcontainers::ContainerAsync};
+use crate::export::{self, ExportOptions};
+use crate::load::{self, LoadOptions};
+use crate::sync::{self, SyncOptions};
+#[cfg(feature = "testcontainers")]
+use crate::retry::RetryPolicy;
+#[cfg(feature = "testcontainers")]
+use crate::storage::ClientSettings;
+use crate::{Aircraft, AircraftStore, Error, Result, Storage};
+
+// The sample corpus of the `testing` feature, which needs no feature here.
+const CORPUS: &str = include_str!("../fixtures/aircraft.json");
+
+/// One thing [`run`] checked.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Check {
+    pub name: &'static str,
+    pub passed: bool,
+    pub detail: String,
+}
+
+/// Loads the sample corpus into the store's collection, which must be empty, syncs a
+/// changed copy of it over the stored aircraft and exports the result, checking the indexes
+/// and document counts along the way. The collection is dropped afterwards whatever
+/// happened. An error means a step could not run at all; a check that did not pass is
+/// returned like the others.
+pub async fn run(store: &AircraftStore) -> Result<Vec<Check>> {
+    let stored = store.collection().count_documents(doc! {}, None).await?;
+    if stored > 0 {
+        return Err(Error::Config(format!("self-test needs an empty collection, {} holds {} documents", store.collection().name(), stored)));
+    }
+    let mut checks = Vec::new();
+    let result = steps(store, &mut checks).await;
+    store.collection().drop(None).await?;
+    result.map(|_| checks)
+}
+
+async fn steps(store: &AircraftStore, checks: &mut Vec<Check>) -> Result<()> {
+    let corpus: Vec<Aircraft> = serde_json::from_str(CORPUS)?;
+    let count = corpus.len() as u64;
+    let mut check = |name, passed, detail: String| checks.push(Check { name, passed, detail });
+
+    store.ensure_indexes().await?;
+    let indexes: Vec<IndexModel> = store.collection().list_indexes(None).await?.try_collect().await?;
+    let icao_code = &store.field_names().icao_code;
+    let unique = indexes.iter().any(|index| {
+        index.keys.contains_key(icao_code) && index.options.as_ref().is_some_and(|options| options.unique == Some(true))
+    });
+    check("unique icao index", unique, format!("{} indexes", indexes.len()));
+
+    let summary = load::load(store, corpus.clone().into_iter().map(Ok), &LoadOptions::default()).await?;
+    check("load writes every aircraft", summary.written == count, format!("{} of {} written", summary.written, count));
+    let stored = store.collection().count_documents(doc! {}, None).await?;
+    check("load stores every aircraft", stored == count, format!("{} of {} stored", stored, count));
+
+    let again: Vec<Result<Aircraft>> = corpus[..1].iter().cloned().map(Ok).collect();
+    let refused = load::load(store, again.into_iter(), &LoadOptions::default()).await;
+    check("duplicate icao code refused", refused.is_err(), "inserting the first aircraft again".to_string());
+
+    // One aircraft changed, one dropped and one new.
+    let mut changed = corpus.clone();
+    changed[0].description.push_str(" (changed)");
+    let dropped = changed.pop().map(|aircraft| aircraft.icao_code).unwrap_or_default();
+    changed.push(Aircraft {
+        icao_code: "T154".to_string(),
+        iata_code: Some("TU5".to_string()),
+        description: "Tupolev Tu-154".to_string(),
+        descriptions: BTreeMap::new(),
+        aliases: Vec::new(),
+    });
+    let options = SyncOptions { prune: true, ..SyncOptions::default() };
+    let summary = sync::sync(store, changed.clone().into_iter().map(Ok), &options).await?;
+    let expected = summary.added.len() == 1 && summary.updated.len() == 1 && summary.deleted == [dropped];
+    check(
+        "sync adds, updates and prunes",
+        expected,
+        format!("{} added, {} updated, {} deleted", summary.added.len(), summary.updated.len(), summary.deleted.len()),
+    );
+    let stored = store.collection().count_documents(doc! {}, None).await?;
+    check("sync leaves the input stored", stored == changed.len() as u64, format!("{} of {} stored", stored, changed.len()));
+
+    let lowercase = changed[0].icao_code.to_ascii_lowercase();
+    let found = store.find_by_icao(&lowercase).await?;
+    check("lookups ignore case", found.is_some(), format!("looked up {}", lowercase));
+
+    let mut buffer = Vec::new();
+    export::export(&mut buffer, store.find_all_with_ids().await?, &ExportOptions::default())?;
+    let exported: Vec<Aircraft> = serde_json::from_slice(&buffer)?;
+    let sorted = exported.windows(2).all(|pair| pair[0].icao_code <= pair[1].icao_code);
+    check("export writes every aircraft sorted", exported.len() == changed.len() && sorted, format!("{} exported", exported.len()));
+    Ok(())
+}
+
+/// Starts a disposable MongoDB in a container and opens `database.collection` on it. The
+/// container is removed when the returned handle is dropped.
+#[cfg(feature = "testcontainers")]
+pub async fn container(database: &str, collection: &str) -> Result<(AircraftStore, ContainerAsync<Mongo>)> {
+    let container = Mongo::default().start().await.map_err(|error| Error::Container(error.to_string()))?;
+    let host = container.get_host().await.map_err(|error| Error::Container(error.to_string()))?;
+    let port = container.get_host_port_ipv4(27017).await.map_err(|error| Error::Container(error.to_string()))?;
+    let uri = format!("mongodb://{}:{}/", host, port);
+    let store = AircraftStore::connect(&uri, &ClientSettings::default(), database, collection, RetryPolicy::default()).await?;
+    Ok((store, container))
+}
@@ -0,0 +1,608 @@
+//! A read-only HTTP API over the stored reference data, for tools without a MongoDB driver.
+//!
+//! | endpoint | response |
+//! |----------|----------|
+//! | `GET /aircraft/{icao}` | the aircraft with that ICAO type designator |
+//! | `GET /aircraft?iata=` | a [`Page`] of aircraft, of one IATA code if given |
+//! | `POST /resolve` | the [`Resolution`] of up to [`MAX_CODES`] ICAO and IATA codes at once |
+//! | `GET /airports/{icao}` | the airport with that ICAO location indicator |
+//! | `GET /airports?iata=&country=` | a [`Page`] of airports |
+//! | `GET /airlines/{icao}` | the airline with that ICAO designator |
+//! | `GET /airlines?iata=&country=` | a [`Page`] of airlines |
+//! | `POST /graphql` | a GraphQL query of the `graphql` schema, resolving routes' references, with the `graphql` feature |
+//! | `GET /graphiql` | GraphiQL over that schema, with the `graphql` feature |
+//! | `GET /openapi.json` | the OpenAPI 3 document of the endpoints above, see [`openapi`] |
+//! | `GET /docs` | Swagger UI over that document |
+//! | `GET /metrics` | the process' [metrics](crate::metrics) in the Prometheus text format |
+//!
+//! Lists take `offset` and `limit` (default 100, at most [`MAX_PAGE_SIZE`]) parameters.
+//! Errors are answered with a JSON `{"error": ...}` body. With [`AccessOptions`] requiring
+//! keys, requests must carry one in an `X-API-Key` or `Authorization: Bearer` header and
+//! are refused with 401 otherwise; over the rate limit they are refused with 429. The
+//! OpenAPI document, the UIs and the metrics are open to every caller.
+//!
+//! Given a store [with a lookup cache](AircraftStore::with_lookup_cache), lookups by ICAO
+//! code are answered from memory, see [`cache`](crate::cache).
+
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use axum::extract::{Path, Query, Request, State};
+use axum::http::header::{AUTHORIZATION, CONTENT_TYPE, RETRY_AFTER};
+use axum::http::{HeaderMap, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use mongodb::bson::{doc, Document};
+use prometheus::TEXT_FORMAT;
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+use tokio::net::TcpListener;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityRequirement, SecurityScheme};
+use utoipa::{IntoParams, OpenApi, ToSchema};
+use tracing::{error, info};
+#[cfg(feature = "graphql")]
+use crate::graphql::{self, Schema};
+use crate::metrics::metrics;
+use crate::record::Record;
+use crate::resolve::{self, Resolution, ResolveRequest, MAX_CODES};
+use crate::{Aircraft, AircraftStore, Airline, Airport, Error, Result, Storage};
+
+/// Most items a page holds, whatever `limit` asks for.
+pub const MAX_PAGE_SIZE: u64 = 1000;
+
+/// Collection of the database API keys are looked up in, as documents with a `key` field;
+/// those with `revoked: true` are refused.
+pub const API_KEYS: &str = "api_keys";
+
+const DEFAULT_PAGE_SIZE: u64 = 100;
+
+// Swagger UI, loaded from a CDN so the binary doesn't carry its assets.
+const DOCS: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+  <title>Aircraft reference data API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="docs"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>SwaggerUIBundle({ url: "/openapi.json", dom_id: "#docs" });</script>
+</body>
+</html>
+"##;
+
+// Span the rate limit counts requests over.
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Who may call the API and how often. The default lets anyone call it without limit.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AccessOptions {
+    /// Keys accepted on top of those in the [`API_KEYS`] collection.
+    pub api_keys: HashSet<String>,
+    /// Also accept the keys in the [`API_KEYS`] collection.
+    pub key_collection: bool,
+    /// Requests each key may make per minute; without required keys the limit is shared
+    /// by every caller.
+    pub rate_limit: Option<u32>,
+}
+
+impl AccessOptions {
+    fn requires_key(&self) -> bool {
+        !self.api_keys.is_empty() || self.key_collection
+    }
+
+    fn lists(&self, key: &str) -> bool {
+        lists_key(&self.api_keys, key)
+    }
+
+    // Who the rate limit counts a request against: its key when keys are required, and
+    // every caller together otherwise, since a key nobody checks is free to change.
+    fn rate_key<'a>(&self, key: Option<&'a str>) -> &'a str {
+        match self.requires_key() {
+            true => key.unwrap_or_default(),
+            false => "",
+        }
+    }
+}
+
+// The access options with what enforcing them needs.
+struct Access {
+    options: AccessOptions,
+    store: AircraftStore,
+    limiter: Mutex<RateLimiter>,
+}
+
+impl Access {
+    async fn accepts(&self, key: &str) -> Result<bool> {
+        if self.options.lists(key) {
+            return Ok(true);
+        }
+        if !self.options.key_collection {
+            return Ok(false);
+        }
+        let keys = self.store.sibling(API_KEYS);
+        Ok(keys.find_one(doc! { "key": key, "revoked": { "$ne": true } }, None).await?.is_some())
+    }
+
+    // Counts a request of `key`, returning how long until it may call again when over the limit.
+    fn throttle(&self, key: &str) -> Option<Duration> {
+        let limit = self.options.rate_limit?;
+        let mut limiter = self.limiter.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        limiter.count(key, limit, Instant::now())
+    }
+}
+
+// The requests of each key in its current rate limit window. Windows that ended are
+// dropped once a window, so keys that stop calling are not kept.
+#[derive(Default)]
+struct RateLimiter {
+    windows: HashMap<String, Window>,
+    swept: Option<Instant>,
+}
+
+// Requests of one key in the current rate limit window.
+struct Window {
+    started: Instant,
+    requests: u32,
+}
+
+impl RateLimiter {
+    // Counts a request of `key` at `now`, returning how long until it may call again when
+    // it is over `limit`.
+    fn count(&mut self, key: &str, limit: u32, now: Instant) -> Option<Duration> {
+        let swept = *self.swept.get_or_insert(now);
+        if now.duration_since(swept) >= RATE_WINDOW {
+            self.windows.retain(|_, window| now.duration_since(window.started) < RATE_WINDOW);
+            self.swept = Some(now);
+        }
+        let window = self.windows.entry(key.to_string()).or_insert(Window { started: now, requests: 0 });
+        if now.duration_since(window.started) >= RATE_WINDOW {
+            *window = Window { started: now, requests: 0 };
+        }
+        if window.requests >= limit {
+            return Some(RATE_WINDOW - now.duration_since(window.started));
+        }
+        window.requests += 1;
+        None
+    }
+}
+
+// Whether `key` is one of `api_keys`, comparing it with every one of them in constant time
+// so the time taken tells nothing of how much of a key was guessed.
+pub(crate) fn lists_key(api_keys: &HashSet<String>, key: &str) -> bool {
+    api_keys.iter().fold(false, |found, listed| found | bool::from(listed.as_bytes().ct_eq(key.as_bytes())))
+}
+
+// The key of an `X-API-Key` or `Authorization: Bearer` header.
+pub(crate) fn api_key(headers: &HeaderMap) -> Option<&str> {
+    if let Some(key) = headers.get("x-api-key") {
+        return key.to_str().ok();
+    }
+    let authorization = headers.get(AUTHORIZATION)?.to_str().ok()?;
+    authorization.strip_prefix("Bearer ").map(str::trim)
+}
+
+async fn authorize(State(access): State<Arc<Access>>, request: Request, next: Next) -> Response {
+    let key = api_key(request.headers()).filter(|key| !key.is_empty()).map(str::to_string);
+    if access.options.requires_key() {
+        let Some(key) = &key else { return ApiError::Unauthorized("missing API key").into_response() };
+        match access.accepts(key).await {
+            Ok(true) => {}
+            Ok(false) => return ApiError::Unauthorized("unknown API key").into_response(),
+            Err(error) => return ApiError::Internal(error).into_response(),
+        }
+    }
+    if let Some(retry_after) = access.throttle(access.options.rate_key(key.as_deref())) {
+        return ApiError::TooManyRequests(retry_after).into_response();
+    }
+    next.run(request).await
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Aircraft reference data", description = "Read-only access to the stored aircraft types, airports and airlines."),
+    paths(get_aircraft, list_aircraft, resolve_codes, get_airport, list_airports, get_airline, list_airlines),
+    components(schemas(ErrorBody)),
+    tags((name = "aircraft"), (name = "airports"), (name = "airlines"))
+)]
+struct ApiDoc;
+
+/// The OpenAPI 3 document of the API, declaring API key authentication when `access`
+/// requires keys.
+pub fn openapi(access: &AccessOptions) -> utoipa::openapi::OpenApi {
+    let mut document = ApiDoc::openapi();
+    // The crate declares no license for utoipa to fill in.
+    document.info.license = None;
+    if access.requires_key() {
+        let components = document.components.get_or_insert_with(Default::default);
+        components.add_security_scheme("apiKey", SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-API-Key"))));
+        components.add_security_scheme("bearer", SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()));
+        document.security = Some(vec![
+            SecurityRequirement::new("apiKey", Vec::<String>::new()),
+            SecurityRequirement::new("bearer", Vec::<String>::new()),
+        ]);
+    }
+    document
+}
+
+/// One page of a list endpoint.
+#[derive(Serialize, ToSchema, Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Items matching the query over all pages.
+    pub total: u64,
+    pub offset: u64,
+    pub limit: u64,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct AircraftQuery {
+    /// Only the aircraft with this IATA type code.
+    iata: Option<String>,
+    /// Items to skip.
+    #[serde(default)]
+    offset: u64,
+    /// Items to return, at most 1000.
+    limit: Option<u64>,
+}
+
+// Airports and airlines share their filterable fields.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct PlaceQuery {
+    /// Only those with this IATA code.
+    iata: Option<String>,
+    /// Only those of this country, e.g. `United Kingdom`.
+    country: Option<String>,
+    /// Items to skip.
+    #[serde(default)]
+    offset: u64,
+    /// Items to return, at most 1000.
+    limit: Option<u64>,
+}
+
+// Body of every error response.
+#[derive(Serialize, ToSchema)]
+struct ErrorBody {
+    error: String,
+}
+
+fn page_size(limit: Option<u64>) -> u64 {
+    limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE)
+}
+
+// Failures of a request, answered with their status and a JSON body.
+#[derive(Debug)]
+pub(crate) enum ApiError {
+    BadRequest(String),
+    NotFound(String),
+    Unauthorized(&'static str),
+    // With the time until the next request is allowed.
+    TooManyRequests(Duration),
+    // Too busy to take the request, such as with a full queue.
+    Unavailable(String),
+    Internal(Error),
+}
+
+impl From<Error> for ApiError {
+    fn from(error: Error) -> Self {
+        ApiError::Internal(error)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            ApiError::BadRequest(message) => (StatusCode::BAD_REQUEST, message),
+            ApiError::NotFound(message) => (StatusCode::NOT_FOUND, message),
+            ApiError::Unauthorized(message) => (StatusCode::UNAUTHORIZED, message.to_string()),
+            ApiError::TooManyRequests(retry_after) => {
+                let body = Json(ErrorBody { error: "rate limit exceeded".to_string() });
+                let seconds = retry_after.as_secs_f64().ceil().to_string();
+                return (StatusCode::TOO_MANY_REQUESTS, [(RETRY_AFTER, seconds)], body).into_response();
+            }
+            ApiError::Unavailable(message) => (StatusCode::SERVICE_UNAVAILABLE, message),
+            ApiError::Internal(error) => {
+                metrics().observe_error(&error);
+                error!(%error, "request failed");
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal error".to_string())
+            }
+        };
+        (status, Json(ErrorBody { error: message })).into_response()
+    }
+}
+
+pub(crate) type ApiResult<T> = std::result::Result<Json<T>, ApiError>;
+
+/// The API's routes, reading the aircraft from `store` and the other datasets from their
+/// collections next to it, open to the callers `access` allows.
+pub fn router(store: AircraftStore, access: AccessOptions) -> Router {
+    let access = Arc::new(Access { options: access, store: store.clone(), limiter: Mutex::default() });
+    let document = Json(openapi(&access.options));
+    let docs = Router::new()
+        .route("/openapi.json", get(move || async move { document }))
+        .route("/docs", get(|| async { Html(DOCS) }))
+        .route("/metrics", get(|| async { ([(CONTENT_TYPE, TEXT_FORMAT)], metrics().render()) }));
+    let api = Router::new()
+        .route("/aircraft", get(list_aircraft))
+        .route("/aircraft/{icao}", get(get_aircraft))
+        .route("/resolve", post(resolve_codes))
+        .route("/airports", get(list_airports))
+        .route("/airports/{icao}", get(get_airport))
+        .route("/airlines", get(list_airlines))
+        .route("/airlines/{icao}", get(get_airline));
+    #[cfg(feature = "graphql")]
+    let (api, docs) = {
+        let graphiql = Html(async_graphql::http::GraphiQLSource::build().endpoint("/graphql").finish());
+        let api = api.route("/graphql", post(query_graphql).with_state(graphql::schema(store.clone())));
+        (api, docs.route("/graphiql", get(move || async move { graphiql })))
+    };
+    api.layer(middleware::from_fn_with_state(access, authorize))
+        .merge(docs)
+        .with_state(store)
+}
+
+/// The aircraft routes of the API over `storage` alone, for serving without MongoDB, e.g.
+/// the embedded dataset in an [`InMemoryStorage`](crate::storage::InMemoryStorage):
+/// `GET /aircraft/{icao}`, `GET /aircraft` and `POST /resolve`, open to every caller.
+pub fn storage_router(storage: Arc<dyn Storage>) -> Router {
+    Router::new()
+        .route("/aircraft", get(list_stored_aircraft))
+        .route("/aircraft/{icao}", get(get_stored_aircraft))
+        .route("/resolve", post(resolve_stored_codes))
+        .route("/metrics", get(|| async { ([(CONTENT_TYPE, TEXT_FORMAT)], metrics().render()) }))
+        .with_state(storage)
+}
+
+/// Serves the API on `address` until the process receives Ctrl-C, then finishes the
+/// requests in flight.
+pub async fn serve(store: AircraftStore, address: SocketAddr, access: AccessOptions) -> Result<()> {
+    serve_router(router(store, access), address).await
+}
+
+/// Serves the [`storage_router`] of `storage` like [`serve`].
+pub async fn serve_storage(storage: Arc<dyn Storage>, address: SocketAddr) -> Result<()> {
+    serve_router(storage_router(storage), address).await
+}
+
+pub(crate) async fn serve_router(router: Router, address: SocketAddr) -> Result<()> {
+    let serve_error = |source| Error::Serve { address, source };
+    let listener = TcpListener::bind(address).await.map_err(serve_error)?;
+    info!(%address, "serving");
+    axum::serve(listener, router)
+        .with_graceful_shutdown(async {
+            tokio::signal::ctrl_c().await.ok();
+            info!("shutting down");
+        })
+        .await
+        .map_err(serve_error)
+}
+
+#[cfg(feature = "graphql")]
+async fn query_graphql(State(schema): State<Schema>, Json(request): Json<async_graphql::Request>) -> Json<async_graphql::Response> {
+    Json(schema.execute(request).await)
+}
+
+#[utoipa::path(
+    get,
+    path = "/aircraft/{icao}",
+    tag = "aircraft",
+    params(("icao" = String, Path, description = "ICAO type designator, e.g. `B38M`")),
+    responses(
+        (status = 200, description = "The aircraft", body = Aircraft),
+        (status = 404, description = "No aircraft with that designator", body = ErrorBody),
+        (status = 401, description = "Missing or unknown API key", body = ErrorBody),
+        (status = 429, description = "Over the rate limit", body = ErrorBody),
+    )
+)]
+async fn get_aircraft(State(store): State<AircraftStore>, Path(icao): Path<String>) -> ApiResult<Aircraft> {
+    let icao = icao.to_ascii_uppercase();
+    match store.find_by_icao(&icao).await? {
+        Some(aircraft) => Ok(Json(aircraft)),
+        None => Err(ApiError::NotFound(format!("no aircraft {}", icao))),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/aircraft",
+    tag = "aircraft",
+    params(AircraftQuery),
+    responses(
+        (status = 200, description = "A page of aircraft", body = Page<Aircraft>),
+        (status = 401, description = "Missing or unknown API key", body = ErrorBody),
+        (status = 429, description = "Over the rate limit", body = ErrorBody),
+    )
+)]
+async fn list_aircraft(State(store): State<AircraftStore>, Query(query): Query<AircraftQuery>) -> ApiResult<Page<Aircraft>> {
+    let limit = page_size(query.limit);
+    let (items, total) = store.page(query.iata.as_deref(), query.offset, limit).await?;
+    Ok(Json(Page { items, total, offset: query.offset, limit }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/resolve",
+    tag = "aircraft",
+    request_body = ResolveRequest,
+    responses(
+        (status = 200, description = "The aircraft each code resolves to, the codes none does and those several do", body = Resolution),
+        (status = 400, description = "More than 10000 codes", body = ErrorBody),
+        (status = 401, description = "Missing or unknown API key", body = ErrorBody),
+        (status = 429, description = "Over the rate limit", body = ErrorBody),
+    )
+)]
+async fn resolve_codes(State(store): State<AircraftStore>, Json(request): Json<ResolveRequest>) -> ApiResult<Resolution> {
+    check_codes(&request)?;
+    Ok(Json(resolve::resolve(&store, &request.codes).await?))
+}
+
+fn check_codes(request: &ResolveRequest) -> std::result::Result<(), ApiError> {
+    match request.codes.len() > MAX_CODES {
+        true => Err(ApiError::BadRequest(format!("at most {} codes are resolved at once", MAX_CODES))),
+        false => Ok(()),
+    }
+}
+
+// The aircraft endpoints of `storage_router`, reading any backend.
+async fn get_stored_aircraft(State(storage): State<Arc<dyn Storage>>, Path(icao): Path<String>) -> ApiResult<Aircraft> {
+    let icao = icao.to_ascii_uppercase();
+    match storage.find_by_icao(&icao).await? {
+        Some(aircraft) => Ok(Json(aircraft)),
+        None => Err(ApiError::NotFound(format!("no aircraft {}", icao))),
+    }
+}
+
+async fn list_stored_aircraft(State(storage): State<Arc<dyn Storage>>, Query(query): Query<AircraftQuery>) -> ApiResult<Page<Aircraft>> {
+    let limit = page_size(query.limit);
+    let mut aircraft = match &query.iata {
+        Some(iata) => storage.find_by_iata(&iata.to_ascii_uppercase()).await?,
+        None => storage.find_all().await?,
+    };
+    aircraft.sort_by(|left, right| left.icao_code.cmp(&right.icao_code));
+    let total = aircraft.len() as u64;
+    let items = aircraft.into_iter().skip(query.offset as usize).take(limit as usize).collect();
+    Ok(Json(Page { items, total, offset: query.offset, limit }))
+}
+
+async fn resolve_stored_codes(State(storage): State<Arc<dyn Storage>>, Json(request): Json<ResolveRequest>) -> ApiResult<Resolution> {
+    check_codes(&request)?;
+    Ok(Json(resolve::resolve(storage.as_ref(), &request.codes).await?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/airports/{icao}",
+    tag = "airports",
+    params(("icao" = String, Path, description = "ICAO location indicator, e.g. `EGLL`")),
+    responses(
+        (status = 200, description = "The airport", body = Airport),
+        (status = 404, description = "No airport with that indicator", body = ErrorBody),
+        (status = 401, description = "Missing or unknown API key", body = ErrorBody),
+        (status = 429, description = "Over the rate limit", body = ErrorBody),
+    )
+)]
+async fn get_airport(store: State<AircraftStore>, icao: Path<String>) -> ApiResult<Airport> {
+    get_record(store, icao).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/airports",
+    tag = "airports",
+    params(PlaceQuery),
+    responses(
+        (status = 200, description = "A page of airports", body = Page<Airport>),
+        (status = 401, description = "Missing or unknown API key", body = ErrorBody),
+        (status = 429, description = "Over the rate limit", body = ErrorBody),
+    )
+)]
+async fn list_airports(store: State<AircraftStore>, query: Query<PlaceQuery>) -> ApiResult<Page<Airport>> {
+    list_records(store, query).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/airlines/{icao}",
+    tag = "airlines",
+    params(("icao" = String, Path, description = "ICAO airline designator, e.g. `BAW`")),
+    responses(
+        (status = 200, description = "The airline", body = Airline),
+        (status = 404, description = "No airline with that designator", body = ErrorBody),
+        (status = 401, description = "Missing or unknown API key", body = ErrorBody),
+        (status = 429, description = "Over the rate limit", body = ErrorBody),
+    )
+)]
+async fn get_airline(store: State<AircraftStore>, icao: Path<String>) -> ApiResult<Airline> {
+    get_record(store, icao).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/airlines",
+    tag = "airlines",
+    params(PlaceQuery),
+    responses(
+        (status = 200, description = "A page of airlines", body = Page<Airline>),
+        (status = 401, description = "Missing or unknown API key", body = ErrorBody),
+        (status = 429, description = "Over the rate limit", body = ErrorBody),
+    )
+)]
+async fn list_airlines(store: State<AircraftStore>, query: Query<PlaceQuery>) -> ApiResult<Page<Airline>> {
+    list_records(store, query).await
+}
+
+async fn get_record<T: Record>(State(store): State<AircraftStore>, Path(key): Path<String>) -> ApiResult<T> {
+    let key = key.to_ascii_uppercase();
+    match store.records::<T>(T::COLLECTION).find_by_key(&key).await? {
+        Some(record) => Ok(Json(record)),
+        None => Err(ApiError::NotFound(format!("nothing in {} under {}", T::COLLECTION, key))),
+    }
+}
+
+async fn list_records<T: Record>(State(store): State<AircraftStore>, Query(query): Query<PlaceQuery>) -> ApiResult<Page<T>> {
+    let mut filter = Document::new();
+    if let Some(iata) = &query.iata {
+        filter.insert("iataCode", iata.to_ascii_uppercase());
+    }
+    if let Some(country) = &query.country {
+        filter.insert("country", country);
+    }
+    let limit = page_size(query.limit);
+    let (items, total) = store.records::<T>(T::COLLECTION).page(filter, query.offset, limit).await?;
+    Ok(Json(Page { items, total, offset: query.offset, limit }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn requiring(keys: &[&str]) -> AccessOptions {
+        AccessOptions { api_keys: keys.iter().map(|key| key.to_string()).collect(), ..AccessOptions::default() }
+    }
+
+    #[test]
+    fn lists_only_the_exact_keys() {
+        let access = requiring(&["secret", "other"]);
+        assert!(access.lists("secret"));
+        assert!(access.lists("other"));
+        assert!(!access.lists("secre"));
+        assert!(!access.lists("secret2"));
+        assert!(!access.lists(""));
+    }
+
+    #[test]
+    fn without_required_keys_every_caller_shares_the_limit() {
+        let open = AccessOptions { rate_limit: Some(10), ..AccessOptions::default() };
+        assert_eq!(open.rate_key(Some("made-up")), "");
+        assert_eq!(open.rate_key(None), "");
+        assert_eq!(requiring(&["secret"]).rate_key(Some("secret")), "secret");
+    }
+
+    #[test]
+    fn refuses_a_key_over_the_limit_until_its_window_ends() {
+        let mut limiter = RateLimiter::default();
+        let start = Instant::now();
+        assert_eq!(limiter.count("a", 2, start), None);
+        assert_eq!(limiter.count("a", 2, start), None);
+        assert_eq!(limiter.count("a", 2, start + Duration::from_secs(20)), Some(Duration::from_secs(40)));
+        assert_eq!(limiter.count("b", 2, start + Duration::from_secs(20)), None);
+        assert_eq!(limiter.count("a", 2, start + RATE_WINDOW), None);
+    }
+
+    #[test]
+    fn drops_the_windows_that_ended() {
+        let mut limiter = RateLimiter::default();
+        let start = Instant::now();
+        limiter.count("a", 2, start);
+        limiter.count("b", 2, start + Duration::from_secs(30));
+        limiter.count("c", 2, start + RATE_WINDOW);
+        let mut keys: Vec<&str> = limiter.windows.keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        assert_eq!(keys, ["b", "c"]);
+    }
+}
@@ -0,0 +1,54 @@
+//! Stopping loads cleanly on Ctrl-C or SIGTERM: the batches being written are finished and
+//! no further one is started.
+
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+use tracing::warn;
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+static LISTENING: Once = Once::new();
+
+/// Whether a shutdown signal has been received since [`listen`].
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::Relaxed)
+}
+
+/// Listens for Ctrl-C and SIGTERM in the background. The first asks loads to stop after the
+/// batches being written; a second exits at once, as the signal would have without this.
+/// Listening again, as each tenant's load does with --all-tenants, changes nothing.
+pub fn listen() {
+    LISTENING.call_once(|| {
+        tokio::spawn(async {
+            loop {
+                signal().await;
+                if REQUESTED.swap(true, Ordering::Relaxed) {
+                    process::exit(130);
+                }
+                warn!("shutting down after the batches being written, signal again to stop at once");
+            }
+        });
+    });
+}
+
+#[cfg(unix)]
+async fn signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+    match signal(SignalKind::terminate()) {
+        Ok(mut terminate) => {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = terminate.recv() => {}
+            }
+        }
+        Err(_) => {
+            tokio::signal::ctrl_c().await.ok();
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn signal() {
+    tokio::signal::ctrl_c().await.ok();
+}
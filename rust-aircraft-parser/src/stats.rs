@@ -0,0 +1,132 @@
+//! Summarizing a stored aircraft collection, as a sanity check after each import.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use futures::TryStreamExt;
+use mongodb::bson::{doc, Bson, Document};
+use mongodb::options::FindOptions;
+use serde::Serialize;
+use crate::{AircraftStore, Result};
+
+// Field the manufacturer is stored under by `enrich doc8643` and `enrich classify`.
+const MANUFACTURER: &str = "manufacturer";
+
+/// Totals of a stored aircraft collection, from [`collect`].
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Stats {
+    pub collection: String,
+    pub documents: u64,
+    /// Aircraft without an IATA code.
+    pub missing_iata: u64,
+    /// Aircraft per manufacturer, most first; those not enriched with one under `unknown`.
+    pub manufacturers: Vec<(String, u64)>,
+    /// The latest run recorded in the load history, if any.
+    pub last_load: Option<LastLoad>,
+    /// Documents created since the latest run started, less those it deleted.
+    pub growth: Option<i64>,
+    /// Bytes the documents take uncompressed, and on disk.
+    pub size_bytes: u64,
+    pub storage_bytes: u64,
+    /// Bytes each index takes on disk, by name.
+    pub index_bytes: BTreeMap<String, u64>,
+}
+
+/// The latest run recorded for a collection.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct LastLoad {
+    pub load_id: String,
+    pub command: String,
+    /// When the run finished, as an RFC 3339 string.
+    pub finished_at: String,
+    pub written: u64,
+    pub deleted: u64,
+}
+
+/// Counts the documents of `store`'s collection, reads its latest load and asks the server
+/// how much space the collection and its indexes take.
+pub async fn collect(store: &AircraftStore) -> Result<Stats> {
+    let collection = store.collection();
+    let iata_code = &store.field_names().iata_code;
+    let mut stats = Stats { collection: collection.name().to_string(), ..Stats::default() };
+    stats.documents = collection.count_documents(doc! {}, None).await?;
+    stats.missing_iata = collection.count_documents(doc! { iata_code: { "$in": [Bson::Null, ""] } }, None).await?;
+
+    let pipeline = [
+        doc! { "$group": { "_id": { "$ifNull": [format!("${}", MANUFACTURER), "unknown"] }, "count": { "$sum": 1 } } },
+        doc! { "$sort": { "count": -1, "_id": 1 } },
+    ];
+    let groups: Vec<Document> = collection.aggregate(pipeline, None).await?.try_collect().await?;
+    stats.manufacturers = groups
+        .iter()
+        .map(|group| (group.get_str("_id").unwrap_or("unknown").to_string(), count(group.get("count"))))
+        .collect();
+
+    let options = FindOptions::builder().sort(doc! { "finishedAt": -1 }).limit(1).build();
+    let loads: Vec<Document> = store.load_history().find(doc! { "collection": collection.name() }, options).await?.try_collect().await?;
+    if let Some(load) = loads.first() {
+        let deleted = count(load.get("deleted"));
+        if let Ok(started_at) = load.get_datetime("startedAt") {
+            let created = collection.count_documents(doc! { "createdAt": { "$gte": started_at } }, None).await?;
+            stats.growth = Some(created as i64 - deleted as i64);
+        }
+        let finished_at = load.get_datetime("finishedAt").ok();
+        stats.last_load = Some(LastLoad {
+            load_id: load.get_str("_id").unwrap_or_default().to_string(),
+            command: load.get_str("command").unwrap_or_default().to_string(),
+            finished_at: finished_at.map(|at| at.try_to_rfc3339_string().unwrap_or_else(|_| at.to_string())).unwrap_or_default(),
+            written: count(load.get("written")),
+            deleted,
+        });
+    }
+
+    let database = collection.client().database(&collection.namespace().db);
+    let sizes = database.run_command(doc! { "collStats": collection.name() }, None).await?;
+    stats.size_bytes = count(sizes.get("size"));
+    stats.storage_bytes = count(sizes.get("storageSize"));
+    if let Ok(indexes) = sizes.get_document("indexSizes") {
+        stats.index_bytes = indexes.iter().map(|(name, size)| (name.clone(), count(Some(size)))).collect();
+    }
+    Ok(stats)
+}
+
+// A count or size the server returns as whichever number type fits it.
+fn count(value: Option<&Bson>) -> u64 {
+    match value {
+        Some(Bson::Int32(value)) => *value as u64,
+        Some(Bson::Int64(value)) => *value as u64,
+        Some(Bson::Double(value)) => *value as u64,
+        _ => 0,
+    }
+}
+
+impl Stats {
+    /// The totals as aligned lines for people.
+    pub fn table(&self) -> String {
+        let mut table = String::new();
+        let _ = writeln!(table, "{:<24}{}", "collection", self.collection);
+        let _ = writeln!(table, "{:<24}{}", "documents", self.documents);
+        let _ = writeln!(table, "{:<24}{}", "missing IATA code", self.missing_iata);
+        match &self.last_load {
+            Some(load) => {
+                let _ = writeln!(table, "{:<24}{} {} ({} written, {} deleted)", "last load", load.finished_at, load.command, load.written, load.deleted);
+            }
+            None => {
+                let _ = writeln!(table, "{:<24}never", "last load");
+            }
+        }
+        if let Some(growth) = self.growth {
+            let _ = writeln!(table, "{:<24}{:+}", "growth since last load", growth);
+        }
+        let _ = writeln!(table, "{:<24}{} bytes ({} on disk)", "size", self.size_bytes, self.storage_bytes);
+        for (index, bytes) in &self.index_bytes {
+            let _ = writeln!(table, "{:<24}{} bytes", format!("index {}", index), bytes);
+        }
+        let _ = writeln!(table, "\n{:<24}aircraft", "manufacturer");
+        for (manufacturer, aircraft) in &self.manufacturers {
+            let _ = writeln!(table, "{:<24}{}", manufacturer, aircraft);
+        }
+        table
+    }
+}
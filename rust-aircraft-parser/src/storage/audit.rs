@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use futures::TryStreamExt;
+use mongodb::bson::{self, doc, Document};
+use mongodb::options::FindOptions;
+use mongodb::{Collection, IndexModel};
+use serde::{Deserialize, Serialize};
+use crate::fields::FieldNames;
+use crate::provenance::Provenance;
+use crate::Result;
+
+// Collection the writes to aircraft collections are recorded in, next to them.
+pub(super) const AUDIT_LOG: &str = "audit_log";
+
+/// One write to an aircraft recorded in `audit_log`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    /// When the write was made.
+    pub at: bson::DateTime,
+    /// Who made it.
+    pub actor: String,
+    /// `insert`, `update` or `delete`.
+    pub operation: String,
+    /// Collection written to.
+    pub collection: String,
+    pub icao_code: String,
+    /// The fields written, under their stored names, as they were before; none for inserts.
+    pub before: Option<Document>,
+    /// The fields written as they are after; none for deletes.
+    pub after: Option<Document>,
+    /// Load the write was part of.
+    pub load_id: String,
+}
+
+/// The audit log of one collection, recording writes on behalf of `actor`.
+pub(super) struct Audit<'a> {
+    pub(super) log: Collection<Document>,
+    pub(super) collection: &'a str,
+    pub(super) fields: &'a FieldNames,
+    pub(super) actor: &'a str,
+    pub(super) provenance: &'a Provenance,
+}
+
+impl Audit<'_> {
+    /// The aircraft fields of the documents of `collection` matching `filter`, by ICAO code,
+    /// as the `before` of the writes about to change them.
+    pub(super) async fn current(&self, collection: &Collection<Document>, filter: Document) -> Result<HashMap<String, Document>> {
+        let documents: Vec<Document> = collection.find(filter, None).await?.try_collect().await?;
+        documents
+            .iter()
+            .map(|document| self.fields.aircraft(document).map(|aircraft| (aircraft.icao_code.clone(), self.fields.document(&aircraft))))
+            .collect()
+    }
+
+    /// Records a write of each ICAO code from its `before` to its `after` fields, leaving
+    /// out those it left as they were, and returns how many were recorded.
+    pub(super) async fn record(&self, changes: Vec<(String, Option<Document>, Option<Document>)>) -> Result<u64> {
+        let at = bson::DateTime::now();
+        let entries = changes
+            .into_iter()
+            .filter(|(_, before, after)| before != after)
+            .map(|(icao_code, before, after)| {
+                let operation = match (&before, &after) {
+                    (None, _) => "insert",
+                    (Some(_), Some(_)) => "update",
+                    (Some(_), None) => "delete",
+                };
+                let entry = AuditEntry {
+                    at,
+                    actor: self.actor.to_string(),
+                    operation: operation.to_string(),
+                    collection: self.collection.to_string(),
+                    icao_code,
+                    before,
+                    after,
+                    load_id: self.provenance.load_id.clone(),
+                };
+                bson::to_document(&entry)
+            })
+            .collect::<bson::ser::Result<Vec<_>>>()?;
+        if entries.is_empty() {
+            return Ok(0);
+        }
+        let result = self.log.insert_many(entries, None).await?;
+        Ok(result.inserted_ids.len() as u64)
+    }
+
+    /// Every recorded write to `icao_code` in the collection, oldest first.
+    pub(super) async fn entries(&self, icao_code: &str) -> Result<Vec<AuditEntry>> {
+        let filter = doc! { "collection": self.collection, "icaoCode": icao_code };
+        let options = FindOptions::builder().sort(doc! { "at": 1 }).build();
+        let documents: Vec<Document> = self.log.find(filter, options).await?.try_collect().await?;
+        Ok(documents.into_iter().map(bson::from_document).collect::<bson::de::Result<_>>()?)
+    }
+
+    /// Creates the index the writes to an aircraft are found through.
+    pub(super) async fn ensure_index(&self) -> Result<()> {
+        let index = IndexModel::builder().keys(doc! { "collection": 1, "icaoCode": 1, "at": 1 }).build();
+        self.log.create_index(index, None).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+use std::time::SystemTime;
+use async_trait::async_trait;
+use aws_sdk_dynamodb::error::DisplayErrorContext;
+use aws_sdk_dynamodb::types::{AttributeValue, DeleteRequest, PutRequest, WriteRequest};
+use aws_sdk_dynamodb::Client;
+use serde_json::{Map, Number, Value};
+use tracing::{info, warn};
+use crate::ids::IdStrategy;
+use crate::provenance::{rfc3339, LoadRecord, Provenance};
+use crate::record::Record;
+use crate::retry::RetryPolicy;
+use crate::{Aircraft, Airport, Error, Result};
+use super::{FailedWrite, Sink, Storage, StoredAircraft};
+
+// BatchWriteItem takes at most 25 put or delete requests.
+const ITEMS_PER_BATCH: usize = 25;
+
+// Table finished runs are recorded in, keyed on loadId.
+const LOAD_HISTORY: &str = "load_history";
+
+type Item = HashMap<String, AttributeValue>;
+
+/// A DynamoDB table holding [`Aircraft`] items, partitioned on the ICAO code. The table
+/// must exist, with `icaoCode` as its string partition key and no sort key. [`Airport`]s
+/// are written to the `airports` table, partitioned the same way.
+#[derive(Clone, Debug)]
+pub struct DynamoStorage {
+    client: Client,
+    table: String,
+    retry: RetryPolicy,
+    ids: IdStrategy,
+    provenance: Provenance,
+}
+
+impl DynamoStorage {
+    /// A client configured from the environment, the shared AWS config files and instance
+    /// metadata, on `table`.
+    pub async fn connect(table: &str) -> Result<Self> {
+        let client = Client::new(&aws_config::from_env().load().await);
+        info!(table, "connected to dynamodb");
+        Ok(DynamoStorage {
+            client,
+            table: table.to_string(),
+            retry: RetryPolicy::default(),
+            ids: IdStrategy::default(),
+            provenance: Provenance::default(),
+        })
+    }
+
+    /// Replaces the policy used to retry the items DynamoDB leaves unprocessed.
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Replaces how the `id` attribute of written items is generated.
+    pub fn with_id_strategy(mut self, ids: IdStrategy) -> Self {
+        self.ids = ids;
+        self
+    }
+
+    /// Replaces the provenance stamped onto every item this storage writes, so the items of
+    /// a load can be traced to its input and rolled back with [`Storage::delete_by_load`].
+    pub fn with_provenance(mut self, provenance: Provenance) -> Self {
+        self.provenance = provenance;
+        self
+    }
+
+    // The item stored for `record`: its fields plus `id` and provenance.
+    fn item<T: Record>(&self, record: &T, now: &str) -> Result<Item> {
+        let Value::Object(mut fields) = serde_json::to_value(record)? else {
+            return Err(Error::InvalidInput(format!("{} is not stored as an object", record.key())));
+        };
+        fields.insert("id".to_string(), Value::String(self.ids.id_for_key(&record.key())));
+        fields.insert("loadId".to_string(), Value::String(self.provenance.load_id.clone()));
+        fields.insert("updatedAt".to_string(), Value::String(now.to_string()));
+        if let Some(source_file) = &self.provenance.source_file {
+            fields.insert("sourceFile".to_string(), Value::String(source_file.clone()));
+        }
+        if let Some(checksum) = &self.provenance.checksum {
+            fields.insert("checksum".to_string(), Value::String(checksum.clone()));
+        }
+        Ok(fields.into_iter().map(|(name, value)| (name, attribute(value))).collect())
+    }
+
+    // Puts every record into `table`, 25 at a time.
+    async fn put<T: Record>(&self, table: &str, records: &[T]) -> Result<u64> {
+        let now = rfc3339(SystemTime::now());
+        for chunk in records.chunks(ITEMS_PER_BATCH) {
+            let requests = chunk
+                .iter()
+                .map(|record| {
+                    let put = PutRequest::builder().set_item(Some(self.item(record, &now)?)).build().map_err(dynamo_error)?;
+                    Ok(WriteRequest::builder().put_request(put).build())
+                })
+                .collect::<Result<Vec<_>>>()?;
+            self.write_batch(table, requests).await?;
+        }
+        Ok(records.len() as u64)
+    }
+
+    // Deletes the items keyed on `keys` from the aircraft table, 25 at a time.
+    async fn delete(&self, keys: &[String]) -> Result<u64> {
+        for chunk in keys.chunks(ITEMS_PER_BATCH) {
+            let requests = chunk
+                .iter()
+                .map(|key| {
+                    let key = HashMap::from([(Aircraft::KEY_FIELD.to_string(), AttributeValue::S(key.clone()))]);
+                    let delete = DeleteRequest::builder().set_key(Some(key)).build().map_err(dynamo_error)?;
+                    Ok(WriteRequest::builder().delete_request(delete).build())
+                })
+                .collect::<Result<Vec<_>>>()?;
+            self.write_batch(&self.table, requests).await?;
+        }
+        Ok(keys.len() as u64)
+    }
+
+    // Sends one BatchWriteItem, then sends the requests DynamoDB left unprocessed again,
+    // backing off as the retry policy says, until none are left or the retries are used up.
+    async fn write_batch(&self, table: &str, mut requests: Vec<WriteRequest>) -> Result<()> {
+        let mut attempt = 0;
+        loop {
+            let output = self
+                .client
+                .batch_write_item()
+                .request_items(table, requests)
+                .send()
+                .await
+                .map_err(dynamo_error)?;
+            requests = output
+                .unprocessed_items
+                .and_then(|mut unprocessed| unprocessed.remove(table))
+                .unwrap_or_default();
+            if requests.is_empty() {
+                return Ok(());
+            }
+            if attempt == self.retry.max_retries {
+                return Err(Error::Dynamo(format!("{} items left unprocessed by {}", requests.len(), table)));
+            }
+            let delay = self.retry.delay(attempt);
+            warn!(table, unprocessed = requests.len(), delay_ms = delay.as_millis() as u64, "retrying unprocessed items");
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    // Every item of `table` matching `filter`, an expression over `:value`.
+    async fn scan(&self, table: &str, filter: Option<(&str, &str)>) -> Result<Vec<Item>> {
+        let mut items = Vec::new();
+        let mut start = None;
+        loop {
+            let mut scan = self.client.scan().table_name(table).set_exclusive_start_key(start);
+            if let Some((expression, value)) = filter {
+                scan = scan
+                    .filter_expression(expression)
+                    .expression_attribute_values(":value", AttributeValue::S(value.to_string()));
+            }
+            let output = scan.send().await.map_err(dynamo_error)?;
+            items.extend(output.items.unwrap_or_default());
+            start = output.last_evaluated_key;
+            if start.is_none() {
+                return Ok(items);
+            }
+        }
+    }
+
+    async fn find_aircraft(&self, filter: Option<(&str, &str)>) -> Result<Vec<Aircraft>> {
+        let mut aircrafts = self.scan(&self.table, filter).await?.into_iter().map(record).collect::<Result<Vec<Aircraft>>>()?;
+        aircrafts.sort_by(|left, right| left.icao_code.cmp(&right.icao_code));
+        Ok(aircrafts)
+    }
+
+    async fn keys_matching(&self, filter: Option<(&str, &str)>) -> Result<Vec<String>> {
+        Ok(self.scan(&self.table, filter).await?.iter().filter_map(|item| string(item, Aircraft::KEY_FIELD)).collect())
+    }
+}
+
+#[async_trait]
+impl Storage for DynamoStorage {
+    async fn insert_batch(&self, aircrafts: &[Aircraft]) -> Result<u64> {
+        self.put(&self.table, aircrafts).await
+    }
+
+    /// The same as inserting: a put replaces the item with the same ICAO code.
+    async fn upsert(&self, aircrafts: &[Aircraft]) -> Result<u64> {
+        self.put(&self.table, aircrafts).await
+    }
+
+    async fn find_by_icao(&self, icao_code: &str) -> Result<Option<Aircraft>> {
+        let output = self
+            .client
+            .get_item()
+            .table_name(&self.table)
+            .key(Aircraft::KEY_FIELD, AttributeValue::S(icao_code.to_ascii_uppercase()))
+            .send()
+            .await
+            .map_err(dynamo_error)?;
+        output.item.map(record).transpose()
+    }
+
+    /// Scans the table, which has no index on the IATA code.
+    async fn find_by_iata(&self, iata_code: &str) -> Result<Vec<Aircraft>> {
+        self.find_aircraft(Some(("iataCode = :value", &iata_code.to_ascii_uppercase()))).await
+    }
+
+    async fn find_all(&self) -> Result<Vec<Aircraft>> {
+        self.find_aircraft(None).await
+    }
+
+    async fn find_all_with_ids(&self) -> Result<Vec<StoredAircraft>> {
+        let mut records = self
+            .scan(&self.table, None)
+            .await?
+            .into_iter()
+            .map(|item| Ok(StoredAircraft { id: string(&item, "id").unwrap_or_default(), aircraft: record(item)? }))
+            .collect::<Result<Vec<_>>>()?;
+        records.sort_by(|left, right| left.aircraft.icao_code.cmp(&right.aircraft.icao_code));
+        Ok(records)
+    }
+
+    async fn delete_all(&self) -> Result<u64> {
+        self.delete(&self.keys_matching(None).await?).await
+    }
+
+    async fn delete_by_icao(&self, icao_codes: &[String]) -> Result<u64> {
+        let mut stored = Vec::new();
+        for icao_code in icao_codes {
+            if self.find_by_icao(icao_code).await?.is_some() {
+                stored.push(icao_code.clone());
+            }
+        }
+        self.delete(&stored).await
+    }
+
+    async fn delete_by_load(&self, load_id: &str) -> Result<u64> {
+        self.delete(&self.keys_matching(Some(("loadId = :value", load_id))).await?).await
+    }
+
+    /// Puts the run into the `load_history` table, which must exist with `loadId` as its
+    /// string partition key.
+    async fn record_load(&self, record: &LoadRecord) -> Result<()> {
+        let provenance = &record.provenance;
+        let mut item = Item::from([
+            ("loadId".to_string(), AttributeValue::S(provenance.load_id.clone())),
+            ("command".to_string(), AttributeValue::S(record.command.clone())),
+            ("collection".to_string(), AttributeValue::S(self.table.clone())),
+            ("startedAt".to_string(), AttributeValue::S(rfc3339(record.started_at))),
+            ("finishedAt".to_string(), AttributeValue::S(rfc3339(record.finished_at))),
+        ]);
+        for (name, count) in [
+            ("parsed", record.parsed),
+            ("written", record.written),
+            ("deleted", record.deleted),
+            ("rejected", record.rejected),
+            ("skipped", record.skipped),
+        ] {
+            item.insert(name.to_string(), AttributeValue::N(count.to_string()));
+        }
+        item.insert("interrupted".to_string(), AttributeValue::Bool(record.interrupted));
+        if let Some(source_file) = &provenance.source_file {
+            item.insert("sourceFile".to_string(), AttributeValue::S(source_file.clone()));
+        }
+        if let Some(checksum) = &provenance.checksum {
+            item.insert("checksum".to_string(), AttributeValue::S(checksum.clone()));
+        }
+        self.client.put_item().table_name(LOAD_HISTORY).set_item(Some(item)).send().await.map_err(dynamo_error)?;
+        Ok(())
+    }
+
+    /// Scans the `load_history` table for the latest run on this table.
+    async fn last_checksum(&self) -> Result<Option<String>> {
+        let runs = self.scan(LOAD_HISTORY, Some(("collection = :value", &self.table))).await?;
+        let last = runs.iter().max_by_key(|run| string(run, "finishedAt"));
+        Ok(last.and_then(|run| string(run, "checksum")))
+    }
+}
+
+/// Airports go to the `airports` table, whatever the aircraft table is.
+#[async_trait]
+impl Sink<Airport> for DynamoStorage {
+    async fn insert_batch(&self, airports: &[Airport]) -> Result<u64> {
+        self.put(Airport::COLLECTION, airports).await
+    }
+
+    async fn insert_unordered(&self, airports: &[Airport]) -> Result<(u64, Vec<FailedWrite<Airport>>)> {
+        Ok((self.put(Airport::COLLECTION, airports).await?, Vec::new()))
+    }
+
+    async fn upsert(&self, airports: &[Airport]) -> Result<u64> {
+        self.put(Airport::COLLECTION, airports).await
+    }
+}
+
+fn dynamo_error(error: impl std::error::Error + 'static) -> Error {
+    Error::Dynamo(DisplayErrorContext(error).to_string())
+}
+
+fn string(item: &Item, name: &str) -> Option<String> {
+    item.get(name).and_then(|value| value.as_s().ok()).cloned()
+}
+
+// The record stored in `item`, ignoring the attributes it doesn't have.
+fn record<T: Record>(item: Item) -> Result<T> {
+    let fields: Map<String, Value> = item.into_iter().map(|(name, value)| (name, json(value))).collect();
+    Ok(serde_json::from_value(Value::Object(fields))?)
+}
+
+fn attribute(value: Value) -> AttributeValue {
+    match value {
+        Value::Null => AttributeValue::Null(true),
+        Value::Bool(value) => AttributeValue::Bool(value),
+        Value::Number(value) => AttributeValue::N(value.to_string()),
+        Value::String(value) => AttributeValue::S(value),
+        Value::Array(values) => AttributeValue::L(values.into_iter().map(attribute).collect()),
+        Value::Object(fields) => AttributeValue::M(fields.into_iter().map(|(name, value)| (name, attribute(value))).collect()),
+    }
+}
+
+fn json(value: AttributeValue) -> Value {
+    match value {
+        AttributeValue::Bool(value) => Value::Bool(value),
+        AttributeValue::N(value) => value
+            .parse::<i64>()
+            .map(Number::from)
+            .ok()
+            .or_else(|| value.parse::<f64>().ok().and_then(Number::from_f64))
+            .map_or(Value::Null, Value::Number),
+        AttributeValue::S(value) => Value::String(value),
+        AttributeValue::L(values) => Value::Array(values.into_iter().map(json).collect()),
+        AttributeValue::M(fields) => Value::Object(fields.into_iter().map(|(name, value)| (name, json(value))).collect()),
+        _ => Value::Null,
+    }
+}
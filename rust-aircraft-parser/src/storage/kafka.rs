@@ -0,0 +1,170 @@
+use std::collections::HashSet;
+use std::time::Duration;
+use async_trait::async_trait;
+use futures::future;
+use rdkafka::message::{Header, OwnedHeaders};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::util::Timeout;
+use rdkafka::ClientConfig;
+use tracing::info;
+use crate::provenance::{LoadRecord, Provenance};
+use crate::{Aircraft, Error, Result};
+use super::{FailedWrite, Storage, StoredAircraft};
+
+// How long a message may wait in the producer's queue for the broker to take it.
+const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A Kafka topic each written aircraft is published to as a JSON message keyed by its ICAO
+/// code, so consumers of a compacted topic always see the latest of every type. Deleted
+/// aircraft are published as tombstones, a key without a payload.
+///
+/// A topic cannot be read back: on its own it only takes writes, but it can mirror another
+/// backend, which then serves the reads and is written first.
+pub struct KafkaStorage {
+    producer: FutureProducer,
+    topic: String,
+    provenance: Provenance,
+    primary: Option<Box<dyn Storage>>,
+}
+
+impl KafkaStorage {
+    /// Connects a producer to the comma-separated `brokers`, publishing to `topic`.
+    pub fn connect(brokers: &str, topic: &str) -> Result<Self> {
+        let producer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set("message.timeout.ms", SEND_TIMEOUT.as_millis().to_string())
+            .create()?;
+        info!(brokers, topic, "connected to kafka");
+        Ok(KafkaStorage { producer, topic: topic.to_string(), provenance: Provenance::default(), primary: None })
+    }
+
+    /// Replaces the provenance sent in the `loadId` and `sourceFile` headers of every message.
+    pub fn with_provenance(mut self, provenance: Provenance) -> Self {
+        self.provenance = provenance;
+        self
+    }
+
+    /// Writes to `primary` first and publishes what it wrote, serving reads from it.
+    pub fn mirroring(mut self, primary: Box<dyn Storage>) -> Self {
+        self.primary = Some(primary);
+        self
+    }
+
+    fn headers(&self) -> OwnedHeaders {
+        let headers = OwnedHeaders::new().insert(Header { key: "loadId", value: Some(&self.provenance.load_id) });
+        match &self.provenance.source_file {
+            Some(source_file) => headers.insert(Header { key: "sourceFile", value: Some(source_file) }),
+            None => headers,
+        }
+    }
+
+    // Publishes every aircraft, waiting until the broker has acknowledged them all.
+    async fn publish(&self, aircrafts: &[Aircraft]) -> Result<u64> {
+        let sends = aircrafts.iter().map(|aircraft| async move {
+            let payload = serde_json::to_vec(aircraft)?;
+            let record = FutureRecord::to(&self.topic).key(&aircraft.icao_code).payload(&payload).headers(self.headers());
+            self.producer.send(record, Timeout::After(SEND_TIMEOUT)).await.map_err(|(error, _)| Error::Kafka(error))?;
+            Ok::<_, Error>(())
+        });
+        future::try_join_all(sends).await?;
+        Ok(aircrafts.len() as u64)
+    }
+
+    async fn publish_tombstones(&self, icao_codes: &[String]) -> Result<u64> {
+        let sends = icao_codes.iter().map(|icao_code| async move {
+            let record = FutureRecord::<str, [u8]>::to(&self.topic).key(icao_code).headers(self.headers());
+            self.producer.send(record, Timeout::After(SEND_TIMEOUT)).await.map_err(|(error, _)| Error::Kafka(error))?;
+            Ok::<_, Error>(())
+        });
+        future::try_join_all(sends).await?;
+        Ok(icao_codes.len() as u64)
+    }
+
+    fn primary(&self, operation: &str) -> Result<&dyn Storage> {
+        self.primary
+            .as_deref()
+            .ok_or_else(|| Error::Config(format!("the kafka backend cannot {}, it only publishes", operation)))
+    }
+}
+
+#[async_trait]
+impl Storage for KafkaStorage {
+    async fn insert_batch(&self, aircrafts: &[Aircraft]) -> Result<u64> {
+        let Some(primary) = &self.primary else { return self.publish(aircrafts).await };
+        let written = primary.insert_batch(aircrafts).await?;
+        self.publish(aircrafts).await?;
+        Ok(written)
+    }
+
+    /// Publishes only the aircraft the primary backend wrote.
+    async fn insert_unordered(&self, aircrafts: &[Aircraft]) -> Result<(u64, Vec<FailedWrite>)> {
+        let Some(primary) = &self.primary else { return Ok((self.publish(aircrafts).await?, Vec::new())) };
+        let (written, failed) = primary.insert_unordered(aircrafts).await?;
+        let refused: HashSet<&str> = failed.iter().map(|failure| failure.record.icao_code.as_str()).collect();
+        let accepted: Vec<Aircraft> = aircrafts.iter().filter(|aircraft| !refused.contains(aircraft.icao_code.as_str())).cloned().collect();
+        self.publish(&accepted).await?;
+        Ok((written, failed))
+    }
+
+    async fn upsert(&self, aircrafts: &[Aircraft]) -> Result<u64> {
+        let Some(primary) = &self.primary else { return self.publish(aircrafts).await };
+        let written = primary.upsert(aircrafts).await?;
+        self.publish(aircrafts).await?;
+        Ok(written)
+    }
+
+    async fn find_by_icao(&self, icao_code: &str) -> Result<Option<Aircraft>> {
+        self.primary("be read")?.find_by_icao(icao_code).await
+    }
+
+    async fn find_by_iata(&self, iata_code: &str) -> Result<Vec<Aircraft>> {
+        self.primary("be read")?.find_by_iata(iata_code).await
+    }
+
+    async fn find_all(&self) -> Result<Vec<Aircraft>> {
+        self.primary("be read")?.find_all().await
+    }
+
+    async fn find_all_with_ids(&self) -> Result<Vec<StoredAircraft>> {
+        self.primary("be read")?.find_all_with_ids().await
+    }
+
+    /// Not published: the primary backend does not say which aircraft it deleted.
+    async fn delete_all(&self) -> Result<u64> {
+        self.primary("delete everything")?.delete_all().await
+    }
+
+    /// Deletes from the primary backend, if any, and publishes a tombstone for every code.
+    async fn delete_by_icao(&self, icao_codes: &[String]) -> Result<u64> {
+        let Some(primary) = &self.primary else { return self.publish_tombstones(icao_codes).await };
+        let deleted = primary.delete_by_icao(icao_codes).await?;
+        self.publish_tombstones(icao_codes).await?;
+        Ok(deleted)
+    }
+
+    async fn delete_by_load(&self, load_id: &str) -> Result<u64> {
+        self.primary("delete a load")?.delete_by_load(load_id).await
+    }
+
+    /// Kept by the primary backend; a topic on its own keeps no history.
+    async fn record_load(&self, record: &LoadRecord) -> Result<()> {
+        match &self.primary {
+            Some(primary) => primary.record_load(record).await,
+            None => Ok(()),
+        }
+    }
+
+    async fn last_checksum(&self) -> Result<Option<String>> {
+        match &self.primary {
+            Some(primary) => primary.last_checksum().await,
+            None => Ok(None),
+        }
+    }
+
+    async fn ensure_indexes(&self) -> Result<()> {
+        match &self.primary {
+            Some(primary) => primary.ensure_indexes().await,
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use std::env;
+use std::future;
+use std::time::Duration;
+use mongodb::bson::{self, doc, Document};
+use mongodb::error::{ErrorKind, WriteFailure};
+use mongodb::options::{FindOneAndUpdateOptions, ReturnDocument};
+use mongodb::Collection;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+use crate::{Error, Result};
+use super::staging::expires_at;
+
+// Collection the leases are kept in, one document per collection, keyed by its name.
+pub(super) const LOCKS: &str = "locks";
+
+/// How long a lease lasts unless renewed. Holders renew it three times as often.
+pub const DEFAULT_LEASE: Duration = Duration::from_secs(60);
+
+// Code of a duplicate key error, raised when an upsert loses to a lease still held.
+const DUPLICATE_KEY: i32 = 11000;
+
+/// A lease on a collection, so only one load or sync writes to it at a time. It is renewed
+/// in the background until [`release`](LoadLock::release)d; dropped without being released,
+/// as when a run fails, it lapses once its lease runs out. A run holding it should stop
+/// once it is [`lost`](LoadLock::lost).
+#[derive(Debug)]
+pub struct LoadLock {
+    locks: Collection<Document>,
+    collection: String,
+    owner: String,
+    renewal: JoinHandle<()>,
+    // Set by the renewal once another run has taken the lease.
+    lost: watch::Receiver<bool>,
+}
+
+impl LoadLock {
+    // Takes the lease on `collection` for `owner`, unless a lease of another owner has not
+    // run out yet. With `force` that lease is broken instead.
+    pub(super) async fn acquire(locks: Collection<Document>, collection: &str, owner: &str, lease: Duration, force: bool) -> Result<LoadLock> {
+        let now = bson::DateTime::now();
+        let holder = doc! {
+            "owner": owner,
+            "host": env::var("HOSTNAME").ok(),
+            "pid": std::process::id(),
+            "acquiredAt": now,
+            "expiresAt": expires_at(now, lease),
+        };
+        if force {
+            let broken = locks.find_one_and_delete(doc! { "_id": collection }, None).await?;
+            if let Some(broken) = broken.filter(|broken| broken.get_str("owner").is_ok_and(|holder| holder != owner)) {
+                warn!(collection, owner = broken.get_str("owner").unwrap_or_default(), "broke the lock of another run");
+            }
+        }
+        let free = doc! { "_id": collection, "$or": [{ "expiresAt": { "$lt": now } }, { "owner": owner }] };
+        let options = FindOneAndUpdateOptions::builder().upsert(true).return_document(ReturnDocument::After).build();
+        match locks.find_one_and_update(free, doc! { "$set": holder }, options).await {
+            Ok(_) => {}
+            Err(error) if is_duplicate_key(&error) => return Err(held(&locks, collection).await),
+            Err(error) => return Err(error.into()),
+        }
+        info!(collection, owner, "locked");
+        let (losing, lost) = watch::channel(false);
+        let renewal = tokio::spawn(renew(locks.clone(), collection.to_string(), owner.to_string(), lease, losing));
+        Ok(LoadLock { locks, collection: collection.to_string(), owner: owner.to_string(), renewal, lost })
+    }
+
+    /// Waits until another run has taken the lease, with the [`Error::Locked`] naming it.
+    /// Never returns while the lease is still this run's.
+    pub async fn lost(&self) -> Error {
+        let mut lost = self.lost.clone();
+        if lost.wait_for(|lost| *lost).await.is_err() {
+            // The renewal was stopped with the lease still held.
+            return future::pending().await;
+        }
+        held(&self.locks, &self.collection).await
+    }
+
+    /// Stops renewing the lease and gives it up, if it is still this run's.
+    pub async fn release(self) -> Result<()> {
+        self.renewal.abort();
+        self.locks.delete_one(doc! { "_id": &self.collection, "owner": &self.owner }, None).await?;
+        info!(collection = %self.collection, "unlocked");
+        Ok(())
+    }
+}
+
+impl Drop for LoadLock {
+    fn drop(&mut self) {
+        self.renewal.abort();
+    }
+}
+
+// Extends the lease every third of it, for as long as the lock is held, telling `lost`
+// once it finds the lease taken by another run.
+async fn renew(locks: Collection<Document>, collection: String, owner: String, lease: Duration, lost: watch::Sender<bool>) {
+    let mut interval = tokio::time::interval(lease / 3);
+    // The first tick is immediate.
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let filter = doc! { "_id": &collection, "owner": &owner };
+        let update = doc! { "$set": { "expiresAt": expires_at(bson::DateTime::now(), lease) } };
+        match locks.update_one(filter, update, None).await {
+            Ok(result) if result.matched_count == 0 => {
+                error!(%collection, "lost the lock to another run");
+                lost.send_replace(true);
+                return;
+            }
+            Ok(_) => {}
+            Err(error) => warn!(%collection, %error, "cannot renew the lock"),
+        }
+    }
+}
+
+fn is_duplicate_key(error: &mongodb::error::Error) -> bool {
+    match error.kind.as_ref() {
+        ErrorKind::Write(WriteFailure::WriteError(failure)) => failure.code == DUPLICATE_KEY,
+        ErrorKind::Command(failure) => failure.code == DUPLICATE_KEY,
+        _ => false,
+    }
+}
+
+// The error for a lock held by another run, naming it.
+async fn held(locks: &Collection<Document>, collection: &str) -> Error {
+    let holder = locks.find_one(doc! { "_id": collection }, None).await.ok().flatten().unwrap_or_default();
+    Error::Locked {
+        collection: collection.to_string(),
+        owner: holder.get_str("owner").unwrap_or("another run").to_string(),
+        until: holder.get_datetime("expiresAt").map_or_else(|_| "-".to_string(), |until| until.to_string()),
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::sync::{Mutex, MutexGuard};
+use async_trait::async_trait;
+use crate::ids::IdStrategy;
+use crate::provenance::{LoadRecord, Provenance};
+use crate::{Aircraft, Error, Result};
+use super::{FailedWrite, Storage, StoredAircraft};
+
+/// A [`Storage`] keeping the aircraft in memory, keyed on the ICAO code as MongoDB's unique
+/// index keys them, for exercising loads and syncs without a database. Nothing outlives the
+/// value.
+#[derive(Default)]
+pub struct InMemoryStorage {
+    state: Mutex<State>,
+    ids: IdStrategy,
+    provenance: Provenance,
+}
+
+#[derive(Default)]
+struct State {
+    aircraft: BTreeMap<String, Entry>,
+    loads: Vec<LoadRecord>,
+}
+
+struct Entry {
+    id: String,
+    load_id: String,
+    aircraft: Aircraft,
+    // Marked inactive by `retire`, until written again.
+    retired: bool,
+}
+
+impl InMemoryStorage {
+    /// An empty store.
+    pub fn new() -> Self {
+        InMemoryStorage::default()
+    }
+
+    /// A store already holding `aircrafts`, as if loaded by a run with the default provenance.
+    pub fn with_aircraft(aircrafts: impl IntoIterator<Item = Aircraft>) -> Self {
+        let storage = InMemoryStorage::new();
+        {
+            let mut state = storage.state();
+            for aircraft in aircrafts {
+                let entry = storage.entry(aircraft);
+                state.aircraft.insert(entry.aircraft.icao_code.clone(), entry);
+            }
+        }
+        storage
+    }
+
+    /// Replaces how the identifiers of written aircraft are generated.
+    pub fn with_id_strategy(mut self, ids: IdStrategy) -> Self {
+        self.ids = ids;
+        self
+    }
+
+    /// Replaces the provenance recorded with every aircraft this storage writes, so the
+    /// aircraft of a load can be rolled back with [`Storage::delete_by_load`].
+    pub fn with_provenance(mut self, provenance: Provenance) -> Self {
+        self.provenance = provenance;
+        self
+    }
+
+    /// Every run recorded with [`Storage::record_load`], oldest first.
+    pub fn loads(&self) -> Vec<LoadRecord> {
+        self.state().loads.clone()
+    }
+
+    // A panicking writer leaves nothing half-written worth refusing to read.
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn entry(&self, aircraft: Aircraft) -> Entry {
+        Entry { id: self.ids.id_for(&aircraft), load_id: self.provenance.load_id.clone(), aircraft, retired: false }
+    }
+}
+
+#[async_trait]
+impl Storage for InMemoryStorage {
+    /// Refuses the whole batch, writing nothing, when an ICAO code in it is already stored
+    /// or repeated.
+    async fn insert_batch(&self, aircrafts: &[Aircraft]) -> Result<u64> {
+        let mut state = self.state();
+        let mut codes = BTreeSet::new();
+        for aircraft in aircrafts {
+            if state.aircraft.contains_key(&aircraft.icao_code) || !codes.insert(&aircraft.icao_code) {
+                return Err(Error::InvalidInput(format!("duplicate icao code {}", aircraft.icao_code)));
+            }
+        }
+        for aircraft in aircrafts {
+            state.aircraft.insert(aircraft.icao_code.clone(), self.entry(aircraft.clone()));
+        }
+        Ok(aircrafts.len() as u64)
+    }
+
+    async fn insert_unordered(&self, aircrafts: &[Aircraft]) -> Result<(u64, Vec<FailedWrite>)> {
+        let mut state = self.state();
+        let mut failed = Vec::new();
+        for aircraft in aircrafts {
+            if state.aircraft.contains_key(&aircraft.icao_code) {
+                failed.push(FailedWrite { record: aircraft.clone(), error: format!("duplicate icao code {}", aircraft.icao_code) });
+            } else {
+                state.aircraft.insert(aircraft.icao_code.clone(), self.entry(aircraft.clone()));
+            }
+        }
+        Ok(((aircrafts.len() - failed.len()) as u64, failed))
+    }
+
+    /// Keeps the identifier of aircraft already stored.
+    async fn upsert(&self, aircrafts: &[Aircraft]) -> Result<u64> {
+        let mut state = self.state();
+        for aircraft in aircrafts {
+            let mut entry = self.entry(aircraft.clone());
+            if let Some(existing) = state.aircraft.remove(&aircraft.icao_code) {
+                entry.id = existing.id;
+            }
+            state.aircraft.insert(aircraft.icao_code.clone(), entry);
+        }
+        Ok(aircrafts.len() as u64)
+    }
+
+    /// Falls back to the aircraft listing `icao_code` among its aliases.
+    async fn find_by_icao(&self, icao_code: &str) -> Result<Option<Aircraft>> {
+        let state = self.state();
+        let entry = state.aircraft.get(&icao_code.to_ascii_uppercase()).or_else(|| {
+            state.aircraft.values().find(|entry| entry.aircraft.aliases.iter().any(|alias| alias.eq_ignore_ascii_case(icao_code)))
+        });
+        Ok(entry.map(|entry| entry.aircraft.clone()))
+    }
+
+    async fn find_by_iata(&self, iata_code: &str) -> Result<Vec<Aircraft>> {
+        Ok(self
+            .state()
+            .aircraft
+            .values()
+            .filter(|entry| entry.aircraft.iata_code.as_deref().is_some_and(|code| code.eq_ignore_ascii_case(iata_code)))
+            .map(|entry| entry.aircraft.clone())
+            .collect())
+    }
+
+    async fn find_all(&self) -> Result<Vec<Aircraft>> {
+        Ok(self.state().aircraft.values().map(|entry| entry.aircraft.clone()).collect())
+    }
+
+    async fn find_all_with_ids(&self) -> Result<Vec<StoredAircraft>> {
+        Ok(self
+            .state()
+            .aircraft
+            .values()
+            .map(|entry| StoredAircraft { id: entry.id.clone(), aircraft: entry.aircraft.clone() })
+            .collect())
+    }
+
+    async fn delete_all(&self) -> Result<u64> {
+        let mut state = self.state();
+        let deleted = state.aircraft.len() as u64;
+        state.aircraft.clear();
+        Ok(deleted)
+    }
+
+    async fn delete_by_icao(&self, icao_codes: &[String]) -> Result<u64> {
+        let mut state = self.state();
+        Ok(icao_codes.iter().filter(|icao_code| state.aircraft.remove(*icao_code).is_some()).count() as u64)
+    }
+
+    async fn retire(&self, icao_codes: &[String]) -> Result<u64> {
+        let mut state = self.state();
+        let mut retired = 0;
+        for icao_code in icao_codes {
+            if let Some(entry) = state.aircraft.get_mut(icao_code).filter(|entry| !entry.retired) {
+                entry.retired = true;
+                retired += 1;
+            }
+        }
+        Ok(retired)
+    }
+
+    async fn retired(&self) -> Result<HashSet<String>> {
+        Ok(self.state().aircraft.values().filter(|entry| entry.retired).map(|entry| entry.aircraft.icao_code.clone()).collect())
+    }
+
+    async fn delete_by_load(&self, load_id: &str) -> Result<u64> {
+        let mut state = self.state();
+        let before = state.aircraft.len();
+        state.aircraft.retain(|_, entry| entry.load_id != load_id);
+        Ok((before - state.aircraft.len()) as u64)
+    }
+
+    async fn record_load(&self, record: &LoadRecord) -> Result<()> {
+        self.state().loads.push(record.clone());
+        Ok(())
+    }
+
+    async fn last_checksum(&self) -> Result<Option<String>> {
+        Ok(self.state().loads.last().and_then(|record| record.provenance.checksum.clone()))
+    }
+}
@@ -0,0 +1,199 @@
+//! Destinations the parsed aircraft can be written to.
+
+mod audit;
+#[cfg(feature = "dynamodb")]
+mod dynamodb;
+#[cfg(feature = "elasticsearch")]
+mod elasticsearch;
+#[cfg(feature = "kafka")]
+mod kafka;
+mod lock;
+mod memory;
+mod mongo;
+#[cfg(feature = "postgres")]
+mod postgres;
+mod records;
+#[cfg(feature = "redis")]
+mod redis;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod staging;
+mod versions;
+
+use std::collections::HashSet;
+use async_trait::async_trait;
+use clap::ValueEnum;
+use serde::Serialize;
+use crate::provenance::LoadRecord;
+use crate::resolve;
+use crate::{Aircraft, Error, Result};
+
+pub use audit::AuditEntry;
+#[cfg(feature = "dynamodb")]
+pub use dynamodb::DynamoStorage;
+#[cfg(feature = "elasticsearch")]
+pub use elasticsearch::ElasticsearchStorage;
+#[cfg(feature = "kafka")]
+pub use kafka::KafkaStorage;
+pub use lock::{LoadLock, DEFAULT_LEASE};
+pub use memory::InMemoryStorage;
+pub use mongo::{aircraft_document, is_transient, AircraftStore, ClientSettings, HealthCheck};
+pub(crate) use mongo::case_insensitive;
+pub use records::RecordStore;
+pub use staging::{Garbage, DEFAULT_STAGING_TTL};
+#[cfg(feature = "postgres")]
+pub use postgres::PostgresStorage;
+#[cfg(feature = "redis")]
+pub use self::redis::RedisStorage;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStorage;
+
+// Columns the SQL backends add to aircraft tables created before records carried provenance.
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+const PROVENANCE_COLUMNS: [&str; 5] = ["load_id", "source_file", "checksum", "created_at", "updated_at"];
+
+// The SQL backends keep finished runs next to the aircraft table.
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+const CREATE_LOAD_HISTORY: &str = "CREATE TABLE IF NOT EXISTS load_history (
+    load_id TEXT PRIMARY KEY,
+    command TEXT NOT NULL,
+    collection TEXT NOT NULL,
+    source_file TEXT,
+    checksum TEXT,
+    started_at TEXT NOT NULL,
+    finished_at TEXT NOT NULL,
+    parsed BIGINT NOT NULL,
+    written BIGINT NOT NULL,
+    deleted BIGINT NOT NULL,
+    rejected BIGINT NOT NULL,
+    skipped BIGINT NOT NULL,
+    interrupted BOOLEAN NOT NULL DEFAULT FALSE
+)";
+
+/// Which aircraft lookups and exports see, by whether a sync with `retire` marked them
+/// inactive.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Inactive {
+    /// Active and inactive aircraft alike.
+    #[default]
+    Include,
+    /// Active aircraft only.
+    Exclude,
+    /// Inactive aircraft only.
+    Only,
+}
+
+/// An aircraft as read back from a backend, with the identifier it is stored under.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoredAircraft {
+    pub id: String,
+    pub aircraft: Aircraft,
+}
+
+/// A record the backend refused to write, such as a duplicate key, while the rest of its
+/// batch was written.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct FailedWrite<T = Aircraft> {
+    pub record: T,
+    pub error: String,
+}
+
+/// Somewhere batches of `T` can be written, as [`load`](crate::load::load) needs.
+#[async_trait]
+pub trait Sink<T>: Send + Sync {
+    /// Inserts every record as new and returns how many were written.
+    async fn insert_batch(&self, records: &[T]) -> Result<u64>;
+
+    /// Inserts every record as new, going on past the records the backend refuses, and
+    /// returns how many were written and the refused ones.
+    async fn insert_unordered(&self, records: &[T]) -> Result<(u64, Vec<FailedWrite<T>>)>;
+
+    /// Inserts or updates every record keyed on its natural key and returns how many were written.
+    async fn upsert(&self, records: &[T]) -> Result<u64>;
+}
+
+#[async_trait]
+impl<S: Storage + ?Sized> Sink<Aircraft> for S {
+    async fn insert_batch(&self, records: &[Aircraft]) -> Result<u64> {
+        Storage::insert_batch(self, records).await
+    }
+
+    async fn insert_unordered(&self, records: &[Aircraft]) -> Result<(u64, Vec<FailedWrite>)> {
+        Storage::insert_unordered(self, records).await
+    }
+
+    async fn upsert(&self, records: &[Aircraft]) -> Result<u64> {
+        Storage::upsert(self, records).await
+    }
+}
+
+/// A backend that aircraft records can be loaded into and read back from.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Inserts every aircraft as a new record and returns how many were written.
+    async fn insert_batch(&self, aircrafts: &[Aircraft]) -> Result<u64>;
+
+    /// Inserts every aircraft as a new record, going on past the ones the backend refuses,
+    /// and returns how many were written and the refused ones. Backends that cannot tell
+    /// which records failed keep the default, which inserts the batch as a whole.
+    async fn insert_unordered(&self, aircrafts: &[Aircraft]) -> Result<(u64, Vec<FailedWrite>)> {
+        Ok((self.insert_batch(aircrafts).await?, Vec::new()))
+    }
+
+    /// Inserts or replaces every aircraft keyed on its ICAO code and returns how many were written.
+    async fn upsert(&self, aircrafts: &[Aircraft]) -> Result<u64>;
+
+    /// Looks up a single aircraft by ICAO code, in any case.
+    async fn find_by_icao(&self, icao_code: &str) -> Result<Option<Aircraft>>;
+
+    /// Returns every aircraft sharing an IATA code, in any case.
+    async fn find_by_iata(&self, iata_code: &str) -> Result<Vec<Aircraft>>;
+
+    /// Returns every aircraft.
+    async fn find_all(&self) -> Result<Vec<Aircraft>>;
+
+    /// Returns the aircraft known by any of the upper-case `codes`, as their ICAO code, IATA
+    /// code or an alias. Backends that cannot look many codes up at once keep the default,
+    /// which reads every aircraft.
+    async fn find_by_codes(&self, codes: &[String]) -> Result<Vec<Aircraft>> {
+        let aircrafts = self.find_all().await?;
+        Ok(aircrafts.into_iter().filter(|aircraft| codes.iter().any(|code| resolve::known_by(aircraft, code))).collect())
+    }
+
+    /// Returns every aircraft together with its stored identifier.
+    async fn find_all_with_ids(&self) -> Result<Vec<StoredAircraft>>;
+
+    /// Removes every record and returns how many were deleted.
+    async fn delete_all(&self) -> Result<u64>;
+
+    /// Removes the records with the given ICAO codes and returns how many were deleted.
+    async fn delete_by_icao(&self, icao_codes: &[String]) -> Result<u64>;
+
+    /// Marks the records with the given ICAO codes inactive instead of deleting them, for
+    /// data that still refers to them, and returns how many were marked. Writing a record
+    /// again makes it active. Backends without soft deletes keep the default, which refuses.
+    async fn retire(&self, icao_codes: &[String]) -> Result<u64> {
+        let _ = icao_codes;
+        Err(Error::Config("soft deletes are only supported by the mongo backend".to_string()))
+    }
+
+    /// ICAO codes of the records marked inactive; none for backends without soft deletes.
+    async fn retired(&self) -> Result<HashSet<String>> {
+        Ok(HashSet::new())
+    }
+
+    /// Removes the records last written by the load `load_id` and returns how many were deleted.
+    async fn delete_by_load(&self, load_id: &str) -> Result<u64>;
+
+    /// Appends a finished run to the load history.
+    async fn record_load(&self, record: &LoadRecord) -> Result<()>;
+
+    /// The input checksum of the most recent run recorded for this collection, if any.
+    async fn last_checksum(&self) -> Result<Option<String>>;
+
+    /// Creates the indexes lookups rely on, if the backend doesn't already have them.
+    /// Backends that create their schema on connect keep the default no-op.
+    async fn ensure_indexes(&self) -> Result<()> {
+        Ok(())
+    }
+}
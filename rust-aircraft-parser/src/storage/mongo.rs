@@ -0,0 +1,1094 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use async_trait::async_trait;
+use futures::{Stream, StreamExt, TryStreamExt};
+use mongodb::{bson, bson::doc, Client, Collection};
+use mongodb::bson::{Bson, Document};
+use mongodb::error::{BulkWriteFailure, Error, ErrorKind, WriteFailure, RETRYABLE_WRITE_ERROR, TRANSIENT_TRANSACTION_ERROR};
+use mongodb::options::{
+    Acknowledgment, AuthMechanism, ClientOptions, Collation, CollationStrength, CountOptions, Credential, FindOneOptions, FindOptions, IndexOptions, InsertManyOptions,
+    ListDatabasesOptions, ReadPreference, SelectionCriteria, Tls, TlsOptions, WriteConcern,
+};
+use mongodb::IndexModel;
+use tracing::info;
+use uuid::Uuid;
+use crate::cache::LookupCache;
+use crate::fields::{FieldNames, ALIASES, DESCRIPTIONS};
+use crate::ids::{IdStrategy, UuidEncoding};
+use crate::overrides::{Overrides, OVERRIDE};
+use crate::provenance::{LoadRecord, Provenance};
+use crate::record::Record;
+use crate::{Aircraft, Result};
+use crate::retry::{retry, RetryPolicy};
+use super::audit::{Audit, AUDIT_LOG};
+use super::lock::{LoadLock, LOCKS};
+use super::staging::{self, Garbage, DEFAULT_STAGING_TTL, EXPIRES_AT};
+use super::versions::{Versions, AIRCRAFT_HISTORY};
+use super::{AuditEntry, FailedWrite, Inactive, RecordStore, Storage, StoredAircraft};
+
+// Server error codes for failovers, step-downs, shutdowns and network timeouts; the same
+// set the driver treats as retryable for writes.
+const TRANSIENT_CODES: [i32; 12] = [6, 7, 89, 91, 189, 262, 9001, 10107, 11600, 11602, 13435, 13436];
+
+// Collection finished runs are recorded in, next to the aircraft collection.
+const LOAD_HISTORY: &str = "load_history";
+
+// Collection copies of aircraft collections are kept in by `load --snapshot`, for `restore`.
+const SNAPSHOTS: &str = "aircraft_snapshots";
+
+// Set to false, with the time in `retiredAt`, on aircraft a sync retired; absent on active ones.
+const ACTIVE: &str = "active";
+const RETIRED_AT: &str = "retiredAt";
+
+/// Client options set on top of those of the connection string, each replacing the
+/// connection string's when given.
+#[derive(Clone, Default, PartialEq)]
+pub struct ClientSettings {
+    /// Acknowledgement every write waits for, e.g. [`Acknowledgment::Majority`].
+    pub write_concern: Option<Acknowledgment>,
+    /// Servers reads are sent to.
+    pub read_preference: Option<ReadPreference>,
+    /// How long an operation waits for a suitable server.
+    pub server_selection_timeout: Option<Duration>,
+    /// How long opening a connection may take.
+    pub connect_timeout: Option<Duration>,
+    /// Name the client reports to the server, shown in its logs and `currentOp`.
+    pub app_name: Option<String>,
+    /// Connect over TLS; implied by the TLS files.
+    pub tls: bool,
+    /// PEM bundle of the certificate authorities the server's certificate is checked against.
+    pub tls_ca_file: Option<PathBuf>,
+    /// PEM file holding the client certificate and its private key, for mutual TLS.
+    pub tls_certificate_key_file: Option<PathBuf>,
+    /// Accept any server certificate; only for testing.
+    pub tls_allow_invalid_certificates: bool,
+    /// How the client authenticates, e.g. [`AuthMechanism::MongoDbX509`] with the client
+    /// certificate as its identity.
+    pub auth_mechanism: Option<AuthMechanism>,
+    /// Database the credentials are defined in, `admin` (or `$external` for X.509) by default.
+    pub auth_source: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+// Leaves the password out of logs.
+impl fmt::Debug for ClientSettings {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("ClientSettings")
+            .field("write_concern", &self.write_concern)
+            .field("read_preference", &self.read_preference)
+            .field("server_selection_timeout", &self.server_selection_timeout)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("app_name", &self.app_name)
+            .field("tls", &self.tls)
+            .field("tls_ca_file", &self.tls_ca_file)
+            .field("tls_certificate_key_file", &self.tls_certificate_key_file)
+            .field("tls_allow_invalid_certificates", &self.tls_allow_invalid_certificates)
+            .field("auth_mechanism", &self.auth_mechanism)
+            .field("auth_source", &self.auth_source)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "***"))
+            .finish()
+    }
+}
+
+impl ClientSettings {
+    fn apply(&self, options: &mut ClientOptions) {
+        if let Some(w) = &self.write_concern {
+            let write_concern = options.write_concern.get_or_insert_with(WriteConcern::default);
+            write_concern.w = Some(w.clone());
+        }
+        if let Some(read_preference) = &self.read_preference {
+            options.selection_criteria = Some(SelectionCriteria::ReadPreference(read_preference.clone()));
+        }
+        if let Some(timeout) = self.server_selection_timeout {
+            options.server_selection_timeout = Some(timeout);
+        }
+        if let Some(timeout) = self.connect_timeout {
+            options.connect_timeout = Some(timeout);
+        }
+        if let Some(app_name) = &self.app_name {
+            options.app_name = Some(app_name.clone());
+        }
+        if self.tls || self.tls_ca_file.is_some() || self.tls_certificate_key_file.is_some() || self.tls_allow_invalid_certificates {
+            let tls = TlsOptions::builder()
+                .ca_file_path(self.tls_ca_file.clone())
+                .cert_key_file_path(self.tls_certificate_key_file.clone())
+                .allow_invalid_certificates(self.tls_allow_invalid_certificates.then_some(true))
+                .build();
+            options.tls = Some(Tls::Enabled(tls));
+        }
+        if self.auth_mechanism.is_none() && self.auth_source.is_none() && self.username.is_none() && self.password.is_none() {
+            return;
+        }
+        // Credentials of the connection string are kept where not replaced.
+        let credential = options.credential.get_or_insert_with(Credential::default);
+        if let Some(mechanism) = &self.auth_mechanism {
+            credential.mechanism = Some(mechanism.clone());
+        }
+        if let Some(source) = &self.auth_source {
+            credential.source = Some(source.clone());
+        }
+        if let Some(username) = &self.username {
+            credential.username = Some(username.clone());
+        }
+        if let Some(password) = &self.password {
+            credential.password = Some(password.clone());
+        }
+    }
+}
+
+/// What [`AircraftStore::check`] found the credentials can see.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HealthCheck {
+    pub databases: Vec<String>,
+    /// Collections of the store's database.
+    pub collections: Vec<String>,
+}
+
+/// A MongoDB collection holding [`Aircraft`] documents.
+#[derive(Clone, Debug)]
+pub struct AircraftStore {
+    collection: Collection<Document>,
+    retry: RetryPolicy,
+    ids: IdStrategy,
+    uuids: UuidEncoding,
+    provenance: Provenance,
+    fields: FieldNames,
+    history: bool,
+    // Who the audited writes are recorded as made by, with auditing on.
+    actor: Option<String>,
+    // Staging collections records are read from and written to instead of the live
+    // collections they are keyed by.
+    staged: BTreeMap<String, String>,
+    // How long after they are written the documents of staging collections expire.
+    staging_ttl: Duration,
+    // Which aircraft lookups see.
+    inactive: Inactive,
+    // Lookups by ICAO code, kept in memory by `serve`.
+    cache: Option<LookupCache>,
+}
+
+impl AircraftStore {
+    /// Wraps an existing collection handle, retrying transient failures with the default policy.
+    pub fn new(collection: Collection<Document>) -> Self {
+        AircraftStore {
+            collection,
+            retry: RetryPolicy::default(),
+            ids: IdStrategy::default(),
+            uuids: UuidEncoding::default(),
+            provenance: Provenance::default(),
+            fields: FieldNames::default(),
+            history: false,
+            actor: None,
+            staged: BTreeMap::new(),
+            staging_ttl: DEFAULT_STAGING_TTL,
+            inactive: Inactive::Include,
+            cache: None,
+        }
+    }
+
+    /// Replaces the policy used to retry transient failures.
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Replaces how `_id`s of newly inserted documents are generated.
+    pub fn with_id_strategy(mut self, ids: IdStrategy) -> Self {
+        self.ids = ids;
+        self
+    }
+
+    /// Replaces how the UUID `_id`s of newly inserted documents are stored.
+    pub fn with_uuid_encoding(mut self, uuids: UuidEncoding) -> Self {
+        self.uuids = uuids;
+        self
+    }
+
+    /// Replaces the provenance stamped onto every document this store writes, so the
+    /// documents of a load can be traced to its input and rolled back with
+    /// [`Storage::delete_by_load`].
+    pub fn with_provenance(mut self, provenance: Provenance) -> Self {
+        self.provenance = provenance;
+        self
+    }
+
+    /// Replaces the names the aircraft fields are stored under.
+    pub fn with_field_names(mut self, fields: FieldNames) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    /// Keeps every version of the aircraft this store writes in `aircraft_history` of the same
+    /// database, each valid from when it was written until it was changed or deleted, so
+    /// [`as_of`](Self::as_of) can tell what the collection held at any time since.
+    pub fn with_history(mut self, history: bool) -> Self {
+        self.history = history;
+        self
+    }
+
+    /// Records every insert, update and delete of an aircraft this store makes in `audit_log`
+    /// of the same database, with the fields before and after and `actor` as who made it.
+    pub fn with_audit(mut self, actor: Option<String>) -> Self {
+        self.actor = actor;
+        self
+    }
+
+    /// Reads and writes the records and codes of the collections keyed in `staged` through
+    /// the staging collections they map to, for loads that replace several collections
+    /// only once all of them were written.
+    pub fn with_staged(mut self, staged: BTreeMap<String, String>) -> Self {
+        self.staged = staged;
+        self
+    }
+
+    /// Replaces how long the documents written to staging collections are kept, after which
+    /// a TTL index removes them if the load writing them was abandoned.
+    pub fn with_staging_ttl(mut self, ttl: Duration) -> Self {
+        self.staging_ttl = ttl;
+        self
+    }
+
+    /// Limits lookups and exports to active or inactive aircraft; syncs see every aircraft
+    /// regardless.
+    pub fn with_inactive(mut self, inactive: Inactive) -> Self {
+        self.inactive = inactive;
+        self
+    }
+
+    /// Answers lookups by ICAO code from `cache` where it can, filling it with those it
+    /// cannot. [`cache::invalidate_on_change`](crate::cache::invalidate_on_change) keeps it
+    /// current.
+    pub fn with_lookup_cache(mut self, cache: LookupCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    // `filter` narrowed down to the aircraft lookups see.
+    fn visible(&self, mut filter: Document) -> Document {
+        match self.inactive {
+            Inactive::Include => {}
+            Inactive::Exclude => {
+                filter.insert(ACTIVE, doc! { "$ne": false });
+            }
+            Inactive::Only => {
+                filter.insert(ACTIVE, false);
+            }
+        }
+        filter
+    }
+
+    // The staging collection of `collection`, or `collection` itself when it isn't staged.
+    fn staged<'a>(&'a self, collection: &'a str) -> &'a str {
+        self.staged.get(collection).map_or(collection, String::as_str)
+    }
+
+    /// The names the aircraft fields are stored under.
+    pub fn field_names(&self) -> &FieldNames {
+        &self.fields
+    }
+
+    /// Connects to `uri`, with `settings` applied over its options, and opens
+    /// `database.collection`, pinging the server (with retries) so an unreachable cluster is
+    /// reported before any data is read.
+    pub async fn connect(
+        uri: &str,
+        settings: &ClientSettings,
+        database: &str,
+        collection: &str,
+        retry_policy: RetryPolicy,
+    ) -> Result<Self> {
+        // Create a new client and connect to the server
+        let mut options = ClientOptions::parse(uri).await?;
+        settings.apply(&mut options);
+        let client = Client::with_options(options)?;
+        // Get a handle on the aircraft collection
+        let database = client.database(database);
+        retry(&retry_policy, is_transient, || database.run_command(doc! { "ping": 1 }, None)).await?;
+        info!(database = database.name(), collection, "connected to mongodb");
+        Ok(AircraftStore::new(database.collection::<Document>(collection)).with_retry_policy(retry_policy))
+    }
+
+    /// The underlying collection handle.
+    pub fn collection(&self) -> &Collection<Document> {
+        &self.collection
+    }
+
+    /// The `load_history` collection finished runs are recorded in, next to this one.
+    pub fn load_history(&self) -> Collection<Document> {
+        history(&self.collection)
+    }
+
+    /// A store on a new collection next to this one, named `<collection>_staging_<unix time>`,
+    /// that a full reload can be written into before [`replace`](Self::replace) swaps it in.
+    /// Its documents expire like those of every staging collection.
+    pub fn staging(&self) -> AircraftStore {
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+        let name = format!("{}_staging_{}", self.collection.name(), seconds);
+        let collection = self.collection.client().database(&self.collection.namespace().db).collection(&name);
+        let mut staged = self.staged.clone();
+        staged.insert(self.collection.name().to_string(), name);
+        AircraftStore { collection, staged, cache: None, ..self.clone() }
+    }
+
+    /// Takes the lease on this store's collection for the run of its provenance, held by one
+    /// load or sync at a time; with `force`, a lease of another run is broken. A staging
+    /// collection is locked under the name of the live one it replaces.
+    pub async fn lock(&self, lease: Duration, force: bool) -> Result<LoadLock> {
+        let name = self.collection.name();
+        let live = self.staged.iter().find(|(_, staging)| staging.as_str() == name).map_or(name, |(live, _)| live.as_str());
+        self.lock_collection(live, lease, force).await
+    }
+
+    /// [`lock`](Self::lock) for another collection of the same database, such as one of the
+    /// [`records`](Self::records) collections, by its live name.
+    pub async fn lock_collection(&self, collection: &str, lease: Duration, force: bool) -> Result<LoadLock> {
+        LoadLock::acquire(self.sibling(LOCKS), collection, &self.provenance.load_id, lease, force).await
+    }
+
+    /// Atomically renames this store's collection over `target` in the same database,
+    /// dropping the old `target`, so readers see either the old or the new data in full.
+    pub async fn replace(&self, target: &str) -> Result<()> {
+        self.rename(self.collection.name(), target).await
+    }
+
+    /// Atomically renames collection `from` of the same database over `to`, dropping the
+    /// old `to`. A staging collection's documents stop expiring first.
+    pub async fn rename(&self, from: &str, to: &str) -> Result<()> {
+        staging::keep(&self.sibling(from)).await?;
+        let db = &self.collection.namespace().db;
+        let command = doc! {
+            "renameCollection": format!("{}.{}", db, from),
+            "to": format!("{}.{}", db, to),
+            "dropTarget": true,
+        };
+        let admin = self.collection.client().database("admin");
+        retry(&self.retry, is_transient, || admin.run_command(command.clone(), None)).await?;
+        info!(from, to, "replaced collection");
+        Ok(())
+    }
+
+    /// The aircraft matching `filter`, on the stored field names, as `aircraft_history` recorded
+    /// them at `at`, ordered by ICAO code. Only the writes of stores with history are recorded.
+    pub async fn as_of(&self, filter: Document, at: bson::DateTime) -> Result<Vec<Aircraft>> {
+        self.versions().at(filter, at).await
+    }
+
+    /// Every audited write to the aircraft `icao_code` in this collection, oldest first.
+    pub async fn audit_entries(&self, icao_code: &str) -> Result<Vec<AuditEntry>> {
+        self.audit_log("").entries(icao_code).await
+    }
+
+    /// Copies every document of the collection into `aircraft_snapshots` of the same
+    /// database, each wrapped with `snapshotId`, the collection it was copied from and when,
+    /// and returns how many were copied.
+    pub async fn snapshot(&self, snapshot_id: &str) -> Result<u64> {
+        let snapshots = self.sibling(SNAPSHOTS);
+        let index = IndexModel::builder().keys(doc! { "collection": 1, "snapshotId": 1 }).build();
+        snapshots.create_index(index, None).await?;
+        let collection = self.collection.name();
+        let pipeline = vec![
+            // Keyed by snapshot and original `_id`, so a retried copy replaces what it copied.
+            doc! { "$replaceWith": {
+                "_id": { "snapshotId": { "$literal": snapshot_id }, "id": "$_id" },
+                "snapshotId": { "$literal": snapshot_id },
+                "collection": { "$literal": collection },
+                "takenAt": bson::DateTime::now(),
+                "document": "$$ROOT",
+            } },
+            doc! { "$merge": { "into": SNAPSHOTS, "whenMatched": "replace" } },
+        ];
+        retry(&self.retry, is_transient, || self.collection.aggregate(pipeline.clone(), None)).await?;
+        let copied = snapshots.count_documents(doc! { "collection": collection, "snapshotId": snapshot_id }, None).await?;
+        info!(collection, snapshot_id, copied, "took snapshot");
+        Ok(copied)
+    }
+
+    /// Replaces the collection with the documents of its snapshot `snapshot_id` through `$out`,
+    /// keeping its indexes, so readers see either the old or the restored data in full, and
+    /// returns how many were restored. A snapshot of an empty collection holds nothing and
+    /// cannot be restored; purging empties the collection instead.
+    pub async fn restore(&self, snapshot_id: &str) -> Result<u64> {
+        let snapshots = self.sibling(SNAPSHOTS);
+        let collection = self.collection.name();
+        let filter = doc! { "collection": collection, "snapshotId": snapshot_id };
+        let count = snapshots.count_documents(filter.clone(), None).await?;
+        if count == 0 {
+            return Err(crate::Error::Config(format!("there is no snapshot {} of {}", snapshot_id, collection)));
+        }
+        let pipeline = vec![doc! { "$match": filter }, doc! { "$replaceWith": "$document" }, doc! { "$out": collection }];
+        retry(&self.retry, is_transient, || snapshots.aggregate(pipeline.clone(), None)).await?;
+        info!(collection, snapshot_id, restored = count, "restored snapshot");
+        Ok(count)
+    }
+
+    /// Checks the credentials are good for a load: lists the databases they can see and the
+    /// collections of this store's database, then inserts a throwaway document into the
+    /// collection and deletes it again. The first failing step is returned as the error.
+    pub async fn check(&self) -> Result<HealthCheck> {
+        let client = self.collection.client();
+        let options = ListDatabasesOptions::builder().authorized_databases(true).build();
+        let databases = client.list_database_names(None, options).await?;
+        let collections = client.database(&self.collection.namespace().db).list_collection_names(None).await?;
+        // Given its own ICAO code so the unique index doesn't refuse it.
+        let id = format!("health-check-{}", Uuid::new_v4());
+        let document = doc! { "_id": &id, &self.fields.icao_code: &id, "healthCheck": bson::DateTime::now() };
+        self.collection.insert_one(document, None).await?;
+        self.collection.delete_one(doc! { "_id": &id }, None).await?;
+        info!(collection = %self.collection.namespace(), "write check passed");
+        Ok(HealthCheck { databases, collections })
+    }
+
+    /// Drops the abandoned staging collections of the store's database and deletes the
+    /// expired documents of the others, as the TTL indexes would in time; with `dry_run`
+    /// only reports what would go.
+    pub async fn gc(&self, dry_run: bool) -> Result<Garbage> {
+        staging::collect(&self.collection.client().database(&self.collection.namespace().db), self.staging_ttl, dry_run).await
+    }
+
+    /// Drops the whole collection, indexes included.
+    pub async fn drop_collection(&self) -> Result<()> {
+        self.collection.drop(None).await?;
+        Ok(())
+    }
+
+    /// Returns the documents matching `filter`.
+    pub async fn find(&self, filter: Document) -> Result<Vec<Document>> {
+        let cursor = self.collection.find(filter, None).await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    /// The aircraft matching `filter` that lookups see, with their ids, ordered by ICAO code
+    /// and read as the stream is polled, so exports of any size take constant memory. With a
+    /// `projection`, the other fields are left on the server.
+    pub async fn stream_with_ids(&self, filter: Document, projection: Option<Document>) -> Result<impl Stream<Item = Result<StoredAircraft>> + '_> {
+        let options = FindOptions::builder().sort(doc! { &self.fields.icao_code: 1 }).collation(case_insensitive()).projection(projection).build();
+        let cursor = self.collection.find(self.visible(filter), options).await?;
+        Ok(cursor.map(move |document| self.stored(&document?)))
+    }
+
+    /// The aircraft with IATA code `iata_code`, in any case, or all of them, ordered by ICAO
+    /// code: at most `limit` of them after skipping the first `offset`, and how many there are
+    /// in all.
+    pub async fn page(&self, iata_code: Option<&str>, offset: u64, limit: u64) -> Result<(Vec<Aircraft>, u64)> {
+        let filter = match iata_code {
+            Some(iata_code) => doc! { &self.fields.iata_code: iata_code },
+            None => doc! {},
+        };
+        let mut options = page_options(&self.fields.icao_code, offset, limit);
+        options.collation = Some(case_insensitive());
+        let documents: Vec<Document> = self.collection.find(filter.clone(), options).await?.try_collect().await?;
+        let count_options = CountOptions::builder().collation(case_insensitive()).build();
+        let total = self.collection.count_documents(filter, count_options).await?;
+        let aircrafts = documents.iter().map(|document| self.fields.aircraft(document)).collect::<Result<_>>()?;
+        Ok((aircrafts, total))
+    }
+
+    /// Another collection of the same database.
+    pub fn sibling(&self, collection: &str) -> Collection<Document> {
+        self.collection.client().database(&self.collection.namespace().db).collection(collection)
+    }
+
+    /// A store for another kind of record in a collection of the same database, sharing
+    /// this store's retry policy, id strategy and provenance.
+    pub fn records<T: Record>(&self, collection: &str) -> RecordStore<T> {
+        let ttl = self.staged.contains_key(collection).then_some(self.staging_ttl);
+        let collection = self.collection.client().database(&self.collection.namespace().db).collection(self.staged(collection));
+        RecordStore::new(collection)
+            .with_retry_policy(self.retry)
+            .with_id_strategy(self.ids)
+            .with_uuid_encoding(self.uuids)
+            .with_provenance(self.provenance.clone())
+            .with_staging_ttl(ttl)
+    }
+
+    /// Sets `fields` (and `updatedAt`) on the document matching `filter`, leaving its other
+    /// fields alone, and returns whether a document matched.
+    pub async fn merge(&self, filter: Document, fields: Document) -> Result<bool> {
+        let before = if self.actor.is_some() { self.collection.find_one(filter.clone(), None).await? } else { None };
+        let mut update = fields.clone();
+        update.insert("updatedAt", bson::DateTime::now());
+        let update = doc! { "$set": update };
+        let result =
+            retry(&self.retry, is_transient, || self.collection.update_one(filter.clone(), update.clone(), None)).await?;
+        if let (Some(actor), Some(before)) = (&self.actor, before) {
+            let icao_code = before.get_str(&self.fields.icao_code).unwrap_or_default().to_string();
+            let old: Document = fields.keys().filter_map(|key| before.get(key).map(|value| (key.clone(), value.clone()))).collect();
+            self.audit_log(actor).record(vec![(icao_code, Some(old), Some(fields))]).await?;
+        }
+        Ok(result.matched_count > 0)
+    }
+
+    /// Sets the fields of `overrides` on the documents of their ICAO codes, recording under
+    /// [`OVERRIDE`] which file overrode which fields, and drops that record from documents
+    /// no longer overridden. Returns how many documents carry an override.
+    pub async fn stamp_overrides(&self, overrides: &Overrides) -> Result<u64> {
+        let now = bson::DateTime::now();
+        let mut stamped = 0;
+        for (icao_code, entry) in &overrides.entries {
+            let mut fields = Document::new();
+            // Removed IATA codes were written as the missing code policy has it.
+            if let Some(iata_code) = entry.iata_code.as_ref().filter(|iata_code| !iata_code.is_empty()) {
+                fields.insert(&self.fields.iata_code, iata_code);
+            }
+            if let Some(description) = &entry.description {
+                fields.insert(&self.fields.description, description);
+            }
+            for (locale, description) in &entry.descriptions {
+                fields.insert(format!("{}.{}", DESCRIPTIONS, locale), description);
+            }
+            if let Some(aliases) = &entry.aliases {
+                fields.insert(ALIASES, aliases.clone());
+            }
+            fields.insert(OVERRIDE, doc! {
+                "sourceFile": &overrides.source_file,
+                "checksum": &overrides.checksum,
+                "fields": entry.fields(),
+                "reason": &entry.reason,
+                "appliedAt": now,
+            });
+            let filter = doc! { &self.fields.icao_code: icao_code };
+            let update = doc! { "$set": fields };
+            let result =
+                retry(&self.retry, is_transient, || self.collection.update_one(filter.clone(), update.clone(), None)).await?;
+            stamped += result.matched_count;
+        }
+        let codes: Vec<&String> = overrides.entries.keys().collect();
+        let stale = doc! { OVERRIDE: { "$exists": true }, &self.fields.icao_code: { "$nin": codes } };
+        let unset = doc! { "$unset": { OVERRIDE: "" } };
+        retry(&self.retry, is_transient, || self.collection.update_many(stale.clone(), unset.clone(), None)).await?;
+        Ok(stamped)
+    }
+
+    /// The non-empty ICAO and IATA codes stored in `collection` of the same database.
+    pub async fn codes(&self, collection: &str) -> Result<HashSet<String>> {
+        let collection = self.collection.client().database(&self.collection.namespace().db).collection::<Document>(self.staged(collection));
+        let mut codes = HashSet::new();
+        for field in ["icaoCode", "iataCode"] {
+            let values = collection.distinct(field, None, None).await?;
+            codes.extend(values.into_iter().filter_map(|value| value.as_str().map(str::to_string)));
+        }
+        codes.remove("");
+        Ok(codes)
+    }
+
+    // Written to a staging collection, the documents carry when they expire too.
+    fn provenance_fields(&self, now: bson::DateTime) -> Document {
+        let mut fields = provenance_fields(&self.provenance, now);
+        if self.is_staging() {
+            fields.insert(EXPIRES_AT, staging::expires_at(now, self.staging_ttl));
+        }
+        fields
+    }
+
+    fn is_staging(&self) -> bool {
+        self.staged.values().any(|staging| staging == self.collection.name())
+    }
+
+    fn insert_documents(&self, aircrafts: &[Aircraft]) -> Vec<Document> {
+        let now = bson::DateTime::now();
+        let mut documents = aircrafts.iter().map(|aircraft| aircraft_document(aircraft, &self.ids, self.uuids, &self.fields)).collect::<Vec<_>>();
+        for document in documents.iter_mut() {
+            document.extend(self.provenance_fields(now));
+            document.insert("createdAt", now);
+        }
+        documents
+    }
+
+    fn versions(&self) -> Versions<'_> {
+        Versions { history: self.sibling(AIRCRAFT_HISTORY), collection: self.collection.name(), fields: &self.fields }
+    }
+
+    fn audit_log<'a>(&'a self, actor: &'a str) -> Audit<'a> {
+        Audit {
+            log: self.sibling(AUDIT_LOG),
+            collection: self.collection.name(),
+            fields: &self.fields,
+            actor,
+            provenance: &self.provenance,
+        }
+    }
+
+    // The audited fields of the aircraft matching `filter`, before a write changes them.
+    async fn audit_before(&self, filter: Document) -> Result<HashMap<String, Document>> {
+        match &self.actor {
+            Some(actor) => self.audit_log(actor).current(&self.collection, filter).await,
+            None => Ok(HashMap::new()),
+        }
+    }
+
+    // Audits the writes of `aircrafts` over the fields they had `before`, with auditing on.
+    async fn audit_writes(&self, mut before: HashMap<String, Document>, aircrafts: &[Aircraft]) -> Result<()> {
+        let Some(actor) = &self.actor else { return Ok(()) };
+        let changes = aircrafts
+            .iter()
+            .map(|aircraft| {
+                let after = self.fields.document(aircraft);
+                // An aircraft written twice is audited from what its first write left.
+                let previous = before.insert(aircraft.icao_code.clone(), after.clone());
+                (aircraft.icao_code.clone(), previous, Some(after))
+            })
+            .collect();
+        self.audit_log(actor).record(changes).await?;
+        Ok(())
+    }
+
+    // Audits the deletes of the aircraft that had the fields of `before`, with auditing on.
+    async fn audit_deletes(&self, before: HashMap<String, Document>) -> Result<()> {
+        let Some(actor) = &self.actor else { return Ok(()) };
+        let changes = before.into_iter().map(|(icao_code, fields)| (icao_code, Some(fields), None)).collect();
+        self.audit_log(actor).record(changes).await?;
+        Ok(())
+    }
+
+    // Records the versions of `aircrafts` just written, with history on.
+    async fn record_versions(&self, aircrafts: &[Aircraft]) -> Result<()> {
+        if self.history {
+            self.versions().record(aircrafts, &self.provenance).await?;
+        }
+        Ok(())
+    }
+
+    // Closes the versions of `icao_codes` just deleted, or of every aircraft, with history on.
+    async fn close_versions(&self, icao_codes: Option<&[&str]>) -> Result<()> {
+        if self.history {
+            self.versions().close(icao_codes, bson::DateTime::now()).await?;
+        }
+        Ok(())
+    }
+
+    // `document` as an aircraft with its `_id` as a string.
+    fn stored(&self, document: &Document) -> Result<StoredAircraft> {
+        let id = match document.get("_id") {
+            Some(bson::Bson::String(id)) => id.clone(),
+            Some(bson::Bson::ObjectId(id)) => id.to_hex(),
+            // Binary UUIDs.
+            Some(id @ bson::Bson::Binary(binary)) => binary.to_uuid().map_or_else(|_| id.to_string(), |uuid| uuid.to_string()),
+            Some(other) => other.to_string(),
+            None => String::new(),
+        };
+        Ok(StoredAircraft { id, aircraft: self.fields.aircraft(document)? })
+    }
+
+    // `find_by_icao` without the cache.
+    async fn lookup_icao(&self, icao_code: &str) -> Result<Option<Aircraft>> {
+        let options = FindOneOptions::builder().collation(case_insensitive()).build();
+        let mut document = match self.ids.is_natural() {
+            true => self.collection.find_one(self.visible(doc! { "_id": icao_code.trim().to_ascii_uppercase() }), None).await?,
+            false => self.collection.find_one(self.visible(doc! { &self.fields.icao_code: icao_code }), options.clone()).await?,
+        };
+        if document.is_none() {
+            document = self.collection.find_one(self.visible(doc! { ALIASES: icao_code }), options).await?;
+        }
+        document.map(|document| self.fields.aircraft(&document)).transpose()
+    }
+
+    async fn find_aircraft(&self, filter: Document) -> Result<Vec<Aircraft>> {
+        self.find(filter)
+            .await?
+            .into_iter()
+            .map(|document| self.fields.aircraft(&document))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl Storage for AircraftStore {
+    /// Inserts every aircraft as a new document with an `_id` from the store's [`IdStrategy`].
+    async fn insert_batch(&self, aircrafts: &[Aircraft]) -> Result<u64> {
+        let written = insert_ordered(&self.collection, &self.retry, self.insert_documents(aircrafts)).await?;
+        self.record_versions(aircrafts).await?;
+        self.audit_writes(HashMap::new(), aircrafts).await?;
+        Ok(written)
+    }
+
+    /// Inserts the aircraft with an unordered `insert_many`, so a duplicate key only fails
+    /// its own document.
+    async fn insert_unordered(&self, aircrafts: &[Aircraft]) -> Result<(u64, Vec<FailedWrite>)> {
+        let (written, failed) = insert_unordered(&self.collection, &self.retry, self.insert_documents(aircrafts), aircrafts).await?;
+        if self.history || self.actor.is_some() {
+            let refused: HashSet<&str> = failed.iter().map(|failure| failure.record.icao_code.as_str()).collect();
+            let accepted: Vec<Aircraft> = aircrafts.iter().filter(|aircraft| !refused.contains(aircraft.icao_code.as_str())).cloned().collect();
+            self.record_versions(&accepted).await?;
+            self.audit_writes(HashMap::new(), &accepted).await?;
+        }
+        Ok((written, failed))
+    }
+
+    /// Upserts every aircraft keyed on its ICAO code, so re-running a load updates the
+    /// existing documents instead of duplicating them. The `_id` is only generated when a
+    /// document is first inserted and is left untouched afterwards. The batch is written
+    /// with one `update` command.
+    async fn upsert(&self, aircrafts: &[Aircraft]) -> Result<u64> {
+        let now = bson::DateTime::now();
+        let icao_codes: Vec<&str> = aircrafts.iter().map(|aircraft| aircraft.icao_code.as_str()).collect();
+        let before = self.audit_before(doc! { &self.fields.icao_code: { "$in": icao_codes } }).await?;
+        let updates = aircrafts
+            .iter()
+            .map(|aircraft| {
+                let mut document = self.fields.document(aircraft);
+                document.extend(self.provenance_fields(now));
+                // A natural `_id` is the filter, which an upsert inserts the document with.
+                let (filter, on_insert) = match self.ids.is_natural() {
+                    true => (doc! { "_id": self.ids.id_for(aircraft) }, doc! { "createdAt": now }),
+                    false => (
+                        doc! { &self.fields.icao_code: &aircraft.icao_code },
+                        doc! { "_id": self.uuids.encode(self.ids.id_for(aircraft)), "createdAt": now },
+                    ),
+                };
+                // Writing an aircraft again makes it active.
+                let mut omitted = self.fields.omitted(aircraft);
+                omitted.extend(doc! { ACTIVE: "", RETIRED_AT: "" });
+                doc! { "q": filter, "u": { "$set": document, "$setOnInsert": on_insert, "$unset": omitted }, "upsert": true }
+            })
+            .collect();
+        let written = upsert_all(&self.collection, &self.retry, updates).await?;
+        self.record_versions(aircrafts).await?;
+        self.audit_writes(before, aircrafts).await?;
+        Ok(written)
+    }
+
+    /// Ignores case, as the ICAO code index does, or by looking up the upper-case code with
+    /// natural `_id`s. A code no aircraft is stored under resolves to the aircraft listing it
+    /// among its aliases. With a [lookup cache](AircraftStore::with_lookup_cache), cached
+    /// lookups are answered from it.
+    async fn find_by_icao(&self, icao_code: &str) -> Result<Option<Aircraft>> {
+        let Some(cache) = &self.cache else {
+            return self.lookup_icao(icao_code).await;
+        };
+        if let Some(cached) = cache.get(icao_code).await {
+            return Ok(cached);
+        }
+        let aircraft = self.lookup_icao(icao_code).await?;
+        cache.insert(icao_code, aircraft.clone()).await;
+        Ok(aircraft)
+    }
+
+    /// Ignores case.
+    async fn find_by_iata(&self, iata_code: &str) -> Result<Vec<Aircraft>> {
+        let options = FindOptions::builder().collation(case_insensitive()).build();
+        let cursor = self.collection.find(self.visible(doc! { &self.fields.iata_code: iata_code }), options).await?;
+        let documents: Vec<Document> = cursor.try_collect().await?;
+        documents.iter().map(|document| self.fields.aircraft(document)).collect()
+    }
+
+    /// One query matching the codes in any case.
+    async fn find_by_codes(&self, codes: &[String]) -> Result<Vec<Aircraft>> {
+        let filter = doc! { "$or": [
+            { &self.fields.icao_code: { "$in": codes } },
+            { &self.fields.iata_code: { "$in": codes } },
+            { ALIASES: { "$in": codes } },
+        ] };
+        let options = FindOptions::builder().collation(case_insensitive()).build();
+        let documents: Vec<Document> = self.collection.find(self.visible(filter), options).await?.try_collect().await?;
+        documents.iter().map(|document| self.fields.aircraft(document)).collect()
+    }
+
+    /// Sees every aircraft whatever [`with_inactive`](AircraftStore::with_inactive) says, as
+    /// syncs compare the input with all of them.
+    async fn find_all(&self) -> Result<Vec<Aircraft>> {
+        self.find_aircraft(doc! {}).await
+    }
+
+    async fn find_all_with_ids(&self) -> Result<Vec<StoredAircraft>> {
+        self.find(self.visible(doc! {})).await?.iter().map(|document| self.stored(document)).collect()
+    }
+
+    async fn delete_all(&self) -> Result<u64> {
+        let before = self.audit_before(doc! {}).await?;
+        let result = retry(&self.retry, is_transient, || self.collection.delete_many(doc! {}, None)).await?;
+        self.close_versions(None).await?;
+        self.audit_deletes(before).await?;
+        Ok(result.deleted_count)
+    }
+
+    /// Sets `active` to false and `retiredAt` to now, leaving the rest of the documents alone.
+    async fn retire(&self, icao_codes: &[String]) -> Result<u64> {
+        let now = bson::DateTime::now();
+        let filter = doc! { &self.fields.icao_code: { "$in": icao_codes }, ACTIVE: { "$ne": false } };
+        let before = self.audit_before(filter.clone()).await?;
+        let update = doc! { "$set": { ACTIVE: false, RETIRED_AT: now, "updatedAt": now } };
+        let result = retry(&self.retry, is_transient, || self.collection.update_many(filter.clone(), update.clone(), None)).await?;
+        if let Some(actor) = &self.actor {
+            // The aircraft's own fields are unchanged; only the flag is recorded.
+            let changes = before.into_keys().map(|icao_code| (icao_code, Some(Document::new()), Some(doc! { ACTIVE: false, RETIRED_AT: now }))).collect();
+            self.audit_log(actor).record(changes).await?;
+        }
+        Ok(result.modified_count)
+    }
+
+    async fn retired(&self) -> Result<HashSet<String>> {
+        let values = self.collection.distinct(&self.fields.icao_code, doc! { ACTIVE: false }, None).await?;
+        Ok(values.into_iter().filter_map(|value| value.as_str().map(str::to_string)).collect())
+    }
+
+    async fn delete_by_icao(&self, icao_codes: &[String]) -> Result<u64> {
+        let filter = doc! { &self.fields.icao_code: { "$in": icao_codes } };
+        let before = self.audit_before(filter.clone()).await?;
+        let result = retry(&self.retry, is_transient, || self.collection.delete_many(filter.clone(), None)).await?;
+        let icao_codes: Vec<&str> = icao_codes.iter().map(String::as_str).collect();
+        self.close_versions(Some(&icao_codes)).await?;
+        self.audit_deletes(before).await?;
+        Ok(result.deleted_count)
+    }
+
+    /// Inserts the run into the `load_history` collection of the same database, keyed on its load id.
+    async fn record_load(&self, record: &LoadRecord) -> Result<()> {
+        record_history(&self.collection, &self.retry, record, self.provenance.tenant.as_deref()).await
+    }
+
+    async fn last_checksum(&self) -> Result<Option<String>> {
+        last_checksum(&self.collection).await
+    }
+
+    async fn delete_by_load(&self, load_id: &str) -> Result<u64> {
+        let filter = doc! { "loadId": load_id };
+        // The codes are looked up first: the documents holding them are about to go.
+        let icao_codes =
+            if self.history { self.collection.distinct(&self.fields.icao_code, filter.clone(), None).await? } else { Vec::new() };
+        let before = self.audit_before(filter.clone()).await?;
+        let result = retry(&self.retry, is_transient, || self.collection.delete_many(filter.clone(), None)).await?;
+        if self.history {
+            let icao_codes: Vec<&str> = icao_codes.iter().filter_map(bson::Bson::as_str).collect();
+            self.close_versions(Some(&icao_codes)).await?;
+        }
+        self.audit_deletes(before).await?;
+        Ok(result.deleted_count)
+    }
+
+    /// Creates a unique index on the ICAO code that ignores case, so `b738` and `B738` cannot
+    /// both be stored, and a non-unique one on the IATA code, ignoring case too. With natural
+    /// `_id`s the `_id` index keeps the upper-case ICAO codes unique instead. Creating an
+    /// index that already exists with the same options is a no-op on the server.
+    async fn ensure_indexes(&self) -> Result<()> {
+        let mut indexes = Vec::new();
+        if !self.ids.is_natural() {
+            indexes.push(
+                IndexModel::builder()
+                    .keys(doc! { &self.fields.icao_code: 1 })
+                    .options(
+                        IndexOptions::builder()
+                            .name(case_insensitive_index(&self.fields.icao_code))
+                            .unique(true)
+                            .collation(case_insensitive())
+                            .build(),
+                    )
+                    .build(),
+            );
+        }
+        indexes.extend([
+            IndexModel::builder()
+                .keys(doc! { &self.fields.iata_code: 1 })
+                .options(IndexOptions::builder().name(case_insensitive_index(&self.fields.iata_code)).collation(case_insensitive()).build())
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { ALIASES: 1 })
+                .options(IndexOptions::builder().name(case_insensitive_index(ALIASES)).collation(case_insensitive()).build())
+                .build(),
+        ]);
+        let result = self.collection.create_indexes(indexes, None).await?;
+        info!(indexes = ?result.index_names, "ensured indexes");
+        if self.history {
+            self.versions().ensure_index().await?;
+        }
+        if let Some(actor) = &self.actor {
+            self.audit_log(actor).ensure_index().await?;
+        }
+        if self.is_staging() {
+            staging::ensure_ttl_index(&self.collection).await?;
+        }
+        Ok(())
+    }
+}
+
+// Compares strings ignoring case, e.g. `b738` equal to `B738`. Queries on the codes must
+// use it to be served by their indexes.
+pub(crate) fn case_insensitive() -> Collation {
+    Collation::builder().locale("en").strength(CollationStrength::Secondary).build()
+}
+
+// Name of the case-insensitive index on `field`. It differs from the default name of the
+// case-sensitive index earlier versions created, which the server would otherwise refuse
+// to replace with different options.
+fn case_insensitive_index(field: &str) -> String {
+    format!("{}_ci", field)
+}
+
+// Sorted on `key` so consecutive pages neither skip nor repeat documents.
+pub(super) fn page_options(key: &str, offset: u64, limit: u64) -> FindOptions {
+    FindOptions::builder().sort(doc! { key: 1 }).skip(offset).limit(limit.min(i64::MAX as u64) as i64).build()
+}
+
+// The provenance fields set on every insert and update; `createdAt` is only set on insert.
+pub(super) fn provenance_fields(provenance: &Provenance, now: bson::DateTime) -> Document {
+    let mut fields = doc! { "loadId": &provenance.load_id, "updatedAt": now };
+    if let Some(source_file) = &provenance.source_file {
+        fields.insert("sourceFile", source_file);
+    }
+    if let Some(checksum) = &provenance.checksum {
+        fields.insert("checksum", checksum);
+    }
+    fields
+}
+
+fn history(collection: &Collection<Document>) -> Collection<Document> {
+    collection.client().database(&collection.namespace().db).collection(LOAD_HISTORY)
+}
+
+// Adds `record` to the load history of the database holding `collection`, as a run for
+// `tenant` if there is one.
+pub(super) async fn record_history(collection: &Collection<Document>, retry_policy: &RetryPolicy, record: &LoadRecord, tenant: Option<&str>) -> Result<()> {
+    let provenance = &record.provenance;
+    let document = doc! {
+        "_id": &provenance.load_id,
+        "command": &record.command,
+        "collection": collection.name(),
+        "sourceFile": &provenance.source_file,
+        "checksum": &provenance.checksum,
+        "tenant": tenant,
+        "startedAt": bson::DateTime::from_system_time(record.started_at),
+        "finishedAt": bson::DateTime::from_system_time(record.finished_at),
+        "parsed": record.parsed as i64,
+        "written": record.written as i64,
+        "deleted": record.deleted as i64,
+        "rejected": record.rejected as i64,
+        "skipped": record.skipped as i64,
+        "interrupted": record.interrupted,
+    };
+    let history = history(collection);
+    retry(retry_policy, is_transient, || history.insert_one(document.clone(), None)).await?;
+    Ok(())
+}
+
+// The input checksum of the latest run recorded for `collection`.
+pub(super) async fn last_checksum(collection: &Collection<Document>) -> Result<Option<String>> {
+    let options = FindOneOptions::builder().sort(doc! { "finishedAt": -1 }).build();
+    let last = history(collection).find_one(doc! { "collection": collection.name() }, options).await?;
+    Ok(last.and_then(|record| record.get_str("checksum").ok().map(str::to_string)))
+}
+
+/// The document inserted for `aircraft`: its fields, named by `fields`, plus an `_id`
+/// generated by `ids` and stored as `uuids` says if it is a UUID.
+pub fn aircraft_document(aircraft: &Aircraft, ids: &IdStrategy, uuids: UuidEncoding, fields: &FieldNames) -> Document {
+    let mut document = fields.document(aircraft);
+    document.insert("_id", uuids.encode(ids.id_for(aircraft)));
+    document
+}
+
+// Runs the upserts of `updates`, each a statement of the `update` command, as one command and
+// returns how many documents they matched or inserted. An upsert can be run again, so the
+// command is retried as a whole.
+pub(super) async fn upsert_all(collection: &Collection<Document>, retry_policy: &RetryPolicy, updates: Vec<Document>) -> Result<u64> {
+    if updates.is_empty() {
+        return Ok(0);
+    }
+    let mut command = doc! { "update": collection.name(), "updates": updates };
+    // Unlike the collection's own writes, a command is not sent with its write concern.
+    if let Some(write_concern) = collection.write_concern() {
+        command.insert("writeConcern", bson::to_document(write_concern)?);
+    }
+    let database = collection.client().database(&collection.namespace().db);
+    let written = retry(retry_policy, is_transient, || async {
+        let response = database.run_command(command.clone(), None).await?;
+        // Refused statements are reported in a response that is otherwise a success.
+        let failure: BulkWriteFailure = bson::from_document(response.clone())?;
+        if failure.write_errors.is_some() || failure.write_concern_error.is_some() {
+            return Err(Error::from(ErrorKind::BulkWrite(failure)));
+        }
+        Ok(response.get_i32("n").unwrap_or_default() as u64)
+    })
+    .await?;
+    Ok(written)
+}
+
+// Inserts `documents`, made from `records`, without stopping at the documents the server
+// refuses. As with `insert_ordered`, retries only insert the documents whose `_id` is not
+// stored yet, so those the failed attempt wrote are not reported as duplicates.
+pub(super) async fn insert_unordered<T: Clone>(
+    collection: &Collection<Document>,
+    retry_policy: &RetryPolicy,
+    documents: Vec<Document>,
+    records: &[T],
+) -> Result<(u64, Vec<FailedWrite<T>>)> {
+    let options = InsertManyOptions::builder().ordered(false).build();
+    let first = AtomicBool::new(true);
+    let (first, documents, options) = (&first, &documents, &options);
+    let written = retry(retry_policy, is_transient, || async move {
+        let mut pending: Vec<usize> = (0..documents.len()).collect();
+        if !first.swap(false, Ordering::Relaxed) {
+            let ids: Vec<Bson> = documents.iter().filter_map(|document| document.get("_id").cloned()).collect();
+            let stored = collection.distinct("_id", doc! { "_id": { "$in": ids } }, None).await?;
+            pending.retain(|&index| documents[index].get("_id").is_none_or(|id| !stored.contains(id)));
+        }
+        let already = (documents.len() - pending.len()) as u64;
+        if pending.is_empty() {
+            return Ok::<_, Error>((already, Vec::new()));
+        }
+        let batch: Vec<Document> = pending.iter().map(|&index| documents[index].clone()).collect();
+        let error = match collection.insert_many(batch, options.clone()).await {
+            Ok(result) => return Ok((already + result.inserted_ids.len() as u64, Vec::new())),
+            Err(error) => error,
+        };
+        match error.kind.as_ref() {
+            ErrorKind::BulkWrite(failure) if failure.write_concern_error.is_none() => {
+                let failed: Vec<_> = failure
+                    .write_errors
+                    .iter()
+                    .flatten()
+                    .map(|write_error| FailedWrite { record: records[pending[write_error.index]].clone(), error: write_error.message.clone() })
+                    .collect();
+                Ok((already + (pending.len() - failed.len()) as u64, failed))
+            }
+            _ => Err(error),
+        }
+    })
+    .await?;
+    Ok(written)
+}
+
+/// Whether `error` is worth retrying: network and server selection failures, errors the
+/// server labels as retryable, and primary failovers or shutdowns.
+pub fn is_transient(error: &Error) -> bool {
+    if error.contains_label(RETRYABLE_WRITE_ERROR) || error.contains_label(TRANSIENT_TRANSACTION_ERROR) {
+        return true;
+    }
+    match error.kind.as_ref() {
+        ErrorKind::Io(_) | ErrorKind::ServerSelection { .. } | ErrorKind::ConnectionPoolCleared { .. } => true,
+        ErrorKind::Command(command_error) => TRANSIENT_CODES.contains(&command_error.code),
+        ErrorKind::Write(WriteFailure::WriteConcernError(concern_error)) => TRANSIENT_CODES.contains(&concern_error.code),
+        ErrorKind::BulkWrite(failure) => failure
+            .write_concern_error
+            .as_ref()
+            .is_some_and(|concern_error| TRANSIENT_CODES.contains(&concern_error.code)),
+        _ => false,
+    }
+}
+
+// Inserts `documents` with an ordered `insert_many`, returning how many are written. A
+// transient failure may come after the documents before it were written, so retries only
+// insert the documents whose `_id` is not stored yet, counting the others as written.
+pub(super) async fn insert_ordered(collection: &Collection<Document>, retry_policy: &RetryPolicy, documents: Vec<Document>) -> Result<u64> {
+    let first = AtomicBool::new(true);
+    let (first, documents) = (&first, &documents);
+    let written = retry(retry_policy, is_transient, || async move {
+        let mut pending = documents.clone();
+        if !first.swap(false, Ordering::Relaxed) {
+            let ids: Vec<Bson> = documents.iter().filter_map(|document| document.get("_id").cloned()).collect();
+            let stored = collection.distinct("_id", doc! { "_id": { "$in": ids } }, None).await?;
+            pending.retain(|document| document.get("_id").is_none_or(|id| !stored.contains(id)));
+        }
+        let already = (documents.len() - pending.len()) as u64;
+        if pending.is_empty() {
+            return Ok::<_, Error>(already);
+        }
+        let result = collection.insert_many(pending, None).await?;
+        Ok(already + result.inserted_ids.len() as u64)
+    })
+    .await?;
+    Ok(written)
+}
@@ -0,0 +1,240 @@
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+use async_trait::async_trait;
+use sqlx::postgres::{PgPool, PgPoolOptions, PgRow};
+use sqlx::{QueryBuilder, Row};
+use crate::ids::IdStrategy;
+use crate::provenance::{rfc3339, LoadRecord, Provenance};
+use crate::{Aircraft, Result};
+use super::{Storage, StoredAircraft, CREATE_LOAD_HISTORY, PROVENANCE_COLUMNS};
+
+// Postgres caps a statement at 65535 bind parameters; each row binds nine.
+const ROWS_PER_INSERT: usize = 1000;
+
+/// A PostgreSQL table holding [`Aircraft`] rows, keyed by ICAO code.
+#[derive(Clone, Debug)]
+pub struct PostgresStorage {
+    pool: PgPool,
+    name: String,
+    // `name` quoted for use in statements.
+    table: String,
+    ids: IdStrategy,
+    provenance: Provenance,
+}
+
+impl PostgresStorage {
+    /// Connects to `url` and creates `table` if it does not exist yet.
+    pub async fn connect(url: &str, table: &str) -> Result<Self> {
+        let pool = PgPoolOptions::new().connect(url).await?;
+        let storage = PostgresStorage {
+            pool,
+            name: table.to_string(),
+            table: quote_identifier(table),
+            ids: IdStrategy::default(),
+            provenance: Provenance::default(),
+        };
+        storage.create_table().await?;
+        Ok(storage)
+    }
+
+    /// Replaces how the `id` of newly inserted rows is generated.
+    pub fn with_id_strategy(mut self, ids: IdStrategy) -> Self {
+        self.ids = ids;
+        self
+    }
+
+    /// Replaces the provenance stamped onto every row this storage writes, so the rows of
+    /// a load can be traced to its input and rolled back with [`Storage::delete_by_load`].
+    pub fn with_provenance(mut self, provenance: Provenance) -> Self {
+        self.provenance = provenance;
+        self
+    }
+
+    async fn create_table(&self) -> Result<()> {
+        let statement = format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                id TEXT NOT NULL UNIQUE,
+                icao_code TEXT PRIMARY KEY,
+                iata_code TEXT NOT NULL,
+                description TEXT NOT NULL,
+                load_id TEXT,
+                source_file TEXT,
+                checksum TEXT,
+                created_at TEXT,
+                updated_at TEXT
+            )",
+            self.table
+        );
+        sqlx::query(&statement).execute(&self.pool).await?;
+        // Tables created before records carried provenance lack its columns.
+        for column in PROVENANCE_COLUMNS {
+            let alter = format!("ALTER TABLE {} ADD COLUMN IF NOT EXISTS {} TEXT", self.table, column);
+            sqlx::query(&alter).execute(&self.pool).await?;
+        }
+        sqlx::query(CREATE_LOAD_HISTORY).execute(&self.pool).await?;
+        // Load histories created before runs could be interrupted lack that column.
+        sqlx::query("ALTER TABLE load_history ADD COLUMN IF NOT EXISTS interrupted BOOLEAN NOT NULL DEFAULT FALSE")
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn write(&self, aircrafts: &[Aircraft], on_conflict: &str) -> Result<u64> {
+        let mut written = 0;
+        for chunk in aircrafts.chunks(ROWS_PER_INSERT) {
+            let mut builder = QueryBuilder::new(format!(
+                "INSERT INTO {} (id, icao_code, iata_code, description, load_id, source_file, checksum, created_at, updated_at) ",
+                self.table
+            ));
+            let now = rfc3339(SystemTime::now());
+            builder.push_values(chunk, |mut row, aircraft| {
+                row.push_bind(self.ids.id_for(aircraft))
+                    .push_bind(&aircraft.icao_code)
+                    // The column predates missing codes being None and keeps them empty.
+                    .push_bind(aircraft.iata_code.as_deref().unwrap_or_default())
+                    .push_bind(&aircraft.description)
+                    .push_bind(&self.provenance.load_id)
+                    .push_bind(&self.provenance.source_file)
+                    .push_bind(&self.provenance.checksum)
+                    .push_bind(&now)
+                    .push_bind(&now);
+            });
+            builder.push(on_conflict);
+            let result = builder.build().execute(&self.pool).await?;
+            written += result.rows_affected();
+        }
+        Ok(written)
+    }
+}
+
+#[async_trait]
+impl Storage for PostgresStorage {
+    async fn insert_batch(&self, aircrafts: &[Aircraft]) -> Result<u64> {
+        self.write(aircrafts, "").await
+    }
+
+    async fn upsert(&self, aircrafts: &[Aircraft]) -> Result<u64> {
+        self.write(
+            aircrafts,
+            " ON CONFLICT (icao_code) DO UPDATE SET
+                iata_code = EXCLUDED.iata_code,
+                description = EXCLUDED.description,
+                load_id = EXCLUDED.load_id,
+                source_file = EXCLUDED.source_file,
+                checksum = EXCLUDED.checksum,
+                updated_at = EXCLUDED.updated_at",
+        )
+        .await
+    }
+
+    async fn find_by_icao(&self, icao_code: &str) -> Result<Option<Aircraft>> {
+        let statement = format!(
+            "SELECT icao_code, iata_code, description FROM {} WHERE icao_code = UPPER($1)",
+            self.table
+        );
+        let row = sqlx::query(&statement)
+            .bind(icao_code)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.as_ref().map(aircraft_from_row))
+    }
+
+    async fn find_by_iata(&self, iata_code: &str) -> Result<Vec<Aircraft>> {
+        let statement = format!(
+            "SELECT icao_code, iata_code, description FROM {} WHERE iata_code = UPPER($1) ORDER BY icao_code",
+            self.table
+        );
+        let rows = sqlx::query(&statement)
+            .bind(iata_code)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.iter().map(aircraft_from_row).collect())
+    }
+
+    async fn find_all(&self) -> Result<Vec<Aircraft>> {
+        let statement = format!(
+            "SELECT icao_code, iata_code, description FROM {} ORDER BY icao_code",
+            self.table
+        );
+        let rows = sqlx::query(&statement).fetch_all(&self.pool).await?;
+        Ok(rows.iter().map(aircraft_from_row).collect())
+    }
+
+    async fn find_all_with_ids(&self) -> Result<Vec<StoredAircraft>> {
+        let statement = format!(
+            "SELECT id, icao_code, iata_code, description FROM {} ORDER BY icao_code",
+            self.table
+        );
+        let rows = sqlx::query(&statement).fetch_all(&self.pool).await?;
+        Ok(rows
+            .iter()
+            .map(|row| StoredAircraft { id: row.get("id"), aircraft: aircraft_from_row(row) })
+            .collect())
+    }
+
+    async fn delete_all(&self) -> Result<u64> {
+        let statement = format!("DELETE FROM {}", self.table);
+        let result = sqlx::query(&statement).execute(&self.pool).await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn record_load(&self, record: &LoadRecord) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO load_history
+                (load_id, command, collection, source_file, checksum, started_at, finished_at, parsed, written, deleted, rejected, skipped, interrupted)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)",
+        )
+        .bind(&record.provenance.load_id)
+        .bind(&record.command)
+        .bind(&self.name)
+        .bind(&record.provenance.source_file)
+        .bind(&record.provenance.checksum)
+        .bind(rfc3339(record.started_at))
+        .bind(rfc3339(record.finished_at))
+        .bind(record.parsed as i64)
+        .bind(record.written as i64)
+        .bind(record.deleted as i64)
+        .bind(record.rejected as i64)
+        .bind(record.skipped as i64)
+        .bind(record.interrupted)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn last_checksum(&self) -> Result<Option<String>> {
+        let checksum: Option<Option<String>> = sqlx::query_scalar(
+            "SELECT checksum FROM load_history WHERE collection = $1 ORDER BY finished_at DESC LIMIT 1",
+        )
+        .bind(&self.name)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(checksum.flatten())
+    }
+
+    async fn delete_by_load(&self, load_id: &str) -> Result<u64> {
+        let statement = format!("DELETE FROM {} WHERE load_id = $1", self.table);
+        let result = sqlx::query(&statement).bind(load_id).execute(&self.pool).await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn delete_by_icao(&self, icao_codes: &[String]) -> Result<u64> {
+        let statement = format!("DELETE FROM {} WHERE icao_code = ANY($1)", self.table);
+        let result = sqlx::query(&statement).bind(icao_codes).execute(&self.pool).await?;
+        Ok(result.rows_affected())
+    }
+}
+
+fn aircraft_from_row(row: &PgRow) -> Aircraft {
+    Aircraft {
+        icao_code: row.get("icao_code"),
+        iata_code: Some(row.get::<String, _>("iata_code")).filter(|code| !code.is_empty()),
+        description: row.get("description"),
+        descriptions: BTreeMap::new(),
+        aliases: Vec::new(),
+    }
+}
+
+fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
@@ -0,0 +1,247 @@
+use std::marker::PhantomData;
+use std::time::Duration;
+use async_trait::async_trait;
+use futures::TryStreamExt;
+use mongodb::bson::{self, doc, Document};
+use mongodb::options::{FindOptions, IndexOptions};
+use mongodb::{Collection, IndexModel};
+use tracing::info;
+use crate::ids::{IdStrategy, UuidEncoding};
+use crate::provenance::{LoadRecord, Provenance};
+use crate::airport::{self, NearbyAirport};
+use crate::record::Record;
+use crate::retry::{retry, RetryPolicy};
+use crate::{Airport, Result};
+use super::mongo::{insert_ordered, insert_unordered, is_transient, last_checksum, page_options, provenance_fields, record_history, upsert_all};
+use super::staging::{self, EXPIRES_AT};
+use super::{FailedWrite, Sink};
+
+/// A MongoDB collection holding one kind of [`Record`], keyed on its
+/// [`KEY_FIELD`](Record::KEY_FIELD). The counterpart of
+/// [`AircraftStore`](super::AircraftStore) for the other reference datasets.
+#[derive(Debug)]
+pub struct RecordStore<T> {
+    collection: Collection<Document>,
+    retry: RetryPolicy,
+    ids: IdStrategy,
+    uuids: UuidEncoding,
+    provenance: Provenance,
+    // Set on staging collections, whose documents expire this long after they are written.
+    staging_ttl: Option<Duration>,
+    record: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for RecordStore<T> {
+    fn clone(&self) -> Self {
+        RecordStore {
+            collection: self.collection.clone(),
+            retry: self.retry,
+            ids: self.ids,
+            uuids: self.uuids,
+            provenance: self.provenance.clone(),
+            staging_ttl: self.staging_ttl,
+            record: PhantomData,
+        }
+    }
+}
+
+impl<T: Record> RecordStore<T> {
+    /// Wraps an existing collection handle, retrying transient failures with the default policy.
+    pub fn new(collection: Collection<Document>) -> Self {
+        RecordStore {
+            collection,
+            retry: RetryPolicy::default(),
+            ids: IdStrategy::default(),
+            uuids: UuidEncoding::default(),
+            provenance: Provenance::default(),
+            staging_ttl: None,
+            record: PhantomData,
+        }
+    }
+
+    /// Replaces the policy used to retry transient failures.
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Replaces how `_id`s of newly inserted documents are generated.
+    pub fn with_id_strategy(mut self, ids: IdStrategy) -> Self {
+        self.ids = ids;
+        self
+    }
+
+    /// Replaces how the UUID `_id`s of newly inserted documents are stored.
+    pub fn with_uuid_encoding(mut self, uuids: UuidEncoding) -> Self {
+        self.uuids = uuids;
+        self
+    }
+
+    /// Replaces the provenance stamped onto every document this store writes.
+    pub fn with_provenance(mut self, provenance: Provenance) -> Self {
+        self.provenance = provenance;
+        self
+    }
+
+    /// Marks the collection as a staging one, whose documents a TTL index removes `ttl`
+    /// after they are written unless it goes live first.
+    pub fn with_staging_ttl(mut self, ttl: Option<Duration>) -> Self {
+        self.staging_ttl = ttl;
+        self
+    }
+
+    /// The underlying collection handle.
+    pub fn collection(&self) -> &Collection<Document> {
+        &self.collection
+    }
+
+    /// Creates the unique index on the key field plus the record type's own
+    /// [`indexes`](Record::indexes).
+    pub async fn ensure_indexes(&self) -> Result<()> {
+        let mut indexes = vec![IndexModel::builder()
+            .keys(doc! { T::KEY_FIELD: 1 })
+            .options(IndexOptions::builder().unique(true).build())
+            .build()];
+        indexes.extend(T::indexes());
+        let result = self.collection.create_indexes(indexes, None).await?;
+        info!(collection = self.collection.name(), indexes = ?result.index_names, "ensured indexes");
+        if self.staging_ttl.is_some() {
+            staging::ensure_ttl_index(&self.collection).await?;
+        }
+        Ok(())
+    }
+
+    /// Looks up the record whose key field equals `key`.
+    pub async fn find_by_key(&self, key: &str) -> Result<Option<T>> {
+        let document = self.collection.find_one(doc! { T::KEY_FIELD: key }, None).await?;
+        Ok(document.map(bson::from_document).transpose()?)
+    }
+
+    /// Returns the records matching `filter`.
+    pub async fn find(&self, filter: Document) -> Result<Vec<T>> {
+        let documents: Vec<Document> = self.collection.find(filter, None).await?.try_collect().await?;
+        documents.into_iter().map(|document| Ok(bson::from_document(document)?)).collect()
+    }
+
+    /// The records matching `filter` ordered by key: at most `limit` of them after skipping
+    /// the first `offset`, and how many match in all.
+    pub async fn page(&self, filter: Document, offset: u64, limit: u64) -> Result<(Vec<T>, u64)> {
+        let options = page_options(T::KEY_FIELD, offset, limit);
+        let documents: Vec<Document> = self.collection.find(filter.clone(), options).await?.try_collect().await?;
+        let total = self.collection.count_documents(filter, None).await?;
+        let records = documents.into_iter().map(|document| Ok(bson::from_document(document)?)).collect::<Result<_>>()?;
+        Ok((records, total))
+    }
+
+    /// Sets `fields` (and `updatedAt`) on the record keyed `key`, leaving its other fields
+    /// alone, and returns whether a record matched.
+    pub async fn merge(&self, key: &str, fields: Document) -> Result<bool> {
+        let mut update = fields;
+        update.insert("updatedAt", bson::DateTime::now());
+        let (filter, update) = (doc! { T::KEY_FIELD: key }, doc! { "$set": update });
+        let result = retry(&self.retry, is_transient, || self.collection.update_one(filter.clone(), update.clone(), None)).await?;
+        Ok(result.matched_count > 0)
+    }
+
+    /// Sets `fields` (and `updatedAt`) on the records keyed by one of `keys`, a thousand
+    /// keys at a time, and returns how many records changed.
+    pub async fn merge_many(&self, keys: &[String], fields: Document) -> Result<u64> {
+        let mut update = fields;
+        update.insert("updatedAt", bson::DateTime::now());
+        let update = doc! { "$set": update };
+        let mut modified = 0;
+        for chunk in keys.chunks(1000) {
+            let filter = doc! { T::KEY_FIELD: { "$in": chunk } };
+            let result = retry(&self.retry, is_transient, || self.collection.update_many(filter.clone(), update.clone(), None)).await?;
+            modified += result.modified_count;
+        }
+        Ok(modified)
+    }
+
+    /// Removes every record and returns how many were deleted.
+    pub async fn delete_all(&self) -> Result<u64> {
+        Ok(retry(&self.retry, is_transient, || self.collection.delete_many(doc! {}, None)).await?.deleted_count)
+    }
+
+    /// Removes the records whose key field is one of `keys` and returns how many were deleted.
+    pub async fn delete_by_keys(&self, keys: &[String]) -> Result<u64> {
+        let filter = doc! { T::KEY_FIELD: { "$in": keys } };
+        Ok(retry(&self.retry, is_transient, || self.collection.delete_many(filter.clone(), None)).await?.deleted_count)
+    }
+
+    /// Appends a finished run to the `load_history` collection of the same database.
+    pub async fn record_load(&self, record: &LoadRecord) -> Result<()> {
+        record_history(&self.collection, &self.retry, record, self.provenance.tenant.as_deref()).await
+    }
+
+    /// The input checksum of the most recent run recorded for this collection, if any.
+    pub async fn last_checksum(&self) -> Result<Option<String>> {
+        last_checksum(&self.collection).await
+    }
+
+    fn provenance_fields(&self, now: bson::DateTime) -> Document {
+        let mut fields = provenance_fields(&self.provenance, now);
+        if let Some(ttl) = self.staging_ttl {
+            fields.insert(EXPIRES_AT, staging::expires_at(now, ttl));
+        }
+        fields
+    }
+
+    fn insert_documents(&self, records: &[T]) -> Result<Vec<Document>> {
+        let now = bson::DateTime::now();
+        let mut documents = Vec::with_capacity(records.len());
+        for record in records {
+            let mut document = record.to_document()?;
+            document.insert("_id", self.uuids.encode(self.ids.id_for_key(&record.key())));
+            document.extend(self.provenance_fields(now));
+            document.insert("createdAt", now);
+            documents.push(document);
+        }
+        Ok(documents)
+    }
+}
+
+impl RecordStore<Airport> {
+    /// The airports within `radius_km` of the point at `latitude`, `longitude`, nearest
+    /// first, at most `limit` of them, found through the `2dsphere` index on `location`.
+    pub async fn near(&self, latitude: f64, longitude: f64, radius_km: f64, limit: Option<i64>) -> Result<Vec<NearbyAirport>> {
+        let filter = doc! {
+            "location": {
+                "$nearSphere": {
+                    "$geometry": { "type": "Point", "coordinates": [longitude, latitude] },
+                    "$maxDistance": radius_km * 1000.0,
+                },
+            },
+        };
+        let options = FindOptions::builder().limit(limit).build();
+        let documents: Vec<Document> = self.collection.find(filter, options).await?.try_collect().await?;
+        let airports = documents.into_iter().map(bson::from_document).collect::<std::result::Result<Vec<Airport>, _>>()?;
+        // The server orders by the same distance; sorting again keeps ties in code order.
+        Ok(airport::nearest(airports, latitude, longitude, None))
+    }
+}
+
+#[async_trait]
+impl<T: Record> Sink<T> for RecordStore<T> {
+    async fn insert_batch(&self, records: &[T]) -> Result<u64> {
+        insert_ordered(&self.collection, &self.retry, self.insert_documents(records)?).await
+    }
+
+    async fn insert_unordered(&self, records: &[T]) -> Result<(u64, Vec<FailedWrite<T>>)> {
+        insert_unordered(&self.collection, &self.retry, self.insert_documents(records)?, records).await
+    }
+
+    async fn upsert(&self, records: &[T]) -> Result<u64> {
+        let now = bson::DateTime::now();
+        let updates = records
+            .iter()
+            .map(|record| {
+                let mut document = record.to_document()?;
+                document.extend(self.provenance_fields(now));
+                let on_insert = doc! { "_id": self.uuids.encode(self.ids.id_for_key(&record.key())), "createdAt": now };
+                Ok(doc! { "q": { T::KEY_FIELD: record.key().as_ref() }, "u": { "$set": document, "$setOnInsert": on_insert }, "upsert": true })
+            })
+            .collect::<Result<_>>()?;
+        upsert_all(&self.collection, &self.retry, updates).await
+    }
+}
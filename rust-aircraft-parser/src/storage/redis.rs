@@ -0,0 +1,268 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, SystemTime};
+use async_trait::async_trait;
+use redis::aio::MultiplexedConnection;
+use redis::AsyncCommands;
+use serde_json::json;
+use tracing::info;
+use crate::ids::IdStrategy;
+use crate::provenance::{rfc3339, LoadRecord, Provenance};
+use crate::{Aircraft, Result};
+use super::{Storage, StoredAircraft};
+
+// Keys of the aircraft hashes and of the sets of ICAO codes sharing an IATA code.
+const AIRCRAFT_PREFIX: &str = "aircraft:";
+const IATA_PREFIX: &str = "iata:";
+
+// List of finished runs as JSON, newest first.
+const LOAD_HISTORY: &str = "load_history";
+
+// Keys asked for per SCAN round trip.
+const SCAN_COUNT: usize = 1000;
+
+/// A Redis cache of the aircraft, for lookups that cannot wait for MongoDB: each aircraft is
+/// an `aircraft:{icao}` hash of its fields and provenance, and `iata:{code}` is the set of
+/// ICAO codes with that IATA code. With a TTL every key written expires, so a cache no
+/// longer loaded empties itself.
+#[derive(Clone)]
+pub struct RedisStorage {
+    connection: MultiplexedConnection,
+    ttl: Option<Duration>,
+    ids: IdStrategy,
+    provenance: Provenance,
+}
+
+impl RedisStorage {
+    /// Connects to `url`, e.g. `redis://localhost:6379/0`.
+    pub async fn connect(url: &str) -> Result<Self> {
+        let connection = redis::Client::open(url)?.get_multiplexed_async_connection().await?;
+        info!("connected to redis");
+        Ok(RedisStorage { connection, ttl: None, ids: IdStrategy::default(), provenance: Provenance::default() })
+    }
+
+    /// Makes every key written expire `ttl` after it was last written.
+    pub fn with_ttl(mut self, ttl: Option<Duration>) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Replaces how the `id` field of written hashes is generated.
+    pub fn with_id_strategy(mut self, ids: IdStrategy) -> Self {
+        self.ids = ids;
+        self
+    }
+
+    /// Replaces the provenance stamped onto every hash this storage writes, so the aircraft
+    /// of a load can be traced to its input and rolled back with [`Storage::delete_by_load`].
+    pub fn with_provenance(mut self, provenance: Provenance) -> Self {
+        self.provenance = provenance;
+        self
+    }
+
+    // Writes every aircraft in one pipeline, moving it out of the index of its old IATA code.
+    async fn write(&self, aircrafts: &[Aircraft]) -> Result<u64> {
+        let mut connection = self.connection.clone();
+        let old_iata_codes: Vec<Option<String>> = {
+            let mut pipe = redis::pipe();
+            for aircraft in aircrafts {
+                pipe.hget(aircraft_key(&aircraft.icao_code), "iataCode");
+            }
+            pipe.query_async(&mut connection).await?
+        };
+        let now = rfc3339(SystemTime::now());
+        let mut pipe = redis::pipe();
+        for (aircraft, old_iata_code) in aircrafts.iter().zip(old_iata_codes) {
+            let key = aircraft_key(&aircraft.icao_code);
+            let mut fields = vec![
+                ("icaoCode", aircraft.icao_code.clone()),
+                ("iataCode", aircraft.iata_code.clone().unwrap_or_default()),
+                ("description", aircraft.description.clone()),
+                ("loadId", self.provenance.load_id.clone()),
+                ("updatedAt", now.clone()),
+            ];
+            fields.extend(self.provenance.source_file.clone().map(|source_file| ("sourceFile", source_file)));
+            fields.extend(self.provenance.checksum.clone().map(|checksum| ("checksum", checksum)));
+            pipe.hset_multiple(&key, &fields).ignore();
+            // Like a MongoDB `_id`, only generated when the aircraft is first written.
+            pipe.hset_nx(&key, "id", self.ids.id_for(aircraft)).ignore();
+            let old_iata_code = old_iata_code.filter(|old| !old.is_empty());
+            if let Some(old_iata_code) = old_iata_code.filter(|old| Some(old) != aircraft.iata_code.as_ref()) {
+                pipe.srem(iata_key(&old_iata_code), &aircraft.icao_code).ignore();
+            }
+            if let Some(iata_code) = &aircraft.iata_code {
+                pipe.sadd(iata_key(iata_code), &aircraft.icao_code).ignore();
+            }
+            if let Some(ttl) = self.ttl {
+                pipe.expire(&key, ttl.as_secs() as i64).ignore();
+                if let Some(iata_code) = &aircraft.iata_code {
+                    pipe.expire(iata_key(iata_code), ttl.as_secs() as i64).ignore();
+                }
+            }
+        }
+        pipe.query_async::<()>(&mut connection).await?;
+        Ok(aircrafts.len() as u64)
+    }
+
+    async fn scan(&self, pattern: &str) -> Result<Vec<String>> {
+        let mut connection = self.connection.clone();
+        let mut keys = Vec::new();
+        let mut cursor = 0u64;
+        loop {
+            let (next, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(pattern)
+                .arg("COUNT")
+                .arg(SCAN_COUNT)
+                .query_async(&mut connection)
+                .await?;
+            keys.extend(batch);
+            if next == 0 {
+                return Ok(keys);
+            }
+            cursor = next;
+        }
+    }
+
+    // The hashes at `keys` that still exist, in order.
+    async fn hashes(&self, keys: &[String]) -> Result<Vec<HashMap<String, String>>> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut pipe = redis::pipe();
+        for key in keys {
+            pipe.hgetall(key);
+        }
+        let hashes: Vec<HashMap<String, String>> = pipe.query_async(&mut self.connection.clone()).await?;
+        Ok(hashes.into_iter().filter(|hash| !hash.is_empty()).collect())
+    }
+
+    async fn aircraft_at(&self, keys: &[String]) -> Result<Vec<Aircraft>> {
+        let mut aircrafts: Vec<Aircraft> = self.hashes(keys).await?.iter().map(aircraft_from_hash).collect();
+        aircrafts.sort_by(|left, right| left.icao_code.cmp(&right.icao_code));
+        Ok(aircrafts)
+    }
+
+    // Deletes the hashes of `icao_codes` and takes them out of the IATA index.
+    async fn delete(&self, icao_codes: &[String]) -> Result<u64> {
+        let keys: Vec<String> = icao_codes.iter().map(|icao_code| aircraft_key(icao_code)).collect();
+        let hashes = self.hashes(&keys).await?;
+        let mut pipe = redis::pipe();
+        for hash in &hashes {
+            let aircraft = aircraft_from_hash(hash);
+            pipe.del(aircraft_key(&aircraft.icao_code)).ignore();
+            if let Some(iata_code) = &aircraft.iata_code {
+                pipe.srem(iata_key(iata_code), &aircraft.icao_code).ignore();
+            }
+        }
+        pipe.query_async::<()>(&mut self.connection.clone()).await?;
+        Ok(hashes.len() as u64)
+    }
+}
+
+#[async_trait]
+impl Storage for RedisStorage {
+    async fn insert_batch(&self, aircrafts: &[Aircraft]) -> Result<u64> {
+        self.write(aircrafts).await
+    }
+
+    /// The same as inserting: a hash is always replaced by the aircraft written to it.
+    async fn upsert(&self, aircrafts: &[Aircraft]) -> Result<u64> {
+        self.write(aircrafts).await
+    }
+
+    async fn find_by_icao(&self, icao_code: &str) -> Result<Option<Aircraft>> {
+        Ok(self.aircraft_at(&[aircraft_key(&icao_code.to_ascii_uppercase())]).await?.into_iter().next())
+    }
+
+    async fn find_by_iata(&self, iata_code: &str) -> Result<Vec<Aircraft>> {
+        let icao_codes: Vec<String> = self.connection.clone().smembers(iata_key(&iata_code.to_ascii_uppercase())).await?;
+        let keys: Vec<String> = icao_codes.iter().map(|icao_code| aircraft_key(icao_code)).collect();
+        self.aircraft_at(&keys).await
+    }
+
+    async fn find_all(&self) -> Result<Vec<Aircraft>> {
+        self.aircraft_at(&self.scan(&format!("{}*", AIRCRAFT_PREFIX)).await?).await
+    }
+
+    async fn find_all_with_ids(&self) -> Result<Vec<StoredAircraft>> {
+        let hashes = self.hashes(&self.scan(&format!("{}*", AIRCRAFT_PREFIX)).await?).await?;
+        let mut records: Vec<StoredAircraft> = hashes
+            .iter()
+            .map(|hash| StoredAircraft { id: hash.get("id").cloned().unwrap_or_default(), aircraft: aircraft_from_hash(hash) })
+            .collect();
+        records.sort_by(|left, right| left.aircraft.icao_code.cmp(&right.aircraft.icao_code));
+        Ok(records)
+    }
+
+    async fn delete_all(&self) -> Result<u64> {
+        let keys = self.scan(&format!("{}*", AIRCRAFT_PREFIX)).await?;
+        let indexes = self.scan(&format!("{}*", IATA_PREFIX)).await?;
+        let mut pipe = redis::pipe();
+        for key in keys.iter().chain(&indexes) {
+            pipe.del(key).ignore();
+        }
+        pipe.query_async::<()>(&mut self.connection.clone()).await?;
+        Ok(keys.len() as u64)
+    }
+
+    async fn delete_by_icao(&self, icao_codes: &[String]) -> Result<u64> {
+        self.delete(icao_codes).await
+    }
+
+    async fn delete_by_load(&self, load_id: &str) -> Result<u64> {
+        let hashes = self.hashes(&self.scan(&format!("{}*", AIRCRAFT_PREFIX)).await?).await?;
+        let icao_codes: Vec<String> = hashes
+            .iter()
+            .filter(|hash| hash.get("loadId").is_some_and(|id| id == load_id))
+            .map(|hash| aircraft_from_hash(hash).icao_code)
+            .collect();
+        self.delete(&icao_codes).await
+    }
+
+    /// Pushes the run onto the `load_history` list as a JSON object.
+    async fn record_load(&self, record: &LoadRecord) -> Result<()> {
+        let provenance = &record.provenance;
+        let entry = json!({
+            "loadId": provenance.load_id,
+            "command": record.command,
+            "sourceFile": provenance.source_file,
+            "checksum": provenance.checksum,
+            "startedAt": rfc3339(record.started_at),
+            "finishedAt": rfc3339(record.finished_at),
+            "parsed": record.parsed,
+            "written": record.written,
+            "deleted": record.deleted,
+            "rejected": record.rejected,
+            "skipped": record.skipped,
+            "interrupted": record.interrupted,
+        });
+        self.connection.clone().lpush::<_, _, ()>(LOAD_HISTORY, entry.to_string()).await?;
+        Ok(())
+    }
+
+    async fn last_checksum(&self) -> Result<Option<String>> {
+        let last: Option<String> = self.connection.clone().lindex(LOAD_HISTORY, 0).await?;
+        let last: Option<serde_json::Value> = last.map(|last| serde_json::from_str(&last)).transpose()?;
+        Ok(last.and_then(|last| last["checksum"].as_str().map(str::to_string)))
+    }
+}
+
+fn aircraft_key(icao_code: &str) -> String {
+    format!("{}{}", AIRCRAFT_PREFIX, icao_code)
+}
+
+fn iata_key(iata_code: &str) -> String {
+    format!("{}{}", IATA_PREFIX, iata_code)
+}
+
+fn aircraft_from_hash(hash: &HashMap<String, String>) -> Aircraft {
+    let field = |name: &str| hash.get(name).cloned().unwrap_or_default();
+    Aircraft {
+        icao_code: field("icaoCode"),
+        iata_code: Some(field("iataCode")).filter(|code| !code.is_empty()),
+        description: field("description"),
+        descriptions: BTreeMap::new(),
+        aliases: Vec::new(),
+    }
+}
@@ -0,0 +1,260 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::SystemTime;
+use async_trait::async_trait;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions, SqliteRow};
+use sqlx::{QueryBuilder, Row};
+use crate::ids::IdStrategy;
+use crate::provenance::{rfc3339, LoadRecord, Provenance};
+use crate::{Aircraft, Result};
+use super::{Storage, StoredAircraft, CREATE_LOAD_HISTORY, PROVENANCE_COLUMNS};
+
+// Older SQLite builds cap a statement at 999 bind parameters; each row binds nine.
+const ROWS_PER_INSERT: usize = 110;
+const CODES_PER_DELETE: usize = 900;
+
+/// A table in a local SQLite file holding [`Aircraft`] rows, with a unique index on the ICAO code.
+#[derive(Clone, Debug)]
+pub struct SqliteStorage {
+    pool: SqlitePool,
+    table: String,
+    ids: IdStrategy,
+    provenance: Provenance,
+}
+
+impl SqliteStorage {
+    /// Opens (creating if needed) the database file at `path` and creates `table` in it.
+    pub async fn open(path: &Path, table: &str) -> Result<Self> {
+        let options = SqliteConnectOptions::new().filename(path).create_if_missing(true);
+        // SQLite serializes writers anyway, and a single connection guarantees every
+        // statement sees the unique index created below.
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await?;
+        let storage = SqliteStorage {
+            pool,
+            table: table.to_string(),
+            ids: IdStrategy::default(),
+            provenance: Provenance::default(),
+        };
+        storage.create_table().await?;
+        Ok(storage)
+    }
+
+    /// Replaces how the `id` of newly inserted rows is generated.
+    pub fn with_id_strategy(mut self, ids: IdStrategy) -> Self {
+        self.ids = ids;
+        self
+    }
+
+    /// Replaces the provenance stamped onto every row this storage writes, so the rows of
+    /// a load can be traced to its input and rolled back with [`Storage::delete_by_load`].
+    pub fn with_provenance(mut self, provenance: Provenance) -> Self {
+        self.provenance = provenance;
+        self
+    }
+
+    async fn create_table(&self) -> Result<()> {
+        let statement = format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                id TEXT NOT NULL PRIMARY KEY,
+                icao_code TEXT NOT NULL,
+                iata_code TEXT NOT NULL,
+                description TEXT NOT NULL,
+                load_id TEXT,
+                source_file TEXT,
+                checksum TEXT,
+                created_at TEXT,
+                updated_at TEXT
+            )",
+            quote_identifier(&self.table)
+        );
+        sqlx::query(&statement).execute(&self.pool).await?;
+        let index = format!(
+            "CREATE UNIQUE INDEX IF NOT EXISTS {} ON {} (icao_code)",
+            quote_identifier(&format!("{}_icao_code", self.table)),
+            quote_identifier(&self.table)
+        );
+        sqlx::query(&index).execute(&self.pool).await?;
+        // Tables created before records carried provenance lack its columns.
+        let columns: Vec<String> = sqlx::query_scalar("SELECT name FROM pragma_table_info(?)")
+            .bind(&self.table)
+            .fetch_all(&self.pool)
+            .await?;
+        for column in PROVENANCE_COLUMNS.iter().filter(|column| !columns.iter().any(|existing| existing == *column)) {
+            let alter = format!("ALTER TABLE {} ADD COLUMN {} TEXT", quote_identifier(&self.table), column);
+            sqlx::query(&alter).execute(&self.pool).await?;
+        }
+        sqlx::query(CREATE_LOAD_HISTORY).execute(&self.pool).await?;
+        // Load histories created before runs could be interrupted lack that column.
+        let columns: Vec<String> = sqlx::query_scalar("SELECT name FROM pragma_table_info('load_history')").fetch_all(&self.pool).await?;
+        if !columns.iter().any(|column| column == "interrupted") {
+            sqlx::query("ALTER TABLE load_history ADD COLUMN interrupted BOOLEAN NOT NULL DEFAULT FALSE").execute(&self.pool).await?;
+        }
+        Ok(())
+    }
+
+    async fn write(&self, aircrafts: &[Aircraft], on_conflict: &str) -> Result<u64> {
+        let mut written = 0;
+        for chunk in aircrafts.chunks(ROWS_PER_INSERT) {
+            let mut builder = QueryBuilder::new(format!(
+                "INSERT INTO {} (id, icao_code, iata_code, description, load_id, source_file, checksum, created_at, updated_at) ",
+                quote_identifier(&self.table)
+            ));
+            let now = rfc3339(SystemTime::now());
+            builder.push_values(chunk, |mut row, aircraft| {
+                row.push_bind(self.ids.id_for(aircraft))
+                    .push_bind(&aircraft.icao_code)
+                    // The column predates missing codes being None and keeps them empty.
+                    .push_bind(aircraft.iata_code.as_deref().unwrap_or_default())
+                    .push_bind(&aircraft.description)
+                    .push_bind(&self.provenance.load_id)
+                    .push_bind(&self.provenance.source_file)
+                    .push_bind(&self.provenance.checksum)
+                    .push_bind(&now)
+                    .push_bind(&now);
+            });
+            builder.push(on_conflict);
+            let result = builder.build().execute(&self.pool).await?;
+            written += result.rows_affected();
+        }
+        Ok(written)
+    }
+
+    async fn select(&self, condition: &str, value: Option<&str>) -> Result<Vec<Aircraft>> {
+        let statement = format!(
+            "SELECT icao_code, iata_code, description FROM {} {} ORDER BY icao_code",
+            quote_identifier(&self.table),
+            condition
+        );
+        let mut query = sqlx::query(&statement);
+        if let Some(value) = value {
+            query = query.bind(value);
+        }
+        let rows = query.fetch_all(&self.pool).await?;
+        Ok(rows.iter().map(aircraft_from_row).collect())
+    }
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn insert_batch(&self, aircrafts: &[Aircraft]) -> Result<u64> {
+        self.write(aircrafts, "").await
+    }
+
+    async fn upsert(&self, aircrafts: &[Aircraft]) -> Result<u64> {
+        self.write(
+            aircrafts,
+            " ON CONFLICT (icao_code) DO UPDATE SET
+                iata_code = excluded.iata_code,
+                description = excluded.description,
+                load_id = excluded.load_id,
+                source_file = excluded.source_file,
+                checksum = excluded.checksum,
+                updated_at = excluded.updated_at",
+        )
+        .await
+    }
+
+    async fn find_by_icao(&self, icao_code: &str) -> Result<Option<Aircraft>> {
+        Ok(self.select("WHERE icao_code = UPPER(?)", Some(icao_code)).await?.into_iter().next())
+    }
+
+    async fn find_by_iata(&self, iata_code: &str) -> Result<Vec<Aircraft>> {
+        self.select("WHERE iata_code = UPPER(?)", Some(iata_code)).await
+    }
+
+    async fn find_all(&self) -> Result<Vec<Aircraft>> {
+        self.select("", None).await
+    }
+
+    async fn find_all_with_ids(&self) -> Result<Vec<StoredAircraft>> {
+        let statement = format!(
+            "SELECT id, icao_code, iata_code, description FROM {} ORDER BY icao_code",
+            quote_identifier(&self.table)
+        );
+        let rows = sqlx::query(&statement).fetch_all(&self.pool).await?;
+        Ok(rows
+            .iter()
+            .map(|row| StoredAircraft { id: row.get("id"), aircraft: aircraft_from_row(row) })
+            .collect())
+    }
+
+    async fn delete_all(&self) -> Result<u64> {
+        let statement = format!("DELETE FROM {}", quote_identifier(&self.table));
+        let result = sqlx::query(&statement).execute(&self.pool).await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn record_load(&self, record: &LoadRecord) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO load_history
+                (load_id, command, collection, source_file, checksum, started_at, finished_at, parsed, written, deleted, rejected, skipped, interrupted)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&record.provenance.load_id)
+        .bind(&record.command)
+        .bind(&self.table)
+        .bind(&record.provenance.source_file)
+        .bind(&record.provenance.checksum)
+        .bind(rfc3339(record.started_at))
+        .bind(rfc3339(record.finished_at))
+        .bind(record.parsed as i64)
+        .bind(record.written as i64)
+        .bind(record.deleted as i64)
+        .bind(record.rejected as i64)
+        .bind(record.skipped as i64)
+        .bind(record.interrupted)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn last_checksum(&self) -> Result<Option<String>> {
+        let checksum: Option<Option<String>> = sqlx::query_scalar(
+            "SELECT checksum FROM load_history WHERE collection = ? ORDER BY finished_at DESC LIMIT 1",
+        )
+        .bind(&self.table)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(checksum.flatten())
+    }
+
+    async fn delete_by_load(&self, load_id: &str) -> Result<u64> {
+        let statement = format!("DELETE FROM {} WHERE load_id = ?", quote_identifier(&self.table));
+        let result = sqlx::query(&statement).bind(load_id).execute(&self.pool).await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn delete_by_icao(&self, icao_codes: &[String]) -> Result<u64> {
+        let mut deleted = 0;
+        for chunk in icao_codes.chunks(CODES_PER_DELETE) {
+            let mut builder = QueryBuilder::new(format!(
+                "DELETE FROM {} WHERE icao_code IN (",
+                quote_identifier(&self.table)
+            ));
+            let mut separated = builder.separated(", ");
+            for icao_code in chunk {
+                separated.push_bind(icao_code);
+            }
+            builder.push(")");
+            deleted += builder.build().execute(&self.pool).await?.rows_affected();
+        }
+        Ok(deleted)
+    }
+}
+
+fn aircraft_from_row(row: &SqliteRow) -> Aircraft {
+    Aircraft {
+        icao_code: row.get("icao_code"),
+        iata_code: Some(row.get::<String, _>("iata_code")).filter(|code| !code.is_empty()),
+        description: row.get("description"),
+        descriptions: BTreeMap::new(),
+        aliases: Vec::new(),
+    }
+}
+
+fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
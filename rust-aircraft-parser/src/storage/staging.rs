@@ -0,0 +1,81 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use mongodb::bson::{self, doc, Document};
+use mongodb::options::IndexOptions;
+use mongodb::{Collection, Database, IndexModel};
+use tracing::info;
+use crate::Result;
+
+// Field the documents of staging collections expire at, taken off once a collection goes live.
+pub(super) const EXPIRES_AT: &str = "expiresAt";
+
+// Name of the TTL index on `expiresAt`.
+const TTL_INDEX: &str = "expiresAt_ttl";
+
+/// How long the documents of a staging collection are kept after they were written unless
+/// configured otherwise, for the load writing them to finish.
+pub const DEFAULT_STAGING_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// What [`AircraftStore::gc`](super::AircraftStore::gc) cleaned up, or would have.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Garbage {
+    /// Staging collections dropped as abandoned.
+    pub dropped: Vec<String>,
+    /// Staging collections still being written, kept.
+    pub kept: Vec<String>,
+    /// Expired documents deleted from the kept collections.
+    pub expired: u64,
+}
+
+// `ttl` after `now`.
+pub(super) fn expires_at(now: bson::DateTime, ttl: Duration) -> bson::DateTime {
+    bson::DateTime::from_millis(now.timestamp_millis().saturating_add(ttl.as_millis().min(i64::MAX as u128) as i64))
+}
+
+// Creates the TTL index the server removes the documents of `collection` through once they
+// expire.
+pub(super) async fn ensure_ttl_index(collection: &Collection<Document>) -> Result<()> {
+    let options = IndexOptions::builder().name(TTL_INDEX.to_string()).expire_after(Duration::ZERO).build();
+    collection.create_index(IndexModel::builder().keys(doc! { EXPIRES_AT: 1 }).options(options).build(), None).await?;
+    Ok(())
+}
+
+// Drops the TTL index of `collection` and takes the expiry off its documents, before it
+// replaces a live collection.
+pub(super) async fn keep(collection: &Collection<Document>) -> Result<()> {
+    if collection.list_index_names().await?.iter().any(|name| name == TTL_INDEX) {
+        collection.drop_index(TTL_INDEX, None).await?;
+    }
+    collection.update_many(doc! { EXPIRES_AT: { "$exists": true } }, doc! { "$unset": { EXPIRES_AT: "" } }, None).await?;
+    Ok(())
+}
+
+// Drops the staging collections of `database` that are abandoned: created more than `ttl`
+// ago, as their `_staging_<unix time>` suffix tells, and holding no document that has not
+// expired. The expired documents of the others are deleted without waiting for the server's
+// TTL monitor. With `dry_run` nothing is deleted.
+pub(super) async fn collect(database: &Database, ttl: Duration, dry_run: bool) -> Result<Garbage> {
+    let now = bson::DateTime::now();
+    let mut garbage = Garbage::default();
+    for name in database.list_collection_names(None).await? {
+        let Some((_, created)) = name.rsplit_once("_staging_") else { continue };
+        let collection = database.collection::<Document>(&name);
+        let created = created.parse().map_or(UNIX_EPOCH, |seconds| UNIX_EPOCH + Duration::from_secs(seconds));
+        let recent = SystemTime::now().duration_since(created).unwrap_or_default() < ttl;
+        let unexpired = collection.count_documents(doc! { EXPIRES_AT: { "$gt": now } }, None).await?;
+        if recent || unexpired > 0 {
+            let expired = doc! { EXPIRES_AT: { "$lte": now } };
+            garbage.expired += match dry_run {
+                true => collection.count_documents(expired, None).await?,
+                false => collection.delete_many(expired, None).await?.deleted_count,
+            };
+            garbage.kept.push(name);
+            continue;
+        }
+        if !dry_run {
+            collection.drop(None).await?;
+            info!(collection = %name, "dropped abandoned staging collection");
+        }
+        garbage.dropped.push(name);
+    }
+    Ok(garbage)
+}
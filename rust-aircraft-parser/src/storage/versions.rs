@@ -0,0 +1,89 @@
+use std::collections::{BTreeMap, HashMap};
+use futures::TryStreamExt;
+use mongodb::bson::{self, doc, Bson, Document};
+use mongodb::options::FindOptions;
+use mongodb::{Collection, IndexModel};
+use crate::fields::FieldNames;
+use crate::provenance::Provenance;
+use crate::{Aircraft, Result};
+
+// Collection every version of the aircraft is kept in, next to the aircraft collection.
+pub(super) const AIRCRAFT_HISTORY: &str = "aircraft_history";
+
+/// The versions of the aircraft of one collection in `aircraft_history`. A write opens a
+/// version of each aircraft it changes, valid from then on, and closes the version it
+/// replaces by setting its `validTo`; a delete only closes it. Each version carries the
+/// aircraft fields under their stored names, the collection and the load it came from.
+pub(super) struct Versions<'a> {
+    pub(super) history: Collection<Document>,
+    pub(super) collection: &'a str,
+    pub(super) fields: &'a FieldNames,
+}
+
+impl Versions<'_> {
+    /// Opens a version of every aircraft that differs from its open one, closing that one,
+    /// and returns how many were opened. Of an ICAO code listed twice the last entry counts.
+    pub(super) async fn record(&self, aircrafts: &[Aircraft], provenance: &Provenance) -> Result<u64> {
+        let latest: BTreeMap<&str, &Aircraft> = aircrafts.iter().map(|aircraft| (aircraft.icao_code.as_str(), aircraft)).collect();
+        let codes: Vec<&str> = latest.keys().copied().collect();
+        let open = self.open(&codes).await?;
+        let changed: Vec<&Aircraft> = latest.into_values().filter(|aircraft| open.get(&aircraft.icao_code) != Some(*aircraft)).collect();
+        if changed.is_empty() {
+            return Ok(0);
+        }
+        let now = bson::DateTime::now();
+        let codes: Vec<&str> = changed.iter().map(|aircraft| aircraft.icao_code.as_str()).collect();
+        self.close(Some(&codes), now).await?;
+        let documents = changed.iter().map(|aircraft| {
+            let mut document = doc! { "collection": self.collection };
+            document.extend(self.fields.document(aircraft));
+            document.extend(doc! {
+                "validFrom": now,
+                "validTo": Bson::Null,
+                "loadId": &provenance.load_id,
+                "sourceFile": &provenance.source_file,
+                "checksum": &provenance.checksum,
+            });
+            document
+        });
+        let result = self.history.insert_many(documents, None).await?;
+        Ok(result.inserted_ids.len() as u64)
+    }
+
+    /// Closes the open versions of `icao_codes`, or of every aircraft of the collection.
+    pub(super) async fn close(&self, icao_codes: Option<&[&str]>, at: bson::DateTime) -> Result<u64> {
+        let mut filter = doc! { "collection": self.collection, "validTo": Bson::Null };
+        if let Some(icao_codes) = icao_codes {
+            filter.insert(&self.fields.icao_code, doc! { "$in": icao_codes });
+        }
+        let result = self.history.update_many(filter, doc! { "$set": { "validTo": at } }, None).await?;
+        Ok(result.modified_count)
+    }
+
+    /// The aircraft matching `filter` as their versions stood at `at`, by ICAO code.
+    pub(super) async fn at(&self, mut filter: Document, at: bson::DateTime) -> Result<Vec<Aircraft>> {
+        filter.insert("collection", self.collection);
+        filter.insert("validFrom", doc! { "$lte": at });
+        filter.insert("$or", vec![doc! { "validTo": Bson::Null }, doc! { "validTo": { "$gt": at } }]);
+        let options = FindOptions::builder().sort(doc! { &self.fields.icao_code: 1 }).build();
+        let documents: Vec<Document> = self.history.find(filter, options).await?.try_collect().await?;
+        documents.iter().map(|document| self.fields.aircraft(document)).collect()
+    }
+
+    /// Creates the index the open versions of an aircraft and its versions at a time are
+    /// found through.
+    pub(super) async fn ensure_index(&self) -> Result<()> {
+        let index = IndexModel::builder().keys(doc! { "collection": 1, &self.fields.icao_code: 1, "validFrom": 1 }).build();
+        self.history.create_index(index, None).await?;
+        Ok(())
+    }
+
+    async fn open(&self, icao_codes: &[&str]) -> Result<HashMap<String, Aircraft>> {
+        let filter = doc! { "collection": self.collection, &self.fields.icao_code: { "$in": icao_codes }, "validTo": Bson::Null };
+        let documents: Vec<Document> = self.history.find(filter, None).await?.try_collect().await?;
+        documents
+            .iter()
+            .map(|document| self.fields.aircraft(document).map(|aircraft| (aircraft.icao_code.clone(), aircraft)))
+            .collect()
+    }
+}
@@ -0,0 +1,319 @@
+//! Bringing a [`Storage`] backend in line with an input file by writing only the differences.
+
+use std::collections::{BTreeMap, HashSet};
+use std::time::Instant;
+use serde::Deserialize;
+use tracing::{debug, info, warn};
+use crate::feed::{Change, Operation};
+use crate::load::DEFAULT_BATCH_SIZE;
+use crate::record::Record;
+use crate::validate::{check, Rejection};
+use crate::{Aircraft, Result, Storage};
+
+/// How [`sync`] reconciles the backend with the input.
+#[derive(Clone, Debug)]
+pub struct SyncOptions {
+    /// Maximum number of aircraft per write.
+    pub batch_size: usize,
+    /// Delete stored records whose ICAO code does not appear in the input.
+    pub prune: bool,
+    /// Mark stored records whose ICAO code does not appear in the input inactive instead,
+    /// for data that still refers to them.
+    pub retire: bool,
+    /// Skip entries that fail to parse instead of aborting the sync.
+    pub lenient: bool,
+    /// How each field of a stored aircraft is updated from the input.
+    pub merge: MergePolicy,
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        SyncOptions { batch_size: DEFAULT_BATCH_SIZE, prune: false, retire: false, lenient: false, merge: MergePolicy::default() }
+    }
+}
+
+/// What a sync does with a field of a stored aircraft when the input has another value.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum FieldMerge {
+    /// Take the input's value.
+    #[default]
+    Overwrite,
+    /// Keep the stored value, e.g. a manually curated description.
+    KeepExisting,
+    /// Take the input's value only where the stored one is empty.
+    FillEmptyOnly,
+}
+
+impl FieldMerge {
+    // Empty is the field's default: a blank description or a missing IATA code.
+    fn merge<T: Clone + Default + PartialEq>(self, stored: &T, input: &T) -> T {
+        match self {
+            FieldMerge::Overwrite => input,
+            FieldMerge::KeepExisting => stored,
+            FieldMerge::FillEmptyOnly if *stored == T::default() => input,
+            FieldMerge::FillEmptyOnly => stored,
+        }
+        .clone()
+    }
+}
+
+/// How [`sync`] updates each field of the stored aircraft, all overwritten by default.
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct MergePolicy {
+    pub iata_code: FieldMerge,
+    pub description: FieldMerge,
+}
+
+impl MergePolicy {
+    /// The `stored` aircraft updated from `input`, the ICAO code they share.
+    pub fn merge(&self, stored: &Aircraft, input: &Aircraft) -> Aircraft {
+        Aircraft {
+            icao_code: stored.icao_code.clone(),
+            iata_code: self.iata_code.merge(&stored.iata_code, &input.iata_code),
+            description: self.description.merge(&stored.description, &input.description),
+            // Translations are only ever added to: the input's replace the stored ones of their locale.
+            descriptions: stored.descriptions.clone().into_iter().chain(input.descriptions.clone()).collect(),
+            // So are aliases, in the order they were first listed.
+            aliases: stored.aliases.iter().chain(&input.aliases).fold(Vec::new(), |mut aliases, alias| {
+                if !aliases.contains(alias) {
+                    aliases.push(alias.clone());
+                }
+                aliases
+            }),
+        }
+    }
+}
+
+/// What a [`sync`] changed, by ICAO code.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SyncSummary {
+    /// Aircraft read from the input.
+    pub parsed: u64,
+    /// Aircraft that were not stored yet and got inserted.
+    pub added: Vec<String>,
+    /// Active stored aircraft whose IATA code or description changed.
+    pub updated: Vec<String>,
+    /// Stored aircraft missing from the input that were deleted, with `prune` set.
+    pub deleted: Vec<String>,
+    /// Stored aircraft missing from the input that were marked inactive, with `retire` set.
+    pub retired: Vec<String>,
+    /// Inactive aircraft listed in the input again, which were made active.
+    pub reactivated: Vec<String>,
+    /// Stored aircraft identical to the input.
+    pub unchanged: u64,
+    /// Aircraft that failed validation and were left alone.
+    pub rejected: Vec<Rejection>,
+    /// Messages for entries skipped because they failed to parse, in lenient mode.
+    pub skipped: Vec<String>,
+    /// Every write made, with the aircraft before and after it, for the change feed.
+    pub changes: Vec<Change>,
+}
+
+/// Compares `aircrafts` with everything in `storage`, keyed on ICAO code, then inserts the
+/// new aircraft, upserts the changed ones as merged by `options.merge` and, when
+/// `options.prune` is set, deletes the stored ones the input no longer lists, or marks them
+/// inactive when `options.retire` is. Inactive aircraft the input lists again are upserted,
+/// which makes them active. The whole input is read and compared before the first write, so
+/// a parse error (outside lenient mode) leaves the backend untouched. When an ICAO code
+/// appears more than once in the input the last entry wins. Aircraft failing validation are
+/// neither written nor pruned.
+pub async fn sync(
+    storage: &dyn Storage,
+    aircrafts: impl Iterator<Item = Result<Aircraft>>,
+    options: &SyncOptions,
+) -> Result<SyncSummary> {
+    let started = Instant::now();
+    let mut summary = SyncSummary::default();
+    let mut desired: BTreeMap<String, Aircraft> = BTreeMap::new();
+    let mut listed: HashSet<String> = HashSet::new();
+    for aircraft in aircrafts {
+        let aircraft = match aircraft {
+            Ok(aircraft) => aircraft,
+            Err(error) if options.lenient => {
+                warn!(%error, "skipped unparseable record");
+                summary.skipped.push(error.to_string());
+                continue;
+            }
+            Err(error) => return Err(error),
+        };
+        summary.parsed += 1;
+        listed.insert(aircraft.icao_code.clone());
+        match check(aircraft) {
+            Ok(aircraft) => {
+                if let Some(previous) = desired.insert(aircraft.icao_code.clone(), aircraft) {
+                    warn!(icao_code = %previous.icao_code, "duplicate icao code, keeping the last entry");
+                }
+            }
+            Err(rejection) => {
+                warn!(key = %rejection.record.key(), reasons = ?rejection.reasons, "rejected record");
+                summary.rejected.push(rejection);
+            }
+        }
+    }
+
+    let retired = storage.retired().await?;
+    let mut stored: BTreeMap<String, Aircraft> = storage
+        .find_all()
+        .await?
+        .into_iter()
+        .map(|aircraft| (aircraft.icao_code.clone(), aircraft))
+        .collect();
+    let mut additions = Vec::new();
+    let mut updates = Vec::new();
+    for (icao_code, aircraft) in desired {
+        let Some(existing) = stored.remove(&icao_code) else {
+            summary.changes.push(Change { operation: Operation::Add, icao_code, old: None, new: Some(aircraft.clone()) });
+            additions.push(aircraft);
+            continue;
+        };
+        let merged = options.merge.merge(&existing, &aircraft);
+        let operation = if retired.contains(&icao_code) {
+            debug!(%icao_code, "reactivated");
+            summary.reactivated.push(icao_code.clone());
+            Operation::Reactivate
+        } else if merged != existing {
+            debug!(%icao_code, "changed");
+            Operation::Update
+        } else {
+            summary.unchanged += 1;
+            continue;
+        };
+        summary.changes.push(Change { operation, icao_code, old: Some(existing), new: Some(merged.clone()) });
+        updates.push(merged);
+    }
+    let missing = stored.into_values().filter(|aircraft| !listed.contains(&aircraft.icao_code));
+    if options.prune || options.retire {
+        let operation = if options.prune { Operation::Delete } else { Operation::Retire };
+        for aircraft in missing.filter(|aircraft| options.prune || !retired.contains(&aircraft.icao_code)) {
+            summary.changes.push(Change { operation, icao_code: aircraft.icao_code.clone(), old: Some(aircraft), new: None });
+        }
+    }
+    let changed = |operation: Operation| -> Vec<String> {
+        summary.changes.iter().filter(|change| change.operation == operation).map(|change| change.icao_code.clone()).collect()
+    };
+    summary.deleted = changed(Operation::Delete);
+    summary.retired = changed(Operation::Retire);
+    // Reactivated aircraft are upserted with the updated ones but only counted as reactivated.
+    summary.updated = changed(Operation::Update);
+
+    let batch_size = options.batch_size.max(1);
+    for batch in additions.chunks(batch_size) {
+        storage.insert_batch(batch).await?;
+    }
+    for batch in updates.chunks(batch_size) {
+        storage.upsert(batch).await?;
+    }
+    for batch in summary.deleted.chunks(batch_size) {
+        storage.delete_by_icao(batch).await?;
+    }
+    for batch in summary.retired.chunks(batch_size) {
+        storage.retire(batch).await?;
+    }
+    summary.added = additions.into_iter().map(|aircraft| aircraft.icao_code).collect();
+    info!(
+        parsed = summary.parsed,
+        added = summary.added.len(),
+        updated = summary.updated.len(),
+        deleted = summary.deleted.len(),
+        retired = summary.retired.len(),
+        reactivated = summary.reactivated.len(),
+        unchanged = summary.unchanged,
+        rejected = summary.rejected.len(),
+        skipped = summary.skipped.len(),
+        elapsed_ms = started.elapsed().as_millis() as u64,
+        "sync finished"
+    );
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::InMemoryStorage;
+    use crate::testing::aircraft;
+    use crate::Error;
+
+    fn ok(aircraft: Vec<Aircraft>) -> impl Iterator<Item = Result<Aircraft>> {
+        aircraft.into_iter().map(Ok)
+    }
+
+    fn stored() -> InMemoryStorage {
+        InMemoryStorage::with_aircraft([aircraft("A320").iata("320").build(), aircraft("B738").iata("738").build(), aircraft("MD11").build()])
+    }
+
+    #[tokio::test]
+    async fn adds_updates_and_prunes_by_icao_code() {
+        let storage = stored();
+        let input = vec![aircraft("A320").iata("320").build(), aircraft("B738").iata("73H").build(), aircraft("E175").build()];
+        let options = SyncOptions { prune: true, ..SyncOptions::default() };
+        let summary = sync(&storage, ok(input.clone()), &options).await.unwrap();
+        assert_eq!(summary.added, ["E175"]);
+        assert_eq!(summary.updated, ["B738"]);
+        assert_eq!(summary.deleted, ["MD11"]);
+        assert_eq!(summary.unchanged, 1);
+        assert_eq!(storage.find_all().await.unwrap(), input);
+    }
+
+    #[tokio::test]
+    async fn keeps_aircraft_missing_from_the_input_without_prune() {
+        let storage = stored();
+        let summary = sync(&storage, ok(vec![aircraft("A320").iata("320").build()]), &SyncOptions::default()).await.unwrap();
+        assert!(summary.deleted.is_empty());
+        assert_eq!(storage.find_all().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn a_parse_error_leaves_the_backend_untouched() {
+        let storage = stored();
+        let input = vec![Ok(aircraft("E175").build()), Err(Error::InvalidInput("entry 2".to_string()))];
+        let options = SyncOptions { prune: true, ..SyncOptions::default() };
+        assert!(sync(&storage, input.into_iter(), &options).await.is_err());
+        assert_eq!(storage.find_all().await.unwrap(), stored().find_all().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn lenient_skips_what_fails_to_parse() {
+        let storage = stored();
+        let input = vec![Err(Error::InvalidInput("entry 1".to_string())), Ok(aircraft("E175").build())];
+        let options = SyncOptions { lenient: true, ..SyncOptions::default() };
+        let summary = sync(&storage, input.into_iter(), &options).await.unwrap();
+        assert_eq!(summary.skipped.len(), 1);
+        assert_eq!(summary.added, ["E175"]);
+    }
+
+    #[tokio::test]
+    async fn merges_each_field_by_its_policy() {
+        let storage = InMemoryStorage::with_aircraft([
+            aircraft("B738").description("Curated").build(),
+            aircraft("A320").iata("320").description("Curated").build(),
+        ]);
+        let input = vec![aircraft("B738").iata("738").description("Boeing").build(), aircraft("A320").iata("32A").description("Airbus").build()];
+        let merge = MergePolicy { iata_code: FieldMerge::FillEmptyOnly, description: FieldMerge::KeepExisting };
+        let summary = sync(&storage, ok(input), &SyncOptions { merge, ..SyncOptions::default() }).await.unwrap();
+        assert_eq!(summary.updated, ["B738"]);
+        assert_eq!(summary.unchanged, 1);
+        let stored = storage.find_all().await.unwrap();
+        assert_eq!(stored, [aircraft("A320").iata("320").description("Curated").build(), aircraft("B738").iata("738").description("Curated").build()]);
+    }
+
+    #[tokio::test]
+    async fn retires_aircraft_missing_from_the_input_and_reactivates_them_once() {
+        let storage = stored();
+        let options = SyncOptions { retire: true, ..SyncOptions::default() };
+        let input = vec![aircraft("A320").iata("320").build(), aircraft("B738").iata("738").build()];
+        let summary = sync(&storage, ok(input.clone()), &options).await.unwrap();
+        assert_eq!(summary.retired, ["MD11"]);
+        assert!(summary.deleted.is_empty());
+        assert_eq!(storage.retired().await.unwrap(), HashSet::from(["MD11".to_string()]));
+
+        let summary = sync(&storage, ok(input.clone()), &options).await.unwrap();
+        assert!(summary.retired.is_empty(), "already retired");
+
+        let summary = sync(&storage, ok(vec![aircraft("MD11").build()]), &SyncOptions::default()).await.unwrap();
+        assert_eq!(summary.reactivated, ["MD11"]);
+        assert!(summary.updated.is_empty());
+        assert!(storage.retired().await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,63 @@
+//! Following the writes to an aircraft collection as they happen, for `tail`, through a
+//! MongoDB change stream. Change streams need a replica set or sharded cluster.
+
+use futures::{Stream, StreamExt};
+use mongodb::bson::{self, Bson, Document};
+use mongodb::change_stream::event::{ChangeStreamEvent, OperationType};
+use mongodb::options::{ChangeStreamOptions, FullDocumentType};
+use serde::Serialize;
+use tracing::info;
+use crate::fields::FieldNames;
+use crate::{Aircraft, AircraftStore, Result};
+
+/// One write to the collection.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Change {
+    /// `insert`, `update`, `replace`, `delete`, or what else the server reported, e.g. `drop`.
+    pub operation: String,
+    /// `_id` of the document written.
+    pub id: Option<Bson>,
+    /// ICAO code of the aircraft written; unknown for deletes, whose document is gone.
+    pub icao_code: Option<String>,
+    /// The aircraft as it is stored after the write, if it still is.
+    pub aircraft: Option<Aircraft>,
+    /// When the server applied the write.
+    pub at: Option<bson::DateTime>,
+}
+
+impl Change {
+    fn new(event: ChangeStreamEvent<Document>, fields: &FieldNames) -> Self {
+        let operation = match &event.operation_type {
+            OperationType::Insert => "insert".to_string(),
+            OperationType::Update => "update".to_string(),
+            OperationType::Replace => "replace".to_string(),
+            OperationType::Delete => "delete".to_string(),
+            OperationType::Drop => "drop".to_string(),
+            OperationType::Rename => "rename".to_string(),
+            OperationType::DropDatabase => "dropDatabase".to_string(),
+            OperationType::Invalidate => "invalidate".to_string(),
+            OperationType::Other(other) => other.clone(),
+            other => format!("{:?}", other),
+        };
+        let id = event.document_key.as_ref().and_then(|key| key.get("_id")).cloned();
+        let aircraft = event.full_document.as_ref().and_then(|document| fields.aircraft(document).ok());
+        Change {
+            operation,
+            id,
+            icao_code: aircraft.as_ref().map(|aircraft| aircraft.icao_code.clone()),
+            aircraft,
+            at: event.wall_time,
+        }
+    }
+}
+
+/// Every write to the store's collection from now on, updates carrying the whole document
+/// as it stands after them. The stream ends only if the collection is dropped or renamed.
+pub async fn changes(store: &AircraftStore) -> Result<impl Stream<Item = Result<Change>>> {
+    let options = ChangeStreamOptions::builder().full_document(Some(FullDocumentType::UpdateLookup)).build();
+    let stream = store.collection().watch(None, options).await?;
+    info!(collection = %store.collection().namespace(), "following changes");
+    let fields = store.field_names().clone();
+    Ok(stream.map(move |event| Ok(Change::new(event?, &fields))))
+}
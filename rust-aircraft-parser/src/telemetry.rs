@@ -0,0 +1,72 @@
+use std::env;
+
+// Traces are exported when one of these standard variables names an OTLP/HTTP collector,
+// e.g. http://localhost:4318 for Jaeger or Tempo. The exporter reads the other
+// OTEL_EXPORTER_OTLP_* variables, such as headers and timeouts, itself.
+const ENDPOINTS: [&str; 2] = ["OTEL_EXPORTER_OTLP_ENDPOINT", "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT"];
+
+fn configured() -> bool {
+    let disabled = env::var("OTEL_SDK_DISABLED").is_ok_and(|value| value.eq_ignore_ascii_case("true"));
+    !disabled && ENDPOINTS.iter().any(|name| env::var_os(name).is_some())
+}
+
+/// The trace export set up by [`layer`], flushing the spans not yet sent when dropped.
+pub struct Telemetry {
+    #[cfg(feature = "otel")]
+    provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+    problem: Option<String>,
+}
+
+impl Telemetry {
+    /// Why traces aren't exported although the environment asks for it, to be logged once
+    /// logging is set up.
+    pub fn problem(&self) -> Option<&str> {
+        self.problem.as_deref()
+    }
+}
+
+/// A layer sending spans to the OTLP collector the environment names, if any.
+#[cfg(feature = "otel")]
+pub fn layer<S>() -> (Option<impl tracing_subscriber::Layer<S>>, Telemetry)
+where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+{
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_otlp::SpanExporter;
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+    use opentelemetry_sdk::Resource;
+
+    if !configured() {
+        return (None, Telemetry { provider: None, problem: None });
+    }
+    let exporter = match SpanExporter::builder().with_http().build() {
+        Ok(exporter) => exporter,
+        Err(error) => return (None, Telemetry { provider: None, problem: Some(format!("cannot export traces: {}", error)) }),
+    };
+    // OTEL_SERVICE_NAME and OTEL_RESOURCE_ATTRIBUTES are read by the default resource.
+    let mut resource = Resource::builder();
+    if env::var_os("OTEL_SERVICE_NAME").is_none() {
+        resource = resource.with_service_name(env!("CARGO_PKG_NAME"));
+    }
+    let provider = SdkTracerProvider::builder().with_batch_exporter(exporter).with_resource(resource.build()).build();
+    let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer(env!("CARGO_PKG_NAME")));
+    (Some(layer), Telemetry { provider: Some(provider), problem: None })
+}
+
+/// Without the otel feature nothing is exported.
+#[cfg(not(feature = "otel"))]
+pub fn layer() -> (Option<tracing_subscriber::layer::Identity>, Telemetry) {
+    let problem = configured().then(|| format!("{} is set, but traces are only exported with the otel feature", ENDPOINTS.join(" or ")));
+    (None, Telemetry { problem })
+}
+
+#[cfg(feature = "otel")]
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if let Some(provider) = self.provider.take() {
+            if let Err(error) = provider.shutdown() {
+                eprintln!("warning: cannot flush traces: {}", error);
+            }
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! Fixtures for tests of code built on this crate, with the `testing` feature: a builder
+//! of [`Aircraft`] and a sample corpus to load into an
+//! [`InMemoryStorage`](crate::storage::InMemoryStorage).
+
+use std::collections::BTreeMap;
+use crate::Aircraft;
+
+/// A JSON array of aircraft in the input layout, covering the cases loads and syncs treat
+/// apart: IATA codes shared by several types (`E75`), missing as an empty string, `null` or
+/// by leaving the field out, and a translated description.
+pub const SAMPLE_JSON: &str = include_str!("../fixtures/aircraft.json");
+
+/// [`SAMPLE_JSON`] parsed, in the order given.
+pub fn sample_aircraft() -> Vec<Aircraft> {
+    serde_json::from_str(SAMPLE_JSON).expect("the sample corpus is valid")
+}
+
+/// Starts building an aircraft with ICAO code `icao_code`, no IATA code and a description
+/// naming the code.
+pub fn aircraft(icao_code: &str) -> AircraftBuilder {
+    AircraftBuilder {
+        aircraft: Aircraft {
+            icao_code: icao_code.to_string(),
+            iata_code: None,
+            description: format!("Aircraft {}", icao_code),
+            descriptions: BTreeMap::new(),
+            aliases: Vec::new(),
+        },
+    }
+}
+
+/// Builds an [`Aircraft`] field by field, from [`aircraft`].
+#[derive(Clone, Debug)]
+pub struct AircraftBuilder {
+    aircraft: Aircraft,
+}
+
+impl AircraftBuilder {
+    pub fn iata(mut self, iata_code: &str) -> Self {
+        self.aircraft.iata_code = Some(iata_code.to_string());
+        self
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.aircraft.description = description.to_string();
+        self
+    }
+
+    /// Adds the description in `lang`, e.g. `fr`.
+    pub fn translation(mut self, lang: &str, description: &str) -> Self {
+        self.aircraft.descriptions.insert(lang.to_string(), description.to_string());
+        self
+    }
+
+    pub fn build(self) -> Aircraft {
+        self.aircraft
+    }
+}
+
+impl From<AircraftBuilder> for Aircraft {
+    fn from(builder: AircraftBuilder) -> Self {
+        builder.build()
+    }
+}
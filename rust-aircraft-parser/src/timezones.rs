@@ -0,0 +1,83 @@
+//! Deriving the IANA time zone of every stored airport from its coordinates, which upstream
+//! airport files often leave out or get wrong.
+
+use std::time::Instant;
+use mongodb::bson::doc;
+use tracing::info;
+use crate::storage::RecordStore;
+use crate::{Airport, Result};
+
+/// Finds the time zone of a point from the boundaries built into the `timezones` feature.
+pub struct TimezoneFinder {
+    #[cfg(feature = "timezones")]
+    finder: tzf_rs::DefaultFinder,
+}
+
+impl TimezoneFinder {
+    /// Loads the boundaries, which takes a moment.
+    #[cfg(feature = "timezones")]
+    pub fn new() -> Result<TimezoneFinder> {
+        Ok(TimezoneFinder { finder: tzf_rs::DefaultFinder::new() })
+    }
+
+    #[cfg(not(feature = "timezones"))]
+    pub fn new() -> Result<TimezoneFinder> {
+        Err(crate::Error::Config("deriving time zones needs the timezones feature".to_string()))
+    }
+
+    /// The IANA time zone at `latitude`, `longitude`, e.g. `America/New_York`, if any.
+    #[cfg(feature = "timezones")]
+    pub fn timezone(&self, latitude: f64, longitude: f64) -> Option<String> {
+        Some(self.finder.get_tz_name(longitude, latitude)).filter(|name| !name.is_empty()).map(str::to_string)
+    }
+
+    #[cfg(not(feature = "timezones"))]
+    pub fn timezone(&self, _latitude: f64, _longitude: f64) -> Option<String> {
+        None
+    }
+}
+
+/// Outcome of [`enrich_airports`].
+#[derive(Debug, Default)]
+pub struct TimezoneSummary {
+    /// Stored airports read.
+    pub parsed: u64,
+    /// Airports whose time zone was written, because it was missing or different.
+    pub updated: u64,
+    /// ICAO codes of the airports that had a different time zone stored.
+    pub corrected: Vec<String>,
+    /// ICAO codes of the airports no time zone was found for.
+    pub unresolved: Vec<String>,
+}
+
+/// Sets the `timezone` of every stored airport to the one its coordinates fall in, leaving
+/// airports no zone is found for as they are.
+pub async fn enrich_airports(airports: &RecordStore<Airport>, finder: &TimezoneFinder) -> Result<TimezoneSummary> {
+    let started = Instant::now();
+    let mut summary = TimezoneSummary::default();
+    for airport in airports.find(doc! {}).await? {
+        summary.parsed += 1;
+        let Some(timezone) = finder.timezone(airport.latitude, airport.longitude) else {
+            summary.unresolved.push(airport.icao_code);
+            continue;
+        };
+        if airport.timezone.as_deref() == Some(timezone.as_str()) {
+            continue;
+        }
+        if airports.merge(&airport.icao_code, doc! { "timezone": &timezone }).await? {
+            summary.updated += 1;
+            if airport.timezone.is_some_and(|stored| !stored.is_empty()) {
+                summary.corrected.push(airport.icao_code);
+            }
+        }
+    }
+    info!(
+        parsed = summary.parsed,
+        updated = summary.updated,
+        corrected = summary.corrected.len(),
+        unresolved = summary.unresolved.len(),
+        elapsed_ms = started.elapsed().as_millis() as u64,
+        "timezones finished"
+    );
+    Ok(summary)
+}
@@ -0,0 +1,112 @@
+//! Format checks applied to every parsed record before it is written.
+
+use std::fs;
+use std::path::Path;
+use serde::Serialize;
+use crate::record::Record;
+use crate::{Aircraft, Error, Result};
+
+/// A record that failed validation, together with every reason it failed.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Rejection<T = Aircraft> {
+    pub record: T,
+    pub reasons: Vec<String>,
+}
+
+/// Checks `aircraft` and returns why it is invalid; an empty list means it is valid.
+///
+/// * `icaoCode` must be an ICAO type designator: 2 to 4 letters or digits.
+/// * `iataCode` must be exactly 3 letters or digits when the type has one.
+/// * `description` must not be blank.
+/// * `aliases` must be 2 to 4 letters or digits each, and differ from `icaoCode`.
+pub fn validate(aircraft: &Aircraft) -> Vec<String> {
+    let mut reasons = Vec::new();
+    let icao_length = aircraft.icao_code.chars().count();
+    if !(2..=4).contains(&icao_length) || !is_alphanumeric(&aircraft.icao_code) {
+        reasons.push(format!("icaoCode {:?} is not 2-4 alphanumeric characters", aircraft.icao_code));
+    }
+    if let Some(iata_code) = &aircraft.iata_code {
+        if iata_code.chars().count() != 3 || !is_alphanumeric(iata_code) {
+            reasons.push(format!("iataCode {:?} is not 3 alphanumeric characters", iata_code));
+        }
+    }
+    if aircraft.description.trim().is_empty() {
+        reasons.push("description is empty".to_string());
+    }
+    for alias in &aircraft.aliases {
+        if !(2..=4).contains(&alias.chars().count()) || !is_alphanumeric(alias) {
+            reasons.push(format!("alias {:?} is not 2-4 alphanumeric characters", alias));
+        } else if alias.eq_ignore_ascii_case(&aircraft.icao_code) {
+            reasons.push(format!("alias {:?} is the icaoCode itself", alias));
+        }
+    }
+    reasons
+}
+
+/// Validates `record` with [`Record::validate`], handing it back when valid and a
+/// [`Rejection`] otherwise.
+pub fn check<T: Record>(record: T) -> std::result::Result<T, Rejection<T>> {
+    let reasons = record.validate();
+    if reasons.is_empty() {
+        Ok(record)
+    } else {
+        Err(Rejection { record, reasons })
+    }
+}
+
+/// Writes `rejections` to `path` as a pretty-printed JSON array.
+pub fn write_rejects<T: Serialize>(path: &Path, rejections: &[Rejection<T>]) -> Result<()> {
+    let json = serde_json::to_string_pretty(rejections)?;
+    fs::write(path, json).map_err(|source| Error::Write { path: path.to_path_buf(), source })
+}
+
+pub(crate) fn is_alphanumeric(code: &str) -> bool {
+    code.chars().all(|character| character.is_ascii_alphanumeric())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use super::*;
+
+    fn aircraft(icao_code: &str, iata_code: Option<&str>, description: &str) -> Aircraft {
+        Aircraft {
+            icao_code: icao_code.to_string(),
+            iata_code: iata_code.map(str::to_string),
+            description: description.to_string(),
+            descriptions: BTreeMap::new(),
+            aliases: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn accepts_a_well_formed_aircraft() {
+        assert!(validate(&aircraft("B738", Some("738"), "Boeing 737-800")).is_empty());
+        assert!(validate(&aircraft("C25", None, "Cessna Citation")).is_empty());
+    }
+
+    #[test]
+    fn gives_every_reason_an_aircraft_is_invalid() {
+        let reasons = validate(&aircraft("B7-38", Some("73"), "  "));
+        assert_eq!(reasons.len(), 3, "{:?}", reasons);
+        assert!(reasons[0].starts_with("icaoCode"));
+        assert!(reasons[1].starts_with("iataCode"));
+        assert_eq!(reasons[2], "description is empty");
+    }
+
+    #[test]
+    fn rejects_an_alias_of_the_code_itself() {
+        let invalid = Aircraft { aliases: vec!["b738".to_string()], ..aircraft("B738", None, "Boeing 737-800") };
+        assert_eq!(validate(&invalid), vec!["alias \"b738\" is the icaoCode itself".to_string()]);
+    }
+
+    #[test]
+    fn check_hands_back_the_aircraft_or_its_rejection() {
+        let valid = aircraft("A320", Some("320"), "Airbus A320");
+        assert_eq!(check(valid.clone()), Ok(valid));
+        let invalid = aircraft("A", Some("320"), "Airbus A320");
+        let rejection = check(invalid.clone()).unwrap_err();
+        assert_eq!(rejection.record, invalid);
+        assert_eq!(rejection.reasons, validate(&invalid));
+    }
+}
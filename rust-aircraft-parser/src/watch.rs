@@ -0,0 +1,79 @@
+//! Waiting for an input file to change, for `watch`.
+//!
+//! The file's directory is watched rather than the file itself, so that editors which save
+//! by writing a new file and renaming it over the old one are followed too. Bursts of
+//! events, such as a save written in several chunks, are reported as one change once the
+//! file has been quiet for the debounce period.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use notify::event::{AccessKind, AccessMode, EventKind};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tracing::debug;
+use crate::{Error, Result};
+
+/// Reports changes to one file, see [`FileWatcher::changed`].
+pub struct FileWatcher {
+    // Stops watching when dropped.
+    _watcher: RecommendedWatcher,
+    events: UnboundedReceiver<notify::Result<Event>>,
+    path: PathBuf,
+    debounce: Duration,
+}
+
+impl FileWatcher {
+    /// Starts watching `path`, which must be a local file.
+    pub fn new(path: &Path, debounce: Duration) -> Result<Self> {
+        let watch_error = |error: notify::Error| Error::Io { path: path.to_path_buf(), source: io::Error::other(error) };
+        let path = path.canonicalize().map_err(|source| Error::Io { path: path.to_path_buf(), source })?;
+        let directory = path.parent().unwrap_or(Path::new("/"));
+        let (sender, events) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            // The receiver only goes away with the watcher.
+            let _ = sender.send(event);
+        })
+        .map_err(watch_error)?;
+        watcher.watch(directory, RecursiveMode::NonRecursive).map_err(watch_error)?;
+        Ok(FileWatcher { _watcher: watcher, events, path, debounce })
+    }
+
+    /// Waits until the file has been modified, created, removed or renamed, and then until no
+    /// further change has happened for the debounce period.
+    pub async fn changed(&mut self) -> Result<()> {
+        loop {
+            if self.next_change().await? {
+                break;
+            }
+        }
+        while let Ok(change) = tokio::time::timeout(self.debounce, self.next_change()).await {
+            change?;
+        }
+        Ok(())
+    }
+
+    // The next event in the directory, true if it changed the file.
+    async fn next_change(&mut self) -> Result<bool> {
+        let event = match self.events.recv().await {
+            Some(event) => event,
+            None => return Err(self.stopped(io::Error::other("the file watcher stopped"))),
+        };
+        let event = event.map_err(|error| self.stopped(io::Error::other(error)))?;
+        // Of the accesses only closing after a write changes the file.
+        let writes = match event.kind {
+            EventKind::Access(AccessKind::Close(AccessMode::Write)) => true,
+            EventKind::Access(_) => false,
+            _ => true,
+        };
+        let changed = writes && event.paths.iter().any(|path| path == &self.path);
+        if changed {
+            debug!(kind = ?event.kind, path = %self.path.display(), "input changed");
+        }
+        Ok(changed)
+    }
+
+    fn stopped(&self, source: io::Error) -> Error {
+        Error::Io { path: self.path.clone(), source }
+    }
+}
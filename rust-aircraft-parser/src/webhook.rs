@@ -0,0 +1,146 @@
+//! Telling someone how a run went by POSTing a summary of it to a webhook, such as a Slack
+//! incoming webhook.
+
+use std::time::{Duration, SystemTime};
+use clap::ValueEnum;
+use reqwest::header::CONTENT_TYPE;
+use serde::Serialize;
+use serde_json::json;
+use tracing::{info, warn};
+use crate::provenance::LoadRecord;
+use crate::{Error, Result};
+
+/// Shape of the JSON body POSTed to the webhook.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WebhookFormat {
+    /// The run summary as a JSON object with camelCase keys
+    #[default]
+    Json,
+    /// A Slack message, {"text": ...}, as incoming webhooks expect
+    Slack,
+}
+
+/// How a run ended, as reported to the webhook.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RunSummary {
+    /// `finished`, `interrupted` or `failed`.
+    pub status: &'static str,
+    /// The subcommand that ran, e.g. `load` or `sync`.
+    pub command: String,
+    /// Identifies the run; unknown for runs that failed before starting one.
+    pub load_id: Option<String>,
+    pub source_file: Option<String>,
+    pub parsed: u64,
+    pub written: u64,
+    /// Split of `written` into records new to the backend and records already stored, when
+    /// the run can tell.
+    pub inserted: Option<u64>,
+    pub updated: Option<u64>,
+    pub deleted: u64,
+    pub rejected: u64,
+    pub skipped: u64,
+    pub duration_ms: u64,
+    /// Why the run failed.
+    pub error: Option<String>,
+}
+
+impl RunSummary {
+    /// A run that finished as `record` says.
+    pub fn finished(record: &LoadRecord) -> Self {
+        let duration = record.finished_at.duration_since(record.started_at).unwrap_or(Duration::ZERO);
+        RunSummary {
+            status: if record.interrupted { "interrupted" } else { "finished" },
+            command: record.command.clone(),
+            load_id: Some(record.provenance.load_id.clone()),
+            source_file: record.provenance.source_file.clone(),
+            parsed: record.parsed,
+            written: record.written,
+            inserted: record.updated.map(|updated| record.written.saturating_sub(updated)),
+            updated: record.updated,
+            deleted: record.deleted,
+            rejected: record.rejected,
+            skipped: record.skipped,
+            duration_ms: duration.as_millis() as u64,
+            error: None,
+        }
+    }
+
+    /// A run of `command` that started at `started_at` and failed just now with `error`.
+    pub fn failed(command: &str, started_at: SystemTime, error: &Error) -> Self {
+        RunSummary {
+            status: "failed",
+            command: command.to_string(),
+            load_id: None,
+            source_file: None,
+            parsed: 0,
+            written: 0,
+            inserted: None,
+            updated: None,
+            deleted: 0,
+            rejected: 0,
+            skipped: 0,
+            duration_ms: started_at.elapsed().unwrap_or(Duration::ZERO).as_millis() as u64,
+            error: Some(error.to_string()),
+        }
+    }
+
+    // The summary as one line of chat.
+    fn text(&self) -> String {
+        let seconds = self.duration_ms as f64 / 1000.0;
+        let mut text = match &self.error {
+            Some(error) => format!(":x: {} failed after {:.1}s: {}", self.command, seconds, error),
+            None => format!(
+                ":white_check_mark: {} finished in {:.1}s: {} parsed, {} written, {} deleted, {} rejected, {} skipped",
+                self.command, seconds, self.parsed, self.written, self.deleted, self.rejected, self.skipped
+            ),
+        };
+        if let Some(source_file) = &self.source_file {
+            text.push_str(&format!(" from {}", source_file));
+        }
+        if let Some(load_id) = &self.load_id {
+            text.push_str(&format!(" (load id {})", load_id));
+        }
+        text
+    }
+}
+
+/// POSTs a [`RunSummary`] to a URL after each run.
+#[derive(Clone, Debug)]
+pub struct Webhook {
+    url: String,
+    format: WebhookFormat,
+    client: reqwest::Client,
+}
+
+impl Webhook {
+    pub fn new(url: &str, format: WebhookFormat) -> Self {
+        Webhook { url: url.to_string(), format, client: reqwest::Client::new() }
+    }
+
+    /// POSTs `summary`, failing if it cannot be delivered or the webhook answers with an error status.
+    pub async fn send(&self, summary: &RunSummary) -> Result<()> {
+        match self.format {
+            WebhookFormat::Json => self.post(summary).await?,
+            WebhookFormat::Slack => self.post(&json!({ "text": summary.text() })).await?,
+        }
+        info!(status = summary.status, command = %summary.command, "notified webhook");
+        Ok(())
+    }
+
+    /// POSTs `value` as JSON whatever the format, such as the changes `tail` forwards.
+    pub async fn post(&self, value: &impl Serialize) -> Result<()> {
+        let body = serde_json::to_vec(value)?;
+        let response = self.client.post(&self.url).header(CONTENT_TYPE, "application/json").body(body).send().await?;
+        response.error_for_status()?;
+        Ok(())
+    }
+
+    /// [`send`](Self::send), logging rather than returning a failure, so a webhook that is
+    /// down never fails the run it reports on.
+    pub async fn notify(&self, summary: &RunSummary) {
+        if let Err(error) = self.send(summary).await {
+            warn!(%error, "could not notify webhook");
+        }
+    }
+}
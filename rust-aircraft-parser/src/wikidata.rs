@@ -0,0 +1,156 @@
+//! Looking aircraft types up on Wikidata and merging what it knows into the stored aircraft.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use mongodb::bson::{doc, Document};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, info};
+use crate::{AircraftStore, Error, Result, Storage};
+
+/// The public Wikidata SPARQL endpoint.
+pub const ENDPOINT: &str = "https://query.wikidata.org/sparql";
+
+// Wikidata asks clients to identify themselves and blocks generic agents.
+const USER_AGENT: &str = concat!("rust-aircraft-parser/", env!("CARGO_PKG_VERSION"));
+
+/// What Wikidata knows about the aircraft type with a given ICAO designator (property P4600).
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TypeEntity {
+    /// Wikidata item of the type, e.g. `http://www.wikidata.org/entity/Q5917`.
+    pub item: String,
+    /// Date of the first flight, e.g. `1994-06-12`.
+    pub first_flight: Option<String>,
+    /// Wikidata item of the manufacturer.
+    pub manufacturer: Option<String>,
+    /// English label of the manufacturer, e.g. `Boeing`.
+    pub manufacturer_label: Option<String>,
+    /// English Wikipedia article about the type.
+    pub wikipedia_url: Option<String>,
+}
+
+impl TypeEntity {
+    /// The fields stored for the entity, under `wikidata*` names that leave the fields of
+    /// other enrichments alone. Missing values are left out so they never blank a field
+    /// already stored.
+    pub fn fields(&self) -> Document {
+        let mut fields = doc! { "wikidataItem": &self.item };
+        for (name, value) in [
+            ("firstFlight", &self.first_flight),
+            ("wikidataManufacturer", &self.manufacturer),
+            ("wikidataManufacturerLabel", &self.manufacturer_label),
+            ("wikipediaUrl", &self.wikipedia_url),
+        ] {
+            if let Some(value) = value {
+                fields.insert(name, value);
+            }
+        }
+        fields
+    }
+}
+
+/// A client of the SPARQL endpoint sending at most one query per `delay`, which keeps
+/// the answers in `cache_dir` so that later runs only ask for designators not seen yet.
+pub struct Wikidata {
+    client: reqwest::Client,
+    endpoint: String,
+    cache_dir: PathBuf,
+    delay: Duration,
+    last_query: Option<Instant>,
+}
+
+impl Wikidata {
+    pub fn new(endpoint: &str, cache_dir: &Path, delay: Duration) -> Result<Self> {
+        fs::create_dir_all(cache_dir).map_err(|source| Error::Write { path: cache_dir.to_path_buf(), source })?;
+        let client = reqwest::Client::builder().user_agent(USER_AGENT).build()?;
+        Ok(Wikidata { client, endpoint: endpoint.to_string(), cache_dir: cache_dir.to_path_buf(), delay, last_query: None })
+    }
+
+    /// The entity of the type `designator`, if Wikidata has one, from the cache when an
+    /// earlier lookup recorded it, including its absence.
+    pub async fn lookup(&mut self, designator: &str) -> Result<Option<TypeEntity>> {
+        let path = self.cache_dir.join(format!("{}.json", designator.replace(['/', '\\', '.'], "_")));
+        if let Some(cached) = fs::read(&path).ok().and_then(|bytes| serde_json::from_slice(&bytes).ok()) {
+            debug!(designator, "wikidata answer cached");
+            return Ok(cached);
+        }
+        let entity = self.query(designator).await?;
+        fs::write(&path, serde_json::to_vec(&entity)?).map_err(|source| Error::Write { path, source })?;
+        Ok(entity)
+    }
+
+    async fn query(&mut self, designator: &str) -> Result<Option<TypeEntity>> {
+        if let Some(elapsed) = self.last_query.map(|last| last.elapsed()) {
+            tokio::time::sleep(self.delay.saturating_sub(elapsed)).await;
+        }
+        self.last_query = Some(Instant::now());
+        let url = reqwest::Url::parse_with_params(&self.endpoint, [("query", sparql(designator).as_str()), ("format", "json")])
+            .map_err(|error| Error::Config(format!("invalid wikidata endpoint {:?}: {}", self.endpoint, error)))?;
+        let response = self.client.get(url).send().await?.error_for_status()?;
+        let body: Value = serde_json::from_slice(&response.bytes().await?)?;
+        let binding = &body["results"]["bindings"][0];
+        let value = |name: &str| binding[name]["value"].as_str().map(str::to_string);
+        Ok(value("item").map(|item| TypeEntity {
+            item,
+            // xsd:dateTime, e.g. 1994-06-12T00:00:00Z, of which only the date is meaningful.
+            first_flight: value("firstFlight").map(|date| date.split('T').next().unwrap_or_default().to_string()),
+            manufacturer: value("manufacturer"),
+            manufacturer_label: value("manufacturerLabel"),
+            wikipedia_url: value("article"),
+        }))
+    }
+}
+
+// The first type with the ICAO designator, its earliest first flight and its English article.
+fn sparql(designator: &str) -> String {
+    let designator = designator.replace(['\\', '"'], "");
+    format!(
+        r#"SELECT ?item ?firstFlight ?manufacturer ?manufacturerLabel ?article WHERE {{
+  ?item wdt:P4600 "{designator}" .
+  OPTIONAL {{ ?item wdt:P606 ?firstFlight . }}
+  OPTIONAL {{ ?item wdt:P176 ?manufacturer . }}
+  OPTIONAL {{ ?article schema:about ?item ; schema:isPartOf <https://en.wikipedia.org/> . }}
+  SERVICE wikibase:label {{ bd:serviceParam wikibase:language "en" . }}
+}}
+ORDER BY ?firstFlight
+LIMIT 1"#
+    )
+}
+
+/// Outcome of [`enrich`].
+#[derive(Debug, Default)]
+pub struct WikidataSummary {
+    /// Stored aircraft looked up.
+    pub parsed: u64,
+    /// Stored aircraft that received fields.
+    pub enriched: u64,
+    /// ICAO codes Wikidata has no type for.
+    pub unknown: Vec<String>,
+}
+
+/// Looks every stored aircraft up with `wikidata` and adds the fields of its entity to its
+/// document, leaving the fields the loader wrote untouched.
+pub async fn enrich(store: &AircraftStore, wikidata: &mut Wikidata) -> Result<WikidataSummary> {
+    let started = Instant::now();
+    let mut summary = WikidataSummary::default();
+    for aircraft in store.find_all().await? {
+        summary.parsed += 1;
+        let Some(entity) = wikidata.lookup(&aircraft.icao_code).await? else {
+            summary.unknown.push(aircraft.icao_code);
+            continue;
+        };
+        if store.merge(doc! { &store.field_names().icao_code: &aircraft.icao_code }, entity.fields()).await? {
+            summary.enriched += 1;
+        }
+    }
+    info!(
+        parsed = summary.parsed,
+        enriched = summary.enriched,
+        unknown = summary.unknown.len(),
+        elapsed_ms = started.elapsed().as_millis() as u64,
+        "wikidata enrich finished"
+    );
+    Ok(summary)
+}
@@ -0,0 +1,63 @@
+//! Takes, renews, loses and releases the lock on a collection of a disposable MongoDB; needs
+//! Docker: `cargo test --features testcontainers --test lock`.
+#![cfg(feature = "testcontainers")]
+
+use std::time::Duration;
+use rust_aircraft_parser::provenance::Provenance;
+use rust_aircraft_parser::{selftest, AircraftStore, Error};
+
+// Short enough for a lease to run out, or be renewed several times, within a test.
+const LEASE: Duration = Duration::from_millis(900);
+
+// `store` writing for the run `load_id`, which owns the locks it takes.
+fn run(store: &AircraftStore, load_id: &str) -> AircraftStore {
+    store.clone().with_provenance(Provenance { load_id: load_id.to_string(), ..Provenance::new() })
+}
+
+fn held_by(locked: Result<impl Sized, Error>, run: &str) -> bool {
+    matches!(locked, Err(Error::Locked { owner, .. }) if owner == run)
+}
+
+#[tokio::test]
+async fn a_lock_is_renewed_until_it_is_released() {
+    let (store, _container) = selftest::container("aircraft_lock", "aircraft").await.expect("mongodb container starts");
+    let (first, second) = (run(&store, "first"), run(&store, "second"));
+
+    let lock = first.lock(LEASE, false).await.expect("a free collection is locked");
+    // Renewed, the lease outlasts its first term.
+    tokio::time::sleep(LEASE * 2).await;
+    assert!(held_by(second.lock(LEASE, false).await, "first"));
+    // Other collections are locked apart.
+    second.lock_collection("airports", LEASE, false).await.expect("another collection is free").release().await.unwrap();
+
+    lock.release().await.unwrap();
+    second.lock(LEASE, false).await.expect("a released collection is free").release().await.unwrap();
+}
+
+#[tokio::test]
+async fn a_lock_dropped_without_being_released_lapses() {
+    let (store, _container) = selftest::container("aircraft_lock", "aircraft").await.expect("mongodb container starts");
+    let (first, second) = (run(&store, "first"), run(&store, "second"));
+
+    drop(first.lock(LEASE, false).await.expect("a free collection is locked"));
+    assert!(held_by(second.lock(LEASE, false).await, "first"));
+    tokio::time::sleep(LEASE + LEASE / 2).await;
+    second.lock(LEASE, false).await.expect("a lapsed lease is free").release().await.unwrap();
+}
+
+#[tokio::test]
+async fn a_broken_lock_is_lost() {
+    let (store, _container) = selftest::container("aircraft_lock", "aircraft").await.expect("mongodb container starts");
+    let (first, second) = (run(&store, "first"), run(&store, "second"));
+
+    let lock = first.lock(LEASE, false).await.expect("a free collection is locked");
+    let taken = second.lock(LEASE, true).await.expect("force breaks the lock");
+    // The renewal finds the lease taken within a third of it.
+    let lost = tokio::time::timeout(LEASE, lock.lost()).await.expect("the loss is noticed");
+    assert!(matches!(lost, Error::Locked { owner, .. } if owner == "second"));
+
+    // Releasing what was lost leaves the lease of the run that took it alone.
+    lock.release().await.unwrap();
+    assert!(held_by(first.lock(LEASE, false).await, "second"));
+    taken.release().await.unwrap();
+}
@@ -0,0 +1,13 @@
+//! Runs the self-test against a disposable MongoDB; needs Docker:
+//! `cargo test --features testcontainers --test self_test`.
+#![cfg(feature = "testcontainers")]
+
+use rust_aircraft_parser::selftest;
+
+#[tokio::test]
+async fn load_sync_and_export_against_mongodb() {
+    let (store, _container) = selftest::container("aircraft_self_test", "aircraft").await.expect("mongodb container starts");
+    let checks = selftest::run(&store).await.expect("every step runs");
+    let failed: Vec<_> = checks.iter().filter(|check| !check.passed).collect();
+    assert!(failed.is_empty(), "failed checks: {:#?}", failed);
+}